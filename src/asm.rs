@@ -0,0 +1,775 @@
+//! Assembleur : du texte WAT au modèle `wasm`, instructions en forme plate
+//! (`local.get $x  i32.const 1  i32.add`) ou parenthésée
+//! (`(i32.add (local.get $x) (i32.const 1))`). Sert au format binaire, qui
+//! ne peut pas recopier le texte des blocs `wat { ... }`, et à
+//! `gaufre assemble`/`gaufre validate`, qui lisent un module `.wat` entier.
+
+use crate::codes::code;
+use crate::diag::{Diagnostic, SourceMap, Span};
+use crate::i18n::tr;
+use crate::inline_wat::{tokens, Kind, WatError};
+use crate::wasm::{
+    Data, Elem, Export, ExportKind, Func, FuncType, Global, Import, Instr, Local, MemArg, MemOp, Module, NumOp, ValType,
+};
+use std::{fs, path::Path};
+
+/// Noms visibles depuis le texte : locals de la fonction (paramètres
+/// compris), globales, fonctions, types et blocs englobants, du plus externe
+/// au plus interne.
+pub struct Scope<'a> {
+    pub locals: Vec<&'a str>,
+    pub globals: Vec<&'a str>,
+    pub funcs: Vec<&'a str>,
+    pub types: Vec<&'a str>,
+    pub labels: Vec<Option<&'a str>>,
+}
+
+/// Instructions sans immédiat, reconnues à leur seul mot-clé.
+const PLAIN: [Instr; 9] = [
+    Instr::Unreachable,
+    Instr::Nop,
+    Instr::Return,
+    Instr::Drop,
+    Instr::Select,
+    Instr::MemorySize,
+    Instr::MemoryGrow,
+    Instr::MemoryCopy,
+    Instr::MemoryFill,
+];
+
+struct Parser<'t, 's> {
+    text: &'t str,
+    toks: Vec<(Kind, usize, usize)>,
+    pos: usize,
+    scope: &'s Scope<'s>,
+    /// blocs ouverts dans le texte, après ceux de `scope`
+    labels: Vec<Option<String>>,
+    /// instructions lues et index du jeton d'où chacune vient
+    out: Vec<(Instr, usize)>,
+}
+
+impl<'t> Parser<'t, '_> {
+    fn peek(&self) -> Option<(Kind, &'t str)> {
+        self.toks.get(self.pos).map(|&(k, lo, hi)| (k, &self.text[lo..hi]))
+    }
+
+    /// Mot-clé qui suit une `(` à venir.
+    fn peek_head(&self) -> Option<&'t str> {
+        match self.toks.get(self.pos..self.pos + 2) {
+            Some(&[(Kind::Open, _, _), (Kind::Word, lo, hi)]) => Some(&self.text[lo..hi]),
+            _ => None,
+        }
+    }
+
+    /// Erreur sur le jeton courant (ou la fin du texte).
+    fn error(&self, message: String) -> WatError {
+        match self.toks.get(self.pos) {
+            Some(&(_, lo, hi)) => WatError { message, offset: lo, len: hi - lo },
+            None => WatError { message, offset: self.text.len(), len: 0 },
+        }
+    }
+
+    /// Erreur sur le jeton qui vient d'être lu.
+    fn back(&mut self, message: impl FnOnce(&str) -> String) -> WatError {
+        self.pos -= 1;
+        let word = self.peek().map_or("", |(_, w)| w);
+        self.error(message(word))
+    }
+
+    fn expect(&mut self, kind: Kind, what: &str) -> Result<&'t str, WatError> {
+        match self.peek() {
+            Some((k, word)) if k == kind => {
+                self.pos += 1;
+                Ok(word)
+            }
+            _ => Err(self.error(tr!("asm.expected", what))),
+        }
+    }
+
+    fn word(&mut self) -> Result<&'t str, WatError> {
+        self.expect(Kind::Word, "instruction")
+    }
+
+    /// `$nom` facultatif après `block`, `loop`, `if`, `else` ou `end`.
+    fn opt_id(&mut self) -> Option<&'t str> {
+        match self.peek() {
+            Some((Kind::Word, w)) if w.starts_with('$') => {
+                self.pos += 1;
+                Some(&w[1..])
+            }
+            _ => None,
+        }
+    }
+
+    /// `(result i32)` facultatif.
+    fn block_type(&mut self) -> Result<Option<ValType>, WatError> {
+        match self.peek_head() {
+            Some("result") => {
+                self.pos += 2;
+                let t = self.word()?;
+                if t != "i32" {
+                    return Err(self.back(|w| tr!("asm.unsupported", w)));
+                }
+                self.expect(Kind::Close, ")")?;
+                Ok(Some(ValType::I32))
+            }
+            Some(head @ ("param" | "type")) => {
+                self.pos += 1;
+                Err(self.error(tr!("asm.unsupported", head)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn number(&mut self) -> Result<i64, WatError> {
+        let w = self.word()?;
+        let (neg, digits) = match w.as_bytes().first() {
+            Some(b'-') => (true, &w[1..]),
+            Some(b'+') => (false, &w[1..]),
+            _ => (false, w),
+        };
+        let digits = digits.replace('_', "");
+        let v = match digits.strip_prefix("0x") {
+            Some(hex) => i64::from_str_radix(hex, 16),
+            None => digits.parse::<i64>(),
+        };
+        match v {
+            Ok(v) if digits.bytes().all(|b| b.is_ascii_hexdigit() || b == b'x') => Ok(if neg { -v } else { v }),
+            _ => Err(self.back(|w| tr!("asm.bad_number", w))),
+        }
+    }
+
+    /// Index par `$nom` ou par numéro.
+    fn index(&mut self, what: &str, names: &[&str]) -> Result<u32, WatError> {
+        let w = self.word()?;
+        if let Some(name) = w.strip_prefix('$') {
+            return match names.iter().position(|n| *n == name) {
+                Some(i) => Ok(i as u32),
+                None => Err(self.back(|w| tr!("asm.unknown_name", what, w))),
+            };
+        }
+        self.pos -= 1;
+        match self.number()? {
+            v @ 0..=0xFFFF_FFFF => Ok(v as u32),
+            _ => Err(self.back(|w| tr!("asm.bad_number", w))),
+        }
+    }
+
+    /// Profondeur d'un label, par `$nom` ou par numéro.
+    fn label(&mut self) -> Result<u32, WatError> {
+        let open: Vec<Option<&str>> =
+            self.scope.labels.iter().copied().chain(self.labels.iter().map(|l| l.as_deref())).collect();
+        match self.peek() {
+            Some((Kind::Word, w)) if w.starts_with('$') => match open.iter().rev().position(|l| *l == Some(&w[1..])) {
+                Some(d) => {
+                    self.pos += 1;
+                    Ok(d as u32)
+                }
+                None => Err(self.error(tr!("asm.unknown_name", "label", w))),
+            },
+            _ => self.index("label", &[]),
+        }
+    }
+
+    /// `offset=N` et `align=N` facultatifs.
+    fn memarg(&mut self, op: MemOp) -> Result<MemArg, WatError> {
+        let mut arg = MemArg::natural(op);
+        while let Some((Kind::Word, w)) = self.peek() {
+            let (key, value) = match w.split_once('=') {
+                Some((key @ ("offset" | "align"), value)) => (key, value),
+                _ => break,
+            };
+            let v = value.parse::<u32>().ok().filter(|&v| key == "offset" || v.is_power_of_two());
+            let Some(v) = v else { return Err(self.error(tr!("asm.bad_number", w))) };
+            if key == "offset" {
+                arg.offset = v;
+            } else {
+                arg.align = v.trailing_zeros();
+            }
+            self.pos += 1;
+        }
+        Ok(arg)
+    }
+
+    /// Instruction ordinaire (ni bloc ni `else`/`end`) et ses immédiats.
+    fn plain(&mut self, kw: &str) -> Result<Instr, WatError> {
+        let scope = self.scope;
+        Ok(match kw {
+            "br" => Instr::Br(self.label()?),
+            "br_if" => Instr::BrIf(self.label()?),
+            "call" => Instr::Call(self.index(&tr!("wasm.entity.func"), &scope.funcs)?),
+            // seulement la forme `(type $t)`, sur la table 0
+            "call_indirect" => {
+                if self.peek_head() != Some("type") {
+                    return Err(self.error(tr!("asm.expected", "(type")));
+                }
+                self.pos += 2;
+                let t = self.index("type", &scope.types)?;
+                self.expect(Kind::Close, ")")?;
+                Instr::CallIndirect(t)
+            }
+            "local.get" => Instr::LocalGet(self.index("local", &scope.locals)?),
+            "local.set" => Instr::LocalSet(self.index("local", &scope.locals)?),
+            "local.tee" => Instr::LocalTee(self.index("local", &scope.locals)?),
+            "global.get" => Instr::GlobalGet(self.index("global", &scope.globals)?),
+            "global.set" => Instr::GlobalSet(self.index("global", &scope.globals)?),
+            "i32.const" => match self.number()? {
+                v @ -0x8000_0000..=0xFFFF_FFFF => Instr::I32Const(v as u32 as i32),
+                _ => return Err(self.back(|w| tr!("asm.bad_number", w))),
+            },
+            _ => {
+                if let Some(op) = MemOp::ALL.into_iter().find(|op| op.name() == kw) {
+                    return Ok(Instr::Mem(op, self.memarg(op)?));
+                }
+                if let Some(op) = NumOp::ALL.into_iter().find(|op| op.name() == kw) {
+                    return Ok(Instr::I32(op));
+                }
+                match PLAIN.into_iter().find(|i| i.keyword() == kw) {
+                    Some(i) => i,
+                    None => return Err(self.back(|w| tr!("asm.unknown_instr", w))),
+                }
+            }
+        })
+    }
+
+    /// Ouvre un `block`, `loop` ou `if` : label et type de bloc.
+    fn open(&mut self, kw: &str) -> Result<Instr, WatError> {
+        let label = self.opt_id().map(str::to_string);
+        let result = self.block_type()?;
+        self.labels.push(label.clone());
+        Ok(match kw {
+            "block" => Instr::Block { label, result },
+            "loop" => Instr::Loop { label, result },
+            _ => Instr::If { result },
+        })
+    }
+
+    /// `end` du jeton `tok` (le mot-clé, ou la `)` en forme parenthésée).
+    fn close(&mut self, tok: usize) -> Result<(), WatError> {
+        if self.labels.pop().is_none() {
+            self.pos = tok;
+            return Err(self.error(tr!("wat.stray_end")));
+        }
+        self.out.push((Instr::End, tok));
+        Ok(())
+    }
+
+    /// Instructions jusqu'à une `)` ou la fin du texte.
+    fn seq(&mut self) -> Result<(), WatError> {
+        while let Some((kind, _)) = self.peek() {
+            match kind {
+                Kind::Close => break,
+                Kind::Open => self.folded()?,
+                Kind::Word => self.flat()?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Corps complet : instructions et blocs refermés.
+    fn body(&mut self) -> Result<(), WatError> {
+        self.seq()?;
+        if !self.labels.is_empty() {
+            return Err(self.error(tr!("wat.unclosed_block", "block")));
+        }
+        Ok(())
+    }
+
+    fn flat(&mut self) -> Result<(), WatError> {
+        let kw = self.word()?;
+        let tok = self.pos - 1;
+        match kw {
+            "block" | "loop" | "if" => {
+                let ins = self.open(kw)?;
+                self.out.push((ins, tok));
+            }
+            "else" => {
+                self.opt_id();
+                self.out.push((Instr::Else, tok));
+            }
+            "end" => {
+                self.opt_id();
+                self.close(tok)?;
+            }
+            _ => {
+                let ins = self.plain(kw)?;
+                self.out.push((ins, tok));
+            }
+        }
+        Ok(())
+    }
+
+    /// `(instr immédiats opérandes...)`, les opérandes avant l'instruction.
+    fn folded(&mut self) -> Result<(), WatError> {
+        self.expect(Kind::Open, "(")?;
+        let kw = self.word()?;
+        let tok = self.pos - 1;
+        match kw {
+            "block" | "loop" => {
+                let ins = self.open(kw)?;
+                self.out.push((ins, tok));
+                self.seq()?;
+            }
+            "if" => {
+                let label = self.opt_id().map(str::to_string);
+                let result = self.block_type()?;
+                while self.peek_head().is_some_and(|h| h != "then") {
+                    self.folded()?;
+                }
+                self.labels.push(label);
+                self.out.push((Instr::If { result }, tok));
+                self.expect(Kind::Open, "(then")?;
+                if self.word()? != "then" {
+                    return Err(self.back(|_| tr!("asm.expected", "then")));
+                }
+                self.seq()?;
+                self.expect(Kind::Close, ")")?;
+                if self.peek_head() == Some("else") {
+                    self.out.push((Instr::Else, self.pos + 1));
+                    self.pos += 2;
+                    self.seq()?;
+                    self.expect(Kind::Close, ")")?;
+                }
+            }
+            _ => {
+                let ins = self.plain(kw)?;
+                while let Some((Kind::Open, _)) = self.peek() {
+                    self.folded()?;
+                }
+                self.expect(Kind::Close, ")")?;
+                self.out.push((ins, tok));
+                return Ok(());
+            }
+        }
+        self.expect(Kind::Close, ")")?;
+        self.close(self.pos - 1)
+    }
+}
+
+/// Instructions d'un texte WAT; les blocs qu'il ouvre doivent s'y refermer.
+pub fn instrs(text: &str, scope: &Scope) -> Result<Vec<Instr>, WatError> {
+    let mut p = Parser { text, toks: tokens(text)?, pos: 0, scope, labels: Vec::new(), out: Vec::new() };
+    p.body()?;
+    if p.peek().is_some() {
+        return Err(p.error(tr!("wat.stray_paren")));
+    }
+    Ok(p.out.into_iter().map(|(ins, _)| ins).collect())
+}
+
+/// Item exporté par un `(export "...")` en ligne ou un champ `export`.
+enum Item<'t> {
+    /// par `$nom` ou numéro, résolu une fois tous les noms connus
+    Ref { kind: &'t str, tok: usize },
+    /// index de la fonction déclarée, hors imports
+    Func(usize),
+    Global(usize),
+    Memory,
+}
+
+/// Paramètres, résultats ou locals, avec leur `$nom` éventuel.
+type Typed<'t> = Vec<(Option<&'t str>, ValType)>;
+
+struct FuncDecl<'t> {
+    name: Option<&'t str>,
+    ty: u32,
+    locals: Typed<'t>,
+    /// jetons du corps
+    body: (usize, usize),
+}
+
+impl<'t> Parser<'t, '_> {
+    fn val_type(&mut self) -> Result<ValType, WatError> {
+        match self.word()? {
+            "i32" => Ok(ValType::I32),
+            _ => Err(self.back(|w| tr!("asm.unsupported", w))),
+        }
+    }
+
+    /// Chaîne entre guillemets, échappements décodés.
+    fn string(&mut self) -> Result<Vec<u8>, WatError> {
+        let w = self.expect(Kind::Word, "\"...\"")?;
+        let Some(body) = w.strip_prefix('"').and_then(|w| w.strip_suffix('"')) else {
+            return Err(self.back(|_| tr!("asm.expected", "\"...\"")));
+        };
+        let mut out = Vec::new();
+        let mut chars = body.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                let mut buf = [0; 4];
+                out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                continue;
+            }
+            let escaped = match chars.next() {
+                Some('n') => Some(b'\n'),
+                Some('t') => Some(b'\t'),
+                Some('r') => Some(b'\r'),
+                Some(q @ ('"' | '\'' | '\\')) => Some(q as u8),
+                Some('u') => {
+                    let rest = chars.as_str();
+                    let code = rest
+                        .strip_prefix('{')
+                        .and_then(|r| r.split_once('}'))
+                        .and_then(|(hex, _)| u32::from_str_radix(&hex.replace('_', ""), 16).ok().map(|v| (hex.len(), v)))
+                        .and_then(|(len, v)| char::from_u32(v).map(|c| (len, c)));
+                    let Some((len, c)) = code else { return Err(self.back(|w| tr!("asm.bad_escape", w))) };
+                    let mut buf = [0; 4];
+                    out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                    chars = rest[len + 2..].chars();
+                    None
+                }
+                Some(h) => {
+                    let l = chars.next();
+                    match (h.to_digit(16), l.and_then(|l| l.to_digit(16))) {
+                        (Some(h), Some(l)) => Some((h * 16 + l) as u8),
+                        _ => return Err(self.back(|w| tr!("asm.bad_escape", w))),
+                    }
+                }
+                None => return Err(self.back(|w| tr!("asm.bad_escape", w))),
+            };
+            out.extend(escaped);
+        }
+        Ok(out)
+    }
+
+    /// Nom d'import ou d'export : une chaîne UTF-8.
+    fn name(&mut self) -> Result<String, WatError> {
+        let bytes = self.string()?;
+        String::from_utf8(bytes).map_err(|_| self.back(|w| tr!("asm.bad_name", w)))
+    }
+
+    /// `(export "nom")` en ligne, autant qu'il y en a.
+    fn inline_exports(&mut self) -> Result<Vec<String>, WatError> {
+        let mut names = Vec::new();
+        while self.peek_head() == Some("export") {
+            self.pos += 2;
+            names.push(self.name()?);
+            self.expect(Kind::Close, ")")?;
+        }
+        Ok(names)
+    }
+
+    /// `(param $x i32)`, `(param i32 i32)`, `(result i32)` ou `(local ...)`;
+    /// un `$nom` déjà pris par un paramètre ou local de `out` est refusé.
+    fn typed(&mut self, head: &str, out: &mut Typed<'t>) -> Result<bool, WatError> {
+        if self.peek_head() != Some(head) {
+            return Ok(false);
+        }
+        self.pos += 2;
+        if let Some(id) = self.opt_id() {
+            if out.iter().any(|&(n, _)| n == Some(id)) {
+                return Err(self.back(|w| tr!("asm.duplicate_name", w)));
+            }
+            out.push((Some(id), self.val_type()?));
+        } else {
+            while let Some((Kind::Word, _)) = self.peek() {
+                out.push((None, self.val_type()?));
+            }
+        }
+        self.expect(Kind::Close, ")")?;
+        Ok(true)
+    }
+
+    /// Signature : `(type $t)` et/ou paramètres et résultats.
+    fn signature(&mut self, m: &mut Module, types: &[Option<&str>]) -> Result<(u32, Typed<'t>), WatError> {
+        let mut declared = None;
+        if self.peek_head() == Some("type") {
+            self.pos += 2;
+            declared = Some(self.ref_index("type", types)?);
+            self.expect(Kind::Close, ")")?;
+        }
+        let (mut params, mut results) = (Vec::new(), Vec::new());
+        while self.typed("param", &mut params)? {}
+        while self.typed("result", &mut results)? {}
+        let ty = match declared {
+            Some(t) if params.is_empty() && results.is_empty() => {
+                let ty = m.types.get(t as usize).ok_or_else(|| self.back(|w| tr!("wasm.bad_index", "type", w)))?;
+                params = ty.params.iter().map(|&t| (None, t)).collect();
+                t
+            }
+            _ => m.func_type(params.iter().map(|p| p.1).collect(), results.iter().map(|r| r.1).collect()),
+        };
+        Ok((ty, params))
+    }
+
+    /// `$nom` parmi `names`, ou numéro.
+    fn ref_index(&mut self, what: &str, names: &[Option<&str>]) -> Result<u32, WatError> {
+        let named: Vec<&str> = names.iter().map(|n| n.unwrap_or("")).collect();
+        self.index(what, &named)
+    }
+
+    /// `(i32.const N)` : décalage d'un segment ou valeur d'une globale.
+    fn const_expr(&mut self) -> Result<i32, WatError> {
+        self.expect(Kind::Open, "(i32.const")?;
+        if self.word()? != "i32.const" {
+            return Err(self.back(|w| tr!("asm.unsupported", w)));
+        }
+        let v = match self.number()? {
+            v @ -0x8000_0000..=0xFFFF_FFFF => v as u32 as i32,
+            _ => return Err(self.back(|w| tr!("asm.bad_number", w))),
+        };
+        self.expect(Kind::Close, ")")?;
+        Ok(v)
+    }
+
+    /// Saute jusqu'à la `)` qui ferme le champ courant, sans la lire.
+    fn skip_field(&mut self) -> Result<usize, WatError> {
+        let mut depth = 0;
+        while let Some((kind, _)) = self.peek() {
+            match kind {
+                Kind::Open => depth += 1,
+                Kind::Close if depth == 0 => return Ok(self.pos),
+                Kind::Close => depth -= 1,
+                Kind::Word => {}
+            }
+            self.pos += 1;
+        }
+        Err(self.error(tr!("wat.unclosed_paren")))
+    }
+}
+
+/// Nom d'un item anonyme dans le modèle : son index.
+fn name_or_index(name: Option<&str>, i: usize) -> String {
+    name.map_or_else(|| i.to_string(), str::to_string)
+}
+
+/// Module d'un texte WAT complet, `(module ...)` ou ses seuls champs. Les
+/// instructions portent la position de leur mot-clé dans `file`.
+pub fn module(text: &str, file: usize) -> Result<Module, WatError> {
+    let empty = Scope { locals: Vec::new(), globals: Vec::new(), funcs: Vec::new(), types: Vec::new(), labels: Vec::new() };
+    let mut p = Parser { text, toks: tokens(text)?, pos: 0, scope: &empty, labels: Vec::new(), out: Vec::new() };
+    let mut m = Module::default();
+    let mut types: Vec<Option<&str>> = Vec::new();
+    let mut funcs: Vec<Option<&str>> = Vec::new();
+    let mut globals: Vec<Option<&str>> = Vec::new();
+    let mut decls: Vec<FuncDecl> = Vec::new();
+    let mut exports: Vec<(String, Item)> = Vec::new();
+    // décalage et jetons des fonctions de chaque segment de table
+    let mut elems: Vec<(u32, Vec<usize>)> = Vec::new();
+
+    let wrapped = p.peek_head() == Some("module");
+    if wrapped {
+        p.pos += 2;
+        p.opt_id();
+    }
+    while p.peek_head().is_some() {
+        p.pos += 1;
+        let field = p.word()?;
+        match field {
+            "type" => {
+                types.push(p.opt_id());
+                p.expect(Kind::Open, "(func")?;
+                if p.word()? != "func" {
+                    return Err(p.back(|_| tr!("asm.expected", "func")));
+                }
+                let (mut params, mut results) = (Vec::new(), Vec::new());
+                while p.typed("param", &mut params)? {}
+                while p.typed("result", &mut results)? {}
+                p.expect(Kind::Close, ")")?;
+                let params = params.into_iter().map(|p| p.1).collect();
+                let results = results.into_iter().map(|r| r.1).collect();
+                m.types.push(FuncType { params, results });
+            }
+            "import" => {
+                // les fonctions importées précèdent les autres dans l'index
+                if !decls.is_empty() {
+                    return Err(p.back(|_| tr!("asm.import_order")));
+                }
+                let (module, field) = (p.name()?, p.name()?);
+                p.expect(Kind::Open, "(func")?;
+                if p.word()? != "func" {
+                    return Err(p.back(|w| tr!("asm.unsupported", w)));
+                }
+                let id = p.opt_id();
+                let (ty, _) = p.signature(&mut m, &types)?;
+                p.expect(Kind::Close, ")")?;
+                m.imports.push(Import { module, field, name: name_or_index(id, funcs.len()), ty });
+                funcs.push(id);
+            }
+            "func" => {
+                let name = p.opt_id();
+                for e in p.inline_exports()? {
+                    exports.push((e, Item::Func(decls.len())));
+                }
+                let (ty, mut locals) = p.signature(&mut m, &types)?;
+                while p.typed("local", &mut locals)? {}
+                let start = p.pos;
+                let end = p.skip_field()?;
+                funcs.push(name);
+                decls.push(FuncDecl { name, ty, locals, body: (start, end) });
+            }
+            "memory" => {
+                if m.memory.is_some() {
+                    return Err(p.back(|w| tr!("asm.unsupported", w)));
+                }
+                p.opt_id();
+                for e in p.inline_exports()? {
+                    exports.push((e, Item::Memory));
+                }
+                let pages = p.number()?;
+                if !(0..=0xFFFF_FFFF).contains(&pages) {
+                    return Err(p.back(|w| tr!("asm.bad_number", w)));
+                }
+                // une taille maximale n'a pas de place dans le modèle
+                if let Some((Kind::Word, _)) = p.peek() {
+                    p.pos += 1;
+                    return Err(p.back(|w| tr!("asm.unsupported", w)));
+                }
+                m.memory = Some(pages as u32);
+            }
+            "table" => {
+                if m.table.is_some() {
+                    return Err(p.back(|w| tr!("asm.unsupported", w)));
+                }
+                p.opt_id();
+                let size = p.number()?;
+                if !(0..=0xFFFF_FFFF).contains(&size) {
+                    return Err(p.back(|w| tr!("asm.bad_number", w)));
+                }
+                if p.word()? != "funcref" {
+                    return Err(p.back(|w| tr!("asm.unsupported", w)));
+                }
+                m.table = Some(size as u32);
+            }
+            "elem" => {
+                let offset = p.const_expr()?;
+                if p.peek() == Some((Kind::Word, "func")) {
+                    p.pos += 1;
+                }
+                let mut toks = Vec::new();
+                while let Some((Kind::Word, _)) = p.peek() {
+                    toks.push(p.pos);
+                    p.pos += 1;
+                }
+                elems.push((offset as u32, toks));
+            }
+            "global" => {
+                let id = p.opt_id();
+                for e in p.inline_exports()? {
+                    exports.push((e, Item::Global(m.globals.len())));
+                }
+                let mutable = p.peek_head() == Some("mut");
+                if mutable {
+                    p.pos += 2;
+                }
+                let ty = p.val_type()?;
+                if mutable {
+                    p.expect(Kind::Close, ")")?;
+                }
+                let init = p.const_expr()?;
+                m.globals.push(Global { name: name_or_index(id, globals.len()), ty, mutable, init });
+                globals.push(id);
+            }
+            "export" => {
+                let name = p.name()?;
+                p.expect(Kind::Open, "(func")?;
+                let kind = p.word()?;
+                let tok = p.pos;
+                p.word()?;
+                p.expect(Kind::Close, ")")?;
+                exports.push((name, Item::Ref { kind, tok }));
+            }
+            "data" => {
+                if p.peek_head() == Some("memory") {
+                    p.pos += 2;
+                    p.skip_field()?;
+                    p.pos += 1;
+                }
+                let offset = if p.peek_head() == Some("offset") {
+                    p.pos += 2;
+                    let v = p.const_expr()?;
+                    p.expect(Kind::Close, ")")?;
+                    v
+                } else {
+                    p.const_expr()?
+                };
+                let mut bytes = Vec::new();
+                while let Some((Kind::Word, _)) = p.peek() {
+                    bytes.extend(p.string()?);
+                }
+                m.data.push(Data { offset: offset as u32, bytes });
+            }
+            _ => return Err(p.back(|w| tr!("asm.unsupported", w))),
+        }
+        p.expect(Kind::Close, ")")?;
+    }
+    if wrapped {
+        p.expect(Kind::Close, ")")?;
+    }
+    if p.peek().is_some() {
+        return Err(p.error(tr!("asm.expected", "(")));
+    }
+
+    let first = m.imports.len();
+    for (name, item) in exports {
+        let kind = match item {
+            Item::Func(i) => ExportKind::Func((first + i) as u32),
+            Item::Global(i) => ExportKind::Global(i as u32),
+            Item::Memory => ExportKind::Memory(0),
+            Item::Ref { kind, tok } => {
+                p.pos = tok;
+                match kind {
+                    "func" => ExportKind::Func(p.ref_index(&tr!("wasm.entity.func"), &funcs)?),
+                    "global" => ExportKind::Global(p.ref_index("global", &globals)?),
+                    "memory" => ExportKind::Memory(p.ref_index(&tr!("wasm.entity.memory"), &[None])?),
+                    _ => {
+                        p.pos -= 1;
+                        return Err(p.error(tr!("asm.unsupported", kind)));
+                    }
+                }
+            }
+        };
+        m.exports.push(Export { name, kind });
+    }
+
+    for (offset, toks) in elems {
+        let mut refs = Vec::new();
+        for tok in toks {
+            p.pos = tok;
+            refs.push(p.ref_index(&tr!("wasm.entity.func"), &funcs)?);
+        }
+        m.elems.push(Elem { offset, funcs: refs });
+    }
+
+    // corps, une fois toutes les fonctions, globales et types nommés
+    let func_names: Vec<&str> = funcs.iter().map(|n| n.unwrap_or("")).collect();
+    let global_names: Vec<&str> = globals.iter().map(|n| n.unwrap_or("")).collect();
+    let type_names: Vec<&str> = types.iter().map(|n| n.unwrap_or("")).collect();
+    for (i, d) in decls.into_iter().enumerate() {
+        let scope = Scope {
+            locals: d.locals.iter().map(|l| l.0.unwrap_or("")).collect(),
+            globals: global_names.clone(),
+            funcs: func_names.clone(),
+            types: type_names.clone(),
+            labels: Vec::new(),
+        };
+        let toks = p.toks[..d.body.1].to_vec();
+        let mut b = Parser { text, toks, pos: d.body.0, scope: &scope, labels: Vec::new(), out: Vec::new() };
+        b.body()?;
+        let locals = d
+            .locals
+            .iter()
+            .enumerate()
+            .map(|(j, &(name, ty))| Local { name: name_or_index(name, j), ty })
+            .collect();
+        let spans = b.out.iter().map(|&(_, tok)| Span::new(file, b.toks[tok].1, b.toks[tok].2)).collect();
+        m.funcs.push(Func {
+            name: name_or_index(d.name, first + i),
+            ty: d.ty,
+            locals,
+            body: b.out.into_iter().map(|(ins, _)| ins).collect(),
+            spans,
+        });
+    }
+    Ok(m)
+}
+
+/// Lit et assemble le fichier `.wat` `path`.
+pub fn assemble_file(path: &Path, sm: &mut SourceMap) -> Result<Module, Diagnostic> {
+    let src = fs::read_to_string(path)
+        .map_err(|e| Diagnostic::new(code!("G0301"), tr!("io.read", path.display(), e), None))?;
+    let file = sm.add(path, src);
+    module(&sm.file(file).src, file).map_err(|e| {
+        let span = Span::new(file, e.offset, e.offset + e.len);
+        Diagnostic::new(code!("G1001"), e.message, Some(span))
+    })
+}
@@ -0,0 +1,180 @@
+//! Attributs `@nom` / `@nom(args)` posés devant un item ou une instruction,
+//! et `@!nom(args)` en tête de fichier.
+//!
+//! Le parser les range tels quels dans l'AST après `check`; chaque passe
+//! lit ceux qui la concernent (`find`). Ajouter un modificateur = une ligne
+//! dans `KNOWN`, sans nouveau mot-clé.
+
+use crate::codes::code;
+use crate::diag::Span;
+use crate::i18n::tr;
+use crate::lints;
+use crate::suggest;
+use crate::parser::{Attr, ExprKind};
+
+/// Ce qui porte l'attribut.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target { Fn, Const, Global, Stmt, File }
+
+/// Forme des arguments acceptés.
+#[derive(Clone, Copy)]
+enum Args {
+    None,      // @inline
+    OptStr,    // @export / @export("nom")
+    Cond,      // @cfg(expr)
+    Lints,     // @!allow(nom, ...)
+}
+
+/// (nom, arguments, cibles permises)
+const KNOWN: &[(&str, Args, &[Target])] = &[
+    ("export", Args::OptStr, &[Target::Fn, Target::Global]),
+    ("inline", Args::None, &[Target::Fn]),
+    ("cold", Args::None, &[Target::Fn]),
+    ("deprecated", Args::OptStr, &[Target::Fn, Target::Const, Target::Global]),
+    ("test", Args::None, &[Target::Fn]),
+    ("cfg", Args::Cond, &[Target::Fn, Target::Const, Target::Global, Target::Stmt]),
+    ("allow", Args::Lints, &[Target::File]),
+    ("warn", Args::Lints, &[Target::File]),
+    ("deny", Args::Lints, &[Target::File]),
+];
+
+fn target_name(t: Target) -> String {
+    match t {
+        Target::Fn => tr!("attr.target.fn"),
+        Target::Const => tr!("attr.target.const"),
+        Target::Global => tr!("attr.target.global"),
+        Target::Stmt => tr!("attr.target.stmt"),
+        Target::File => tr!("attr.target.file"),
+    }
+}
+
+/// Refus de `check`; `suggestion` (un nom connu proche de celui écrit)
+/// remplacerait le texte de `span`.
+pub struct Error {
+    pub code: &'static str,
+    pub message: String,
+    pub span: Span,
+    pub suggestion: Option<String>,
+}
+
+fn err(code: &'static str, message: String, span: Span) -> Error {
+    Error { code, message, span, suggestion: None }
+}
+
+/// Vérifie noms, arguments, cibles et combinaisons.
+pub fn check(attrs: &[Attr], target: Target) -> Result<(), Error> {
+    for (i, a) in attrs.iter().enumerate() {
+        let Some(&(_, args, targets)) = KNOWN.iter().find(|(n, _, _)| *n == a.name) else {
+            // parmi les attributs permis ici seulement
+            let names = KNOWN.iter().filter(|(_, _, ts)| ts.contains(&target)).map(|(n, _, _)| *n);
+            let suggestion = suggest::closest(&a.name, names).map(str::to_string);
+            return Err(Error { suggestion, ..err(code!("G0201"), tr!("attr.unknown", a.name), a.name_span) });
+        };
+        if !targets.contains(&target) {
+            return Err(err(code!("G0202"), tr!("attr.wrong_target", a.name, target_name(target)), a.span));
+        }
+        if attrs[..i].iter().any(|b| b.name == a.name) {
+            return Err(err(code!("G0203"), tr!("attr.repeated", a.name), a.span));
+        }
+        let ok = match args {
+            Args::None => a.args.is_empty(),
+            Args::OptStr => a.args.is_empty() || (a.args.len() == 1 && a.str_arg().is_some()),
+            Args::Cond => a.args.len() == 1 && a.str_arg().is_none(),
+            Args::Lints => !a.args.is_empty() && a.lint_args().all(|l| l.is_some()),
+        };
+        if !ok {
+            let usage = match args {
+                Args::None => tr!("attr.no_args", a.name),
+                Args::OptStr => tr!("attr.opt_str", a.name),
+                Args::Cond => tr!("attr.cond", a.name),
+                Args::Lints => tr!("attr.lints", a.name),
+            };
+            return Err(err(code!("G0204"), usage, a.span));
+        }
+        if let Args::Lints = args {
+            for (arg, name) in a.args.iter().zip(a.lint_args().flatten()) {
+                if lints::code(name).is_none() {
+                    let suggestion = lints::closest(name).map(str::to_string);
+                    return Err(Error { suggestion, ..err(code!("G0210"), tr!("attr.unknown_lint", name, lints::names()), arg.span) });
+                }
+            }
+        }
+    }
+    if let (Some(_), Some(cold)) = (find(attrs, "inline"), find(attrs, "cold")) {
+        return Err(err(code!("G0205"), tr!("attr.inline_cold"), cold.span));
+    }
+    Ok(())
+}
+
+pub fn find<'a>(attrs: &'a [Attr], name: &str) -> Option<&'a Attr> {
+    attrs.iter().find(|a| a.name == name)
+}
+
+pub fn has(attrs: &[Attr], name: &str) -> bool {
+    find(attrs, name).is_some()
+}
+
+impl Attr {
+    /// L'argument chaîne de `@export("x")` / `@deprecated("x")`.
+    pub fn str_arg(&self) -> Option<&str> {
+        match self.args.first().map(|e| &e.kind) {
+            Some(ExprKind::Str(s)) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Les noms de `@!allow(a, b)`; `None` pour un argument qui n'est pas
+    /// un simple nom.
+    pub fn lint_args(&self) -> impl Iterator<Item = Option<&str>> {
+        self.args.iter().map(|e| match &e.kind {
+            ExprKind::Var(n) => Some(n.as_str()),
+            _ => None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diag::{Diagnostic, SourceMap};
+    use crate::{codegen, lints, modules};
+    use std::collections::HashMap;
+
+    /// Programme `src` chargé puis abaissé; avertissements à part.
+    fn compile(name: &str, src: &str) -> (Result<(), Diagnostic>, Vec<&'static str>) {
+        let path = std::env::temp_dir().join(format!("gaufre-attrs-{name}.gfr"));
+        std::fs::write(&path, src).unwrap();
+        let mut sm = SourceMap::default();
+        let mut warnings = Vec::new();
+        let r = modules::load_program(&path, &mut sm, &HashMap::new(), "en", &lints::Config::default(), &mut warnings)
+            .and_then(|prog| codegen::lower(&prog, false).map(|_| ()).map_err(Into::into));
+        (r, warnings.iter().map(|d| d.code).collect())
+    }
+
+    fn code(name: &str, src: &str) -> Option<&'static str> {
+        compile(name, src).0.err().map(|d| d.code)
+    }
+
+    #[test]
+    fn accepted_attributes() {
+        let src = "@!allow(unused_loop_var)\n@deprecated(\"use g\")\nfn f() -> i32 {\n  return 1\n}\n@inline\n@export(\"twice\")\nfn g(x: i32) -> i32 {\n  return x * 2\n}\n@cold\nfn h() {}\n@cfg(0)\nconst C = 1\nfn main() {\n  for k = 1 to 2 {}\n  log(f(), g(2))\n  h()\n}\n";
+        let (r, warnings) = compile("ok", src);
+        r.unwrap_or_else(|d| panic!("{}", d.message));
+        assert_eq!(warnings, ["G0314"]);
+    }
+
+    #[test]
+    fn rejected_attributes() {
+        let d = compile("unknown", "@exprot\nfn main() {}\n").0.unwrap_err();
+        assert_eq!((d.code, d.fixes[0].replacement.as_str()), ("G0201", "export"));
+        assert_eq!(code("target", "@inline\nconst C = 1\nfn main() {}\n"), Some("G0202"));
+        assert_eq!(code("repeated", "@cold\n@cold\nfn f() {}\nfn main() {}\n"), Some("G0203"));
+        assert_eq!(code("args", "@export(1)\nfn f() {}\nfn main() {}\n"), Some("G0204"));
+        assert_eq!(code("inline-cold", "@inline\n@cold\nfn f() -> i32 {\n  return 1\n}\nfn main() {}\n"), Some("G0205"));
+        assert_eq!(code("inline-body", "@inline\nfn f() {\n  log(1)\n}\nfn main() {}\n"), Some("G0206"));
+        assert_eq!(code("test-sig", "@test\nfn t(x: i32) {}\nfn main() {}\n"), Some("G0207"));
+        assert_eq!(code("test-call", "@test\nfn t() {}\nfn main() {\n  t()\n}\n"), Some("G0208"));
+        assert_eq!(code("dup-export", "@export(\"x\")\nfn f() {}\n@export(\"x\")\nfn g() {}\nfn main() {}\n"), Some("G0209"));
+        assert_eq!(code("lint", "@!allow(unused_loop)\nfn main() {}\n"), Some("G0210"));
+        assert_eq!(code("export-main", "@export\nfn main() {}\n"), Some("G0211"));
+    }
+}
@@ -0,0 +1,561 @@
+//! Format binaire WebAssembly (version 1) : en-tête puis sections type,
+//! import, function, table, memory, global, export, elem, code et data, les
+//! entiers en LEB128. Le texte des blocs `wat { ... }` y est assemblé par `asm`.
+//!
+//! Suivent trois sections personnalisées pour le débogage : `name` (noms
+//! des fonctions, locals, labels et globales, que les outils de
+//! développement des navigateurs affichent dans les piles d'appels),
+//! `producers` (langage et version du compilateur) et `gaufre.sources`
+//! (chemin et empreinte SHA-256 de chaque fichier lu), puis
+//! `sourceMappingURL` si une source map accompagne le module.
+
+use crate::asm::{self, Scope};
+use crate::codes::code;
+use crate::diag::{Diagnostic, SourceFile, Span};
+use crate::i18n::tr;
+use crate::sha256;
+use crate::wasm::{ExportKind, Func, Instr, Module, ValType};
+use std::fmt;
+
+/// Bloc `wat { ... }` que l'assembleur ne sait pas traduire.
+#[derive(Debug, Clone)]
+pub struct AsmError {
+    pub func: String,
+    pub message: String,
+    pub span: Span,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", tr!("binary.asm", self.func, self.message))
+    }
+}
+impl std::error::Error for AsmError {}
+impl From<AsmError> for Diagnostic {
+    fn from(e: AsmError) -> Self {
+        let span = (e.span != Span::default()).then_some(e.span);
+        Diagnostic::new(code!("G0507"), e.to_string(), span)
+    }
+}
+
+fn uleb(out: &mut Vec<u8>, mut v: u64) {
+    loop {
+        let byte = (v & 0x7F) as u8;
+        v >>= 7;
+        if v == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn sleb(out: &mut Vec<u8>, mut v: i64) {
+    loop {
+        let byte = (v & 0x7F) as u8;
+        v >>= 7;
+        // fini quand le reste n'est plus que l'extension du bit de signe
+        if (v == 0 && byte & 0x40 == 0) || (v == -1 && byte & 0x40 != 0) {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn name(out: &mut Vec<u8>, s: &str) {
+    uleb(out, s.len() as u64);
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn val(out: &mut Vec<u8>, t: ValType) {
+    out.push(match t {
+        ValType::I32 => 0x7F,
+    });
+}
+
+fn vals(out: &mut Vec<u8>, ts: &[ValType]) {
+    uleb(out, ts.len() as u64);
+    ts.iter().for_each(|&t| val(out, t));
+}
+
+fn block_type(out: &mut Vec<u8>, result: Option<ValType>) {
+    match result {
+        Some(t) => val(out, t),
+        None => out.push(0x40),
+    }
+}
+
+/// Section `id` de `count` éléments, omise si elle est vide.
+fn section(out: &mut Vec<u8>, id: u8, count: usize, items: impl FnOnce(&mut Vec<u8>)) {
+    if count == 0 {
+        return;
+    }
+    let mut body = Vec::new();
+    uleb(&mut body, count as u64);
+    items(&mut body);
+    out.push(id);
+    uleb(out, body.len() as u64);
+    out.extend(body);
+}
+
+/// Section personnalisée nommée `section`, omise si elle est vide.
+fn custom(out: &mut Vec<u8>, section: &str, body: Vec<u8>) {
+    if body.is_empty() {
+        return;
+    }
+    let mut named = Vec::new();
+    name(&mut named, section);
+    out.push(0);
+    uleb(out, (named.len() + body.len()) as u64);
+    out.extend(named);
+    out.extend(body);
+}
+
+/// Sous-section `id` de la section `name` : une table index -> nom, ou
+/// index -> table pour les locals et labels de chaque fonction.
+fn name_map(out: &mut Vec<u8>, id: u8, names: &[(u32, &str)]) {
+    if names.is_empty() {
+        return;
+    }
+    let mut body = Vec::new();
+    uleb(&mut body, names.len() as u64);
+    for &(i, n) in names {
+        uleb(&mut body, i as u64);
+        name(&mut body, n);
+    }
+    out.push(id);
+    uleb(out, body.len() as u64);
+    out.extend(body);
+}
+
+fn indirect_name_map(out: &mut Vec<u8>, id: u8, maps: &[(u32, Vec<(u32, &str)>)]) {
+    let maps: Vec<_> = maps.iter().filter(|(_, names)| !names.is_empty()).collect();
+    if maps.is_empty() {
+        return;
+    }
+    let mut body = Vec::new();
+    uleb(&mut body, maps.len() as u64);
+    for (i, names) in maps {
+        uleb(&mut body, *i as u64);
+        uleb(&mut body, names.len() as u64);
+        for &(j, n) in names {
+            uleb(&mut body, j as u64);
+            name(&mut body, n);
+        }
+    }
+    out.push(id);
+    uleb(out, body.len() as u64);
+    out.extend(body);
+}
+
+fn instr(out: &mut Vec<u8>, ins: &Instr) {
+    match ins {
+        Instr::Unreachable => out.push(0x00),
+        Instr::Nop => out.push(0x01),
+        Instr::Block { result, .. } => {
+            out.push(0x02);
+            block_type(out, *result);
+        }
+        Instr::Loop { result, .. } => {
+            out.push(0x03);
+            block_type(out, *result);
+        }
+        Instr::If { result } => {
+            out.push(0x04);
+            block_type(out, *result);
+        }
+        Instr::Else => out.push(0x05),
+        Instr::End => out.push(0x0B),
+        Instr::Br(d) => {
+            out.push(0x0C);
+            uleb(out, *d as u64);
+        }
+        Instr::BrIf(d) => {
+            out.push(0x0D);
+            uleb(out, *d as u64);
+        }
+        Instr::Return => out.push(0x0F),
+        Instr::Call(f) => {
+            out.push(0x10);
+            uleb(out, *f as u64);
+        }
+        Instr::CallIndirect(t) => {
+            out.push(0x11);
+            uleb(out, *t as u64);
+            // table 0
+            out.push(0x00);
+        }
+        Instr::Drop => out.push(0x1A),
+        Instr::Select => out.push(0x1B),
+        Instr::LocalGet(i) | Instr::LocalSet(i) | Instr::LocalTee(i) | Instr::GlobalGet(i) | Instr::GlobalSet(i) => {
+            out.push(match ins {
+                Instr::LocalGet(_) => 0x20,
+                Instr::LocalSet(_) => 0x21,
+                Instr::LocalTee(_) => 0x22,
+                Instr::GlobalGet(_) => 0x23,
+                _ => 0x24,
+            });
+            uleb(out, *i as u64);
+        }
+        Instr::Mem(op, a) => {
+            out.push(op.opcode());
+            uleb(out, a.align as u64);
+            uleb(out, a.offset as u64);
+        }
+        // l'immédiat est l'index de la mémoire, toujours 0
+        Instr::MemorySize => out.extend([0x3F, 0x00]),
+        Instr::MemoryGrow => out.extend([0x40, 0x00]),
+        Instr::I32Const(v) => {
+            out.push(0x41);
+            sleb(out, *v as i64);
+        }
+        Instr::I32(op) => out.push(op.opcode()),
+        // préfixe 0xFC : mémoire de destination puis de source
+        Instr::MemoryCopy => out.extend([0xFC, 10, 0x00, 0x00]),
+        Instr::MemoryFill => out.extend([0xFC, 11, 0x00]),
+        Instr::Raw(_) => unreachable!("bloc `wat` assemblé par `code`"),
+    }
+}
+
+/// Corps d'une fonction : locals groupés par type, instructions, `end`.
+/// Range dans `named` le nom de chaque bloc nommé, numéroté dans l'ordre
+/// d'apparition de tous les `block`, `loop` et `if`, et dans `offsets` la
+/// position de chaque instruction dans le corps avec son Span.
+fn code(
+    m: &Module,
+    idx: u32,
+    f: &Func,
+    named: &mut Vec<(u32, String)>,
+    offsets: &mut Vec<(usize, Span)>,
+) -> Result<Vec<u8>, AsmError> {
+    let params = m.type_of(idx).params.len();
+    let mut groups: Vec<(u32, ValType)> = Vec::new();
+    for l in &f.locals[params..] {
+        match groups.last_mut() {
+            Some((n, t)) if *t == l.ty => *n += 1,
+            _ => groups.push((1, l.ty)),
+        }
+    }
+    let mut out = Vec::new();
+    uleb(&mut out, groups.len() as u64);
+    for (n, t) in groups {
+        uleb(&mut out, n as u64);
+        val(&mut out, t);
+    }
+
+    // blocs ouverts, pour les `br $nom` des blocs `wat`
+    let mut labels: Vec<Option<&str>> = Vec::new();
+    let mut blocks = 0;
+    let mut label = |ins: &Instr| match ins {
+        Instr::Block { label, .. } | Instr::Loop { label, .. } => {
+            if let Some(l) = label {
+                named.push((blocks, l.clone()));
+            }
+            blocks += 1;
+        }
+        Instr::If { .. } => blocks += 1,
+        _ => {}
+    };
+    for (k, ins) in f.body.iter().enumerate() {
+        let span = f.spans.get(k).copied().unwrap_or_default();
+        label(ins);
+        match ins {
+            Instr::Block { label, .. } | Instr::Loop { label, .. } => labels.push(label.as_deref()),
+            Instr::If { .. } => labels.push(None),
+            Instr::End => {
+                labels.pop();
+            }
+            Instr::Raw(text) => {
+                let scope = Scope {
+                    locals: f.locals.iter().map(|l| l.name.as_str()).collect(),
+                    globals: m.globals.iter().map(|g| g.name.as_str()).collect(),
+                    funcs: (0..(m.imports.len() + m.funcs.len()) as u32).map(|g| m.func_name(g)).collect(),
+                    types: Vec::new(),
+                    labels: labels.clone(),
+                };
+                let assembled = asm::instrs(text, &scope).map_err(|e| AsmError {
+                    func: f.name.clone(),
+                    message: e.message,
+                    span,
+                })?;
+                for i in &assembled {
+                    label(i);
+                    offsets.push((out.len(), span));
+                    instr(&mut out, i);
+                }
+                continue;
+            }
+            _ => {}
+        }
+        offsets.push((out.len(), span));
+        instr(&mut out, ins);
+    }
+    out.push(0x0B);
+    Ok(out)
+}
+
+/// Section `name` : fonctions (imports compris), locals, labels et globales.
+fn names(m: &Module, labels: &[Vec<(u32, String)>]) -> Vec<u8> {
+    let first = m.imports.len() as u32;
+    let funcs: Vec<(u32, &str)> = (0..first + m.funcs.len() as u32).map(|f| (f, m.func_name(f))).collect();
+    let locals: Vec<(u32, Vec<(u32, &str)>)> = m
+        .funcs
+        .iter()
+        .enumerate()
+        .map(|(i, f)| (first + i as u32, f.locals.iter().enumerate().map(|(j, l)| (j as u32, l.name.as_str())).collect()))
+        .collect();
+    let labels: Vec<(u32, Vec<(u32, &str)>)> = labels
+        .iter()
+        .enumerate()
+        .map(|(i, ls)| (first + i as u32, ls.iter().map(|(j, l)| (*j, l.as_str())).collect()))
+        .collect();
+    let globals: Vec<(u32, &str)> = m.globals.iter().enumerate().map(|(i, g)| (i as u32, g.name.as_str())).collect();
+    // `asm` nomme par leur index ce qui n'a pas d'identifiant
+    let has_name = |(i, n): &(u32, &str)| n.parse() != Ok(*i);
+    let funcs: Vec<_> = funcs.into_iter().filter(has_name).collect();
+    let globals: Vec<_> = globals.into_iter().filter(has_name).collect();
+    let locals: Vec<_> = locals.into_iter().map(|(f, ls)| (f, ls.into_iter().filter(has_name).collect())).collect();
+    let mut out = Vec::new();
+    name_map(&mut out, 1, &funcs);
+    indirect_name_map(&mut out, 2, &locals);
+    // sous-sections de la proposition « extended name section »
+    indirect_name_map(&mut out, 3, &labels);
+    name_map(&mut out, 7, &globals);
+    out
+}
+
+/// Section `producers` : langage source et outil qui a produit le module.
+fn producers() -> Vec<u8> {
+    let version = env!("CARGO_PKG_VERSION");
+    let mut out = Vec::new();
+    uleb(&mut out, 2);
+    for (field, version) in [("language", ""), ("processed-by", version)] {
+        name(&mut out, field);
+        uleb(&mut out, 1);
+        name(&mut out, "gaufre");
+        name(&mut out, version);
+    }
+    out
+}
+
+/// Section `gaufre.sources` : pour chaque fichier, son chemin puis les 32
+/// octets de son empreinte SHA-256.
+fn sources(files: &[SourceFile]) -> Vec<u8> {
+    if files.is_empty() {
+        return Vec::new();
+    }
+    let mut out = Vec::new();
+    uleb(&mut out, files.len() as u64);
+    for f in files {
+        name(&mut out, &f.path.to_string_lossy());
+        out.extend(sha256::digest(f.src.as_bytes()));
+    }
+    out
+}
+
+/// Module encodé, et pour la source map la position dans `bytes` de chaque
+/// instruction avec le Span d'où elle vient.
+pub struct Binary {
+    pub bytes: Vec<u8>,
+    pub offsets: Vec<(usize, Span)>,
+}
+
+/// Module au format binaire, avec les sections de débogage; `files` sont
+/// les sources lues pour le produire, `map_url` l'adresse de la source map
+/// (relative au `.wasm`). Seul un bloc `wat` inassemblable échoue.
+pub fn encode(m: &Module, files: &[SourceFile], map_url: Option<&str>) -> Result<Binary, AsmError> {
+    let first = m.imports.len() as u32;
+    let mut labels = vec![Vec::new(); m.funcs.len()];
+    let mut offsets = vec![Vec::new(); m.funcs.len()];
+    let codes = m
+        .funcs
+        .iter()
+        .zip(labels.iter_mut().zip(&mut offsets))
+        .enumerate()
+        .map(|(i, (f, (ls, os)))| code(m, first + i as u32, f, ls, os))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut out = b"\0asm".to_vec();
+    out.extend(1u32.to_le_bytes());
+    section(&mut out, 1, m.types.len(), |s| {
+        for t in &m.types {
+            s.push(0x60);
+            vals(s, &t.params);
+            vals(s, &t.results);
+        }
+    });
+    section(&mut out, 2, m.imports.len(), |s| {
+        for i in &m.imports {
+            name(s, &i.module);
+            name(s, &i.field);
+            s.push(0x00);
+            uleb(s, i.ty as u64);
+        }
+    });
+    section(&mut out, 3, m.funcs.len(), |s| m.funcs.iter().for_each(|f| uleb(s, f.ty as u64)));
+    section(&mut out, 4, m.table.iter().len(), |s| {
+        if let Some(size) = m.table {
+            // funcref, limites sans maximum
+            s.push(0x70);
+            s.push(0x00);
+            uleb(s, size as u64);
+        }
+    });
+    section(&mut out, 5, m.memory.iter().len(), |s| {
+        if let Some(pages) = m.memory {
+            // limites sans maximum
+            s.push(0x00);
+            uleb(s, pages as u64);
+        }
+    });
+    section(&mut out, 6, m.globals.len(), |s| {
+        for g in &m.globals {
+            val(s, g.ty);
+            s.push(u8::from(g.mutable));
+            instr(s, &Instr::I32Const(g.init));
+            s.push(0x0B);
+        }
+    });
+    section(&mut out, 7, m.exports.len(), |s| {
+        for e in &m.exports {
+            name(s, &e.name);
+            let (kind, idx) = match e.kind {
+                ExportKind::Func(i) => (0x00, i),
+                ExportKind::Memory(i) => (0x02, i),
+                ExportKind::Global(i) => (0x03, i),
+            };
+            s.push(kind);
+            uleb(s, idx as u64);
+        }
+    });
+    section(&mut out, 9, m.elems.len(), |s| {
+        for e in &m.elems {
+            // segment actif de la table 0
+            s.push(0x00);
+            instr(s, &Instr::I32Const(e.offset as i32));
+            s.push(0x0B);
+            uleb(s, e.funcs.len() as u64);
+            e.funcs.iter().for_each(|&f| uleb(s, f as u64));
+        }
+    });
+    // début de chaque corps dans la section, puis taille de la section
+    let mut starts = Vec::new();
+    let mut size = 0;
+    section(&mut out, 10, codes.len(), |s| {
+        for c in &codes {
+            uleb(s, c.len() as u64);
+            starts.push(s.len());
+            s.extend_from_slice(c);
+        }
+        size = s.len();
+    });
+    let base = out.len() - size;
+    let offsets = starts
+        .into_iter()
+        .zip(offsets)
+        .flat_map(|(start, os)| os.into_iter().map(move |(at, span)| (base + start + at, span)))
+        .collect();
+    section(&mut out, 11, m.data.len(), |s| {
+        for d in &m.data {
+            // segment actif de la mémoire 0
+            s.push(0x00);
+            instr(s, &Instr::I32Const(d.offset as i32));
+            s.push(0x0B);
+            uleb(s, d.bytes.len() as u64);
+            s.extend_from_slice(&d.bytes);
+        }
+    });
+    custom(&mut out, "name", names(m, &labels));
+    custom(&mut out, "producers", producers());
+    custom(&mut out, "gaufre.sources", sources(files));
+    if let Some(url) = map_url {
+        let mut body = Vec::new();
+        name(&mut body, url);
+        custom(&mut out, "sourceMappingURL", body);
+    }
+    Ok(Binary { bytes: out, offsets })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{encode, sleb, uleb};
+    use crate::{asm, validate, wat};
+
+    fn u(v: u64) -> Vec<u8> {
+        let mut out = Vec::new();
+        uleb(&mut out, v);
+        out
+    }
+
+    fn s(v: i64) -> Vec<u8> {
+        let mut out = Vec::new();
+        sleb(&mut out, v);
+        out
+    }
+
+    #[test]
+    fn uleb_boundaries() {
+        assert_eq!(u(0), [0x00]);
+        assert_eq!(u(63), [0x3F]);
+        assert_eq!(u(64), [0x40]);
+        assert_eq!(u(127), [0x7F]);
+        assert_eq!(u(128), [0x80, 0x01]);
+        assert_eq!(u(i32::MAX as u64), [0xFF, 0xFF, 0xFF, 0xFF, 0x07]);
+        assert_eq!(u(u32::MAX as u64), [0xFF, 0xFF, 0xFF, 0xFF, 0x0F]);
+    }
+
+    #[test]
+    fn sleb_boundaries() {
+        assert_eq!(s(0), [0x00]);
+        assert_eq!(s(-1), [0x7F]);
+        // le bit 6 du dernier octet porte le signe
+        assert_eq!(s(63), [0x3F]);
+        assert_eq!(s(64), [0xC0, 0x00]);
+        assert_eq!(s(-64), [0x40]);
+        assert_eq!(s(-65), [0xBF, 0x7F]);
+        assert_eq!(s(i32::MAX as i64), [0xFF, 0xFF, 0xFF, 0xFF, 0x07]);
+        assert_eq!(s(i32::MIN as i64), [0x80, 0x80, 0x80, 0x80, 0x78]);
+    }
+
+    const WAT: &str = r#"
+(module
+  (import "env" "log" (func $log (param i32)))
+  (memory (export "memory") 1)
+  (global $n (mut i32) (i32.const -65))
+  (func $main (export "main") (local $i i32)
+    (block $done
+      (loop $next
+        (br_if $done (i32.ge_s (local.get $i) (i32.const 64)))
+        (call $log (local.get $i))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        br $next))
+    global.get $n
+    call $log)
+  (data (i32.const 16) "gaufre\0a"))
+"#;
+
+    // texte assemblé, validé puis encodé ; réimprimé et réassemblé, il
+    // donne les mêmes octets
+    #[test]
+    fn assemble_encode_validate() {
+        let m = asm::module(WAT, 0).unwrap_or_else(|e| panic!("{}", e.message));
+        validate::module(&m, true).unwrap_or_else(|e| panic!("{e}"));
+        let bytes = encode(&m, &[], None).unwrap_or_else(|e| panic!("{e}")).bytes;
+        assert_eq!(bytes[..8], *b"\0asm\x01\0\0\0");
+        // `i32.const 64` puis `i32.ge_s`, et la globale initialisée à -65
+        assert!(bytes.windows(3).any(|w| w == [0x41, 0xC0, 0x00]));
+        assert!(bytes.windows(4).any(|w| w == [0x41, 0xBF, 0x7F, 0x0B]));
+
+        let again = asm::module(&wat::print(&m), 0).unwrap_or_else(|e| panic!("{}", e.message));
+        validate::module(&again, true).unwrap_or_else(|e| panic!("{e}"));
+        assert_eq!(encode(&again, &[], None).unwrap_or_else(|e| panic!("{e}")).bytes, bytes);
+    }
+
+    #[test]
+    fn invalid_module_rejected() {
+        let m = asm::module("(module (func $f (result i32) i32.const 1 i32.const 2))", 0)
+            .unwrap_or_else(|e| panic!("{}", e.message));
+        assert!(validate::module(&m, true).is_err());
+    }
+}
@@ -0,0 +1,286 @@
+//! Ligne de commande : `gaufre [options] <fichier.gfr> [sortie.wat]`,
+//! `gaufre check [--validate] <fichier.gfr>`, `gaufre assemble
+//! [--source-map] <fichier.wat> [sortie.wasm]`, `gaufre validate <fichier.wat>`, `gaufre translate --to PROFIL <fichier.gfr> [sortie.gfr]` ou
+//! `gaufre explain G0101`.
+
+use crate::grammar;
+use crate::i18n::{tr, Lang};
+use crate::lints::{self, Level};
+use std::collections::HashMap;
+use std::path::Path;
+
+pub fn usage() -> String {
+    tr!("cli.usage")
+}
+
+/// Valeurs de l'option `name` dans `args`, sous la forme `name valeur` ou
+/// `name=valeur`.
+fn values<'a>(args: &'a [String], name: &'a str) -> impl Iterator<Item = Option<&'a str>> {
+    let mut it = args.iter();
+    std::iter::from_fn(move || {
+        for a in it.by_ref() {
+            match a.strip_prefix(name) {
+                Some("") => return Some(it.next().map(String::as_str)),
+                Some(rest) if rest.starts_with('=') => return Some(rest.strip_prefix('=')),
+                _ => {}
+            }
+        }
+        None
+    })
+}
+
+/// Langue des messages : `--lang fr|en` ou `--lang=fr|en`, sinon
+/// l'environnement. Lue avant `parse` pour que ses propres erreurs soient
+/// déjà traduites.
+pub fn lang(args: &[String]) -> Lang {
+    values(args, "--lang").flatten().find_map(Lang::parse).unwrap_or_else(Lang::from_env)
+}
+
+/// Forme des diagnostics sur la sortie d'erreur.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorFormat {
+    #[default]
+    Human,
+    /// un objet JSON par ligne et par diagnostic
+    Json,
+}
+impl ErrorFormat {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "human" => Some(Self::Human),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+}
+
+/// `--error-format=json` ou `--error-format json`; comme `lang`, lue avant
+/// `parse` pour que les erreurs d'usage suivent déjà ce format.
+pub fn error_format(args: &[String]) -> ErrorFormat {
+    values(args, "--error-format").flatten().find_map(ErrorFormat::parse).unwrap_or_default()
+}
+
+/// Ce que produit un build : `--emit wat` (défaut), `--emit wasm`, le
+/// module binaire, ou `--emit ir`, le dump de la représentation
+/// intermédiaire.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Emit {
+    #[default]
+    Wat,
+    Wasm,
+    Ir,
+}
+impl Emit {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "wat" => Some(Self::Wat),
+            "wasm" => Some(Self::Wasm),
+            "ir" => Some(Self::Ir),
+            _ => None,
+        }
+    }
+    /// extension de la sortie quand elle n'est pas donnée
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Wat => "wat",
+            Self::Wasm => "wasm",
+            Self::Ir => "ir",
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub enum Command {
+    #[default]
+    Build,
+    /// compile sans rien écrire; `--validate` valide aussi le module WebAssembly
+    Check { validate: bool },
+    /// `gaufre assemble fichier.wat [sortie.wasm]`
+    Assemble,
+    /// `gaufre validate fichier.wat` : lit et valide sans rien écrire
+    Validate,
+    /// `from` absent : le `#grammar` du fichier, sinon `en`
+    Translate { from: Option<String>, to: String },
+    /// `gaufre explain G0101` : pas de fichier source
+    Explain(String),
+}
+
+#[derive(Debug, Default)]
+pub struct Options {
+    pub command: Command,
+    pub input: String,
+    pub output: Option<String>,
+    /// `-D DEBUG` (vaut 1) ou `-D LEVEL=3`; un nom absent vaut 0 dans `if const`
+    pub defines: HashMap<String, i32>,
+    /// `--grammar fr` ou `--grammar maison.gram` : profil des fichiers sans
+    /// `#grammar`
+    pub grammar: String,
+    /// `--test` : compile les `@test` et un `main` qui les lance
+    pub test: bool,
+    /// `--emit wat|wasm|ir`
+    pub emit: Emit,
+    /// `--source-map` : écrit aussi `sortie.wasm.map` à côté du binaire
+    pub source_map: bool,
+    /// `-A nom`, `-W nom`, `--deny nom`, `-W error`
+    pub lints: lints::Config,
+    pub error_format: ErrorFormat,
+}
+
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Options, String> {
+    let mut opts = Options { grammar: "en".into(), ..Options::default() };
+    let mut positional = Vec::new();
+    let mut args = args.into_iter().peekable();
+    // `--lang` et `--error-format` peuvent précéder la sous-commande : mis de
+    // côté le temps de la reconnaître, puis lus avec les autres options
+    let mut global = Vec::new();
+    while let Some(a) = args.next_if(|a| a == "--lang" || a.starts_with("--lang=") || a.starts_with("--error-format")) {
+        let takes_value = a == "--lang" || a == "--error-format";
+        global.push(a);
+        if takes_value {
+            global.extend(args.next());
+        }
+    }
+    let (mut from, mut to) = (None, None);
+    let explain = args.next_if(|a| a == "explain").is_some();
+    let translate = !explain && args.next_if(|a| a == "translate").is_some();
+    let check = !explain && !translate && args.next_if(|a| a == "check").is_some();
+    let wat = match args.next_if(|a| !explain && !translate && !check && (a == "assemble" || a == "validate")) {
+        Some(a) if a == "assemble" => Some(Command::Assemble),
+        Some(_) => Some(Command::Validate),
+        None => None,
+    };
+    let build = !explain && !translate && wat.is_none();
+    let mut validate = false;
+    let mut args = global.into_iter().chain(args);
+    while let Some(a) = args.next() {
+        if translate && (a == "--from" || a == "--to") {
+            let p = profile(&a, args.next())?;
+            if a == "--from" { from = Some(p) } else { to = Some(p) }
+        } else if build && a == "--test" {
+            opts.test = true;
+        } else if check && a == "--validate" {
+            validate = true;
+        } else if (build && !check || wat == Some(Command::Assemble)) && a == "--source-map" {
+            opts.source_map = true;
+        } else if build && !check && a == "--emit" {
+            opts.emit = args.next().as_deref().and_then(Emit::parse).ok_or_else(|| tr!("cli.emit_arg"))?;
+        } else if build && a == "--grammar" {
+            opts.grammar = profile(&a, args.next())?;
+        } else if let Some(rest) = a.strip_prefix("--error-format") {
+            let value = match rest {
+                "" => args.next(),
+                _ => rest.strip_prefix('=').map(str::to_string),
+            };
+            opts.error_format = value.as_deref().and_then(ErrorFormat::parse).ok_or_else(|| tr!("cli.error_format_arg"))?;
+        } else if let Some(rest) = a.strip_prefix("--lang").filter(|r| r.is_empty() || r.starts_with('=')) {
+            // déjà appliqué par `lang`; seulement vérifié ici
+            let value = match rest {
+                "" => args.next(),
+                _ => Some(rest[1..].to_string()),
+            };
+            value.as_deref().and_then(Lang::parse).ok_or_else(|| tr!("cli.lang_arg"))?;
+        } else if build && matches!(a.as_str(), "-A" | "--allow" | "-W" | "--warn" | "--deny") {
+            let name = args.next().ok_or_else(|| tr!("cli.lint_arg", a, lints::names()))?;
+            if matches!(a.as_str(), "-W" | "--warn") && name == "error" {
+                opts.lints.warnings_are_errors = true;
+                continue;
+            }
+            if lints::code(&name).is_none() {
+                return Err(tr!("cli.lint_arg", a, lints::names()));
+            }
+            let level = match a.as_str() {
+                "-A" | "--allow" => Level::Allow,
+                "-W" | "--warn" => Level::Warn,
+                _ => Level::Deny,
+            };
+            opts.lints.levels.push((name, level));
+        } else if let Some(rest) = a.strip_prefix("-D").filter(|_| build) {
+            // `-D NOM` ou `-DNOM`
+            let def = if rest.is_empty() {
+                args.next().ok_or_else(|| tr!("cli.define_arg"))?
+            } else {
+                rest.to_string()
+            };
+            let (name, value) = parse_define(&def)?;
+            opts.defines.insert(name, value);
+        } else if a.starts_with('-') && a.len() > 1 {
+            return Err(tr!("cli.unknown_option", a));
+        } else {
+            positional.push(a);
+        }
+    }
+    if build && opts.source_map && opts.emit != Emit::Wasm {
+        return Err(tr!("cli.source_map_emit"));
+    }
+    let mut positional = positional.into_iter();
+    if explain {
+        opts.command = Command::Explain(positional.next().ok_or_else(|| tr!("cli.explain_code"))?);
+    } else if translate {
+        let to = to.ok_or_else(|| tr!("cli.translate_to"))?;
+        opts.command = Command::Translate { from, to };
+    } else if check {
+        opts.command = Command::Check { validate };
+    } else if let Some(command) = wat {
+        opts.command = command;
+    }
+    if !explain {
+        opts.input = positional.next().ok_or_else(|| tr!("cli.missing_input"))?;
+        // `check` et `validate` n'écrivent rien
+        if !check && opts.command != Command::Validate {
+            opts.output = positional.next();
+        }
+    }
+    if let Some(extra) = positional.next() {
+        return Err(tr!("cli.extra_arg", extra));
+    }
+    Ok(opts)
+}
+
+/// Valeur de `--grammar`, `--from` ou `--to` : un profil intégré ou un
+/// fichier existant (relatif au répertoire courant). Un fichier `.gram` mal
+/// formé reste une erreur de compilation, signalée à la lecture.
+fn profile(option: &str, value: Option<String>) -> Result<String, String> {
+    value
+        .filter(|p| grammar::Grammar::builtin(p).is_some() || Path::new(p).exists())
+        .ok_or_else(|| tr!("cli.profile_arg", option, grammar::BUILTIN_NAMES))
+}
+
+fn parse_define(def: &str) -> Result<(String, i32), String> {
+    let (name, value) = match def.split_once('=') {
+        Some((n, v)) => {
+            let v = v.parse::<i32>().map_err(|_| tr!("cli.define_value", def))?;
+            (n, v)
+        }
+        None => (def, 1),
+    };
+    let mut cs = name.chars();
+    let ok = cs.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && cs.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !ok {
+        return Err(tr!("cli.define_name", def));
+    }
+    Ok((name.to_string(), value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{error_format, lang, parse, Command, ErrorFormat};
+    use crate::i18n::Lang;
+
+    fn args(s: &str) -> Vec<String> {
+        s.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn lang_both_forms() {
+        assert_eq!(lang(&args("--lang en check a.gfr")), Lang::En);
+        assert_eq!(lang(&args("check --lang=en a.gfr")), Lang::En);
+        assert_eq!(lang(&args("--lang=fr explain G0101")), Lang::Fr);
+        assert_eq!(error_format(&args("--error-format=json a.gfr")), ErrorFormat::Json);
+        let opts = parse(args("--lang=en check --error-format json a.gfr")).unwrap();
+        assert_eq!(opts.command, Command::Check { validate: false });
+        assert_eq!(opts.error_format, ErrorFormat::Json);
+        assert!(parse(args("--lang=de a.gfr")).is_err());
+        assert!(parse(args("--langen a.gfr")).is_err());
+    }
+}
@@ -1,370 +1,1126 @@
-use crate::parser::{Program, Stmt, Expr};
-use std::collections::BTreeMap;
-
-/// échappement pour littéral WAT
-fn wat_escape(s: &str) -> String {
-    let mut out = String::new();
-    for &b in s.as_bytes() {
-        match b {
-            b'"' => out.push_str("\\\""),
-            b'\\' => out.push_str("\\\\"),
-            b'\n' => out.push_str("\\n"),
-            b'\r' => out.push_str("\\r"),
-            b'\t' => out.push_str("\\t"),
-            0x20..=0x7E => out.push(b as char),
-            _ => out.push_str(&format!("\\{:02x}", b)),
-        }
-    }
-    out
+use crate::attrs;
+use crate::consteval::{self, Ctx, EvalError};
+use crate::grammar;
+use crate::i18n::tr;
+use crate::codes::code;
+use crate::diag::{Diagnostic, Span};
+use crate::inline_wat;
+use crate::ir::{self, FuncId, GlobalId, Label, LocalId, Ty};
+use crate::parser::{Attr, BinOp, Expr, ExprKind, FnDecl, FnType, ForKind, Program, Stmt, StmtKind, Type};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+#[derive(Debug, Clone)]
+pub enum CodegenError {
+    Const { name: String, err: EvalError, span: Span },
+    Global { name: String, err: EvalError, span: Span },
+    InvalidStep(i32, Span),
+    Eval { err: EvalError, span: Span },
+    Attr(&'static str, String, Span),
+    NotPure { code: &'static str, reason: String, span: Span },
+    Arity { name: String, expected: usize, found: usize, span: Span },
+    NoValue(String, Span),
+    ReturnValue(Span),
+    MissingReturnValue(Span),
+    MissingReturn(String, Span),
+    NotCallable(String, Span),
+    Type { expected: String, found: String, span: Span },
+}
+impl fmt::Display for CodegenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Const { name, err, .. } => write!(f, "{}", tr!("codegen.const", name, err)),
+            Self::Global { name, err, .. } => write!(f, "{}", tr!("codegen.global", name, err)),
+            Self::InvalidStep(v, _) => write!(f, "{}", tr!("eval.invalid_step", v)),
+            Self::Eval { err, .. } => write!(f, "{}", tr!("codegen.eval", err)),
+            Self::NotPure { reason, .. } => write!(f, "{reason}"),
+            Self::Attr(_, m, _) => write!(f, "{m}"),
+            Self::Arity { name, expected, found, .. } => write!(f, "{}", tr!("codegen.arity", name, expected, found)),
+            Self::NoValue(n, _) => write!(f, "{}", tr!("codegen.no_value", n)),
+            Self::ReturnValue(_) => write!(f, "{}", tr!("codegen.return_value")),
+            Self::MissingReturnValue(_) => write!(f, "{}", tr!("codegen.missing_return_value")),
+            Self::MissingReturn(n, _) => write!(f, "{}", tr!("codegen.missing_return", n)),
+            Self::NotCallable(n, _) => write!(f, "{}", tr!("module.not_fn", n)),
+            Self::Type { expected, found, .. } => write!(f, "{}", tr!("codegen.type", expected, found)),
+        }
+    }
+}
+impl std::error::Error for CodegenError {}
+impl CodegenError {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Const { span, .. } | Self::Global { span, .. } | Self::Arity { span, .. } | Self::Type { span, .. } => *span,
+            Self::Eval { span, .. } | Self::NotPure { span, .. } => *span,
+            Self::InvalidStep(_, s) | Self::NoValue(_, s) | Self::ReturnValue(s) | Self::Attr(_, _, s) => *s,
+            Self::MissingReturnValue(s) | Self::MissingReturn(_, s) | Self::NotCallable(_, s) => *s,
+        }
+    }
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Const { err, .. } | Self::Global { err, .. } | Self::Eval { err, .. } => err.code(),
+            Self::InvalidStep(..) => code!("G0409"),
+            Self::Attr(code, _, _) | Self::NotPure { code, .. } => code,
+            Self::Arity { .. } => code!("G0501"),
+            Self::NoValue(..) => code!("G0502"),
+            Self::ReturnValue(_) => code!("G0503"),
+            Self::MissingReturnValue(_) => code!("G0504"),
+            Self::MissingReturn(..) => code!("G0505"),
+            Self::NotCallable(..) => code!("G0312"),
+            Self::Type { .. } => code!("G0509"),
+        }
+    }
+}
+impl From<CodegenError> for Diagnostic {
+    fn from(e: CodegenError) -> Self {
+        let d = Diagnostic::new(e.code(), e.to_string(), Some(e.span()));
+        let location = match &e {
+            CodegenError::Const { err, .. } | CodegenError::Global { err, .. } | CodegenError::Eval { err, .. } => err.location(),
+            _ => None,
+        };
+        match location {
+            Some((name, span)) => d.with_note(span, tr!("note.evaluating", name)),
+            None => d,
+        }
+    }
 }
 
-pub fn generate_wat(prog: &Program) -> String {
-    // --- segments de données ---
-    let mut data = String::new();
-    // "console.log" à 0
-    data.push_str(&format!("  (data (i32.const 0) \"{}\")\n", wat_escape("console.log")));
 
-    // Intern des chaînes JSON "\"...\"" avec dédup
-    let mut str_off: BTreeMap<String, (usize, usize)> = BTreeMap::new();
-    let mut next_off: usize = 16;
+/// `$i32_to_json(v, dst) -> len` : écrit l'entier en décimal à `dst` et
+/// renvoie le nombre d'octets écrits.
+fn i32_to_json() -> ir::Func {
+    use ir::{BinOp as B, Expr as E, StmtKind as S};
+    let [v, dst, neg, pos, d, i, j, t] = std::array::from_fn(|k| LocalId(k as u32));
+    let [digits_exit, digits, rev_exit, rev] = std::array::from_fn(|k| Label(k as u32));
+    let (get, c) = (E::Local, E::Const);
+    let add = |l, r| E::bin(B::Add, l, r);
+    let block = |ss: Vec<S>| ss.into_iter().map(ir::Stmt::from).collect::<Vec<_>>();
+    let body = block(vec![
+        S::Set(neg, c(0)),
+        S::If(
+            E::bin(B::LtS, get(v), c(0)),
+            block(vec![S::Set(neg, c(1)), S::Set(v, E::bin(B::Sub, c(0), get(v)))]),
+            Vec::new(),
+        ),
+        // v == 0 -> "0"
+        S::If(E::eqz(get(v)), block(vec![S::Store8 { addr: get(dst), value: c(48) }, S::Return(Some(c(1)))]), Vec::new()),
+        // chiffres de droite à gauche, en non signé pour que -i32::MIN reste juste
+        S::Set(pos, c(0)),
+        S::Block(digits_exit, block(vec![S::Loop(digits, block(vec![
+            S::Set(d, E::bin(B::RemU, get(v), c(10))),
+            S::Store8 { addr: add(get(dst), get(pos)), value: add(get(d), c(48)) },
+            S::Set(pos, add(get(pos), c(1))),
+            S::Set(v, E::bin(B::DivU, get(v), c(10))),
+            S::BrIf(digits_exit, E::eqz(get(v))),
+            S::Br(digits),
+        ]))])),
+        // ajoute '-' si négatif
+        S::If(
+            get(neg),
+            block(vec![S::Store8 { addr: add(get(dst), get(pos)), value: c(45) }, S::Set(pos, add(get(pos), c(1)))]),
+            Vec::new(),
+        ),
+        // retourne [0..pos-1] sur place
+        S::Set(i, c(0)),
+        S::Set(j, E::bin(B::Sub, get(pos), c(1))),
+        S::Block(rev_exit, block(vec![S::Loop(rev, block(vec![
+            S::BrIf(rev_exit, E::bin(B::GeU, get(i), get(j))),
+            S::Set(t, E::Load8U(Box::new(add(get(dst), get(i))))),
+            S::Store8 { addr: add(get(dst), get(i)), value: E::Load8U(Box::new(add(get(dst), get(j)))) },
+            S::Store8 { addr: add(get(dst), get(j)), value: get(t) },
+            S::Set(i, add(get(i), c(1))),
+            S::Set(j, E::bin(B::Sub, get(j), c(1))),
+            S::Br(rev),
+        ]))])),
+        S::Return(Some(get(pos))),
+    ]);
+    ir::Func {
+        name: "i32_to_json".to_string(),
+        export: None,
+        params: 2,
+        result: Some(Ty::I32),
+        locals: ["v", "dst", "neg", "pos", "d", "i", "j", "t"]
+            .map(|n| ir::Local { name: n.to_string(), ty: Ty::I32 })
+            .to_vec(),
+        labels: ["digits_exit", "digits", "rev_exit", "rev"].map(String::from).to_vec(),
+        body,
+        span: Span::default(),
+    }
+}
+
+/// Plan mémoire : "console.log" à 0, zone de retour de `invoke`, données
+/// (chaînes JSON précalculées), puis le tampon où les logs sont assemblés,
+/// dimensionné d'après le plus long log du programme (global `$@buf`).
+const CONSOLE_LOG: &str = "console.log";
+const RET_PTR: usize = 16;
+const RET_CAP: usize = 1024;
+const DATA_START: usize = RET_PTR + RET_CAP;
+const PAGE: usize = 65536;
+/// Nom sous lequel la mémoire est exportée vers l'hôte.
+const MEMORY_EXPORT: &str = "memory";
+
+/// Fonctions et globale que le compilateur ajoute à tout module.
+const INVOKE: FuncId = FuncId(0);
+const I32_TO_JSON: FuncId = FuncId(1);
+const FIRST_FUNC: u32 = 2;
+const BUF: GlobalId = GlobalId(0);
+
+fn align16(n: usize) -> usize {
+    n.div_ceil(16) * 16
+}
+
+struct FnSig<'a> {
+    decl: &'a FnDecl,
+    inline: Option<&'a Expr>, // `@inline` : l'expression du `return`
+    test: bool,               // `@test`
+    id: Option<FuncId>,       // absent : `@test` hors d'un build `--test`
+}
+
+/// Morceau du tableau JSON d'un log.
+enum Piece<'e> {
+    Text(String),  // déjà rendu à la compilation
+    Int(&'e Expr), // calculé à l'exécution
+}
+
+/// État de l'abaissement : données et items partagés, puis la fonction courante.
+struct Gen<'a> {
+    // segments de données + intern des chaînes JSON "\"...\"" avec dédup
+    data: Vec<ir::Data>,
+    str_off: BTreeMap<String, (usize, usize)>,
+    next_off: usize,
+    max_log: usize, // taille maximale d'un log assemblé dans le tampon
+    ctx: Ctx<'a>,
+    consts: HashMap<&'a str, i32>,
+    funcs: HashMap<&'a str, FnSig<'a>>,
+    globals: HashMap<&'a str, (GlobalId, &'a Type)>,
+    table: Vec<FuncId>, // fonctions prises comme valeurs
+    sigs: Vec<ir::Sig>, // signatures des appels par la table
+    // fonction courante
+    ret: bool,
+    in_test: bool,
+    inlining: Vec<&'a str>, // appels @inline en cours de dépliage
+    scope: Vec<(&'a str, LocalId)>, // paramètres et variables de boucle visibles
+    fn_locals: HashMap<LocalId, &'a FnType>, // paramètres de type `fn(...)`
+    locals: Vec<ir::Local>, // paramètres, `@pos`, variables de boucle et locals cachés
+    hidden: usize,          // locals cachés (bornes, pas, arguments de log)
+    labels: Vec<String>,
+    pos: LocalId, // `@pos` : position d'écriture dans le tampon des logs
+}
 
-    let mut intern_string = |s: &str| -> (usize, usize) {
-        let json = serde_json::to_string(s).unwrap(); // "\"...\""
-        if let Some(&(off, len)) = str_off.get(&json) {
+fn contains_call(e: &Expr) -> bool {
+    match &e.kind {
+        ExprKind::Call { .. } => true,
+        ExprKind::Neg(x) | ExprKind::Not(x) => contains_call(x),
+        ExprKind::Bin(_, l, r) => contains_call(l) || contains_call(r),
+        ExprKind::Str(_) | ExprKind::Var(_) | ExprKind::Int(_) => false,
+    }
+}
+
+impl<'a> Gen<'a> {
+    /// Range un texte JSON dans les données (une seule fois par texte).
+    fn intern(&mut self, json: String) -> (usize, usize) {
+        if let Some(&(off, len)) = self.str_off.get(&json) {
             return (off, len);
         }
-        let len = json.as_bytes().len();
-        data.push_str(&format!(
-            "  (data (i32.const {off}) \"{lit}\")\n",
-            off = next_off,
-            lit = wat_escape(&json)
-        ));
-        str_off.insert(json, (next_off, len));
-        next_off += len;
-        if next_off % 16 != 0 {
-            next_off += 16 - (next_off % 16);
+        let (off, len) = (self.next_off, json.len());
+        self.data.push(ir::Data { offset: off as u32, bytes: json.clone().into_bytes() });
+        self.str_off.insert(json, (off, len));
+        self.next_off = align16(self.next_off + len);
+        (off, len)
+    }
+
+    fn new_local(&mut self, name: String) -> LocalId {
+        self.locals.push(ir::Local { name, ty: Ty::I32 });
+        LocalId(self.locals.len() as u32 - 1)
+    }
+
+    /// Les locals internes commencent par `@`, impossible dans un nom gaufre.
+    fn hidden_local(&mut self, base: &str) -> LocalId {
+        let name = format!("@{base}{}", self.hidden);
+        self.hidden += 1;
+        self.new_local(name)
+    }
+
+    fn label(&mut self, name: String) -> Label {
+        self.labels.push(name);
+        Label(self.labels.len() as u32 - 1)
+    }
+
+    /// Local d'une variable visible.
+    fn local(&self, name: &str) -> Option<LocalId> {
+        self.scope.iter().rev().find(|(n, _)| *n == name).map(|&(_, l)| l)
+    }
+
+    /// Local d'une nouvelle variable de boucle : `i`, ou `i.2`, `i.3`...
+    /// si elle masque une variable encore visible du même nom. Deux boucles
+    /// successives partagent le même local.
+    fn bind(&mut self, name: &'a str) -> LocalId {
+        let mut l = name.to_string();
+        let mut k = 1;
+        while self.scope.iter().any(|&(_, w)| self.locals[w.0 as usize].name == l) {
+            k += 1;
+            l = format!("{name}.{k}");
         }
-        (next_off - len, len)
-    };
+        match self.locals.iter().position(|x| x.name == l) {
+            Some(i) => LocalId(i as u32),
+            None => self.new_local(l),
+        }
+    }
 
-    // Pré-intern toutes les chaînes utilisées dans le programme
-    fn walk_strings<'a>(ss: &'a [Stmt], out: &mut Vec<&'a str>) {
-        for s in ss {
-            match s {
-                Stmt::Log(args) => {
-                    for a in args {
-                        if let Expr::Str(t) = a {
-                            out.push(t);
-                        }
-                    }
+    fn uses_local(&self, e: &Expr) -> bool {
+        match &e.kind {
+            ExprKind::Var(n) => self.local(n).is_some(),
+            ExprKind::Neg(x) | ExprKind::Not(x) => self.uses_local(x),
+            ExprKind::Bin(_, l, r) => self.uses_local(l) || self.uses_local(r),
+            ExprKind::Call { args, .. } => args.iter().any(|a| self.uses_local(a)),
+            ExprKind::Str(_) | ExprKind::Int(_) => false,
+        }
+    }
+
+    /// Type d'une valeur fonction : fonction nommée, ou paramètre ou globale
+    /// de type `fn(...)`; `None` pour un entier.
+    fn fn_type(&self, e: &Expr) -> Option<FnType> {
+        let ExprKind::Var(n) = &e.kind else { return None };
+        match self.local(n) {
+            Some(l) => self.fn_locals.get(&l).map(|&t| t.clone()),
+            None => match self.funcs.get(n.as_str()) {
+                Some(sig) => Some(sig.decl.ty()),
+                None => self.globals.get(n.as_str()).and_then(|(_, ty)| ty.as_fn()).cloned(),
+            },
+        }
+    }
+
+    /// Valeur d'un argument, d'une affectation ou de l'initialisation d'une
+    /// globale : une valeur fonction de même signature là où `want` l'attend,
+    /// un entier sinon.
+    fn lower_value(&mut self, e: &Expr, want: Option<&FnType>) -> Result<ir::Expr, CodegenError> {
+        let Some(want) = want else { return self.lower_expr(e) };
+        let found = self.fn_type(e);
+        if found.as_ref() != Some(want) {
+            let found = found.map_or(Type::I32, Type::Fn);
+            return Err(CodegenError::Type { expected: want.to_string(), found: found.to_string(), span: e.span });
+        }
+        let ExprKind::Var(n) = &e.kind else { unreachable!("seul un nom a un type fonction") };
+        if let Some(l) = self.local(n) {
+            return Ok(ir::Expr::Local(l));
+        }
+        let Some(sig) = self.funcs.get(n.as_str()) else {
+            return Ok(ir::Expr::Global(self.globals[n.as_str()].0));
+        };
+        if sig.test && !self.in_test {
+            return Err(CodegenError::Attr(code!("G0208"), tr!("codegen.test_call", n), e.span));
+        }
+        let id = sig.id.expect("fonction @test prise hors d'un build --test");
+        let slot = match self.table.iter().position(|&f| f == id) {
+            Some(i) => i,
+            None => {
+                self.table.push(id);
+                self.table.len() - 1
+            }
+        };
+        Ok(ir::Expr::Const((ir::TABLE_BASE as usize + slot) as i32))
+    }
+
+    /// Valeur à la compilation si l'expression ne dépend que de constantes et
+    /// d'appels de `const fn`; une évaluation qui échoue (division par zéro,
+    /// carburant épuisé...) est une erreur plutôt qu'un piège à l'exécution.
+    fn fold(&mut self, e: &Expr) -> Result<Option<i32>, CodegenError> {
+        if self.uses_local(e) {
+            return Ok(None);
+        }
+        match self.ctx.eval(e) {
+            Ok(v) => Ok(Some(v)),
+            Err(EvalError::NotConst(_) | EvalError::NotConstFn(_)) => Ok(None),
+            Err(err) => Err(CodegenError::Eval { err, span: e.span }),
+        }
+    }
+
+    // valeur i32 d'une expression (les chaînes n'existent qu'en argument de log)
+    fn lower_expr(&mut self, e: &Expr) -> Result<ir::Expr, CodegenError> {
+        // calcul constant (y compris appels de const fn) : plié ici
+        if matches!(e.kind, ExprKind::Neg(_) | ExprKind::Not(_) | ExprKind::Bin(..) | ExprKind::Call { .. })
+            && let Some(v) = self.fold(e)?
+        {
+            return Ok(ir::Expr::Const(v));
+        }
+        let bool_of = |x| ir::Expr::eqz(ir::Expr::eqz(x));
+        Ok(match &e.kind {
+            ExprKind::Int(v) => ir::Expr::Const(*v),
+            ExprKind::Var(n) => {
+                if let Some(t) = self.fn_type(e) {
+                    return Err(CodegenError::Type { expected: Type::I32.to_string(), found: t.to_string(), span: e.span });
+                }
+                // les noms ont été résolus par modules.rs
+                if let Some(l) = self.local(n) {
+                    ir::Expr::Local(l)
+                } else if let Some(&v) = self.consts.get(n.as_str()) {
+                    ir::Expr::Const(v)
+                } else {
+                    ir::Expr::Global(self.globals[n.as_str()].0)
                 }
-                Stmt::For { body, .. } => walk_strings(body, out),
             }
+            ExprKind::Neg(x) => ir::Expr::bin(ir::BinOp::Sub, ir::Expr::Const(0), self.lower_expr(x)?),
+            ExprKind::Not(x) => ir::Expr::eqz(self.lower_expr(x)?),
+            // && et || n'évaluent la droite que si nécessaire
+            ExprKind::Bin(BinOp::And, l, r) => ir::Expr::If(
+                Box::new(self.lower_expr(l)?),
+                Box::new(bool_of(self.lower_expr(r)?)),
+                Box::new(ir::Expr::Const(0)),
+            ),
+            ExprKind::Bin(BinOp::Or, l, r) => ir::Expr::If(
+                Box::new(self.lower_expr(l)?),
+                Box::new(ir::Expr::Const(1)),
+                Box::new(bool_of(self.lower_expr(r)?)),
+            ),
+            ExprKind::Bin(op, l, r) => {
+                let op = match op {
+                    BinOp::Add => ir::BinOp::Add,
+                    BinOp::Sub => ir::BinOp::Sub,
+                    BinOp::Mul => ir::BinOp::Mul,
+                    BinOp::Div => ir::BinOp::DivS,
+                    BinOp::Rem => ir::BinOp::RemS,
+                    BinOp::Eq => ir::BinOp::Eq,
+                    BinOp::Ne => ir::BinOp::Ne,
+                    BinOp::Lt => ir::BinOp::LtS,
+                    BinOp::Le => ir::BinOp::LeS,
+                    BinOp::Gt => ir::BinOp::GtS,
+                    BinOp::Ge => ir::BinOp::GeS,
+                    BinOp::And | BinOp::Or => unreachable!(),
+                };
+                ir::Expr::bin(op, self.lower_expr(l)?, self.lower_expr(r)?)
+            }
+            ExprKind::Call { name, .. } => match self.lower_call(e)? {
+                (call, true) => call,
+                (_, false) => return Err(CodegenError::NoValue(name.clone(), e.span)),
+            },
+            ExprKind::Str(_) => unreachable!("chaîne hors d'un log"),
+        })
+    }
+
+    /// Abaisse un appel; le booléen dit s'il produit une valeur.
+    fn lower_call(&mut self, e: &Expr) -> Result<(ir::Expr, bool), CodegenError> {
+        let ExprKind::Call { name, args } = &e.kind else { unreachable!() };
+        // un paramètre masque la fonction du même nom
+        let Some(sig) = self.funcs.get(name.as_str()).filter(|_| self.local(name).is_none()) else {
+            return self.lower_call_indirect(name, args, e.span);
+        };
+        let decl = sig.decl;
+        if decl.params.len() != args.len() {
+            return Err(CodegenError::Arity { name: name.clone(), expected: decl.params.len(), found: args.len(), span: e.span });
+        }
+        if sig.test && !self.in_test {
+            return Err(CodegenError::Attr(
+                code!("G0208"),
+                tr!("codegen.test_call", name),
+                e.span,
+            ));
+        }
+        let id = sig.id.expect("fonction @test appelée hors d'un build --test");
+        // une fonction @inline récursive n'est dépliée qu'une fois
+        if let Some(body) = sig.inline
+            && !self.inlining.contains(&decl.name.as_str())
+        {
+            return Ok((self.lower_inline(decl, body, args)?, true));
         }
+        let args = args.iter().zip(&decl.params).map(|(a, p)| self.lower_value(a, p.ty.as_fn())).collect::<Result<_, _>>()?;
+        Ok((ir::Expr::Call(id, args), decl.ret))
     }
-    let mut strings = Vec::new();
-    walk_strings(&prog.stmts, &mut strings);
-    for s in strings {
-        let _ = intern_string(s);
+
+    /// Appel d'un paramètre ou d'une globale de type `fn(...)`, par la table.
+    fn lower_call_indirect(&mut self, name: &str, args: &[Expr], span: Span) -> Result<(ir::Expr, bool), CodegenError> {
+        let callee = match self.local(name) {
+            Some(l) => self.fn_locals.get(&l).map(|&t| (t, ir::Expr::Local(l))),
+            None => match self.globals[name] {
+                (g, Type::Fn(t)) => Some((t, ir::Expr::Global(g))),
+                (_, Type::I32) => None,
+            },
+        };
+        let Some((t, callee)) = callee else {
+            return Err(CodegenError::NotCallable(name.to_string(), span));
+        };
+        if t.params.len() != args.len() {
+            return Err(CodegenError::Arity { name: name.to_string(), expected: t.params.len(), found: args.len(), span });
+        }
+        let args = args.iter().zip(&t.params).map(|(a, p)| self.lower_value(a, p.as_fn())).collect::<Result<_, _>>()?;
+        let sig = ir::Sig { params: vec![Ty::I32; t.params.len()], result: t.ret.then_some(Ty::I32) };
+        let sig = match self.sigs.iter().position(|s| *s == sig) {
+            Some(i) => i,
+            None => {
+                self.sigs.push(sig);
+                self.sigs.len() - 1
+            }
+        };
+        Ok((ir::Expr::CallIndirect(ir::SigId(sig as u32), Box::new(callee), args), t.ret))
     }
 
-    // Collecte des variables i32 (noms rencontrés dans les for)
-    fn collect_vars<'a>(ss: &'a [Stmt], acc: &mut BTreeMap<&'a str, ()>) {
-        for s in ss {
-            if let Stmt::For { name, body, .. } = s {
-                acc.insert(name.as_str(), ());
-                collect_vars(body, acc);
+    /// `@inline` : arguments dans des locals cachés, puis l'expression du
+    /// `return` avec les paramètres pointant sur ces locals (et rien d'autre
+    /// de la fonction appelante en vue).
+    fn lower_inline(&mut self, f: &'a FnDecl, body: &'a Expr, args: &[Expr]) -> Result<ir::Expr, CodegenError> {
+        let mut scope = Vec::new();
+        let mut sets = Vec::new();
+        for (p, a) in f.params.iter().zip(args) {
+            let v = self.lower_value(a, p.ty.as_fn())?;
+            let l = self.hidden_local("arg");
+            sets.push(ir::StmtKind::Set(l, v).at(a.span));
+            if let Type::Fn(t) = &p.ty {
+                self.fn_locals.insert(l, t);
             }
+            scope.push((p.name.as_str(), l));
         }
+        let outer = std::mem::replace(&mut self.scope, scope);
+        self.inlining.push(&f.name);
+        let r = self.lower_expr(body);
+        self.inlining.pop();
+        self.scope = outer;
+        Ok(ir::Expr::Seq(sets, Box::new(r?)))
     }
-    let mut vars = BTreeMap::<&str, ()>::new();
-    collect_vars(&prog.stmts, &mut vars);
 
-    // --- Génération du corps de main ---
-    let mut body = String::new();
+    fn lower_stmts(&mut self, ss: &'a [Stmt]) -> Result<Vec<ir::Stmt>, CodegenError> {
+        let mut out = Vec::new();
+        for st in ss {
+            let kinds = match &st.kind {
+                StmtKind::Log(args) => self.lower_log(args)?,
+                StmtKind::Assign { name, value } => vec![match self.local(name) {
+                    Some(l) => ir::StmtKind::Set(l, self.lower_value(value, self.fn_locals.get(&l).copied())?),
+                    None => {
+                        let (g, ty) = self.globals[name.as_str()];
+                        ir::StmtKind::SetGlobal(g, self.lower_value(value, ty.as_fn())?)
+                    }
+                }],
+                StmtKind::For { name, start, end, step, kind, body, .. } => {
+                    self.lower_for(name, start, end, step.as_ref(), *kind, body, st.span)?
+                }
+                StmtKind::Return(v) => {
+                    let v = match (v, self.ret) {
+                        (Some(e), true) => Some(self.lower_expr(e)?),
+                        (None, false) => None,
+                        (Some(_), false) => return Err(CodegenError::ReturnValue(st.span)),
+                        (None, true) => return Err(CodegenError::MissingReturnValue(st.span)),
+                    };
+                    vec![ir::StmtKind::Return(v)]
+                }
+                StmtKind::Expr(e) => vec![match self.lower_call(e)? {
+                    (v, true) => ir::StmtKind::Drop(v),
+                    (ir::Expr::Call(f, args), false) => ir::StmtKind::Call(f, args),
+                    (ir::Expr::CallIndirect(sig, callee, args), false) => ir::StmtKind::CallIndirect(sig, *callee, args),
+                    (_, false) => unreachable!("seul un appel est sans valeur"),
+                }],
+                StmtKind::Wat(code) => vec![self.lower_wat(code)],
+                StmtKind::IfConst { .. } => unreachable!("if const résolu par modules.rs"),
+            };
+            out.extend(kinds.into_iter().map(|k| k.at(st.span)));
+        }
+        Ok(out)
+    }
 
-    // helper: émet un log avec args multiples -> construit JSON array dans 512
-    let mut emit_log = |args: &[Expr], body: &mut String| {
-        // '['
-        body.push_str("    i32.const 512\n    i32.const 91  ;; '['\n    i32.store8\n");
-        // pos = 1
-        body.push_str("    i32.const 1\n    local.set $pos\n");
+    /// Bloc `wat { ... }` recopié, `$var` renvoyant au local de la variable.
+    fn lower_wat(&self, code: &str) -> ir::StmtKind {
+        let code = inline_wat::map_ids(code, &mut |id| self.local(id).map(|l| self.locals[l.0 as usize].name.clone()));
+        ir::StmtKind::Wat(code)
+    }
 
+    // log avec args multiples -> tableau JSON ["…", 3, …].
+    // Tout ce qui est connu à la compilation (chaînes, constantes, appels de
+    // const fn) est rendu en JSON tout de suite et rangé dans les données;
+    // seuls les entiers calculés à l'exécution passent par $i32_to_json.
+    fn lower_log(&mut self, args: &[Expr]) -> Result<Vec<ir::StmtKind>, CodegenError> {
+        use ir::{BinOp as B, Expr as E, StmtKind as S};
+        let mut pieces = Vec::new();
+        let mut text = String::from("[");
         for (k, e) in args.iter().enumerate() {
-            // virgule si pas premier
             if k > 0 {
-                body.push_str(
-                    "    i32.const 512\n    local.get $pos\n    i32.add\n    i32.const 44  ;; ','\n    i32.store8\n",
-                );
-                body.push_str("    local.get $pos\n    i32.const 1\n    i32.add\n    local.set $pos\n");
+                text.push(',');
             }
+            match &e.kind {
+                ExprKind::Str(s) => text.push_str(&serde_json::to_string(s).unwrap()),
+                _ => match self.fold(e)? {
+                    Some(v) => text.push_str(&v.to_string()),
+                    None => {
+                        pieces.push(Piece::Text(std::mem::take(&mut text)));
+                        pieces.push(Piece::Int(e));
+                    }
+                },
+            }
+        }
+        text.push(']');
+        pieces.push(Piece::Text(text));
 
-            match e {
-                Expr::Str(s) => {
-                    let (off, len) = intern_string(s);
-                    body.push_str(&format!(
-                        "    ;; copie string JSON\n    i32.const 512\n    local.get $pos\n    i32.add\n    i32.const {src}\n    i32.const {len}\n    memory.copy\n",
-                        src = off,
-                        len = len
-                    ));
-                    body.push_str("    local.get $pos\n    i32.const ");
-                    body.push_str(&format!("{}\n", len));
-                    body.push_str("    i32.add\n    local.set $pos\n");
+        // entièrement constant : invoke lit directement le segment de données
+        if let [Piece::Text(json)] = pieces.as_slice() {
+            let (off, len) = self.intern(json.clone());
+            return Ok(vec![invoke(E::Const(off as i32), E::Const(len as i32))]);
+        }
+
+        // un appel pourrait lui-même loguer et écraser le tampon : on évalue
+        // alors tous les arguments avant d'écrire le '['
+        let mut out = Vec::new();
+        let mut saved = Vec::new();
+        let ints = pieces.iter().filter_map(|p| match p { Piece::Int(e) => Some(*e), Piece::Text(_) => None });
+        if ints.clone().any(contains_call) {
+            for e in ints {
+                let l = self.hidden_local("arg");
+                out.push(S::Set(l, self.lower_expr(e)?));
+                saved.push(l);
+            }
+        }
+
+        let pos = self.pos;
+        let at_pos = || E::bin(B::Add, E::Global(BUF), E::Local(pos));
+        let advance = |n: E| S::Set(pos, E::bin(B::Add, E::Local(pos), n));
+        out.push(S::Set(pos, E::Const(0)));
+        let mut max_len = 0;
+        let mut saved = saved.into_iter();
+        for p in pieces {
+            match p {
+                Piece::Text(t) if t.is_empty() => {}
+                Piece::Text(t) if t.len() == 1 => {
+                    out.push(S::Store8 { addr: at_pos(), value: E::Const(t.as_bytes()[0].into()) });
+                    out.push(advance(E::Const(1)));
+                    max_len += 1;
                 }
-                Expr::Var(name) => {
-                    body.push_str(&format!(
-                        concat!(
-                            "    ;; var {n} -> JSON\n",
-                            "    local.get ${n}\n",
-                            "    i32.const 512\n",
-                            "    local.get $pos\n",
-                            "    i32.add\n",
-                            "    call $i32_to_json\n", // retourne len
-                            "    local.get $pos\n",
-                            "    i32.add\n",
-                            "    local.set $pos\n"
-                        ),
-                        n = name
-                    ));
+                Piece::Text(t) => {
+                    let (off, len) = self.intern(t);
+                    out.push(S::MemoryCopy { dst: at_pos(), src: E::Const(off as i32), len: E::Const(len as i32) });
+                    out.push(advance(E::Const(len as i32)));
+                    max_len += len;
                 }
-                Expr::Int(v) => {
-                    body.push_str(&format!(
-                        concat!(
-                            "    ;; int {v}\n",
-                            "    i32.const {v}\n",
-                            "    i32.const 512\n",
-                            "    local.get $pos\n",
-                            "    i32.add\n",
-                            "    call $i32_to_json\n",
-                            "    local.get $pos\n",
-                            "    i32.add\n",
-                            "    local.set $pos\n"
-                        ),
-                        v = v
-                    ));
+                Piece::Int(e) => {
+                    let v = match saved.next() {
+                        Some(l) => E::Local(l),
+                        None => self.lower_expr(e)?,
+                    };
+                    // $i32_to_json renvoie la longueur écrite
+                    out.push(advance(E::Call(I32_TO_JSON, vec![v, at_pos()])));
+                    max_len += "-2147483648".len();
                 }
             }
         }
+        self.max_log = self.max_log.max(max_len);
+        out.push(invoke(E::Global(BUF), E::Local(pos)));
+        Ok(out)
+    }
 
-        // ']' et longueur totale = pos + 1
-        body.push_str(
-            "    i32.const 512\n    local.get $pos\n    i32.add\n    i32.const 93  ;; ']'\n    i32.store8\n",
-        );
-        body.push_str("    local.get $pos\n    i32.const 1\n    i32.add\n    local.set $pos\n");
-
-        // invoke console.log(["…", …])
-        body.push_str(
-            concat!(
-                "    i32.const 0      ;; name: \"console.log\"\n",
-                "    i32.const 11\n",
-                "    i32.const 512   ;; args ptr\n",
-                "    local.get $pos  ;; args len\n",
-                "    i32.const 4096  ;; ret ptr\n",
-                "    i32.const 1024  ;; ret cap\n",
-                "    call $invoke\n",
-                "    drop\n",
-            ),
+    // for (to / downto / until, pas optionnel) avec body.
+    // Bornes et pas sont évalués une seule fois, dans des locals cachés, et la
+    // sortie est testée avant l'incrément pour ne jamais déborder à i32::MAX.
+    #[allow(clippy::too_many_arguments)]
+    fn lower_for(
+        &mut self,
+        name: &'a str,
+        start: &Expr,
+        end: &Expr,
+        step: Option<&Expr>,
+        kind: ForKind,
+        inner: &'a [Stmt],
+        span: Span,
+    ) -> Result<Vec<ir::StmtKind>, CodegenError> {
+        use ir::{BinOp as B, Expr as E, StmtKind as S};
+        let end_local = self.hidden_local("end");
+        let k = self.hidden - 1;
+        let mut out = Vec::new();
+
+        // la variable n'est visible que dans le corps : end/step voient
+        // l'ancienne, et son local n'est lu par aucun d'eux
+        let start = self.lower_expr(start)?;
+        let var = self.bind(name);
+        out.push(S::Set(var, start));
+        out.push(S::Set(end_local, self.lower_expr(end)?));
+        let step = match step {
+            Some(e) => match self.fold(e)? {
+                Some(v) if v <= 0 => return Err(CodegenError::InvalidStep(v, e.span)),
+                Some(v) => E::Const(v),
+                None => {
+                    let step_local = self.hidden_local("step");
+                    out.push(S::Set(step_local, self.lower_expr(e)?));
+                    // pas <= 0 : la boucle ne terminerait jamais
+                    out.push(S::If(
+                        E::bin(B::LeS, E::Local(step_local), E::Const(0)),
+                        vec![S::Unreachable.at(span)],
+                        Vec::new(),
+                    ));
+                    E::Local(step_local)
+                }
+            },
+            None => E::Const(1),
+        };
+
+        // (test d'entrée, écart restant = a - b, test de continuation, incrément)
+        let (enter_exit, a, b, cont_exit, update) = match kind {
+            ForKind::To => (B::GtS, end_local, var, B::LtU, B::Add),
+            ForKind::Until => (B::GeS, end_local, var, B::LeU, B::Add),
+            ForKind::Downto => (B::LtS, var, end_local, B::LtU, B::Sub),
+        };
+        let exit = self.label(format!("exit{k}"));
+        let repeat = self.label(format!("loop{k}"));
+
+        self.scope.push((name, var));
+        let mut body = self.lower_stmts(inner)?;
+        self.scope.pop();
+        body.extend(
+            [
+                // sortie si l'écart restant (non signé, exact) est plus petit que le pas
+                S::BrIf(exit, E::bin(cont_exit, E::bin(B::Sub, E::Local(a), E::Local(b)), step.clone())),
+                S::Set(var, E::bin(update, E::Local(var), step)),
+                S::Br(repeat),
+            ]
+            .map(|s| s.at(span)),
         );
+        out.push(S::Block(
+            exit,
+            vec![
+                S::BrIf(exit, E::bin(enter_exit, E::Local(var), E::Local(end_local))).at(span),
+                S::Loop(repeat, body).at(span),
+            ],
+        ));
+        Ok(out)
+    }
+
+    fn lower_func(&mut self, f: &'a FnDecl, export: Option<String>) -> Result<ir::Func, CodegenError> {
+        // un bloc wat peut contenir son propre `return`
+        if f.ret && !f.body.iter().any(|s| matches!(s.kind, StmtKind::Return(_) | StmtKind::Wat(_))) {
+            return Err(CodegenError::MissingReturn(f.name.clone(), f.span));
+        }
+        self.ret = f.ret;
+        self.in_test = attrs::has(&f.attrs, "test") || f.name == TEST_RUNNER;
+        self.locals.clear();
+        self.fn_locals.clear();
+        self.scope.clear();
+        for p in &f.params {
+            let l = self.new_local(p.name.clone());
+            if let Type::Fn(t) = &p.ty {
+                self.fn_locals.insert(l, t);
+            }
+            self.scope.push((p.name.as_str(), l));
+        }
+        self.pos = self.new_local("@pos".to_string());
+        self.hidden = 0;
+        self.labels.clear();
+        let body = self.lower_stmts(&f.body)?;
+        Ok(ir::Func {
+            name: f.name.clone(),
+            export,
+            params: f.params.len() as u32,
+            result: f.ret.then_some(Ty::I32),
+            locals: std::mem::take(&mut self.locals),
+            labels: std::mem::take(&mut self.labels),
+            body,
+            span: f.span,
+        })
+    }
+}
+
+/// invoke console.log(["…", …]), réponse ignorée
+fn invoke(ptr: ir::Expr, len: ir::Expr) -> ir::StmtKind {
+    let c = |v: usize| ir::Expr::Const(v as i32);
+    ir::StmtKind::Drop(ir::Expr::Call(INVOKE, vec![c(0), c(CONSOLE_LOG.len()), ptr, len, c(RET_PTR), c(RET_CAP)]))
+}
+
+/// Nom de la fonction générée par `--test` (exportée comme `main`).
+const TEST_RUNNER: &str = "@tests";
+
+/// Nom d'export : `@export("x")`, sinon le nom court (`helper` pour `util::helper`).
+fn export_name<'s>(attr: &'s Attr, full: &'s str) -> &'s str {
+    attr.str_arg().unwrap_or_else(|| full.rsplit(grammar::PATH_SEP).next().unwrap())
+}
+
+/// `main` des builds `--test` : appelle chaque `@test` et logue son succès
+/// (un test qui échoue trappe et arrête la suite).
+fn test_runner(prog: &Program) -> FnDecl {
+    let tests: Vec<&FnDecl> = prog.funcs.iter().filter(|f| attrs::has(&f.attrs, "test")).collect();
+    let stmt = |kind| Stmt { kind, attrs: Vec::new(), span: Span::default() };
+    let text = |s: String| Expr { kind: ExprKind::Str(s), span: Span::default() };
+    let mut body = Vec::new();
+    for t in &tests {
+        let call = Expr { kind: ExprKind::Call { name: t.name.clone(), args: Vec::new() }, span: t.span };
+        body.push(stmt(StmtKind::Expr(call)));
+        body.push(stmt(StmtKind::Log(vec![text(format!("test {} ... ok", t.name))])));
+    }
+    body.push(stmt(StmtKind::Log(vec![text(format!("{} test(s) ok", tests.len()))])));
+    FnDecl {
+        name: TEST_RUNNER.to_string(),
+        params: Vec::new(),
+        ret: false,
+        konst: false,
+        body,
+        public: false,
+        attrs: Vec::new(),
+        span: Span::default(),
+    }
+}
+
+/// Abaisse le programme résolu en représentation intermédiaire. `test` :
+/// build `--test`, où les fonctions `@test` sont compilées et `main` est
+/// remplacé par un lanceur de tests.
+pub fn lower(prog: &Program, test: bool) -> Result<ir::Module, CodegenError> {
+    for f in &prog.funcs {
+        if let Some(a) = attrs::find(&f.attrs, "inline")
+            && !matches!(f.body.as_slice(), [Stmt { kind: StmtKind::Return(Some(_)), .. }])
+        {
+            return Err(CodegenError::Attr(code!("G0206"), tr!("codegen.inline_body"), a.span));
+        }
+        if let Some(a) = attrs::find(&f.attrs, "test")
+            && (!f.params.is_empty() || f.ret)
+        {
+            return Err(CodegenError::Attr(code!("G0207"), tr!("codegen.test_signature"), a.span));
+        }
+        // le point d'entrée s'exporte toujours sous le nom `main`
+        if f.name == grammar::KW_MAIN
+            && let Some(a) = attrs::find(&f.attrs, "export")
+        {
+            return Err(CodegenError::Attr(code!("G0211"), tr!("codegen.export_main"), a.span));
+        }
+    }
+    let runner = test.then(|| test_runner(prog));
+
+    // connus mais pas constants : les globales et les fonctions (valeurs fonction)
+    let is_global = |n: &str| prog.globals.iter().any(|g| g.name == n) || prog.funcs.iter().any(|f| f.name == n);
+
+    for f in prog.funcs.iter().filter(|f| f.konst) {
+        consteval::check_const_fn(
+            f,
+            &|n| prog.consts.iter().any(|c| c.name == n),
+            &|n| prog.funcs.iter().any(|f| f.konst && f.name == n),
+        )
+        .map_err(|(code, reason, span)| CodegenError::NotPure { code, reason, span })?;
+    }
+
+    let mut ctx = Ctx::new(&prog.consts, &prog.funcs, &is_global);
+    let consts = ctx
+        .eval_consts()
+        .map_err(|(c, err)| CodegenError::Const { name: c.name.clone(), err, span: c.span })?;
+
+    // noms exportés, uniques dans le module; la mémoire et `main` (le point
+    // d'entrée ou le lanceur de tests) sont réservés avant ceux de l'auteur
+    let mut exports: HashMap<String, Span> = HashMap::new();
+    exports.insert(MEMORY_EXPORT.to_string(), Span::default());
+    if test || prog.funcs.iter().any(|f| f.name == grammar::KW_MAIN) {
+        exports.insert(grammar::KW_MAIN.to_string(), Span::default());
+    }
+    let mut add_export = |name: &str, span: Span| -> Result<String, CodegenError> {
+        if exports.insert(name.to_string(), span).is_some() {
+            return Err(CodegenError::Attr(code!("G0209"), tr!("codegen.duplicate_export", name), span));
+        }
+        Ok(name.to_string())
     };
 
-    // émet un for i=start..end (inclus) avec body
-    fn emit_for(
-        name: &str,
-        start: i32,
-        end: i32,
-        inner: &[Stmt],
-        body: &mut String,
-        emit_log: &mut dyn FnMut(&[Expr], &mut String),
-    ) {
-        body.push_str(&format!(
-            "    ;; for {n} = {a} to {b}\n    i32.const {a}\n    local.set ${n}\n",
-            n = name,
-            a = start,
-            b = end
-        ));
-        body.push_str("    block $exit\n    loop $loop\n");
-        // break si i > end
-        body.push_str(&format!(
-            "    local.get ${n}\n    i32.const {b}\n    i32.gt_s\n    br_if $exit\n",
-            n = name,
-            b = end
-        ));
-        // corps
-        for st in inner {
-            match st {
-                Stmt::Log(args) => emit_log(args, body),
-                Stmt::For {
-                    name,
-                    start,
-                    end,
-                    body: inner2,
-                } => emit_for(name, *start, *end, inner2, body, emit_log),
+    // les fonctions @cold en dernier, loin du code chaud
+    let mut order: Vec<&FnDecl> = prog.funcs.iter().filter(|f| test || !attrs::has(&f.attrs, "test")).collect();
+    order.sort_by_key(|f| attrs::has(&f.attrs, "cold"));
+    let ids: HashMap<&str, FuncId> =
+        order.iter().enumerate().map(|(i, f)| (f.name.as_str(), FuncId(FIRST_FUNC + i as u32))).collect();
+
+    let mut g = Gen {
+        data: vec![ir::Data { offset: 0, bytes: CONSOLE_LOG.as_bytes().to_vec() }],
+        str_off: BTreeMap::new(),
+        next_off: DATA_START,
+        max_log: 0,
+        ctx,
+        consts,
+        funcs: prog.funcs.iter().map(|f| {
+            let inline = match (attrs::has(&f.attrs, "inline"), f.body.as_slice()) {
+                (true, [Stmt { kind: StmtKind::Return(Some(e)), .. }]) => Some(e),
+                _ => None,
+            };
+            let id = ids.get(f.name.as_str()).copied();
+            (f.name.as_str(), FnSig { decl: f, inline, test: attrs::has(&f.attrs, "test"), id })
+        }).collect(),
+        globals: prog.globals.iter().enumerate().map(|(i, g)| (g.name.as_str(), (GlobalId(i as u32 + 1), &g.ty))).collect(),
+        table: Vec::new(),
+        sigs: Vec::new(),
+        ret: false,
+        in_test: false,
+        inlining: Vec::new(),
+        scope: Vec::new(),
+        fn_locals: HashMap::new(),
+        locals: Vec::new(),
+        hidden: 0,
+        labels: Vec::new(),
+        pos: LocalId(0),
+    };
+
+    // `$@buf` d'abord (adresse connue une fois les données rangées), puis les
+    // globales, initialisées à la compilation (constantes et const fn, ou
+    // fonction nommée pour une globale de type `fn(...)`)
+    let mut globals = vec![ir::Global { name: "@buf".to_string(), ty: Ty::I32, mutable: false, init: 0, export: None }];
+    for decl in &prog.globals {
+        let v = match decl.ty.as_fn() {
+            Some(t) => match g.lower_value(&decl.init, Some(t))? {
+                ir::Expr::Const(v) => v,
+                _ => {
+                    let ExprKind::Var(n) = &decl.init.kind else { unreachable!("seul un nom a un type fonction") };
+                    let err = EvalError::NotConst(n.clone());
+                    return Err(CodegenError::Global { name: decl.name.clone(), err, span: decl.span });
+                }
+            },
+            None => match g.fn_type(&decl.init) {
+                Some(t) => {
+                    return Err(CodegenError::Type { expected: Type::I32.to_string(), found: t.to_string(), span: decl.init.span });
+                }
+                None => g.ctx.eval(&decl.init).map_err(|err| CodegenError::Global { name: decl.name.clone(), err, span: decl.span })?,
+            },
+        };
+        let export = match attrs::find(&decl.attrs, "export") {
+            Some(a) => Some(add_export(export_name(a, &decl.name), a.span)?),
+            None if decl.export => Some(add_export(&decl.name, decl.span)?),
+            None => None,
+        };
+        globals.push(ir::Global { name: decl.name.clone(), ty: Ty::I32, mutable: true, init: v, export });
+    }
+
+    let mut funcs = vec![i32_to_json()];
+    for f in order.into_iter().chain(&runner) {
+        let export = if f.name == TEST_RUNNER || (f.name == grammar::KW_MAIN && !test) {
+            Some(grammar::KW_MAIN.to_string())
+        } else {
+            attrs::find(&f.attrs, "export").map(|a| add_export(export_name(a, &f.name), a.span)).transpose()?
+        };
+        funcs.push(g.lower_func(f, export)?);
+    }
+
+    let buf = align16(g.next_off);
+    let pages = (buf + align16(g.max_log)).div_ceil(PAGE).max(1);
+    globals[BUF.0 as usize].init = buf as i32;
+
+    Ok(ir::Module {
+        imports: vec![ir::Import {
+            module: "gaufre".to_string(),
+            field: "invoke".to_string(),
+            name: "invoke".to_string(),
+            params: vec![Ty::I32; 6],
+            result: Some(Ty::I32),
+        }],
+        pages: pages as u32,
+        memory_export: Some(MEMORY_EXPORT.to_string()),
+        globals,
+        data: g.data,
+        table: g.table,
+        sigs: g.sigs,
+        funcs,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::lower;
+    use crate::diag::{Diagnostic, SourceMap};
+    use crate::{emit, ir, lints, modules, validate};
+    use std::collections::HashMap;
+
+    /// Programme `src` résolu et abaissé; `name` distingue le fichier
+    /// temporaire de chaque test.
+    fn lower_src(name: &str, src: &str) -> Result<ir::Module, Diagnostic> {
+        let path = std::env::temp_dir().join(format!("gaufre-codegen-{name}.gfr"));
+        std::fs::write(&path, src).unwrap();
+        let mut sm = SourceMap::default();
+        let prog = modules::load_program(&path, &mut sm, &HashMap::new(), "en", &lints::Config::default(), &mut Vec::new())?;
+        Ok(lower(&prog, false)?)
+    }
+
+    /// Exécute `f` sur la représentation intermédiaire : locals, arithmétique
+    /// et contrôle, sans mémoire ni appels.
+    fn run(f: &ir::Func, args: &[i32]) -> i32 {
+        use ir::{BinOp as B, Expr as E, StmtKind as S};
+        enum Flow {
+            Next,
+            Br(ir::Label),
+            Ret(i32),
+        }
+        fn expr(e: &E, l: &[i32]) -> i32 {
+            match e {
+                E::Const(v) => *v,
+                E::Local(x) => l[x.0 as usize],
+                E::Unary(ir::UnOp::Eqz, x) => (expr(x, l) == 0) as i32,
+                E::Binary(op, a, b) => {
+                    let (a, b) = (expr(a, l), expr(b, l));
+                    let (ua, ub) = (a as u32, b as u32);
+                    match op {
+                        B::Add => a.wrapping_add(b),
+                        B::Sub => a.wrapping_sub(b),
+                        B::Mul => a.wrapping_mul(b),
+                        B::Eq => (a == b) as i32,
+                        B::Ne => (a != b) as i32,
+                        B::LtS => (a < b) as i32,
+                        B::LtU => (ua < ub) as i32,
+                        B::LeS => (a <= b) as i32,
+                        B::LeU => (ua <= ub) as i32,
+                        B::GtS => (a > b) as i32,
+                        B::GeS => (a >= b) as i32,
+                        B::GeU => (ua >= ub) as i32,
+                        _ => panic!("hors du sous-ensemble : {op:?}"),
+                    }
+                }
+                _ => panic!("hors du sous-ensemble : {e:?}"),
             }
         }
-        // i++
-        body.push_str(&format!(
-            "    local.get ${n}\n    i32.const 1\n    i32.add\n    local.set ${n}\n",
-            n = name
-        ));
-        // continue
-        body.push_str("    br $loop\n    end\n    end\n");
-    }
-
-    for s in &prog.stmts {
-        match s {
-            Stmt::Log(args) => emit_log(args, &mut body),
-            Stmt::For {
-                name,
-                start,
-                end,
-                body: inner,
-            } => emit_for(name, *start, *end, inner, &mut body, &mut emit_log),
-        }
-    }
-
-    // Locals: $pos + toutes les variables i32
-    let mut locals = String::new();
-    locals.push_str("    (local $pos i32)\n");
-    for v in vars.keys() {
-        locals.push_str(&format!("    (local ${} i32)\n", v));
-    }
-
-    // fonction util: i32 -> JSON (corrigée avec block/loop nommés)
-    let i32_to_json = r#"
-  ;; i32_to_json(val, dst) -> len
-  (func $i32_to_json (param $v i32) (param $dst i32) (result i32)
-    (local $neg i32) (local $pos i32) (local $d i32) (local $i i32) (local $j i32) (local $t i32)
-    i32.const 0
-    local.set $neg
-    local.get $v
-    i32.const 0
-    i32.lt_s
-    if
-      i32.const 1
-      local.set $neg
-      i32.const 0
-      local.get $v
-      i32.sub
-      local.set $v
-    end
-    ;; v==0 -> "0"
-    local.get $v
-    i32.eqz
-    if
-      local.get $dst
-      i32.const 48
-      i32.store8
-      i32.const 1
-      return
-    end
-    i32.const 0
-    local.set $pos
-    block $digits_exit
-      loop $digits
-        local.get $v
-        i32.const 10
-        i32.rem_u
-        local.set $d
-        local.get $dst
-        local.get $pos
-        i32.add
-        local.get $d
-        i32.const 48
-        i32.add
-        i32.store8
-        local.get $pos
-        i32.const 1
-        i32.add
-        local.set $pos
-
-        local.get $v
-        i32.const 10
-        i32.div_u
-        local.set $v
-
-        local.get $v
-        i32.eqz
-        br_if $digits_exit
-        br $digits
-      end
-    end
-    ;; ajoute '-' si négatif
-    local.get $neg
-    if
-      local.get $dst
-      local.get $pos
-      i32.add
-      i32.const 45
-      i32.store8
-      local.get $pos
-      i32.const 1
-      i32.add
-      local.set $pos
-    end
-    ;; reverse in place [0..pos-1]
-    i32.const 0
-    local.set $i
-    local.get $pos
-    i32.const 1
-    i32.sub
-    local.set $j
-    block $rev_exit
-      loop $rev
-        local.get $i
-        local.get $j
-        i32.ge_u
-        br_if $rev_exit
-
-        local.get $dst
-        local.get $i
-        i32.add
-        i32.load8_u
-        local.set $t
-
-        local.get $dst
-        local.get $i
-        i32.add
-        local.get $dst
-        local.get $j
-        i32.add
-        i32.load8_u
-        i32.store8
-
-        local.get $dst
-        local.get $j
-        i32.add
-        local.get $t
-        i32.store8
-
-        local.get $i
-        i32.const 1
-        i32.add
-        local.set $i
-
-        local.get $j
-        i32.const 1
-        i32.sub
-        local.set $j
-
-        br $rev
-      end
-    end
-    local.get $pos
-  )
-"#;
-
-    // Assemble le module
-    let mut wat = String::new();
-    wat.push_str("(module\n");
-    wat.push_str("  (import \"gaufre\" \"invoke\" (func $invoke (param i32 i32 i32 i32 i32 i32) (result i32)))\n");
-    wat.push_str("  (memory (export \"memory\") 1)\n");
-    wat.push_str(&data);
-    wat.push_str(i32_to_json);
-    wat.push_str("  (func (export \"main\")\n");
-    wat.push_str(&locals);
-    wat.push_str(&body);
-    wat.push_str("  )\n");
-    wat.push_str(")\n");
-
-    wat
+        fn stmts(ss: &[ir::Stmt], l: &mut [i32]) -> Flow {
+            for s in ss {
+                match &s.kind {
+                    S::Set(x, e) => l[x.0 as usize] = expr(e, l),
+                    S::Return(v) => return Flow::Ret(v.as_ref().map_or(0, |e| expr(e, l))),
+                    S::Block(label, body) => match stmts(body, l) {
+                        Flow::Br(b) if b == *label => {}
+                        Flow::Next => {}
+                        flow => return flow,
+                    },
+                    S::Loop(label, body) => loop {
+                        match stmts(body, l) {
+                            Flow::Br(b) if b == *label => {}
+                            Flow::Next => break,
+                            flow => return flow,
+                        }
+                    },
+                    S::Br(label) => return Flow::Br(*label),
+                    S::BrIf(label, c) if expr(c, l) != 0 => return Flow::Br(*label),
+                    S::BrIf(..) => {}
+                    S::If(c, a, b) => match stmts(if expr(c, l) != 0 { a } else { b }, l) {
+                        Flow::Next => {}
+                        flow => return flow,
+                    },
+                    kind => panic!("hors du sous-ensemble : {kind:?}"),
+                }
+            }
+            Flow::Next
+        }
+        let mut locals = vec![0; f.locals.len()];
+        locals[..args.len()].copy_from_slice(args);
+        match stmts(&f.body, &mut locals) {
+            Flow::Ret(v) => v,
+            _ => panic!("`{}` sans return", f.name),
+        }
+    }
+
+    fn func<'m>(m: &'m ir::Module, name: &str) -> &'m ir::Func {
+        m.funcs.iter().find(|f| f.name == name).unwrap()
+    }
+
+    // bornes aux extrémités de i32 : la boucle s'arrête sans déborder; `n`
+    // compte les tours, `last` garde la dernière valeur de la variable
+    const LOOPS: &str = "
+const MIN = 0 - 2147483647 - 1
+
+fn up(n: i32, last: i32) -> i32 {
+  for i = 2147483645 to 2147483647 {
+    n = n + 1
+    last = i
+  }
+  return n * 100 + last - 2147483600
+}
+fn stepped(n: i32, last: i32) -> i32 {
+  for i = 2147483640 to 2147483647 step 5 {
+    n = n + 1
+    last = i
+  }
+  return n * 100 + last - 2147483600
+}
+fn down(n: i32, last: i32) -> i32 {
+  for i = MIN + 3 downto MIN step 2 {
+    n = n + 1
+    last = i
+  }
+  return n * 100 + last - MIN
+}
+fn below(n: i32, bound: i32) -> i32 {
+  for i = 0 until bound step 2 {
+    n = n + 1
+  }
+  return n
+}
+
+fn main() {}
+";
+
+    #[test]
+    fn loop_bounds_at_i32_limits() {
+        let m = lower_src("loops", LOOPS).unwrap_or_else(|d| panic!("{}", d.message));
+        assert_eq!(run(func(&m, "up"), &[0, 0]), 3 * 100 + 47);
+        assert_eq!(run(func(&m, "stepped"), &[0, 0]), 2 * 100 + 45);
+        assert_eq!(run(func(&m, "down"), &[0, 0]), 2 * 100 + 1);
+        assert_eq!(run(func(&m, "below"), &[0, 5]), 3);
+        assert_eq!(run(func(&m, "below"), &[0, 0]), 0);
+        assert_eq!(run(func(&m, "below"), &[0, i32::MIN]), 0);
+    }
+
+    #[test]
+    fn loop_step_must_be_positive() {
+        let src = LOOPS.replace("step 5", "step 0");
+        assert_eq!(lower_src("step_zero", &src).err().map(|d| d.code), Some("G0409"));
+    }
+
+    // constantes dans n'importe quel ordre; une globale est initialisée à
+    // la compilation et s'exporte sous son nom
+    const ITEMS: &str = "
+const B = A * 2
+const A = 3
+export global total: i32 = B + 1
+global hits: i32 = 0
+
+fn main() {
+  hits = hits + total
+}
+";
+
+    #[test]
+    fn consts_and_globals() {
+        let m = lower_src("items", ITEMS).unwrap_or_else(|d| panic!("{}", d.message));
+        let global = |name| m.globals.iter().find(|g| g.name == name).unwrap();
+        assert_eq!(global("total").init, 7);
+        assert_eq!(global("total").export.as_deref(), Some("total"));
+        assert!(global("hits").mutable && global("hits").export.is_none());
+    }
+
+    #[test]
+    fn const_and_global_errors() {
+        fn code(name: &str, src: &str) -> Option<&'static str> {
+            lower_src(name, src).err().map(|d| d.code)
+        }
+        // cycle entre constantes, globale lue par un initialiseur, constante affectée
+        assert_eq!(code("const_cycle", &ITEMS.replace("const A = 3", "const A = B")), Some("G0405"));
+        assert_eq!(code("global_init", &ITEMS.replace("hits: i32 = 0", "hits: i32 = total")), Some("G0401"));
+        assert_eq!(code("assign_const", &ITEMS.replace("hits = hits", "A = hits")), Some("G0316"));
+    }
+
+    const FN_VALUES: &str = "
+fn double(x: i32) -> i32 {
+  return x * 2
+}
+fn square(x: i32) -> i32 {
+  return x * x
+}
+global op: fn(i32) -> i32 = double
+
+fn apply(f: fn(i32) -> i32, x: i32) -> i32 {
+  return f(x)
+}
+
+fn main() {
+  log(apply(square, 7), op(21))
+  op = square
+}
+";
+
+    #[test]
+    fn function_values() {
+        let m = lower_src("fn_values", FN_VALUES).unwrap_or_else(|d| panic!("{}", d.message));
+        let table: Vec<&str> = m.table.iter().map(|&f| m.func_name(f)).collect();
+        assert_eq!(table, ["double", "square"]);
+        assert_eq!(m.sigs, [ir::Sig { params: vec![ir::Ty::I32], result: Some(ir::Ty::I32) }]);
+        // `double` est la première entrée après l'index 0 laissé vide
+        let op = m.globals.iter().find(|g| g.name == "op").unwrap();
+        assert_eq!(op.init, ir::TABLE_BASE as i32);
+        let w = emit::module(&m).unwrap_or_else(|e| panic!("{e}"));
+        assert_eq!(w.table, Some(3));
+        validate::module(&w, true).unwrap_or_else(|e| panic!("{e}"));
+    }
+
+    #[test]
+    fn function_value_types() {
+        fn code(name: &str, src: &str) -> Option<&'static str> {
+            lower_src(name, src).err().map(|d| d.code)
+        }
+        let wrong_sig = FN_VALUES.replace("apply(square, 7)", "apply(apply, 7)");
+        assert_eq!(code("fn_sig", &wrong_sig), Some("G0509"));
+        assert_eq!(code("fn_arith", &FN_VALUES.replace("op(21)", "op + 1")), Some("G0509"));
+        assert_eq!(code("fn_int", &FN_VALUES.replace("op = square", "op = 3")), Some("G0509"));
+        assert_eq!(code("fn_call_int", &FN_VALUES.replace("f(x)", "x(f)")), Some("G0312"));
+    }
 }
@@ -0,0 +1,2583 @@
+//! Codes stables des diagnostics (`G0101`) et leurs explications longues.
+//!
+//! Chaque diagnostic porte un code de ce tableau, écrit avec `code!("G0101")`
+//! (un code absent du tableau ne compile pas). `gaufre explain G0101` affiche
+//! l'explication dans la langue courante, avec un exemple fautif et sa
+//! correction. Un code ne change jamais de sens; on n'en réutilise pas un
+//! code retiré.
+//!
+//! Numérotation par phase : G00xx lexer, G01xx parser, G02xx attributs,
+//! G03xx modules et noms, G04xx évaluation à la compilation, G05xx
+//! génération, G06xx profils de grammaire, G07xx traduction, G08xx écriture
+//! des résultats, G09xx lints (voir `lints`), G10xx fichiers `.wat` lus par
+//! `assemble` et `validate`.
+
+use crate::diag::Severity;
+use crate::i18n::{self, tr, Lang};
+
+pub struct Explanation {
+    pub code: &'static str,
+    pub severity: Severity,
+    /// Titre sur la première ligne, puis l'explication après une ligne vide.
+    fr: &'static str,
+    en: &'static str,
+    /// Exemples communs aux deux langues (profil `en`); une ligne
+    /// `// fichier.gfr` sépare les fichiers d'un exemple à plusieurs fichiers.
+    wrong: &'static str,
+    fixed: &'static str,
+}
+
+use Severity::{Error, Warning};
+
+const EXPLANATIONS: &[Explanation] = &[
+    // lexer
+    Explanation {
+        code: "G0001",
+        severity: Error,
+        fr: "chaîne non terminée\n\n\
+             Une chaîne commence par `\"` et s'arrête au `\"` suivant. Il n'y a pas \
+             d'échappement : une chaîne ne peut pas contenir de `\"`, et sans `\"` \
+             fermant elle court jusqu'à la fin du fichier.",
+        en: "unterminated string\n\n\
+             A string starts with `\"` and stops at the next `\"`. There are no escapes: \
+             a string cannot contain `\"`, and without a closing `\"` it runs to the end \
+             of the file.",
+        wrong: r#"
+fn main() {
+  log("bonjour)
+}"#,
+        fixed: r#"
+fn main() {
+  log("bonjour")
+}"#,
+    },
+    Explanation {
+        code: "G0002",
+        severity: Error,
+        fr: "bloc `wat` non terminé\n\n\
+             Le texte d'un bloc `wat { ... }` va jusqu'à la première `}` qui n'est pas \
+             dans une chaîne ou un commentaire WAT. Un commentaire `(; ... ;)` ou une \
+             chaîne laissés ouverts avalent la suite du fichier.",
+        en: "unterminated `wat` block\n\n\
+             The text of a `wat { ... }` block runs to the first `}` that is not inside \
+             a WAT string or comment. A `(; ... ;)` comment or a string left open \
+             swallows the rest of the file.",
+        wrong: r#"
+fn main() {
+  wat {
+    nop (; rien à faire
+  }
+}"#,
+        fixed: r#"
+fn main() {
+  wat {
+    nop (; rien à faire ;)
+  }
+}"#,
+    },
+    Explanation {
+        code: "G0003",
+        severity: Error,
+        fr: "caractère inattendu\n\n\
+             Ce caractère ne commence aucun token du profil de grammaire du fichier : \
+             ni identifiant, ni nombre, ni chaîne, ni opérateur. Le message donne son \
+             premier octet en hexadécimal. Les identifiants sont en ASCII.",
+        en: "unexpected character\n\n\
+             This character does not start any token of the file's grammar profile: \
+             not an identifier, a number, a string or an operator. The message shows \
+             its first byte in hexadecimal. Identifiers are ASCII.",
+        wrong: r#"
+fn main() {
+  log(2 ^ 3)
+}"#,
+        fixed: r#"
+fn main() {
+  log(2 * 2 * 2)
+}"#,
+    },
+    Explanation {
+        code: "G0004",
+        severity: Error,
+        fr: "bloc `wat` mal formé\n\n\
+             Le compilateur vérifie qu'un bloc `wat` est bien imbriqué : parenthèses \
+             équilibrées, chaque `block`, `loop` ou `if` de la forme plate fermé par \
+             `end`, `else` seulement dans un `if`, commentaires `(;` fermés. Le reste \
+             (pile, types, noms) est vérifié par `wat2wasm`.",
+        en: "malformed `wat` block\n\n\
+             The compiler checks that a `wat` block is well nested: balanced \
+             parentheses, every flat `block`, `loop` or `if` closed by `end`, `else` \
+             only inside an `if`, `(;` comments closed. The rest (stack, types, names) \
+             is checked by `wat2wasm`.",
+        wrong: r#"
+fn main() {
+  wat {
+    block
+      nop
+  }
+}"#,
+        fixed: r#"
+fn main() {
+  wat {
+    block
+      nop
+    end
+  }
+}"#,
+    },
+    // parser
+    Explanation {
+        code: "G0101",
+        severity: Error,
+        fr: "token inattendu\n\n\
+             Le parser attendait autre chose à cet endroit; le message liste ce qui \
+             était possible, écrit dans le profil de grammaire du fichier. Souvent une \
+             parenthèse ou une accolade oubliée, ou un mot-clé d'un autre profil.",
+        en: "unexpected token\n\n\
+             The parser expected something else here; the message lists what was \
+             possible, spelled in the file's grammar profile. Often a forgotten \
+             parenthesis or brace, or a keyword from another profile.",
+        wrong: r#"
+fn main() {
+  for i = 1 30 {
+    log(i)
+  }
+}"#,
+        fixed: r#"
+fn main() {
+  for i = 1 to 30 {
+    log(i)
+  }
+}"#,
+    },
+    Explanation {
+        code: "G0102",
+        severity: Error,
+        fr: "entier hors plage i32\n\n\
+             Un littéral entier doit tenir dans un i32 (au plus 2147483647). Pour une \
+             valeur qui déborde volontairement, la calculer : l'arithmétique est \
+             modulo 2^32.",
+        en: "integer out of i32 range\n\n\
+             An integer literal must fit in an i32 (at most 2147483647). For a value \
+             that overflows on purpose, compute it: arithmetic wraps modulo 2^32.",
+        wrong: r#"
+const BIG = 3000000000
+
+fn main() {
+  log(BIG)
+}"#,
+        fixed: r#"
+const BIG = 2000000000
+
+fn main() {
+  log(BIG)
+}"#,
+    },
+    Explanation {
+        code: "G0103",
+        severity: Error,
+        fr: "macro inconnue\n\n\
+             Une macro doit être définie dans le même fichier, avant son premier \
+             appel. Les macros ne s'importent pas d'un autre module.",
+        en: "unknown macro\n\n\
+             A macro must be defined in the same file, before its first call. Macros \
+             are not imported from other modules.",
+        wrong: r#"
+fn main() {
+  twice!(log("x"))
+}
+
+macro twice(body) {
+  body
+  body
+}"#,
+        fixed: r#"
+macro twice(body) {
+  body
+  body
+}
+
+fn main() {
+  twice!(log("x"))
+}"#,
+    },
+    Explanation {
+        code: "G0104",
+        severity: Error,
+        fr: "macro définie plusieurs fois\n\n\
+             Deux macros d'un même fichier ne peuvent pas avoir le même nom; la note \
+             montre la première définition.",
+        en: "macro defined more than once\n\n\
+             Two macros in the same file cannot share a name; the note shows the first \
+             definition.",
+        wrong: r#"
+macro show(x) { log(x) }
+macro show(x) { log("valeur", x) }
+
+fn main() {
+  show!(1)
+}"#,
+        fixed: r#"
+macro show(x) { log(x) }
+macro show_named(x) { log("valeur", x) }
+
+fn main() {
+  show!(1)
+  show_named!(2)
+}"#,
+    },
+    Explanation {
+        code: "G0105",
+        severity: Error,
+        fr: "mauvais nombre d'arguments de macro\n\n\
+             Un appel de macro passe exactement un argument par paramètre. Un argument \
+             est une suite de tokens jusqu'à la virgule de premier niveau suivante : \
+             une virgule dans des parenthèses ne sépare pas les arguments.",
+        en: "wrong number of macro arguments\n\n\
+             A macro call passes exactly one argument per parameter. An argument is a \
+             run of tokens up to the next top-level comma: a comma inside parentheses \
+             does not split arguments.",
+        wrong: r#"
+macro twice(body) {
+  body
+  body
+}
+
+fn main() {
+  twice!(log("a"), log("b"))
+}"#,
+        fixed: r#"
+macro twice(body) {
+  body
+  body
+}
+
+fn main() {
+  twice!(log("a"))
+  twice!(log("b"))
+}"#,
+    },
+    Explanation {
+        code: "G0106",
+        severity: Error,
+        fr: "récursion de macro trop profonde\n\n\
+             Les expansions de macro sont limitées à 64 niveaux imbriqués. Une macro \
+             qui s'appelle elle-même sans fin atteint toujours cette limite : il n'y a \
+             pas de condition d'arrêt à l'expansion. Utiliser une boucle ou une \
+             fonction.",
+        en: "macro recursion too deep\n\n\
+             Macro expansions are limited to 64 nested levels. A macro that calls \
+             itself endlessly always hits this limit: expansion has no stopping \
+             condition. Use a loop or a function.",
+        wrong: r#"
+macro forever(x) {
+  log(x)
+  forever!(x)
+}
+
+fn main() {
+  forever!(1)
+}"#,
+        fixed: r#"
+macro repeat(n, x) {
+  for k = 1 to n { log(x) }
+}
+
+fn main() {
+  repeat!(3, 1)
+}"#,
+    },
+    Explanation {
+        code: "G0107",
+        severity: Error,
+        fr: "attribut sur un appel de macro\n\n\
+             Un appel de macro ne peut pas porter d'attribut. Pour le rendre \
+             conditionnel, le mettre dans un `if const`.",
+        en: "attribute on a macro call\n\n\
+             A macro call cannot carry an attribute. To make it conditional, put it \
+             inside an `if const`.",
+        wrong: r#"
+macro trace(x) { log("trace", x) }
+
+fn main() {
+  @cfg(DEBUG)
+  trace!(1)
+}"#,
+        fixed: r#"
+macro trace(x) { log("trace", x) }
+
+fn main() {
+  if const DEBUG {
+    trace!(1)
+  }
+}"#,
+    },
+    Explanation {
+        code: "G0108",
+        severity: Error,
+        fr: "attribut mal placé\n\n\
+             Au niveau du fichier, un attribut précède une fonction, une constante ou \
+             une globale, pas un `import`, un `use` ni une définition de macro.",
+        en: "misplaced attribute\n\n\
+             At file level, an attribute comes before a function, a constant or a \
+             global, not an `import`, a `use` or a macro definition.",
+        wrong: r#"
+@inline
+macro double(x) { log(x * 2) }
+
+fn main() {
+  double!(1)
+}"#,
+        fixed: r#"
+@inline
+fn double(x: i32) -> i32 {
+  return x * 2
+}
+
+fn main() {
+  log(double(1))
+}"#,
+    },
+    Explanation {
+        code: "G0109",
+        severity: Error,
+        fr: "attribut de fichier mal placé\n\n\
+             Un attribut `@!nom(...)` (comme `@!allow(shadowed)`) porte sur tout le \
+             fichier : il s'écrit en tête, avant le premier item, `import` ou `use`.",
+        en: "misplaced file attribute\n\n\
+             An `@!name(...)` attribute (such as `@!allow(shadowed)`) applies to the \
+             whole file: it goes at the top, before the first item, `import` or `use`.",
+        wrong: r#"
+fn main() {
+  for _ = 1 to 3 {
+  }
+}
+
+@!allow(empty_range)"#,
+        fixed: r#"
+@!allow(empty_range)
+
+fn main() {
+  for _ = 1 to 3 {
+  }
+}"#,
+    },
+    Explanation {
+        code: "G0110",
+        severity: Error,
+        fr: "appel de macro non reconnu\n\n\
+             Une macro à règles `macro nom { (motif) => { corps } ... }` essaie ses \
+             règles dans l'ordre : l'appel doit être reconnu en entier par l'un des \
+             motifs, tokens écrits tels quels compris. Dans le corps, les variables \
+             d'une même répétition `$( ... )*` doivent aussi avoir été répétées le \
+             même nombre de fois. La note montre la définition.",
+        en: "macro call not matched\n\n\
+             A rule macro `macro name { (pattern) => { body } ... }` tries its rules \
+             in order: the whole call must be matched by one of the patterns, \
+             literal tokens included. In the body, the variables of one repetition \
+             `$( ... )*` must also have repeated the same number of times. The note \
+             shows the definition.",
+        wrong: r#"
+macro show {
+  ($label:literal = $v:expr) => { log($label, $v) }
+}
+
+fn main() {
+  show!("total", 6 * 7)
+}"#,
+        fixed: r#"
+macro show {
+  ($label:literal = $v:expr) => { log($label, $v) }
+}
+
+fn main() {
+  show!("total" = 6 * 7)
+}"#,
+    },
+    Explanation {
+        code: "G0111",
+        severity: Error,
+        fr: "règle de macro invalide\n\n\
+             Dans un motif, une variable s'écrit `$nom:fragment` avec un fragment \
+             parmi `ident`, `literal`, `expr`, `block` et `tt`, une répétition \
+             `$( ... ) sep *` ou `$( ... ) sep +` (séparateur facultatif). Dans le \
+             corps, `$nom` doit être une variable du motif, utilisée dans autant de \
+             `$( ... )*` qu'elle en a dans le motif.",
+        en: "invalid macro rule\n\n\
+             In a pattern, a variable is written `$name:fragment` with a fragment \
+             among `ident`, `literal`, `expr`, `block` and `tt`, a repetition \
+             `$( ... ) sep *` or `$( ... ) sep +` (optional separator). In the body, \
+             `$name` must be a pattern variable, used inside as many `$( ... )*` as \
+             it has in the pattern.",
+        wrong: r#"
+macro all {
+  ($($v:expr),*) => { log($v) }
+}
+
+fn main() {
+  all!(1, 2, 3)
+}"#,
+        fixed: r#"
+macro all {
+  ($($v:expr),*) => { $( log($v) )* }
+}
+
+fn main() {
+  all!(1, 2, 3)
+}"#,
+    },
+    // attributs
+    Explanation {
+        code: "G0201",
+        severity: Error,
+        fr: "attribut inconnu\n\n\
+             Les attributs existants sont `@export`, `@inline`, `@cold`, \
+             `@deprecated`, `@test` et `@cfg`.",
+        en: "unknown attribute\n\n\
+             The existing attributes are `@export`, `@inline`, `@cold`, `@deprecated`, \
+             `@test` and `@cfg`.",
+        wrong: r#"
+@inlined
+fn sq(x: i32) -> i32 {
+  return x * x
+}
+
+fn main() {
+  log(sq(3))
+}"#,
+        fixed: r#"
+@inline
+fn sq(x: i32) -> i32 {
+  return x * x
+}
+
+fn main() {
+  log(sq(3))
+}"#,
+    },
+    Explanation {
+        code: "G0202",
+        severity: Error,
+        fr: "attribut sur la mauvaise cible\n\n\
+             Chaque attribut ne s'applique qu'à certains éléments : `@export` aux \
+             fonctions et globales, `@inline`, `@cold` et `@test` aux fonctions, \
+             `@deprecated` aux fonctions, constantes et globales, `@cfg` partout, \
+             instructions comprises.",
+        en: "attribute on the wrong target\n\n\
+             Each attribute applies only to some items: `@export` to functions and \
+             globals, `@inline`, `@cold` and `@test` to functions, `@deprecated` to \
+             functions, constants and globals, `@cfg` everywhere, statements included.",
+        wrong: r#"
+@export
+const MAX = 10
+
+fn main() {
+  log(MAX)
+}"#,
+        fixed: r#"
+@export
+global max: i32 = 10
+
+fn main() {
+  log(max)
+}"#,
+    },
+    Explanation {
+        code: "G0203",
+        severity: Error,
+        fr: "attribut répété\n\n\
+             Un même attribut ne peut apparaître qu'une fois sur un élément. Pour \
+             exporter une fonction sous un autre nom, passer ce nom à un seul \
+             `@export`.",
+        en: "repeated attribute\n\n\
+             An attribute can appear only once on an item. To export a function under \
+             another name, give that name to a single `@export`.",
+        wrong: r#"
+@export
+@export("carre")
+fn sq(x: i32) -> i32 {
+  return x * x
+}
+
+fn main() {
+}"#,
+        fixed: r#"
+@export("carre")
+fn sq(x: i32) -> i32 {
+  return x * x
+}
+
+fn main() {
+}"#,
+    },
+    Explanation {
+        code: "G0204",
+        severity: Error,
+        fr: "arguments d'attribut invalides\n\n\
+             `@inline`, `@cold` et `@test` ne prennent pas d'argument; `@export` et \
+             `@deprecated` prennent au plus une chaîne; `@cfg` prend exactement une \
+             condition, comme `if const`.",
+        en: "invalid attribute arguments\n\n\
+             `@inline`, `@cold` and `@test` take no argument; `@export` and \
+             `@deprecated` take at most one string; `@cfg` takes exactly one \
+             condition, like `if const`.",
+        wrong: r#"
+@cfg
+fn trace() {
+  log("trace")
+}
+
+fn main() {
+}"#,
+        fixed: r#"
+@cfg(DEBUG)
+fn trace() {
+  log("trace")
+}
+
+fn main() {
+}"#,
+    },
+    Explanation {
+        code: "G0205",
+        severity: Error,
+        fr: "`@inline` et `@cold` ensemble\n\n\
+             `@inline` recopie la fonction à chaque appel, `@cold` la range à part \
+             comme rarement appelée : les deux ne vont pas ensemble.",
+        en: "`@inline` together with `@cold`\n\n\
+             `@inline` copies the function into every call, `@cold` sets it aside as \
+             rarely called: the two do not go together.",
+        wrong: r#"
+@inline
+@cold
+fn fail() -> i32 {
+  return 0 - 1
+}
+
+fn main() {
+  log(fail())
+}"#,
+        fixed: r#"
+@cold
+fn fail() -> i32 {
+  return 0 - 1
+}
+
+fn main() {
+  log(fail())
+}"#,
+    },
+    Explanation {
+        code: "G0206",
+        severity: Error,
+        fr: "corps de fonction `@inline` invalide\n\n\
+             Une fonction `@inline` est remplacée à chaque appel par l'expression \
+             qu'elle renvoie : son corps doit être une seule instruction `return expr`.",
+        en: "invalid `@inline` function body\n\n\
+             An `@inline` function is replaced at each call by the expression it \
+             returns: its body must be a single `return expr` statement.",
+        wrong: r#"
+@inline
+fn sq(x: i32) -> i32 {
+  log(x)
+  return x * x
+}
+
+fn main() {
+  log(sq(3))
+}"#,
+        fixed: r#"
+@inline
+fn sq(x: i32) -> i32 {
+  return x * x
+}
+
+fn main() {
+  log(sq(3))
+}"#,
+    },
+    Explanation {
+        code: "G0207",
+        severity: Error,
+        fr: "signature de test invalide\n\n\
+             Le lanceur de `--test` appelle chaque fonction `@test` sans argument et \
+             n'attend rien en retour : elle ne prend pas de paramètre et ne renvoie \
+             rien.",
+        en: "invalid test signature\n\n\
+             The `--test` runner calls each `@test` function with no argument and \
+             expects nothing back: it takes no parameter and returns nothing.",
+        wrong: r#"
+@test
+fn t_sq(x: i32) {
+  log(x * x)
+}
+
+fn main() {
+}"#,
+        fixed: r#"
+@test
+fn t_sq() {
+  log(4 * 4)
+}
+
+fn main() {
+}"#,
+    },
+    Explanation {
+        code: "G0208",
+        severity: Error,
+        fr: "appel d'une fonction `@test`\n\n\
+             Les fonctions `@test` n'existent que dans un build `--test`; seul un \
+             autre `@test` peut les appeler. Mettre le code commun dans une fonction \
+             ordinaire.",
+        en: "call to a `@test` function\n\n\
+             `@test` functions only exist in a `--test` build; only another `@test` \
+             can call them. Move shared code into an ordinary function.",
+        wrong: r#"
+@test
+fn t_sq() {
+  log(4 * 4)
+}
+
+fn main() {
+  t_sq()
+}"#,
+        fixed: r#"
+fn check_sq() {
+  log(4 * 4)
+}
+
+@test
+fn t_sq() {
+  check_sq()
+}
+
+fn main() {
+  check_sq()
+}"#,
+    },
+    Explanation {
+        code: "G0209",
+        severity: Error,
+        fr: "export en double\n\n\
+             Deux exports du module WASM ne peuvent pas porter le même nom. Un \
+             `@export` sans argument utilise le nom court (`helper` pour \
+             `util::helper`), et `main` comme `memory` sont toujours exportés.",
+        en: "duplicate export\n\n\
+             Two exports of the WASM module cannot share a name. An `@export` without \
+             an argument uses the short name (`helper` for `util::helper`), and both \
+             `main` and `memory` are always exported.",
+        wrong: r#"
+@export("run")
+fn start() {
+}
+
+@export("run")
+fn restart() {
+}
+
+fn main() {
+}"#,
+        fixed: r#"
+@export("run")
+fn start() {
+}
+
+@export("rerun")
+fn restart() {
+}
+
+fn main() {
+}"#,
+    },
+    Explanation {
+        code: "G0210",
+        severity: Error,
+        fr: "lint inconnu\n\n\
+             `@!allow`, `@!warn` et `@!deny` prennent des noms de lint : \
+             `unused_loop_var`, `empty_range`, `unreachable`, `shadowed`, \
+             `long_string`, `duplicate_log` et `deprecated`. Les mêmes noms servent \
+             à `-A`, `-W` et `--deny` sur la ligne de commande.",
+        en: "unknown lint\n\n\
+             `@!allow`, `@!warn` and `@!deny` take lint names: `unused_loop_var`, \
+             `empty_range`, `unreachable`, `shadowed`, `long_string`, `duplicate_log` \
+             and `deprecated`. The same names are used by `-A`, `-W` and `--deny` on \
+             the command line.",
+        wrong: r#"
+@!allow(unused)
+
+fn main() {
+  for i = 1 to 3 {
+  }
+}"#,
+        fixed: r#"
+@!allow(unused_loop_var)
+
+fn main() {
+  for i = 1 to 3 {
+  }
+}"#,
+    },
+    Explanation {
+        code: "G0211",
+        severity: Error,
+        fr: "`@export` sur `main`\n\n\
+             `main` est le point d'entrée que l'hôte appelle : il est toujours \
+             exporté sous le nom `main` (et remplacé par le lanceur de tests avec \
+             `--test`), son nom d'export ne se change donc pas. Pour offrir un \
+             autre point d'entrée, mettre le code dans une fonction `@export` que \
+             `main` appelle.",
+        en: "`@export` on `main`\n\n\
+             `main` is the entry point the host calls: it is always exported as \
+             `main` (and replaced by the test runner under `--test`), so its export \
+             name cannot change. To offer another entry point, move the code into an \
+             `@export` function that `main` calls.",
+        wrong: r#"
+@export("start")
+fn main() {
+  log("bonjour")
+}"#,
+        fixed: r#"
+@export("start")
+fn greet() {
+  log("bonjour")
+}
+
+fn main() {
+  greet()
+}"#,
+    },
+    // modules et noms
+    Explanation {
+        code: "G0301",
+        severity: Error,
+        fr: "fichier illisible\n\n\
+             Le fichier source, un fichier importé ou un profil `.gram` n'a pas pu \
+             être lu. Un `import` est relatif au fichier qui l'écrit, pas au \
+             répertoire courant. Le code de sortie est alors 3 (erreur \
+             d'entrée-sortie) au lieu de 1.",
+        en: "unreadable file\n\n\
+             The source file, an imported file or a `.gram` profile could not be read. \
+             An `import` is relative to the file that writes it, not to the current \
+             directory. The exit code is then 3 (I/O error) instead of 1.",
+        wrong: r#"
+// lib/util.gfr
+pub fn helper() { log("aide") }
+// lib/app.gfr
+import "lib/util.gfr"
+fn main() {
+  util::helper()
+}"#,
+        fixed: r#"
+// lib/util.gfr
+pub fn helper() { log("aide") }
+// lib/app.gfr
+import "util.gfr"
+fn main() {
+  util::helper()
+}"#,
+    },
+    Explanation {
+        code: "G0302",
+        severity: Error,
+        fr: "import circulaire\n\n\
+             Un module ne peut pas s'importer lui-même, directement ou par une chaîne \
+             d'imports. Déplacer le code partagé dans un troisième module importé par \
+             les deux.",
+        en: "circular import\n\n\
+             A module cannot import itself, directly or through a chain of imports. \
+             Move the shared code into a third module imported by both.",
+        wrong: r#"
+// main.gfr
+import "a.gfr"
+fn main() {
+  a::f()
+}
+// a.gfr
+import "b.gfr"
+pub fn f() { b::g() }
+// b.gfr
+import "a.gfr"
+pub fn g() { log(1) }"#,
+        fixed: r#"
+// main.gfr
+import "a.gfr"
+fn main() {
+  a::f()
+}
+// a.gfr
+import "b.gfr"
+pub fn f() { b::g() }
+// b.gfr
+pub fn g() { log(1) }"#,
+    },
+    Explanation {
+        code: "G0303",
+        severity: Error,
+        fr: "nom de module invalide\n\n\
+             Un `import` crée un module nommé comme le fichier, sans extension. Ce nom \
+             doit être un identifiant : lettres, chiffres et `_`, sans chiffre en tête.",
+        en: "invalid module name\n\n\
+             An `import` creates a module named after the file, without extension. \
+             That name must be an identifier: letters, digits and `_`, not starting \
+             with a digit.",
+        wrong: r#"
+// mes-outils.gfr
+pub fn aide() { log("aide") }
+// main.gfr
+import "mes-outils.gfr"
+fn main() {
+}"#,
+        fixed: r#"
+// mes_outils.gfr
+pub fn aide() { log("aide") }
+// main.gfr
+import "mes_outils.gfr"
+fn main() {
+  mes_outils::aide()
+}"#,
+    },
+    Explanation {
+        code: "G0304",
+        severity: Error,
+        fr: "module importé deux fois\n\n\
+             Deux imports d'un même fichier créent deux modules du même nom : il faut \
+             un seul `import` par module, même si les fichiers sont dans des \
+             répertoires différents.",
+        en: "module imported twice\n\n\
+             Two imports in the same file creating modules with the same name: there \
+             must be one `import` per module name, even if the files are in different \
+             directories.",
+        wrong: r#"
+// a/util.gfr
+pub fn f() { log("a") }
+// b/util.gfr
+pub fn g() { log("b") }
+// main.gfr
+import "a/util.gfr"
+import "b/util.gfr"
+fn main() {
+}"#,
+        fixed: r#"
+// a/util.gfr
+pub fn f() { log("a") }
+// b/outils.gfr
+pub fn g() { log("b") }
+// main.gfr
+import "a/util.gfr"
+import "b/outils.gfr"
+fn main() {
+  util::f()
+  outils::g()
+}"#,
+    },
+    Explanation {
+        code: "G0305",
+        severity: Error,
+        fr: "nom défini plusieurs fois\n\n\
+             Fonctions, constantes et globales d'un module partagent le même espace de \
+             noms : deux d'entre elles ne peuvent pas avoir le même nom. La note montre \
+             la première définition.",
+        en: "name defined more than once\n\n\
+             Functions, constants and globals of a module share one namespace: two of \
+             them cannot have the same name. The note shows the first definition.",
+        wrong: r#"
+const size = 4
+
+fn size() -> i32 {
+  return 8
+}
+
+fn main() {
+  log(size)
+}"#,
+        fixed: r#"
+const SIZE = 4
+
+fn size() -> i32 {
+  return 8
+}
+
+fn main() {
+  log(SIZE, size())
+}"#,
+    },
+    Explanation {
+        code: "G0306",
+        severity: Error,
+        fr: "`use` en conflit\n\n\
+             Un `use` introduit un nom court dans le module; il ne peut pas masquer un \
+             item du module ni un autre `use`. Garder le chemin complet pour l'un des \
+             deux.",
+        en: "conflicting `use`\n\n\
+             A `use` brings a short name into the module; it cannot shadow an item of \
+             the module or another `use`. Keep the full path for one of them.",
+        wrong: r#"
+// math.gfr
+pub fn sq(x: i32) -> i32 { return x * x }
+// main.gfr
+import "math.gfr"
+use math::sq
+fn sq(x: i32) -> i32 {
+  return x * x
+}
+fn main() {
+  log(sq(2))
+}"#,
+        fixed: r#"
+// math.gfr
+pub fn sq(x: i32) -> i32 { return x * x }
+// main.gfr
+import "math.gfr"
+fn sq(x: i32) -> i32 {
+  return x * x
+}
+fn main() {
+  log(sq(2), math::sq(3))
+}"#,
+    },
+    Explanation {
+        code: "G0307",
+        severity: Error,
+        fr: "module inconnu\n\n\
+             Le premier segment d'un chemin `a::b` doit être un module importé par le \
+             fichier courant (ou introduit par un `use`). Les imports ne se \
+             transmettent pas : un module importé par un autre n'est pas visible ici.",
+        en: "unknown module\n\n\
+             The first segment of a path `a::b` must be a module imported by the \
+             current file (or brought in by a `use`). Imports are not transitive: a \
+             module imported by another one is not visible here.",
+        wrong: r#"
+// math.gfr
+pub fn sq(x: i32) -> i32 { return x * x }
+// main.gfr
+fn main() {
+  log(math::sq(3))
+}"#,
+        fixed: r#"
+// math.gfr
+pub fn sq(x: i32) -> i32 { return x * x }
+// main.gfr
+import "math.gfr"
+fn main() {
+  log(math::sq(3))
+}"#,
+    },
+    Explanation {
+        code: "G0308",
+        severity: Error,
+        fr: "sous-module inconnu\n\n\
+             Dans `a::b::c`, `b` doit être un module importé par le fichier de `a`. \
+             Le message nomme le module où la recherche a échoué.",
+        en: "unknown submodule\n\n\
+             In `a::b::c`, `b` must be a module imported by the file of `a`. The \
+             message names the module where the lookup failed.",
+        wrong: r#"
+// plan.gfr
+pub fn area(x: i32) -> i32 { return x * x }
+// geo.gfr
+import "plan.gfr"
+// main.gfr
+import "geo.gfr"
+fn main() {
+  log(geo::espace::volume(2))
+}"#,
+        fixed: r#"
+// plan.gfr
+pub fn area(x: i32) -> i32 { return x * x }
+// geo.gfr
+import "plan.gfr"
+// main.gfr
+import "geo.gfr"
+fn main() {
+  log(geo::plan::area(2))
+}"#,
+    },
+    Explanation {
+        code: "G0309",
+        severity: Error,
+        fr: "item privé\n\n\
+             Seuls les items marqués `pub` sont accessibles depuis un autre module. La \
+             note montre la définition à rendre publique.",
+        en: "private item\n\n\
+             Only items marked `pub` can be reached from another module. The note \
+             shows the definition to make public.",
+        wrong: r#"
+// util.gfr
+fn helper() { log("aide") }
+// main.gfr
+import "util.gfr"
+fn main() {
+  util::helper()
+}"#,
+        fixed: r#"
+// util.gfr
+pub fn helper() { log("aide") }
+// main.gfr
+import "util.gfr"
+fn main() {
+  util::helper()
+}"#,
+    },
+    Explanation {
+        code: "G0310",
+        severity: Error,
+        fr: "item absent du module\n\n\
+             Le module existe mais ne définit ni item ni sous-module de ce nom.",
+        en: "item missing from module\n\n\
+             The module exists but defines no item or submodule with that name.",
+        wrong: r#"
+// util.gfr
+pub fn helper() { log("aide") }
+// main.gfr
+import "util.gfr"
+fn main() {
+  util::help()
+}"#,
+        fixed: r#"
+// util.gfr
+pub fn helper() { log("aide") }
+// main.gfr
+import "util.gfr"
+fn main() {
+  util::helper()
+}"#,
+    },
+    Explanation {
+        code: "G0311",
+        severity: Error,
+        fr: "module utilisé comme valeur ou fonction\n\n\
+             Ce chemin désigne un module : il faut nommer un de ses items.",
+        en: "module used as a value or function\n\n\
+             This path names a module: name one of its items.",
+        wrong: r#"
+// util.gfr
+pub fn helper() { log("aide") }
+// main.gfr
+import "util.gfr"
+fn main() {
+  util()
+}"#,
+        fixed: r#"
+// util.gfr
+pub fn helper() { log("aide") }
+// main.gfr
+import "util.gfr"
+fn main() {
+  util::helper()
+}"#,
+    },
+    Explanation {
+        code: "G0312",
+        severity: Error,
+        fr: "appel d'une valeur\n\n\
+             Seules les fonctions, et les paramètres et globales de type \
+             `fn(...)`, s'appellent; une constante ou une globale `i32` se lit sans \
+             parenthèses.",
+        en: "call of a value\n\n\
+             Only functions, and parameters and globals of type `fn(...)`, can be \
+             called; a constant or an `i32` global is read without parentheses.",
+        wrong: r#"
+const N = 3
+
+fn main() {
+  log(N())
+}"#,
+        fixed: r#"
+const N = 3
+
+fn main() {
+  log(N)
+}"#,
+    },
+    Explanation {
+        code: "G0313",
+        severity: Error,
+        fr: "affectation d'une fonction\n\n\
+             Une fonction se lit comme une valeur mais ne s'affecte pas; pour \
+             changer de fonction, il faut une globale de type `fn(...)`.",
+        en: "assignment to a function\n\n\
+             A function can be read as a value but not assigned; to switch \
+             functions, use a global of type `fn(...)`.",
+        wrong: r#"
+fn double(x: i32) -> i32 {
+  return x * 2
+}
+
+fn triple(x: i32) -> i32 {
+  return x * 3
+}
+
+fn main() {
+  double = triple
+}"#,
+        fixed: r#"
+fn double(x: i32) -> i32 {
+  return x * 2
+}
+
+fn triple(x: i32) -> i32 {
+  return x * 3
+}
+
+global op: fn(i32) -> i32 = double
+
+fn main() {
+  op = triple
+  log(op(5))
+}"#,
+    },
+    Explanation {
+        code: "G0314",
+        severity: Warning,
+        fr: "usage d'un item obsolète\n\n\
+             L'item est marqué `@deprecated`; le message reprend la raison donnée en \
+             argument, s'il y en a une. La compilation continue.",
+        en: "use of a deprecated item\n\n\
+             The item is marked `@deprecated`; the message repeats the reason given as \
+             its argument, if any. Compilation goes on.",
+        wrong: r#"
+@deprecated("utiliser sq")
+fn square(x: i32) -> i32 {
+  return x * x
+}
+
+fn sq(x: i32) -> i32 {
+  return x * x
+}
+
+fn main() {
+  log(square(3))
+}"#,
+        fixed: r#"
+@deprecated("utiliser sq")
+fn square(x: i32) -> i32 {
+  return x * x
+}
+
+fn sq(x: i32) -> i32 {
+  return x * x
+}
+
+fn main() {
+  log(sq(3))
+}"#,
+    },
+    Explanation {
+        code: "G0315",
+        severity: Error,
+        fr: "affectation d'une variable de boucle\n\n\
+             La variable d'un `for` est gérée par la boucle et ne peut pas être \
+             modifiée dans son corps. Utiliser `step` ou une autre variable.",
+        en: "assignment to a loop variable\n\n\
+             The variable of a `for` is driven by the loop and cannot be modified in \
+             its body. Use `step` or another variable.",
+        wrong: r#"
+fn main() {
+  for i = 1 to 10 {
+    log(i)
+    i = i + 1
+  }
+}"#,
+        fixed: r#"
+fn main() {
+  for i = 1 to 10 step 2 {
+    log(i)
+  }
+}"#,
+    },
+    Explanation {
+        code: "G0316",
+        severity: Error,
+        fr: "affectation d'une constante\n\n\
+             Une `const` est recopiée à chaque usage et ne change pas; pour une valeur \
+             modifiable au niveau du fichier, utiliser une `global`.",
+        en: "assignment to a constant\n\n\
+             A `const` is copied at each use and never changes; for a modifiable \
+             file-level value, use a `global`.",
+        wrong: r#"
+const COUNT = 0
+
+fn main() {
+  COUNT = COUNT + 1
+}"#,
+        fixed: r#"
+global count: i32 = 0
+
+fn main() {
+  count = count + 1
+}"#,
+    },
+    Explanation {
+        code: "G0317",
+        severity: Error,
+        fr: "pas de fonction `main`\n\n\
+             Le fichier passé au compilateur doit définir `fn main()`, point d'entrée \
+             exporté du module. Un module importé, lui, n'en a pas.",
+        en: "no `main` function\n\n\
+             The file given to the compiler must define `fn main()`, the exported \
+             entry point of the module. An imported module has none.",
+        wrong: r#"
+fn start() {
+  log("go")
+}"#,
+        fixed: r#"
+fn main() {
+  log("go")
+}"#,
+    },
+    Explanation {
+        code: "G0318",
+        severity: Error,
+        fr: "signature de `main` invalide\n\n\
+             `main` est appelée par l'hôte sans argument et ne renvoie rien.",
+        en: "invalid `main` signature\n\n\
+             `main` is called by the host with no argument and returns nothing.",
+        wrong: r#"
+fn main() -> i32 {
+  return 0
+}"#,
+        fixed: r#"
+fn main() {
+}"#,
+    },
+    Explanation {
+        code: "G0319",
+        severity: Error,
+        fr: "`main` dans un module importé\n\n\
+             Seul le fichier principal définit `main`; un module importé doit \
+             l'appeler autrement.",
+        en: "`main` in an imported module\n\n\
+             Only the main file defines `main`; an imported module must call it \
+             something else.",
+        wrong: r#"
+// util.gfr
+fn main() { log("util") }
+pub fn helper() { log("aide") }
+// app.gfr
+import "util.gfr"
+fn main() {
+  util::helper()
+}"#,
+        fixed: r#"
+// util.gfr
+fn run() { log("util") }
+pub fn helper() { log("aide") }
+// app.gfr
+import "util.gfr"
+fn main() {
+  util::helper()
+}"#,
+    },
+    Explanation {
+        code: "G0320",
+        severity: Error,
+        fr: "condition de compilation invalide\n\n\
+             La condition d'un `if const` ou d'un `@cfg` est évaluée à la compilation \
+             avec seulement les `-D` de la ligne de commande (un nom absent vaut 0) : \
+             pas d'appel de fonction, pas de division par zéro.",
+        en: "invalid compile-time condition\n\n\
+             The condition of an `if const` or a `@cfg` is evaluated at compile time \
+             with only the command-line `-D` names (a missing name is 0): no function \
+             calls, no division by zero.",
+        wrong: r#"
+const fn level() -> i32 {
+  return 2
+}
+
+fn main() {
+  if const level() > 1 {
+    log("détails")
+  }
+}"#,
+        fixed: r#"
+fn main() {
+  if const LEVEL > 1 {
+    log("détails")
+  }
+}"#,
+    },
+    Explanation {
+        code: "G0321",
+        severity: Error,
+        fr: "paramètre défini plusieurs fois\n\n\
+             Les paramètres d'une fonction ont chacun leur nom : avec deux `a`, les \
+             deux seraient des locals WebAssembly du même nom et chaque lecture de \
+             `a` ne pourrait désigner que l'un d'eux. La note montre le premier.",
+        en: "parameter defined more than once\n\n\
+             Each parameter of a function needs its own name: with two `a`, both \
+             would be WebAssembly locals with the same name and every read of `a` \
+             could only mean one of them. The note shows the first one.",
+        wrong: r#"
+fn add(a: i32, a: i32) -> i32 {
+  return a + a
+}
+
+fn main() {
+  log(add(1, 2))
+}"#,
+        fixed: r#"
+fn add(a: i32, b: i32) -> i32 {
+  return a + b
+}
+
+fn main() {
+  log(add(1, 2))
+}"#,
+    },
+    // évaluation à la compilation
+    Explanation {
+        code: "G0401",
+        severity: Error,
+        fr: "initialiseur non constant\n\n\
+             L'initialiseur d'une `const` ou d'une `global` est calculé à la \
+             compilation : il ne peut lire ni une globale ni une variable, seulement \
+             des littéraux, des constantes et des appels de `const fn`.",
+        en: "non-constant initializer\n\n\
+             The initializer of a `const` or a `global` is computed at compile time: \
+             it can read neither a global nor a variable, only literals, constants \
+             and `const fn` calls.",
+        wrong: r#"
+global base: i32 = 10
+const LIMIT = base * 2
+
+fn main() {
+  log(LIMIT)
+}"#,
+        fixed: r#"
+const BASE = 10
+const LIMIT = BASE * 2
+
+fn main() {
+  log(LIMIT)
+}"#,
+    },
+    Explanation {
+        code: "G0402",
+        severity: Error,
+        fr: "nom inconnu\n\n\
+             Aucune variable, constante, globale ou fonction de ce nom n'est visible \
+             ici. Un item d'un autre module s'écrit avec son chemin (`util::helper`) \
+             ou après un `use`.",
+        en: "unknown name\n\n\
+             No variable, constant, global or function with this name is visible here. \
+             An item from another module is written with its path (`util::helper`) or \
+             after a `use`.",
+        wrong: r#"
+fn main() {
+  log(total)
+}"#,
+        fixed: r#"
+global total: i32 = 0
+
+fn main() {
+  log(total)
+}"#,
+    },
+    Explanation {
+        code: "G0403",
+        severity: Error,
+        fr: "division par zéro à la compilation\n\n\
+             Une division ou un reste par zéro trappe en WASM; calculé à la \
+             compilation, il est refusé.",
+        en: "division by zero at compile time\n\n\
+             A division or remainder by zero traps in WASM; computed at compile time, \
+             it is rejected.",
+        wrong: r#"
+const PARTS = 0
+const SIZE = 100 / PARTS
+
+fn main() {
+  log(SIZE)
+}"#,
+        fixed: r#"
+const PARTS = 4
+const SIZE = 100 / PARTS
+
+fn main() {
+  log(SIZE)
+}"#,
+    },
+    Explanation {
+        code: "G0404",
+        severity: Error,
+        fr: "débordement de division à la compilation\n\n\
+             `i32::MIN / -1` n'a pas de résultat en i32 et trappe en WASM; calculé à la \
+             compilation, il est refusé. Les autres opérations sont modulo 2^32.",
+        en: "division overflow at compile time\n\n\
+             `i32::MIN / -1` has no i32 result and traps in WASM; computed at compile \
+             time, it is rejected. Other operations wrap modulo 2^32.",
+        wrong: r#"
+const MIN = 0 - 2147483647 - 1
+const X = MIN / (0 - 1)
+
+fn main() {
+  log(X)
+}"#,
+        fixed: r#"
+const MIN = 0 - 2147483647 - 1
+const X = MIN / 2
+
+fn main() {
+  log(X)
+}"#,
+    },
+    Explanation {
+        code: "G0405",
+        severity: Error,
+        fr: "définition circulaire\n\n\
+             Une constante ne peut pas dépendre d'elle-même, directement ou par \
+             d'autres constantes.",
+        en: "circular definition\n\n\
+             A constant cannot depend on itself, directly or through other constants.",
+        wrong: r#"
+const A = B + 1
+const B = A * 2
+
+fn main() {
+  log(A)
+}"#,
+        fixed: r#"
+const A = 1
+const B = A * 2
+
+fn main() {
+  log(A, B)
+}"#,
+    },
+    Explanation {
+        code: "G0406",
+        severity: Error,
+        fr: "appel d'une fonction ordinaire à la compilation\n\n\
+             Seule une `const fn` peut être exécutée par le compilateur, par exemple \
+             dans l'initialiseur d'une constante.",
+        en: "call of an ordinary function at compile time\n\n\
+             Only a `const fn` can be run by the compiler, for example in the \
+             initializer of a constant.",
+        wrong: r#"
+fn sq(x: i32) -> i32 {
+  return x * x
+}
+const AREA = sq(4)
+
+fn main() {
+  log(AREA)
+}"#,
+        fixed: r#"
+const fn sq(x: i32) -> i32 {
+  return x * x
+}
+const AREA = sq(4)
+
+fn main() {
+  log(AREA)
+}"#,
+    },
+    Explanation {
+        code: "G0407",
+        severity: Error,
+        fr: "évaluation trop longue\n\n\
+             Une évaluation à la compilation est limitée à 1 000 000 d'étapes \
+             (instructions, tours de boucle, appels). Réduire le calcul ou le faire à \
+             l'exécution avec une fonction ordinaire.",
+        en: "evaluation too long\n\n\
+             A compile-time evaluation is limited to 1,000,000 steps (statements, loop \
+             iterations, calls). Shrink the computation or do it at run time with an \
+             ordinary function.",
+        wrong: r#"
+const fn sum(n: i32, acc: i32) -> i32 {
+  for k = 1 to n {
+    acc = acc + k
+  }
+  return acc
+}
+const TOTAL = sum(5000000, 0)
+
+fn main() {
+  log(TOTAL)
+}"#,
+        fixed: r#"
+const fn sum(n: i32) -> i32 {
+  return n * (n + 1) / 2
+}
+const TOTAL = sum(5000000)
+
+fn main() {
+  log(TOTAL)
+}"#,
+    },
+    Explanation {
+        code: "G0408",
+        severity: Error,
+        fr: "récursion trop profonde à la compilation\n\n\
+             Les appels de `const fn` imbriqués sont limités à 200. Le langage n'a \
+             pas de `if` à l'exécution : une `const fn` qui s'appelle elle-même ne \
+             s'arrête jamais. Écrire le calcul avec une boucle.",
+        en: "recursion too deep at compile time\n\n\
+             Nested `const fn` calls are limited to 200. The language has no run-time \
+             `if`: a `const fn` that calls itself never stops. Write the computation \
+             with a loop.",
+        wrong: r#"
+const fn fact(n: i32) -> i32 {
+  return n * fact(n - 1)
+}
+const F5 = fact(5)
+
+fn main() {
+  log(F5)
+}"#,
+        fixed: r#"
+const fn fact(n: i32, acc: i32) -> i32 {
+  for k = 1 to n {
+    acc = acc * k
+  }
+  return acc
+}
+const F5 = fact(5, 1)
+
+fn main() {
+  log(F5)
+}"#,
+    },
+    Explanation {
+        code: "G0409",
+        severity: Error,
+        fr: "pas de boucle invalide\n\n\
+             Le `step` d'une boucle doit être strictement positif; pour compter à \
+             rebours, utiliser `downto` avec un pas positif. Un pas connu à la \
+             compilation est vérifié à la compilation, sinon la boucle trappe.",
+        en: "invalid loop step\n\n\
+             A loop `step` must be strictly positive; to count down, use `downto` with \
+             a positive step. A step known at compile time is checked at compile time, \
+             otherwise the loop traps.",
+        wrong: r#"
+fn main() {
+  for i = 10 to 1 step 0 - 1 {
+    log(i)
+  }
+}"#,
+        fixed: r#"
+fn main() {
+  for i = 10 downto 1 {
+    log(i)
+  }
+}"#,
+    },
+    Explanation {
+        code: "G0410",
+        severity: Error,
+        fr: "`const fn` sans `return`\n\n\
+             L'exécution d'une `const fn` a atteint la fin de son corps sans \
+             rencontrer de `return`.",
+        en: "`const fn` without `return`\n\n\
+             Running a `const fn` reached the end of its body without meeting a \
+             `return`.",
+        wrong: r#"
+const fn half(x: i32) -> i32 {
+  if const FAST {
+    return x / 2
+  }
+}
+const H = half(8)
+
+fn main() {
+  log(H)
+}"#,
+        fixed: r#"
+const fn half(x: i32) -> i32 {
+  return x / 2
+}
+const H = half(8)
+
+fn main() {
+  log(H)
+}"#,
+    },
+    Explanation {
+        code: "G0411",
+        severity: Error,
+        fr: "globale lue dans une `const fn`\n\n\
+             Une `const fn` doit donner le même résultat à la compilation et à \
+             l'exécution : elle ne lit que ses paramètres, ses variables et des \
+             constantes.",
+        en: "global read in a `const fn`\n\n\
+             A `const fn` must give the same result at compile time and at run time: \
+             it only reads its parameters, its variables and constants.",
+        wrong: r#"
+global scale: i32 = 3
+
+const fn scaled(x: i32) -> i32 {
+  return x * scale
+}
+
+fn main() {
+  log(scaled(2))
+}"#,
+        fixed: r#"
+const SCALE = 3
+
+const fn scaled(x: i32) -> i32 {
+  return x * SCALE
+}
+
+fn main() {
+  log(scaled(2))
+}"#,
+    },
+    Explanation {
+        code: "G0412",
+        severity: Error,
+        fr: "appel non `const` dans une `const fn`\n\n\
+             Une `const fn` n'appelle que d'autres `const fn`, pour que le compilateur \
+             puisse l'exécuter entièrement.",
+        en: "non-`const` call in a `const fn`\n\n\
+             A `const fn` only calls other `const fn`s, so that the compiler can run \
+             it entirely.",
+        wrong: r#"
+fn sq(x: i32) -> i32 {
+  return x * x
+}
+
+const fn quad(x: i32) -> i32 {
+  return sq(sq(x))
+}
+
+fn main() {
+  log(quad(2))
+}"#,
+        fixed: r#"
+const fn sq(x: i32) -> i32 {
+  return x * x
+}
+
+const fn quad(x: i32) -> i32 {
+  return sq(sq(x))
+}
+
+fn main() {
+  log(quad(2))
+}"#,
+    },
+    Explanation {
+        code: "G0413",
+        severity: Error,
+        fr: "`log` dans une `const fn`\n\n\
+             Une `const fn` n'a pas d'effet visible; un `log` exécuté à la compilation \
+             n'aurait nulle part où écrire.",
+        en: "`log` in a `const fn`\n\n\
+             A `const fn` has no visible effect; a `log` run at compile time would \
+             have nowhere to write.",
+        wrong: r#"
+const fn sq(x: i32) -> i32 {
+  log(x)
+  return x * x
+}
+
+fn main() {
+  log(sq(3))
+}"#,
+        fixed: r#"
+const fn sq(x: i32) -> i32 {
+  return x * x
+}
+
+fn main() {
+  log(3, sq(3))
+}"#,
+    },
+    Explanation {
+        code: "G0414",
+        severity: Error,
+        fr: "bloc `wat` dans une `const fn`\n\n\
+             Le compilateur n'exécute pas de WAT : une `const fn` ne contient que du \
+             gaufre.",
+        en: "`wat` block in a `const fn`\n\n\
+             The compiler does not run WAT: a `const fn` contains only gaufre code.",
+        wrong: r#"
+const fn id(x: i32) -> i32 {
+  wat { nop }
+  return x
+}
+
+fn main() {
+  log(id(1))
+}"#,
+        fixed: r#"
+const fn id(x: i32) -> i32 {
+  return x
+}
+
+fn main() {
+  log(id(1))
+}"#,
+    },
+    Explanation {
+        code: "G0415",
+        severity: Error,
+        fr: "globale modifiée dans une `const fn`\n\n\
+             Une `const fn` ne modifie que ses paramètres et ses variables.",
+        en: "global modified in a `const fn`\n\n\
+             A `const fn` only modifies its parameters and its variables.",
+        wrong: r#"
+global calls: i32 = 0
+
+const fn sq(x: i32) -> i32 {
+  calls = calls + 1
+  return x * x
+}
+
+fn main() {
+  log(sq(3))
+}"#,
+        fixed: r#"
+global calls: i32 = 0
+
+const fn sq(x: i32) -> i32 {
+  return x * x
+}
+
+fn main() {
+  calls = calls + 1
+  log(sq(3))
+}"#,
+    },
+    Explanation {
+        code: "G0416",
+        severity: Error,
+        fr: "`const fn` sans valeur de retour\n\n\
+             Une `const fn` sert à calculer une valeur : elle est déclarée `-> i32`.",
+        en: "`const fn` without a return value\n\n\
+             A `const fn` computes a value: it is declared `-> i32`.",
+        wrong: r#"
+const fn nothing(x: i32) {
+}
+
+fn main() {
+  nothing(1)
+}"#,
+        fixed: r#"
+fn nothing(x: i32) {
+}
+
+fn main() {
+  nothing(1)
+}"#,
+    },
+    // génération
+    Explanation {
+        code: "G0501",
+        severity: Error,
+        fr: "mauvais nombre d'arguments\n\n\
+             Un appel passe exactement un argument par paramètre de la fonction.",
+        en: "wrong number of arguments\n\n\
+             A call passes exactly one argument per parameter of the function.",
+        wrong: r#"
+fn add(a: i32, b: i32) -> i32 {
+  return a + b
+}
+
+fn main() {
+  log(add(1))
+}"#,
+        fixed: r#"
+fn add(a: i32, b: i32) -> i32 {
+  return a + b
+}
+
+fn main() {
+  log(add(1, 2))
+}"#,
+    },
+    Explanation {
+        code: "G0502",
+        severity: Error,
+        fr: "fonction sans valeur dans une expression\n\n\
+             Une fonction sans `-> i32` ne renvoie rien : elle s'appelle en \
+             instruction, pas dans une expression.",
+        en: "function without a value in an expression\n\n\
+             A function without `-> i32` returns nothing: call it as a statement, not \
+             inside an expression.",
+        wrong: r#"
+fn greet() {
+  log("salut")
+}
+
+fn main() {
+  log(greet())
+}"#,
+        fixed: r#"
+fn greet() {
+  log("salut")
+}
+
+fn main() {
+  greet()
+}"#,
+    },
+    Explanation {
+        code: "G0503",
+        severity: Error,
+        fr: "`return` avec une valeur dans une fonction sans retour\n\n\
+             Pour renvoyer une valeur, déclarer la fonction `-> i32`.",
+        en: "`return` with a value in a function without a return type\n\n\
+             To return a value, declare the function `-> i32`.",
+        wrong: r#"
+fn answer() {
+  return 42
+}
+
+fn main() {
+  answer()
+}"#,
+        fixed: r#"
+fn answer() -> i32 {
+  return 42
+}
+
+fn main() {
+  log(answer())
+}"#,
+    },
+    Explanation {
+        code: "G0504",
+        severity: Error,
+        fr: "`return` sans valeur dans une fonction `-> i32`\n\n\
+             Une fonction déclarée `-> i32` renvoie une valeur à chaque `return`.",
+        en: "`return` without a value in an `-> i32` function\n\n\
+             A function declared `-> i32` returns a value at every `return`.",
+        wrong: r#"
+fn answer() -> i32 {
+  return
+}
+
+fn main() {
+  log(answer())
+}"#,
+        fixed: r#"
+fn answer() -> i32 {
+  return 42
+}
+
+fn main() {
+  log(answer())
+}"#,
+    },
+    Explanation {
+        code: "G0505",
+        severity: Error,
+        fr: "fonction `-> i32` sans `return` final\n\n\
+             La dernière instruction d'une fonction `-> i32` doit être un `return` \
+             avec une valeur, même si toutes les branches précédentes renvoient déjà.",
+        en: "`-> i32` function without a final `return`\n\n\
+             The last statement of an `-> i32` function must be a `return` with a \
+             value, even if every earlier branch already returns.",
+        wrong: r#"
+fn sq(x: i32) -> i32 {
+  log(x * x)
+}
+
+fn main() {
+  log(sq(3))
+}"#,
+        fixed: r#"
+fn sq(x: i32) -> i32 {
+  return x * x
+}
+
+fn main() {
+  log(sq(3))
+}"#,
+    },
+    Explanation {
+        code: "G0506",
+        severity: Error,
+        fr: "erreur interne du compilateur\n\n\
+             Le module WebAssembly généré ne tient pas debout : une instruction \
+             manque de valeurs sur la pile, un bloc est mal refermé ou un index ne \
+             désigne rien. C'est un bug de gaufre et non du programme : merci de le \
+             signaler avec le fichier source. `--emit ir` montre la représentation \
+             intermédiaire de l'instruction désignée. Le contenu des blocs \
+             `wat { ... }` n'est pas suivi et ne la provoque pas.",
+        en: "internal compiler error\n\n\
+             The generated WebAssembly module does not hold together: an \
+             instruction lacks values on the stack, a block is badly closed or an \
+             index refers to nothing. This is a bug in gaufre, not in the program: \
+             please report it with the source file. `--emit ir` shows the \
+             intermediate representation of the statement pointed at. The content \
+             of `wat { ... }` blocks is not tracked and does not cause it.",
+        wrong: r#"
+// gaufre prog.gfr : erreur[G0506] sur le `log`
+fn main() {
+  log("Bonjour")
+}"#,
+        fixed: r#"
+// gaufre --emit ir prog.gfr prog.ir, à joindre au signalement
+fn main() {
+  log("Bonjour")
+}"#,
+    },
+    Explanation {
+        code: "G0507",
+        severity: Error,
+        fr: "bloc `wat` impossible à assembler\n\n\
+             Avec `--emit wasm` ou `check --validate`, gaufre assemble lui-même \
+             le texte des blocs `wat { ... }`. Il connaît les instructions i32, les accès mémoire, \
+             `memory.copy`/`memory.fill` et les blocs en forme plate ou \
+             parenthésée, avec les `$noms` des variables, globales, fonctions et \
+             labels. Un autre type de valeur (i64, f32...) ou une instruction \
+             inconnue n'y passe pas; `--emit wat` recopie toujours le bloc tel quel.",
+        en: "`wat` block cannot be assembled\n\n\
+             With `--emit wasm` or `check --validate`, gaufre assembles the text \
+             of `wat { ... }` blocks itself. It knows i32 instructions, memory accesses, \
+             `memory.copy`/`memory.fill` and blocks in flat or folded form, with the \
+             `$names` of variables, globals, functions and labels. Another value \
+             type (i64, f32...) or an unknown instruction does not go through; \
+             `--emit wat` still copies the block as is.",
+        wrong: r#"
+fn main() {
+  wat { i64.const 1 drop }
+}"#,
+        fixed: r#"
+fn main() {
+  wat { i32.const 1 drop }
+}"#,
+    },
+    Explanation {
+        code: "G0508",
+        severity: Error,
+        fr: "bloc `wat` invalide\n\n\
+             Le validateur du module (`gaufre check --validate`, et après chaque \
+             génération dans les builds de débogage du compilateur) vérifie aussi \
+             les blocs `wat { ... }` qu'il sait assembler : chaque instruction doit \
+             trouver ses opérandes sur la pile, avec le bon type, et un bloc doit \
+             laisser exactement son résultat. Le bloc désigné ne respecte pas ces \
+             règles; le module serait refusé au chargement. Un bloc que \
+             l'assembleur ne comprend pas relève de `G0507`.",
+        en: "invalid `wat` block\n\n\
+             The module validator (`gaufre check --validate`, and after every \
+             generation in debug builds of the compiler) also checks the \
+             `wat { ... }` blocks it can assemble: every instruction must find its \
+             operands on the stack, with the right type, and a block must leave \
+             exactly its result. The block pointed at breaks these rules; the \
+             module would be rejected when loaded. A block the assembler does not \
+             understand falls under `G0507`.",
+        wrong: r#"
+fn main() {
+  wat { i32.add drop }
+}"#,
+        fixed: r#"
+fn main() {
+  wat { i32.const 1 i32.const 2 i32.add drop }
+}"#,
+    },
+    Explanation {
+        code: "G0509",
+        severity: Error,
+        fr: "type incompatible\n\n\
+             Une valeur fonction (une fonction nommée, ou un paramètre ou une \
+             globale de type `fn(...)`) ne va que là où son type exact est attendu : \
+             un paramètre, une globale ou une affectation de type `fn(...)`, avec \
+             le même nombre de paramètres et le même retour. À l'inverse, un tel \
+             emplacement n'accepte pas d'entier, et une valeur fonction ne se \
+             prête pas au calcul.",
+        en: "mismatched types\n\n\
+             A function value (a named function, or a parameter or global of type \
+             `fn(...)`) only goes where its exact type is expected: a parameter, \
+             global or assignment of type `fn(...)`, with the same number of \
+             parameters and the same return. Conversely, such a slot does not take \
+             an integer, and a function value cannot be computed with.",
+        wrong: r#"
+fn add(a: i32, b: i32) -> i32 {
+  return a + b
+}
+
+fn apply(f: fn(i32) -> i32, x: i32) -> i32 {
+  return f(x)
+}
+
+fn main() {
+  log(apply(add, 1))
+}"#,
+        fixed: r#"
+fn inc(a: i32) -> i32 {
+  return a + 1
+}
+
+fn apply(f: fn(i32) -> i32, x: i32) -> i32 {
+  return f(x)
+}
+
+fn main() {
+  log(apply(inc, 1))
+}"#,
+    },
+    // profils de grammaire
+    Explanation {
+        code: "G0601",
+        severity: Error,
+        fr: "profil de grammaire inconnu\n\n\
+             `#grammar` prend un profil intégré (`en`, `fr`, `pascal`) ou le \
+             chemin d'un fichier `.gram`, relatif au fichier source. Sur la ligne \
+             de commande, un profil inconnu après `--grammar` est une erreur \
+             d'usage (code de sortie 2).",
+        en: "unknown grammar profile\n\n\
+             `#grammar` takes a built-in profile (`en`, `fr`, `pascal`) or the path \
+             of a `.gram` file, relative to the source file. On the command line, an \
+             unknown profile after `--grammar` is a usage error (exit code 2).",
+        wrong: r#"
+#grammar francais
+fonction principal() {
+}"#,
+        fixed: r#"
+#grammar fr
+fonction principal() {
+}"#,
+    },
+    Explanation {
+        code: "G0602",
+        severity: Error,
+        fr: "lexème invalide\n\n\
+             Dans un profil, un lexème est soit un mot (lettres, chiffres, `_`), soit \
+             une suite de symboles sans `\"` ni `#`. Il ne peut pas contenir `//`, qui \
+             commence un commentaire, ni être un nom de type comme `i32`.",
+        en: "invalid lexeme\n\n\
+             In a profile, a lexeme is either a word (letters, digits, `_`) or a run of \
+             symbols without `\"` or `#`. It cannot contain `//`, which starts a \
+             comment, or be a type name like `i32`.",
+        wrong: r#"
+// maison.gram
+assign <-"
+// main.gfr
+#grammar maison.gram
+fn main() {
+}"#,
+        fixed: r#"
+// maison.gram
+assign <-
+// main.gfr
+#grammar maison.gram
+fn main() {
+}"#,
+    },
+    Explanation {
+        code: "G0603",
+        severity: Error,
+        fr: "clé de profil inconnue\n\n\
+             Chaque ligne d'un `.gram` commence par une clé du profil `en` (`fn`, \
+             `for`, `assign`, `ne`...); le message liste les clés possibles.",
+        en: "unknown profile key\n\n\
+             Each line of a `.gram` starts with a key of the `en` profile (`fn`, \
+             `for`, `assign`, `ne`...); the message lists the possible keys.",
+        wrong: r#"
+// maison.gram
+loop pour
+// main.gfr
+#grammar maison.gram
+fn main() {
+}"#,
+        fixed: r#"
+// maison.gram
+for pour
+// main.gfr
+#grammar maison.gram
+fn main() {
+}"#,
+    },
+    Explanation {
+        code: "G0604",
+        severity: Error,
+        fr: "lexème en conflit\n\n\
+             Deux clés d'un profil ne peuvent pas avoir le même lexème, et un lexème \
+             ne peut pas reprendre la ponctuation fixe (`( ) { } , : :: -> @ ! + - * \
+             / %`). Le lexème d'une clé non redéfinie reste celui de `en`.",
+        en: "conflicting lexeme\n\n\
+             Two keys of a profile cannot have the same lexeme, and a lexeme cannot \
+             reuse the fixed punctuation (`( ) { } , : :: -> @ ! + - * / %`). The \
+             lexeme of a key that is not redefined stays the `en` one.",
+        wrong: r#"
+// maison.gram
+assign ==
+// main.gfr
+#grammar maison.gram
+fn main() {
+}"#,
+        fixed: r#"
+// maison.gram
+assign :=
+// main.gfr
+#grammar maison.gram
+fn main() {
+}"#,
+    },
+    Explanation {
+        code: "G0605",
+        severity: Error,
+        fr: "ligne de profil mal formée\n\n\
+             Une ligne d'un `.gram` contient une clé et un lexème séparés par des \
+             blancs; `#` commente la fin de la ligne.",
+        en: "malformed profile line\n\n\
+             A line of a `.gram` holds a key and a lexeme separated by blanks; `#` \
+             comments out the rest of the line.",
+        wrong: r#"
+// maison.gram
+for = pour
+// main.gfr
+#grammar maison.gram
+fn main() {
+}"#,
+        fixed: r#"
+// maison.gram
+for pour # boucle
+// main.gfr
+#grammar maison.gram
+fn main() {
+}"#,
+    },
+    Explanation {
+        code: "G0606",
+        severity: Error,
+        fr: "clé définie deux fois\n\n\
+             Chaque clé apparaît au plus une fois dans un `.gram`.",
+        en: "key defined twice\n\n\
+             Each key appears at most once in a `.gram`.",
+        wrong: r#"
+// maison.gram
+for pour
+for boucle
+// main.gfr
+#grammar maison.gram
+fn main() {
+}"#,
+        fixed: r#"
+// maison.gram
+for pour
+// main.gfr
+#grammar maison.gram
+fn main() {
+}"#,
+    },
+    // traduction
+    Explanation {
+        code: "G0701",
+        severity: Error,
+        fr: "identifiant devenu mot-clé\n\n\
+             `gaufre translate` ne renomme pas les identifiants : un nom qui est un \
+             mot-clé du profil cible doit être renommé avant la traduction.",
+        en: "identifier that becomes a keyword\n\n\
+             `gaufre translate` does not rename identifiers: a name that is a keyword \
+             of the target profile must be renamed before translating.",
+        wrong: r#"
+// gaufre translate --to fr
+fn main() {
+  for pour = 1 to 3 {
+    log(pour)
+  }
+}"#,
+        fixed: r#"
+// gaufre translate --to fr
+fn main() {
+  for tour = 1 to 3 {
+    log(tour)
+  }
+}"#,
+    },
+    Explanation {
+        code: "G0702",
+        severity: Error,
+        fr: "traduction ambiguë\n\n\
+             Relue avec le profil cible, la traduction donnerait d'autres tokens : deux \
+             symboles collés y forment un opérateur. Ajouter des espaces dans la \
+             source.",
+        en: "ambiguous translation\n\n\
+             Read back with the target profile, the translation would give other \
+             tokens: two adjacent symbols form an operator there. Add spaces in the \
+             source.",
+        wrong: r#"
+// gaufre translate --to fleche.gram (assign <-)
+global a: i32 = 1
+
+fn main() {
+  log(a<-2)
+}"#,
+        fixed: r#"
+// gaufre translate --to fleche.gram (assign <-)
+global a: i32 = 1
+
+fn main() {
+  log(a < -2)
+}"#,
+    },
+    // écriture des résultats
+    Explanation {
+        code: "G0801",
+        severity: Error,
+        fr: "fichier de sortie impossible à écrire\n\n\
+             Le `.wat` (ou le fichier traduit) n'a pas pu être écrit : répertoire \
+             absent, droits insuffisants, disque plein. Le code de sortie est alors 3, \
+             comme pour un fichier illisible.",
+        en: "cannot write the output file\n\n\
+             The `.wat` (or the translated file) could not be written: missing \
+             directory, insufficient permissions, full disk. The exit code is then 3, \
+             as for an unreadable file.",
+        wrong: r#"
+// gaufre hello.gfr build/hello.wat (sans répertoire build/)
+fn main() {
+  log("Bonjour")
+}"#,
+        fixed: r#"
+// mkdir build && gaufre hello.gfr build/hello.wat
+fn main() {
+  log("Bonjour")
+}"#,
+    },
+    // lints
+    Explanation {
+        code: "G0901",
+        severity: Warning,
+        fr: "variable de boucle jamais lue (lint `unused_loop_var`)\n\n\
+             Le corps de la boucle n'utilise pas sa variable, ni dans une expression \
+             ni comme `$nom` d'un bloc `wat`. Si seul le nombre de tours compte, \
+             nommer la variable `_` ou la préfixer par `_`.",
+        en: "loop variable never read (lint `unused_loop_var`)\n\n\
+             The loop body does not use its variable, neither in an expression nor as \
+             `$name` in a `wat` block. If only the number of iterations matters, name \
+             the variable `_` or prefix it with `_`.",
+        wrong: r#"
+fn main() {
+  for i = 1 to 3 {
+    log("encore")
+  }
+}"#,
+        fixed: r#"
+fn main() {
+  for _i = 1 to 3 {
+    log("encore")
+  }
+}"#,
+    },
+    Explanation {
+        code: "G0902",
+        severity: Warning,
+        fr: "intervalle vide (lint `empty_range`)\n\n\
+             Les deux bornes sont des nombres écrits tels quels et la boucle ne fait \
+             aucun tour : `to` et `until` comptent en montant, seul `downto` compte \
+             en descendant.",
+        en: "empty range (lint `empty_range`)\n\n\
+             Both bounds are literal numbers and the loop never runs: `to` and `until` \
+             count upwards, only `downto` counts downwards.",
+        wrong: r#"
+fn main() {
+  for i = 10 to 1 {
+    log(i)
+  }
+}"#,
+        fixed: r#"
+fn main() {
+  for i = 10 downto 1 {
+    log(i)
+  }
+}"#,
+    },
+    Explanation {
+        code: "G0903",
+        severity: Warning,
+        fr: "code inaccessible (lint `unreachable`)\n\n\
+             Les instructions qui suivent un `return` dans le même bloc ne sont \
+             jamais exécutées. Le code sous un `if const` compte aussi : une branche \
+             qui se termine par `return` rend inaccessible la suite de cette branche \
+             seulement.",
+        en: "unreachable code (lint `unreachable`)\n\n\
+             Statements that follow a `return` in the same block never run. Code under \
+             an `if const` counts too: a branch ending in `return` only makes the rest \
+             of that branch unreachable.",
+        wrong: r#"
+fn double(x: i32) -> i32 {
+  return x * 2
+  log("double", x)
+}
+
+fn main() {
+  log(double(4))
+}"#,
+        fixed: r#"
+fn double(x: i32) -> i32 {
+  log("double", x)
+  return x * 2
+}
+
+fn main() {
+  log(double(4))
+}"#,
+    },
+    Explanation {
+        code: "G0904",
+        severity: Warning,
+        fr: "variable masquée (lint `shadowed`)\n\n\
+             La variable de boucle porte le nom d'un paramètre, d'une boucle \
+             englobante, ou d'une constante ou globale du même fichier : dans le corps \
+             de la boucle, ce nom désigne la variable de boucle et l'autre n'est plus \
+             accessible.",
+        en: "shadowed variable (lint `shadowed`)\n\n\
+             The loop variable has the name of a parameter, of an enclosing loop, or \
+             of a constant or global of the same file: in the loop body the name means \
+             the loop variable and the other one can no longer be reached.",
+        wrong: r#"
+fn table(n: i32) {
+  for n = 1 to 3 {
+    log(n * n)
+  }
+}
+
+fn main() {
+  table(5)
+}"#,
+        fixed: r#"
+fn table(n: i32) {
+  for k = 1 to 3 {
+    log(k * n)
+  }
+}
+
+fn main() {
+  table(5)
+}"#,
+    },
+    Explanation {
+        code: "G0905",
+        severity: Warning,
+        fr: "chaîne très longue (lint `long_string`)\n\n\
+             Une chaîne de plus de 4096 octets est recopiée dans la section de \
+             données, et le tampon partagé des `log` grandit à la taille du plus long \
+             message. Découper le texte en plusieurs `log`, ou le lire d'ailleurs.",
+        en: "very long string literal (lint `long_string`)\n\n\
+             A string over 4096 bytes is copied into the data section, and the shared \
+             `log` buffer grows to the size of the longest message. Split the text \
+             across several `log`s, or read it from elsewhere.",
+        wrong: r#"
+fn main() {
+  log("... (5000 caractères) ...")
+}"#,
+        fixed: r#"
+fn main() {
+  log("... (2500 premiers caractères) ...")
+  log("... (2500 suivants) ...")
+}"#,
+    },
+    Explanation {
+        code: "G0906",
+        severity: Warning,
+        fr: "`log` répété (lint `duplicate_log`)\n\n\
+             Deux `log` qui se suivent ont exactement les mêmes arguments; le second \
+             est souvent un copier-coller oublié. Si la répétition est voulue, une \
+             boucle la rend explicite.",
+        en: "repeated `log` (lint `duplicate_log`)\n\n\
+             Two consecutive `log`s have exactly the same arguments; the second is \
+             often a leftover copy and paste. If the repetition is intended, a loop \
+             makes it explicit.",
+        wrong: r#"
+fn main() {
+  log("début")
+  log("début")
+}"#,
+        fixed: r#"
+fn main() {
+  log("début")
+  log("fin")
+}"#,
+    },
+    // fichiers WAT
+    Explanation {
+        code: "G1001",
+        severity: Error,
+        fr: "fichier WAT illisible\n\n\
+             `gaufre assemble` et `gaufre validate` lisent un module WAT : champs \
+             `type`, `import` (de fonctions), `func`, `memory`, `global`, `export` \
+             et `data`, instructions i32, mémoire et de contrôle en forme plate ou \
+             parenthésée, `$noms` de locals, labels, fonctions et globales, chaînes \
+             avec échappements (`\\n`, `\\22`, `\\u{e9}`). Le texte désigné sort de \
+             cette syntaxe ou de ce qu'elle couvre (i64, tables, mémoire maximale...), \
+             ou redéclare le `$nom` d'un paramètre ou local de la même fonction.",
+        en: "unreadable WAT file\n\n\
+             `gaufre assemble` and `gaufre validate` read a WAT module: `type`, \
+             `import` (of functions), `func`, `memory`, `global`, `export` and `data` \
+             fields, i32, memory and control instructions in flat or folded form, \
+             `$names` of locals, labels, functions and globals, strings with escapes \
+             (`\\n`, `\\22`, `\\u{e9}`). The text pointed at is outside this syntax or \
+             what it covers (i64, tables, maximum memory...), or declares again the \
+             `$name` of a parameter or local of the same function.",
+        wrong: r#"
+(module
+  (func $main (export "main")
+    i32.const 1
+    i32.const 2
+    i32.addd
+    drop))"#,
+        fixed: r#"
+(module
+  (func $main (export "main")
+    i32.const 1
+    i32.const 2
+    i32.add
+    drop))"#,
+    },
+    Explanation {
+        code: "G1002",
+        severity: Error,
+        fr: "module WAT invalide\n\n\
+             Le module se lit mais ne passe pas la validation WebAssembly : une \
+             instruction ne trouve pas ses opérandes sur la pile, un bloc ne laisse \
+             pas son résultat, un index ne désigne rien, un export est en double ou \
+             une globale immuable est modifiée. L'erreur désigne l'instruction en \
+             cause; un moteur WebAssembly refuserait le module.",
+        en: "invalid WAT module\n\n\
+             The module parses but fails WebAssembly validation: an instruction does \
+             not find its operands on the stack, a block does not leave its result, \
+             an index refers to nothing, an export is duplicated or an immutable \
+             global is modified. The error points at the offending instruction; a \
+             WebAssembly engine would reject the module.",
+        wrong: r#"
+(module
+  (func $f (result i32)
+    i32.const 1
+    i32.const 2))"#,
+        fixed: r#"
+(module
+  (func $f (result i32)
+    i32.const 1
+    i32.const 2
+    i32.add))"#,
+    },
+];
+
+const _: () = {
+    let mut i = 0;
+    while i < EXPLANATIONS.len() {
+        let mut j = i + 1;
+        while j < EXPLANATIONS.len() {
+            assert!(!i18n::str_eq(EXPLANATIONS[i].code, EXPLANATIONS[j].code), "code en double");
+            j += 1;
+        }
+        i += 1;
+    }
+};
+
+/// Utilisé par `code!` pour refuser un code inconnu à la compilation.
+pub const fn known(code: &str) -> bool {
+    let mut i = 0;
+    while i < EXPLANATIONS.len() {
+        if i18n::str_eq(EXPLANATIONS[i].code, code) {
+            return true;
+        }
+        i += 1;
+    }
+    false
+}
+
+pub fn find(code: &str) -> Option<&'static Explanation> {
+    EXPLANATIONS.iter().find(|e| e.code.eq_ignore_ascii_case(code))
+}
+
+pub fn severity(code: &str) -> Severity {
+    find(code).map_or(Severity::Error, |e| e.severity)
+}
+
+impl Explanation {
+    /// Texte de `gaufre explain` dans la langue courante.
+    pub fn render(&self) -> String {
+        let text = match i18n::lang() {
+            Lang::Fr => self.fr,
+            Lang::En => self.en,
+        };
+        let (title, body) = text.split_once("\n\n").unwrap_or((text, ""));
+        let level = match self.severity {
+            Severity::Error => tr!("diag.error"),
+            Severity::Warning => tr!("diag.warning"),
+        };
+        let indent = |s: &str| {
+            let lines = s.trim_start_matches('\n').lines();
+            lines.map(|l| if l.is_empty() { "\n".to_string() } else { format!("    {l}\n") }).collect::<String>()
+        };
+        format!(
+            "{} ({level}): {title}\n\n{body}\n\n{}\n\n{}\n{}\n\n{}",
+            self.code,
+            tr!("explain.wrong"),
+            indent(self.wrong),
+            tr!("explain.fixed"),
+            indent(self.fixed),
+        )
+    }
+}
+
+macro_rules! code {
+    ($code:literal) => {{
+        const _: () = assert!($crate::codes::known($code), concat!("code inconnu: ", $code));
+        $code
+    }};
+}
+pub(crate) use code;
+
+#[cfg(test)]
+mod tests {
+    use super::{find, severity, EXPLANATIONS};
+    use crate::diag::{Severity, SourceMap};
+    use crate::{codegen, emit, lints, modules, validate};
+    use std::collections::{HashMap, HashSet};
+
+    #[test]
+    fn lookup() {
+        assert_eq!(find("g0402").map(|e| e.code), Some("G0402"));
+        assert!(find("G9999").is_none());
+        assert_eq!((severity("G0901"), severity("G0101")), (Severity::Warning, Severity::Error));
+        let mut seen = HashSet::new();
+        for e in EXPLANATIONS {
+            assert!(seen.insert(e.code), "{} en double", e.code);
+            assert!(e.fr.contains("\n\n") && e.en.contains("\n\n"), "{} sans titre", e.code);
+        }
+    }
+
+    /// Diagnostics (erreur, avertissements) de l'exemple `text`, chargé et
+    /// abaissé et validé comme par `check --validate`; chaque `// x.gfr` commence un fichier, et
+    /// le programme est `main.gfr` ou, à défaut, le dernier fichier.
+    fn compile(name: &str, text: &str) -> Vec<&'static str> {
+        let dir = std::env::temp_dir().join(format!("gaufre-codes-{name}"));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut files = vec![("main.gfr".to_string(), String::new())];
+        for line in text.trim_start_matches('\n').lines() {
+            match line.strip_prefix("// ").filter(|f| f.ends_with(".gfr")) {
+                Some(file) if files.last().unwrap().1.is_empty() => files.last_mut().unwrap().0 = file.to_string(),
+                Some(file) => files.push((file.to_string(), String::new())),
+                None => files.last_mut().unwrap().1 += &format!("{line}\n"),
+            }
+        }
+        for (file, src) in &files {
+            let path = dir.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, src).unwrap();
+        }
+        let entry = files.iter().find(|(f, _)| f == "main.gfr").unwrap_or(files.last().unwrap());
+        let mut sm = SourceMap::default();
+        let mut found = Vec::new();
+        let r = modules::load_program(&dir.join(&entry.0), &mut sm, &HashMap::new(), "en", &lints::Config::default(), &mut found)
+            .and_then(|prog| {
+                let w = emit::module(&codegen::lower(&prog, false)?)?;
+                Ok(validate::module(&w, true)?)
+            });
+        let mut codes: Vec<&'static str> = found.iter().map(|d| d.code).collect();
+        codes.extend(r.err().map(|d| d.code));
+        codes
+    }
+
+    /// Les exemples de `gaufre explain` jusqu'à la génération, et ceux des
+    /// lints : le fautif donne son code, le corrigé compile sans rien dire.
+    /// Restent à part l'erreur interne (G0506), sans exemple possible, et la
+    /// chaîne de plus de 4096 octets, abrégée dans l'exemple (G0905).
+    #[test]
+    fn examples() {
+        let phases = |c: &str| c < "G0600" || c.starts_with("G09");
+        for e in EXPLANATIONS.iter().filter(|e| phases(e.code) && !["G0506", "G0905"].contains(&e.code)) {
+            let wrong = compile(&format!("{}-wrong", e.code), e.wrong);
+            assert!(wrong.contains(&e.code), "{}: exemple fautif {wrong:?}", e.code);
+            let fixed = compile(&format!("{}-fixed", e.code), e.fixed);
+            assert!(fixed.is_empty(), "{}: exemple corrigé {fixed:?}", e.code);
+        }
+    }
+}
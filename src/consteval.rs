@@ -0,0 +1,427 @@
+use crate::codes::code;
+use crate::diag::Span;
+use crate::i18n::tr;
+use crate::parser::{BinOp, ConstDecl, Expr, ExprKind, FnDecl, ForKind, Stmt, StmtKind};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Étapes (instructions, tours de boucle, appels) permises par évaluation.
+pub const FUEL: u64 = 1_000_000;
+/// Appels de `const fn` imbriqués.
+pub const CALL_DEPTH_LIMIT: usize = 200;
+
+#[derive(Debug, Clone)]
+pub enum EvalError {
+    NotConst(String),
+    Unknown(String),
+    DivByZero,
+    Overflow,
+    Cycle(String),
+    NotConstFn(String),
+    OutOfFuel,
+    TooDeep(String),
+    InvalidStep(i32),
+    NoReturn(String),
+    /// erreur dans le corps d'une `const fn`, à l'instruction la plus profonde
+    InFn { name: String, span: Span, err: Box<EvalError> },
+}
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotConst(n) => write!(f, "{}", tr!("eval.not_const", n)),
+            Self::Unknown(n) => write!(f, "{}", tr!("eval.unknown", n)),
+            Self::DivByZero => write!(f, "{}", tr!("eval.div_by_zero")),
+            Self::Overflow => write!(f, "{}", tr!("eval.overflow")),
+            Self::Cycle(n) => write!(f, "{}", tr!("eval.cycle", n)),
+            Self::NotConstFn(n) => write!(f, "{}", tr!("eval.not_const_fn", n)),
+            Self::OutOfFuel => write!(f, "{}", tr!("eval.out_of_fuel", FUEL)),
+            Self::TooDeep(n) => write!(f, "{}", tr!("eval.too_deep", n, CALL_DEPTH_LIMIT)),
+            Self::InvalidStep(v) => write!(f, "{}", tr!("eval.invalid_step", v)),
+            Self::NoReturn(n) => write!(f, "{}", tr!("eval.no_return", n)),
+            Self::InFn { err, .. } => err.fmt(f),
+        }
+    }
+}
+impl std::error::Error for EvalError {}
+impl EvalError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::NotConst(_) => code!("G0401"),
+            Self::Unknown(_) => code!("G0402"),
+            Self::DivByZero => code!("G0403"),
+            Self::Overflow => code!("G0404"),
+            Self::Cycle(_) => code!("G0405"),
+            Self::NotConstFn(_) => code!("G0406"),
+            Self::OutOfFuel => code!("G0407"),
+            Self::TooDeep(_) => code!("G0408"),
+            Self::InvalidStep(_) => code!("G0409"),
+            Self::NoReturn(_) => code!("G0410"),
+            Self::InFn { err, .. } => err.code(),
+        }
+    }
+    /// Fonction et instruction où l'erreur s'est produite, le cas échéant.
+    pub fn location(&self) -> Option<(&str, Span)> {
+        match self {
+            Self::InFn { name, span, .. } => Some((name, *span)),
+            _ => None,
+        }
+    }
+}
+
+/// Applique un opérateur avec la sémantique des instructions WASM
+/// (arithmétique modulo 2^32, division signée qui trappe).
+pub fn apply(op: BinOp, a: i32, b: i32) -> Result<i32, EvalError> {
+    Ok(match op {
+        BinOp::Add => a.wrapping_add(b),
+        BinOp::Sub => a.wrapping_sub(b),
+        BinOp::Mul => a.wrapping_mul(b),
+        BinOp::Div | BinOp::Rem if b == 0 => return Err(EvalError::DivByZero),
+        BinOp::Div if a == i32::MIN && b == -1 => return Err(EvalError::Overflow),
+        BinOp::Div => a / b,
+        BinOp::Rem => a.wrapping_rem(b),
+        BinOp::Eq => (a == b) as i32,
+        BinOp::Ne => (a != b) as i32,
+        BinOp::Lt => (a < b) as i32,
+        BinOp::Le => (a <= b) as i32,
+        BinOp::Gt => (a > b) as i32,
+        BinOp::Ge => (a >= b) as i32,
+        BinOp::And => (a != 0 && b != 0) as i32,
+        BinOp::Or => (a != 0 || b != 0) as i32,
+    })
+}
+
+/// Ce qu'une évaluation demande à son contexte : la valeur d'un nom et,
+/// éventuellement, le résultat d'un appel.
+pub trait Env {
+    fn var(&mut self, name: &str) -> Result<i32, EvalError>;
+    fn call(&mut self, name: &str, _args: &[i32]) -> Result<i32, EvalError> {
+        Err(EvalError::NotConst(name.to_string()))
+    }
+}
+/// Une closure ne résout que des noms (aucun appel permis).
+impl<F: FnMut(&str) -> Result<i32, EvalError>> Env for F {
+    fn var(&mut self, name: &str) -> Result<i32, EvalError> {
+        self(name)
+    }
+}
+
+/// Évalue une expression entière dans `env`.
+pub fn eval(e: &Expr, env: &mut dyn Env) -> Result<i32, EvalError> {
+    match &e.kind {
+        ExprKind::Int(v) => Ok(*v),
+        ExprKind::Var(n) => env.var(n),
+        ExprKind::Neg(x) => Ok(eval(x, env)?.wrapping_neg()),
+        ExprKind::Not(x) => Ok((eval(x, env)? == 0) as i32),
+        // court-circuit : `0 && 1 / 0` vaut 0, comme à l'exécution
+        ExprKind::Bin(op @ (BinOp::And | BinOp::Or), l, r) => {
+            let a = eval(l, env)? != 0;
+            if a == (*op == BinOp::Or) {
+                return Ok(a as i32);
+            }
+            Ok((eval(r, env)? != 0) as i32)
+        }
+        ExprKind::Bin(op, l, r) => {
+            let a = eval(l, env)?;
+            let b = eval(r, env)?;
+            apply(*op, a, b)
+        }
+        ExprKind::Call { name, args } => {
+            let args = args.iter().map(|a| eval(a, env)).collect::<Result<Vec<_>, _>>()?;
+            env.call(name, &args)
+        }
+        ExprKind::Str(_) => unreachable!("chaîne hors d'un log"),
+    }
+}
+
+enum State { Todo, InProgress, Done(i32) }
+
+/// Évaluateur de compilation : constantes du programme (dans n'importe quel
+/// ordre de déclaration) et appels de `const fn`, avec une réserve de
+/// carburant par évaluation.
+pub struct Ctx<'a> {
+    decls: &'a [ConstDecl],
+    state: HashMap<&'a str, State>,
+    /// nom connu mais pas constant (global...) -> NotConst, sinon Unknown
+    is_known: &'a dyn Fn(&str) -> bool,
+    fns: HashMap<&'a str, &'a FnDecl>, // les `const fn`
+    fuel: u64,
+    depth: usize,
+}
+
+/// Une `const fn` en cours d'exécution : ses locals, puis les constantes.
+struct Frame<'c, 'a> {
+    ctx: &'c mut Ctx<'a>,
+    locals: &'c [(&'a str, i32)],
+}
+impl Env for Frame<'_, '_> {
+    fn var(&mut self, name: &str) -> Result<i32, EvalError> {
+        match self.locals.iter().rev().find(|(n, _)| *n == name) {
+            Some(&(_, v)) => Ok(v),
+            None => self.ctx.konst(name),
+        }
+    }
+    fn call(&mut self, name: &str, args: &[i32]) -> Result<i32, EvalError> {
+        self.ctx.call(name, args)
+    }
+}
+
+impl<'a> Ctx<'a> {
+    pub fn new(decls: &'a [ConstDecl], funcs: &'a [FnDecl], is_known: &'a dyn Fn(&str) -> bool) -> Self {
+        Self {
+            decls,
+            state: decls.iter().map(|d| (d.name.as_str(), State::Todo)).collect(),
+            is_known,
+            fns: funcs.iter().filter(|f| f.konst).map(|f| (f.name.as_str(), f)).collect(),
+            fuel: FUEL,
+            depth: 0,
+        }
+    }
+
+    /// Valeur d'une constante, calculée au premier usage.
+    fn konst(&mut self, name: &str) -> Result<i32, EvalError> {
+        match self.state.get(name) {
+            Some(State::Done(v)) => return Ok(*v),
+            Some(State::InProgress) => return Err(EvalError::Cycle(name.to_string())),
+            Some(State::Todo) => {}
+            None if (self.is_known)(name) => return Err(EvalError::NotConst(name.to_string())),
+            None => return Err(EvalError::Unknown(name.to_string())),
+        }
+        let d = self.decls.iter().find(|d| d.name == name).unwrap();
+        self.state.insert(d.name.as_str(), State::InProgress);
+        let v = eval(&d.value, &mut Frame { ctx: self, locals: &[] })?;
+        self.state.insert(d.name.as_str(), State::Done(v));
+        Ok(v)
+    }
+
+    /// Calcule toutes les constantes; en cas d'erreur, la déclaration fautive.
+    pub fn eval_consts(&mut self) -> Result<HashMap<&'a str, i32>, (&'a ConstDecl, EvalError)> {
+        let mut out = HashMap::new();
+        for d in self.decls {
+            self.fuel = FUEL;
+            let v = self.konst(&d.name).map_err(|e| (d, e))?;
+            out.insert(d.name.as_str(), v);
+        }
+        Ok(out)
+    }
+
+    /// Évalue une expression hors de toute fonction (initialiseur, argument).
+    pub fn eval(&mut self, e: &Expr) -> Result<i32, EvalError> {
+        self.fuel = FUEL;
+        eval(e, &mut Frame { ctx: self, locals: &[] })
+    }
+
+    fn tick(&mut self) -> Result<(), EvalError> {
+        self.fuel = self.fuel.checked_sub(1).ok_or(EvalError::OutOfFuel)?;
+        Ok(())
+    }
+
+    fn call(&mut self, name: &str, args: &[i32]) -> Result<i32, EvalError> {
+        let Some(&f) = self.fns.get(name) else {
+            return Err(EvalError::NotConstFn(name.to_string()));
+        };
+        if self.depth == CALL_DEPTH_LIMIT {
+            return Err(EvalError::TooDeep(name.to_string()));
+        }
+        self.tick()?;
+        let mut locals: Vec<(&str, i32)> = f.params.iter().map(|p| p.name.as_str()).zip(args.iter().copied()).collect();
+        self.depth += 1;
+        let r = self.exec(&f.name, &f.body, &mut locals);
+        self.depth -= 1;
+        r?.ok_or_else(|| EvalError::NoReturn(name.to_string()))
+    }
+
+    /// Exécute des instructions; `Some(v)` dès qu'un `return v` est atteint.
+    fn exec(&mut self, func: &str, ss: &'a [Stmt], locals: &mut Vec<(&'a str, i32)>) -> Result<Option<i32>, EvalError> {
+        for st in ss {
+            let r = self.tick().and_then(|_| self.stmt(func, st, locals));
+            match r {
+                Ok(None) => {}
+                Ok(ret) => return Ok(ret),
+                Err(err @ EvalError::InFn { .. }) => return Err(err),
+                Err(err) => return Err(EvalError::InFn { name: func.to_string(), span: st.span, err: Box::new(err) }),
+            }
+        }
+        Ok(None)
+    }
+
+    fn stmt(&mut self, func: &str, st: &'a Stmt, locals: &mut Vec<(&'a str, i32)>) -> Result<Option<i32>, EvalError> {
+        match &st.kind {
+            StmtKind::Assign { name, value } => {
+                let v = eval(value, &mut Frame { ctx: self, locals })?;
+                // check_const_fn garantit que c'est un local
+                if let Some(slot) = locals.iter_mut().rev().find(|(n, _)| n == name) {
+                    slot.1 = v;
+                }
+            }
+            StmtKind::Expr(e) => {
+                eval(e, &mut Frame { ctx: self, locals })?;
+            }
+            StmtKind::Return(e) => {
+                let v = match e {
+                    Some(e) => eval(e, &mut Frame { ctx: self, locals })?,
+                    None => return Err(EvalError::NoReturn(func.to_string())),
+                };
+                return Ok(Some(v));
+            }
+            // mêmes règles que la boucle générée par codegen::lower_for
+            StmtKind::For { name, start, end, step, kind, body, .. } => {
+                let mut i = eval(start, &mut Frame { ctx: self, locals })?;
+                let end = eval(end, &mut Frame { ctx: self, locals })?;
+                let step = match step {
+                    Some(s) => eval(s, &mut Frame { ctx: self, locals })?,
+                    None => 1,
+                };
+                if step <= 0 {
+                    return Err(EvalError::InvalidStep(step));
+                }
+                let enter = match kind {
+                    ForKind::To => i <= end,
+                    ForKind::Until => i < end,
+                    ForKind::Downto => i >= end,
+                };
+                if !enter {
+                    return Ok(None);
+                }
+                let slot = locals.len();
+                locals.push((name, i));
+                loop {
+                    self.tick()?;
+                    if let Some(v) = self.exec(func, body, locals)? {
+                        return Ok(Some(v));
+                    }
+                    let left = match kind {
+                        ForKind::To | ForKind::Until => end as i64 - i as i64,
+                        ForKind::Downto => i as i64 - end as i64,
+                    };
+                    let last = match kind {
+                        ForKind::Until => left <= step as i64,
+                        ForKind::To | ForKind::Downto => left < step as i64,
+                    };
+                    if last {
+                        break;
+                    }
+                    i = if *kind == ForKind::Downto { i - step } else { i + step };
+                    locals[slot].1 = i;
+                }
+                locals.truncate(slot);
+            }
+            StmtKind::Log(_) | StmtKind::Wat(_) | StmtKind::IfConst { .. } => {
+                unreachable!("refusé par check_const_fn / résolu par modules.rs")
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Une `const fn` ne fait que calculer : elle renvoie i32 et n'a ni `log`, ni
+/// `wat`, ni accès aux globales, ni appel à une fonction ordinaire.
+/// `is_const`/`is_const_fn` portent sur les noms complets des items.
+pub fn check_const_fn(
+    f: &FnDecl,
+    is_const: &dyn Fn(&str) -> bool,
+    is_const_fn: &dyn Fn(&str) -> bool,
+) -> Result<(), (&'static str, String, Span)> {
+    fn expr(e: &Expr, scope: &[&str], is_const: &dyn Fn(&str) -> bool, is_const_fn: &dyn Fn(&str) -> bool) -> Result<(), (&'static str, String, Span)> {
+        match &e.kind {
+            ExprKind::Var(n) if !scope.contains(&n.as_str()) && !is_const(n) => {
+                Err((code!("G0411"), tr!("const_fn.read_global", n), e.span))
+            }
+            ExprKind::Call { name, .. } if !is_const_fn(name) => {
+                Err((code!("G0412"), tr!("const_fn.call", name), e.span))
+            }
+            ExprKind::Call { args, .. } => args.iter().try_for_each(|a| expr(a, scope, is_const, is_const_fn)),
+            ExprKind::Neg(x) | ExprKind::Not(x) => expr(x, scope, is_const, is_const_fn),
+            ExprKind::Bin(_, l, r) => {
+                expr(l, scope, is_const, is_const_fn)?;
+                expr(r, scope, is_const, is_const_fn)
+            }
+            ExprKind::Var(_) | ExprKind::Int(_) | ExprKind::Str(_) => Ok(()),
+        }
+    }
+    fn stmts<'s>(ss: &'s [Stmt], scope: &mut Vec<&'s str>, is_const: &dyn Fn(&str) -> bool, is_const_fn: &dyn Fn(&str) -> bool) -> Result<(), (&'static str, String, Span)> {
+        for st in ss {
+            match &st.kind {
+                StmtKind::Log(_) => return Err((code!("G0413"), tr!("const_fn.log"), st.span)),
+                StmtKind::Wat(_) => return Err((code!("G0414"), tr!("const_fn.wat"), st.span)),
+                StmtKind::Assign { name, .. } if !scope.contains(&name.as_str()) => {
+                    return Err((code!("G0415"), tr!("const_fn.write_global", name), st.span));
+                }
+                StmtKind::Assign { value, .. } => expr(value, scope, is_const, is_const_fn)?,
+                StmtKind::Expr(e) | StmtKind::Return(Some(e)) => expr(e, scope, is_const, is_const_fn)?,
+                StmtKind::Return(None) | StmtKind::IfConst { .. } => {}
+                StmtKind::For { name, start, end, step, body, .. } => {
+                    for e in [Some(start), Some(end), step.as_ref()].into_iter().flatten() {
+                        expr(e, scope, is_const, is_const_fn)?;
+                    }
+                    scope.push(name);
+                    stmts(body, scope, is_const, is_const_fn)?;
+                    scope.pop();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    if !f.ret {
+        return Err((code!("G0416"), tr!("const_fn.ret"), f.span));
+    }
+    let mut scope: Vec<&str> = f.params.iter().map(|p| p.name.as_str()).collect();
+    stmts(&f.body, &mut scope, is_const, is_const_fn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply, Ctx, EvalError};
+    use crate::diag::SourceMap;
+    use crate::parser::{BinOp, Program};
+    use crate::{lints, modules};
+    use std::collections::HashMap;
+
+    fn load(name: &str, src: &str) -> Program {
+        let path = std::env::temp_dir().join(format!("gaufre-consteval-{name}.gfr"));
+        std::fs::write(&path, src).unwrap();
+        let mut sm = SourceMap::default();
+        modules::load_program(&path, &mut sm, &HashMap::new(), "en", &lints::Config::default(), &mut Vec::new())
+            .unwrap_or_else(|d| panic!("{}", d.message))
+    }
+
+    const FNS: &str = "const fn sq(x: i32) -> i32 {\n  return x * x\n}\nconst fn sum_sq(n: i32, acc: i32) -> i32 {\n  for k = 1 to n {\n    acc = acc + sq(k)\n  }\n  return acc\n}\nconst fn down(n: i32) -> i32 {\n  for _k = 1 to n {\n    return down(n - 1) + 1\n  }\n  return 0\n}\nconst fn inv(x: i32) -> i32 {\n  return 100 / x\n}\nfn main() {}\n";
+
+    /// Valeur de `const X = expr` ajoutée aux fonctions de `FNS`, sur une
+    /// pile de la taille de celle du thread principal : 200 appels imbriqués
+    /// ne tiennent pas dans celle d'un thread de test en debug.
+    fn eval(name: &str, expr: &str) -> Result<i32, EvalError> {
+        let prog = load(name, &format!("{FNS}const X = {expr}\n"));
+        let run = move || {
+            let mut ctx = Ctx::new(&prog.consts, &prog.funcs, &|_| false);
+            ctx.eval_consts().map(|v| v["X"]).map_err(|(_, e)| e)
+        };
+        std::thread::Builder::new().stack_size(8 << 20).spawn(run).unwrap().join().unwrap()
+    }
+
+    #[test]
+    fn wasm_arithmetic() {
+        assert_eq!(apply(BinOp::Add, i32::MAX, 1).unwrap(), i32::MIN);
+        assert_eq!(apply(BinOp::Rem, i32::MIN, -1).unwrap(), 0);
+        assert_eq!(apply(BinOp::Div, -7, 2).unwrap(), -3);
+        assert!(matches!(apply(BinOp::Div, i32::MIN, -1), Err(EvalError::Overflow)));
+        assert!(matches!(apply(BinOp::Rem, 1, 0), Err(EvalError::DivByZero)));
+    }
+
+    #[test]
+    fn const_fn_calls() {
+        assert_eq!(eval("sum", "sum_sq(10, 0)").unwrap(), 385);
+        assert_eq!(eval("nested", "down(150)").unwrap(), 150);
+    }
+
+    #[test]
+    fn limits_and_location() {
+        assert_eq!(eval("fuel", "sum_sq(2000000, 0)").unwrap_err().code(), "G0407");
+        assert_eq!(eval("deep", "down(250)").unwrap_err().code(), "G0408");
+        let e = eval("div", "inv(0)").unwrap_err();
+        assert_eq!(e.code(), "G0403");
+        // l'instruction fautive, dans la `const fn`
+        let (func, span) = e.location().unwrap();
+        assert_eq!((func, &FNS[span.lo..span.hi]), ("inv", "return 100 / x"));
+    }
+}
@@ -0,0 +1,342 @@
+use crate::codes;
+use crate::i18n::tr;
+use serde_json::{json, Value};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Plage d'octets [lo, hi) dans un fichier du SourceMap.
+/// `expn` vaut 0 pour du texte écrit tel quel, sinon 1 + l'index de
+/// l'expansion de macro (propre au fichier) qui a produit le token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub file: usize,
+    pub lo: usize,
+    pub hi: usize,
+    pub expn: usize,
+}
+impl Span {
+    pub fn new(file: usize, lo: usize, hi: usize) -> Self {
+        Self { file, lo, hi, expn: 0 }
+    }
+    /// Plage couvrant `self` et `other`; `self` seul si `other` vient
+    /// d'ailleurs (autre fichier ou autre expansion).
+    pub fn to(self, other: Span) -> Span {
+        if (self.file, self.expn) != (other.file, other.expn) {
+            return self;
+        }
+        Span { lo: self.lo.min(other.lo), hi: self.hi.max(other.hi), ..self }
+    }
+}
+
+/// Une expansion `name!(...)` : où elle est appelée, où la macro est définie.
+#[derive(Debug, Clone)]
+pub struct Expansion {
+    pub name: String,
+    pub call: Span,
+    pub def: Span,
+}
+
+pub struct SourceFile {
+    pub path: PathBuf,
+    pub src: String,
+    pub expansions: Vec<Expansion>,
+}
+
+/// Tous les fichiers lus pendant une compilation; un Span y renvoie par index.
+#[derive(Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+impl SourceMap {
+    pub fn add(&mut self, path: &Path, src: String) -> usize {
+        self.files.push(SourceFile { path: path.to_path_buf(), src, expansions: Vec::new() });
+        self.files.len() - 1
+    }
+    pub fn file(&self, id: usize) -> &SourceFile {
+        &self.files[id]
+    }
+    pub fn files(&self) -> &[SourceFile] {
+        &self.files
+    }
+    pub fn set_expansions(&mut self, file: usize, expansions: Vec<Expansion>) {
+        self.files[file].expansions = expansions;
+    }
+    pub fn expansion(&self, span: Span) -> Option<&Expansion> {
+        span.expn.checked_sub(1).map(|i| &self.files[span.file].expansions[i])
+    }
+    /// (ligne, colonne), à partir de 1; la colonne compte les caractères.
+    pub fn line_col(&self, span: Span) -> (usize, usize) {
+        let src = &self.files[span.file].src;
+        let before = &src[..span.lo.min(src.len())];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        (line, before[line_start..].chars().count() + 1)
+    }
+    fn line_text(&self, span: Span) -> &str {
+        let src = &self.files[span.file].src;
+        let lo = span.lo.min(src.len());
+        let start = src[..lo].rfind('\n').map_or(0, |i| i + 1);
+        let end = src[lo..].find('\n').map_or(src.len(), |i| lo + i);
+        src[start..end].trim_end_matches('\r')
+    }
+}
+
+/// Niveaux d'expansion de macro détaillés sous une erreur.
+const MAX_EXPANSION_NOTES: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity { Error, Warning }
+
+/// Correction proposée : remplacer le texte de `span` par `replacement`
+/// (une insertion si `span` est vide).
+#[derive(Debug, Clone)]
+pub struct Fix {
+    pub message: String,
+    pub span: Span,
+    pub replacement: String,
+}
+
+/// Une erreur (ou un avertissement) prête à afficher : code, message,
+/// position principale, notes et corrections proposées.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    /// `G0101`, voir `codes`
+    pub code: &'static str,
+    /// celle du code, sauf pour un lint relevé par `deny`
+    pub severity: Severity,
+    pub message: String,
+    pub span: Option<Span>,
+    /// en tranches pour garder `Result<_, Diagnostic>` compact
+    pub notes: Box<[(Span, String)]>,
+    pub fixes: Box<[Fix]>,
+}
+impl Diagnostic {
+    /// Erreur ou avertissement selon `code` (un avertissement n'arrête pas
+    /// la compilation).
+    pub fn new(code: &'static str, message: impl Into<String>, span: Option<Span>) -> Self {
+        Self { code, severity: codes::severity(code), message: message.into(), span, notes: Box::default(), fixes: Box::default() }
+    }
+    pub fn with_note(mut self, span: Span, note: impl Into<String>) -> Self {
+        self.add_note(span, note);
+        self
+    }
+    pub fn add_note(&mut self, span: Span, note: impl Into<String>) {
+        let mut notes = std::mem::take(&mut self.notes).into_vec();
+        notes.push((span, note.into()));
+        self.notes = notes.into_boxed_slice();
+    }
+    pub fn with_fix(mut self, message: impl Into<String>, span: Span, replacement: impl Into<String>) -> Self {
+        let mut fixes = std::mem::take(&mut self.fixes).into_vec();
+        fixes.push(Fix { message: message.into(), span, replacement: replacement.into() });
+        self.fixes = fixes.into_boxed_slice();
+        self
+    }
+
+    /// Rendu façon rustc :
+    /// ```text
+    /// erreur[G0101]: message
+    ///  --> fichier.gfr:3:5
+    ///   |
+    /// 3 |     log(x)
+    ///   |         ^
+    /// ```
+    pub fn render(&self, sm: &SourceMap) -> String {
+        let level = match self.severity {
+            Severity::Error => tr!("diag.error"),
+            Severity::Warning => tr!("diag.warning"),
+        };
+        let note = tr!("diag.note");
+        let mut out = format!("{level}[{}]: {}\n", self.code, self.message);
+        if let Some(span) = self.span {
+            snippet(&mut out, sm, span);
+            // remonte la chaîne des appels de macro (les premiers niveaux seulement)
+            let mut cur = span;
+            let mut shown_defs = Vec::new();
+            let mut level = 0;
+            while let Some(e) = sm.expansion(cur) {
+                if level == MAX_EXPANSION_NOTES {
+                    let mut rest = 0;
+                    while let Some(e) = sm.expansion(cur) {
+                        rest += 1;
+                        cur = e.call;
+                    }
+                    out.push_str(&format!("{note}: {}\n", tr!("diag.more_expansions", rest)));
+                    break;
+                }
+                out.push_str(&format!("{note}: {}\n", tr!("diag.in_expansion", e.name)));
+                snippet(&mut out, sm, e.call);
+                if !shown_defs.contains(&e.def) {
+                    out.push_str(&format!("{note}: {}\n", tr!("diag.macro_defined_here", e.name)));
+                    snippet(&mut out, sm, e.def);
+                    shown_defs.push(e.def);
+                }
+                cur = e.call;
+                level += 1;
+            }
+        }
+        for (span, text) in self.notes.iter() {
+            out.push_str(&format!("{note}: {text}\n"));
+            snippet(&mut out, sm, *span);
+        }
+        let help = tr!("diag.help");
+        for fix in &self.fixes {
+            out.push_str(&format!("{help}: {}\n", fix.message));
+            fix_snippet(&mut out, sm, fix);
+        }
+        out
+    }
+
+    /// Objet JSON de `--error-format=json` : la position principale puis
+    /// les secondaires (expansions de macro, notes), les corrections et le
+    /// rendu texte.
+    pub fn to_json(&self, sm: &SourceMap) -> Value {
+        let mut spans = Vec::new();
+        if let Some(span) = self.span {
+            spans.push(span_json(sm, span, true, None));
+            let mut cur = span;
+            let mut shown_defs = Vec::new();
+            while let Some(e) = sm.expansion(cur) {
+                spans.push(span_json(sm, e.call, false, Some(tr!("diag.in_expansion", e.name))));
+                if !shown_defs.contains(&e.def) {
+                    spans.push(span_json(sm, e.def, false, Some(tr!("diag.macro_defined_here", e.name))));
+                    shown_defs.push(e.def);
+                }
+                cur = e.call;
+            }
+        }
+        for (span, text) in self.notes.iter() {
+            spans.push(span_json(sm, *span, false, Some(text.clone())));
+        }
+        let fixes: Vec<Value> = self.fixes.iter().map(|f| json!({
+            "message": f.message,
+            "span": span_json(sm, f.span, false, None),
+            "replacement": f.replacement,
+        })).collect();
+        json!({
+            "code": self.code,
+            "severity": match self.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+            },
+            "message": self.message,
+            "spans": spans,
+            "fixes": fixes,
+            "rendered": self.render(sm),
+        })
+    }
+}
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+impl std::error::Error for Diagnostic {}
+
+/// Lignes et colonnes à partir de 1, comme dans le rendu texte.
+fn span_json(sm: &SourceMap, span: Span, primary: bool, label: Option<String>) -> Value {
+    let (line_start, column_start) = sm.line_col(span);
+    let (line_end, column_end) = sm.line_col(Span { lo: span.hi, ..span });
+    json!({
+        "file": sm.file(span.file).path.display().to_string(),
+        "byte_start": span.lo,
+        "byte_end": span.hi,
+        "line_start": line_start,
+        "column_start": column_start,
+        "line_end": line_end,
+        "column_end": column_end,
+        "is_primary": primary,
+        "label": label,
+    })
+}
+
+/// La ligne de `fix.span` avec la correction appliquée, soulignée de `~`;
+/// l'extrait tel quel si la correction déborde de la ligne.
+fn fix_snippet(out: &mut String, sm: &SourceMap, fix: &Fix) {
+    let src = &sm.file(fix.span.file).src;
+    let (lo, hi) = (fix.span.lo.min(src.len()), fix.span.hi.min(src.len()));
+    let start = src[..lo].rfind('\n').map_or(0, |i| i + 1);
+    let end = src[lo..].find('\n').map_or(src.len(), |i| lo + i);
+    if hi > end || fix.replacement.contains('\n') {
+        return snippet(out, sm, fix.span);
+    }
+    let (line, col) = sm.line_col(fix.span);
+    let text = format!("{}{}{}", &src[start..lo], fix.replacement, src[hi..end].trim_end_matches('\r'));
+    let gutter = " ".repeat(line.to_string().len());
+    let pad: String = src[start..lo].chars().map(|c| if c == '\t' { '\t' } else { ' ' }).collect();
+    let width = fix.replacement.chars().count().max(1);
+    out.push_str(&format!("{gutter}--> {}:{line}:{col}\n", sm.file(fix.span.file).path.display()));
+    out.push_str(&format!("{gutter} |\n{line} | {text}\n"));
+    out.push_str(&format!("{gutter} | {pad}{}\n", "~".repeat(width)));
+}
+
+fn snippet(out: &mut String, sm: &SourceMap, span: Span) {
+    let (line, col) = sm.line_col(span);
+    let text = sm.line_text(span);
+    let gutter = " ".repeat(line.to_string().len());
+    let width = sm.file(span.file).src.get(span.lo..span.hi)
+        .map_or(1, |s| s.lines().next().unwrap_or("").chars().count().max(1));
+    let pad: String = text.chars().take(col - 1).map(|c| if c == '\t' { '\t' } else { ' ' }).collect();
+    out.push_str(&format!("{gutter}--> {}:{line}:{col}\n", sm.file(span.file).path.display()));
+    out.push_str(&format!("{gutter} |\n{line} | {text}\n"));
+    out.push_str(&format!("{gutter} | {pad}{}\n", "^".repeat(width)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Diagnostic, Expansion, SourceMap, Span};
+    use std::path::Path;
+
+    const SRC: &str = "fn main() {\n\tlog(\"é\", x)\n}\n";
+
+    fn unknown_x() -> (SourceMap, Diagnostic) {
+        let mut sm = SourceMap::default();
+        let file = sm.add(Path::new("app.gfr"), SRC.to_string());
+        let x = SRC.find('x').unwrap();
+        let d = Diagnostic::new("G0402", "unknown `x`", Some(Span::new(file, x, x + 1)))
+            .with_note(Span::new(file, 0, 9), "in `main`")
+            .with_fix("did you mean `y`?", Span::new(file, x, x + 1), "y");
+        (sm, d)
+    }
+
+    #[test]
+    fn text_rendering() {
+        let (sm, d) = unknown_x();
+        // colonnes en caractères : la tabulation et `é` comptent pour un
+        assert_eq!(sm.line_col(d.span.unwrap()), (2, 11));
+        let out = d.render(&sm);
+        let lines: Vec<&str> = out.lines().collect();
+        assert!(lines[0].ends_with("[G0402]: unknown `x`"), "{out}");
+        assert_eq!(lines[1..5], [" --> app.gfr:2:11", "  |", "2 | \tlog(\"é\", x)", "  | \t         ^"]);
+        assert!(out.contains("1 | fn main() {\n  | ^^^^^^^^^\n"), "{out}");
+        // la correction appliquée, soulignée
+        assert!(out.ends_with("2 | \tlog(\"é\", y)\n  | \t         ~\n"), "{out}");
+    }
+
+    #[test]
+    fn json_object() {
+        let (sm, d) = unknown_x();
+        let v = d.to_json(&sm);
+        assert_eq!((v["code"].as_str(), v["severity"].as_str()), (Some("G0402"), Some("error")));
+        let spans = v["spans"].as_array().unwrap();
+        assert_eq!(spans.len(), 2);
+        let x = SRC.find('x').unwrap() as u64;
+        assert_eq!((spans[0]["byte_start"].as_u64(), spans[0]["column_start"].as_u64(), spans[0]["column_end"].as_u64()), (Some(x), Some(11), Some(12)));
+        assert_eq!((spans[0]["is_primary"].as_bool(), spans[0]["label"].is_null()), (Some(true), true));
+        assert_eq!((spans[1]["is_primary"].as_bool(), spans[1]["label"].as_str()), (Some(false), Some("in `main`")));
+        assert_eq!(v["fixes"][0]["replacement"].as_str(), Some("y"));
+        assert_eq!(v["rendered"].as_str(), Some(d.render(&sm).as_str()));
+    }
+
+    #[test]
+    fn macro_expansions_become_secondary_spans() {
+        let mut sm = SourceMap::default();
+        let src = "macro m() { x }\nfn main() {\n  log(m!())\n}\n";
+        let file = sm.add(Path::new("m.gfr"), src.to_string());
+        let call = Span::new(file, src.find("m!()").unwrap(), src.find("m!()").unwrap() + 4);
+        sm.set_expansions(file, vec![Expansion { name: "m".into(), call, def: Span::new(file, 0, 15) }]);
+        let x = Span { expn: 1, ..Span::new(file, 12, 13) };
+        let v = Diagnostic::new("G0402", "unknown `x`", Some(x)).to_json(&sm);
+        let starts: Vec<_> = v["spans"].as_array().unwrap().iter().map(|s| s["byte_start"].as_u64().unwrap()).collect();
+        assert_eq!(starts, [12, call.lo as u64, 0]);
+    }
+}
@@ -0,0 +1,225 @@
+//! De la représentation intermédiaire au modèle WebAssembly : les
+//! expressions sont aplaties dans l'ordre de la pile (opérandes de gauche à
+//! droite, puis l'instruction) et les labels deviennent des profondeurs de
+//! bloc. Le constructeur de `wasm` vérifie la pile au passage.
+
+use crate::ir::{self, Expr, Stmt, StmtKind};
+use crate::wasm::{self, BuildError, Export, ExportKind, FuncBuilder, Instr, MemArg, MemOp, NumOp, ValType};
+
+fn val(t: ir::Ty) -> ValType {
+    match t {
+        ir::Ty::I32 => ValType::I32,
+    }
+}
+
+fn num(op: ir::BinOp) -> NumOp {
+    match op {
+        ir::BinOp::Add => NumOp::Add,
+        ir::BinOp::Sub => NumOp::Sub,
+        ir::BinOp::Mul => NumOp::Mul,
+        ir::BinOp::DivS => NumOp::DivS,
+        ir::BinOp::DivU => NumOp::DivU,
+        ir::BinOp::RemS => NumOp::RemS,
+        ir::BinOp::RemU => NumOp::RemU,
+        ir::BinOp::Eq => NumOp::Eq,
+        ir::BinOp::Ne => NumOp::Ne,
+        ir::BinOp::LtS => NumOp::LtS,
+        ir::BinOp::LtU => NumOp::LtU,
+        ir::BinOp::LeS => NumOp::LeS,
+        ir::BinOp::LeU => NumOp::LeU,
+        ir::BinOp::GtS => NumOp::GtS,
+        ir::BinOp::GeS => NumOp::GeS,
+        ir::BinOp::GeU => NumOp::GeU,
+    }
+}
+
+struct Emitter<'f> {
+    b: FuncBuilder,
+    func: &'f ir::Func,
+    /// index de type de chaque `ir::Sig`
+    sigs: &'f [u32],
+    /// blocs ouverts, du plus externe au plus interne (`None` : un `if`)
+    open: Vec<Option<ir::Label>>,
+}
+
+impl Emitter<'_> {
+    /// Profondeur du bloc de `l`; hors de portée, une profondeur invalide
+    /// que le constructeur signale.
+    fn depth(&self, l: ir::Label) -> u32 {
+        let d = self.open.iter().rev().position(|o| *o == Some(l)).unwrap_or(self.open.len());
+        d as u32
+    }
+
+    fn nested(&mut self, open: Instr, label: Option<ir::Label>, body: impl FnOnce(&mut Self)) {
+        self.b.push(open);
+        self.open.push(label);
+        body(self);
+        self.open.pop();
+        self.b.push(Instr::End);
+    }
+
+    fn expr(&mut self, e: &Expr) {
+        match e {
+            Expr::Const(v) => self.b.i32_const(*v),
+            Expr::Local(l) => self.b.local_get(l.0),
+            Expr::Global(g) => self.b.push(Instr::GlobalGet(g.0)),
+            Expr::Unary(ir::UnOp::Eqz, x) => {
+                self.expr(x);
+                self.b.num(NumOp::Eqz);
+            }
+            Expr::Binary(op, l, r) => {
+                self.expr(l);
+                self.expr(r);
+                self.b.num(num(*op));
+            }
+            Expr::Load8U(a) => {
+                self.expr(a);
+                self.b.push(Instr::Mem(MemOp::Load8U, MemArg::natural(MemOp::Load8U)));
+            }
+            Expr::Call(f, args) => {
+                args.iter().for_each(|a| self.expr(a));
+                self.b.call(f.0);
+            }
+            Expr::CallIndirect(sig, callee, args) => self.call_indirect(*sig, callee, args),
+            Expr::If(c, a, b) => {
+                self.expr(c);
+                self.nested(Instr::If { result: Some(ValType::I32) }, None, |e| {
+                    e.expr(a);
+                    e.b.push(Instr::Else);
+                    e.expr(b);
+                });
+            }
+            Expr::Seq(ss, x) => {
+                self.stmts(ss);
+                self.expr(x);
+            }
+        }
+    }
+
+    fn call_indirect(&mut self, sig: ir::SigId, callee: &Expr, args: &[Expr]) {
+        args.iter().for_each(|a| self.expr(a));
+        self.expr(callee);
+        self.b.push(Instr::CallIndirect(self.sigs[sig.0 as usize]));
+    }
+
+    fn stmts(&mut self, ss: &[Stmt]) {
+        for s in ss {
+            self.stmt(s);
+        }
+    }
+
+    fn stmt(&mut self, s: &Stmt) {
+        let outer = self.b.at(s.span);
+        match &s.kind {
+            StmtKind::Set(l, e) => {
+                self.expr(e);
+                self.b.local_set(l.0);
+            }
+            StmtKind::SetGlobal(g, e) => {
+                self.expr(e);
+                self.b.push(Instr::GlobalSet(g.0));
+            }
+            StmtKind::Store8 { addr, value } => {
+                self.expr(addr);
+                self.expr(value);
+                self.b.push(Instr::Mem(MemOp::Store8, MemArg::natural(MemOp::Store8)));
+            }
+            StmtKind::MemoryCopy { dst, src, len } => {
+                self.expr(dst);
+                self.expr(src);
+                self.expr(len);
+                self.b.push(Instr::MemoryCopy);
+            }
+            StmtKind::Call(f, args) => {
+                args.iter().for_each(|a| self.expr(a));
+                self.b.call(f.0);
+            }
+            StmtKind::CallIndirect(sig, callee, args) => self.call_indirect(*sig, callee, args),
+            StmtKind::Drop(e) => {
+                self.expr(e);
+                self.b.push(Instr::Drop);
+            }
+            StmtKind::Return(v) => {
+                if let Some(e) = v {
+                    self.expr(e);
+                }
+                self.b.push(Instr::Return);
+            }
+            StmtKind::Block(l, body) => {
+                let label = Some(self.func.label_name(*l).to_string());
+                self.nested(Instr::Block { label, result: None }, Some(*l), |e| e.stmts(body));
+            }
+            StmtKind::Loop(l, body) => {
+                let label = Some(self.func.label_name(*l).to_string());
+                self.nested(Instr::Loop { label, result: None }, Some(*l), |e| e.stmts(body));
+            }
+            StmtKind::Br(l) => self.b.push(Instr::Br(self.depth(*l))),
+            StmtKind::BrIf(l, c) => {
+                self.expr(c);
+                self.b.push(Instr::BrIf(self.depth(*l)));
+            }
+            StmtKind::If(c, a, b) => {
+                self.expr(c);
+                self.nested(Instr::If { result: None }, None, |e| {
+                    e.stmts(a);
+                    if !b.is_empty() {
+                        e.b.push(Instr::Else);
+                        e.stmts(b);
+                    }
+                });
+            }
+            StmtKind::Unreachable => self.b.push(Instr::Unreachable),
+            StmtKind::Wat(code) => self.b.push(Instr::Raw(code.clone())),
+        }
+        self.b.at(outer);
+    }
+}
+
+/// Module WebAssembly de la représentation intermédiaire; une erreur est un
+/// bug du compilateur.
+pub fn module(m: &ir::Module) -> Result<wasm::Module, BuildError> {
+    let mut w = wasm::Module::default();
+    for i in &m.imports {
+        let ty = w.func_type(i.params.iter().map(|&t| val(t)).collect(), i.result.map(val).into_iter().collect());
+        w.imports.push(wasm::Import { module: i.module.clone(), field: i.field.clone(), name: i.name.clone(), ty });
+    }
+    // toutes les fonctions déclarées avant les corps, qui s'appellent entre elles
+    for f in &m.funcs {
+        let params = f.locals[..f.params as usize].iter().map(|l| val(l.ty)).collect();
+        let ty = w.func_type(params, f.result.map(val).into_iter().collect());
+        w.funcs.push(wasm::Func { name: f.name.clone(), ty, locals: Vec::new(), body: Vec::new(), spans: Vec::new() });
+    }
+    // signatures des appels par la table, connues avant les corps
+    let sigs: Vec<u32> =
+        m.sigs.iter().map(|s| w.func_type(s.params.iter().map(|&t| val(t)).collect(), s.result.map(val).into_iter().collect())).collect();
+    if !m.table.is_empty() {
+        w.table = Some(ir::TABLE_BASE + m.table.len() as u32);
+        w.elems.push(wasm::Elem { offset: ir::TABLE_BASE, funcs: m.table.iter().map(|f| f.0).collect() });
+    }
+    w.memory = Some(m.pages);
+    if let Some(name) = &m.memory_export {
+        w.exports.push(Export { name: name.clone(), kind: ExportKind::Memory(0) });
+    }
+    for (i, g) in m.globals.iter().enumerate() {
+        w.globals.push(wasm::Global { name: g.name.clone(), ty: val(g.ty), mutable: g.mutable, init: g.init });
+        if let Some(name) = &g.export {
+            w.exports.push(Export { name: name.clone(), kind: ExportKind::Global(i as u32) });
+        }
+    }
+    let first = m.imports.len() as u32;
+    for (i, f) in m.funcs.iter().enumerate() {
+        if let Some(name) = &f.export {
+            w.exports.push(Export { name: name.clone(), kind: ExportKind::Func(first + i as u32) });
+        }
+    }
+    w.data = m.data.iter().map(|d| wasm::Data { offset: d.offset, bytes: d.bytes.clone() }).collect();
+
+    for (i, f) in m.funcs.iter().enumerate() {
+        let locals = f.locals.iter().map(|l| wasm::Local { name: l.name.clone(), ty: val(l.ty) }).collect();
+        let mut e = Emitter { b: w.builder(first + i as u32, locals), func: f, sigs: &sigs, open: Vec::new() };
+        e.b.at(f.span);
+        e.stmts(&f.body);
+        w.funcs[i] = e.b.finish()?;
+    }
+    Ok(w)
+}
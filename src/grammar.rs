@@ -1,12 +1,62 @@
+//! Lexèmes du langage. Les constantes ci-dessous sont le profil `en` (et les
+//! noms internes : `main`, `::`); un `Grammar` est le profil utilisé pour lire
+//! un fichier : `en`, `fr`, `pascal`, ou un fichier `.gram` de la forme
+//!
+//! ```text
+//! # commentaire
+//! for     pour
+//! assign  :=
+//! ```
+//!
+//! Un fichier source choisit le sien avec `#grammar fr` (ou un chemin) en
+//! première ligne; sinon c'est celui de `--grammar` (`en` par défaut).
+
+use crate::codes::code;
+use crate::diag::{Diagnostic, Span};
+use crate::i18n::tr;
+use crate::lexer::Token;
+use std::collections::HashMap;
+use std::fmt;
+
 // Mots-clés
 pub const KW_FN:   &str = "fn";
 pub const KW_MAIN: &str = "main";
 pub const KW_LOG:  &str = "log";
 pub const KW_FOR:  &str = "for";
 pub const KW_TO:   &str = "to";
+pub const KW_DOWNTO: &str = "downto";
+pub const KW_UNTIL:  &str = "until";
+pub const KW_STEP:   &str = "step";
+pub const KW_CONST:  &str = "const";
+pub const KW_GLOBAL: &str = "global";
+pub const KW_EXPORT: &str = "export";
+pub const KW_RETURN: &str = "return";
+pub const KW_IMPORT: &str = "import";
+pub const KW_USE:    &str = "use";
+pub const KW_PUB:    &str = "pub";
+pub const KW_MACRO:  &str = "macro";
+pub const KW_IF:     &str = "if";   // seulement `if const` pour l'instant
+pub const KW_ELSE:   &str = "else";
+pub const KW_WAT:    &str = "wat"; // wat { ... } : WAT brut recopié tel quel
+
+// Types
+pub const TY_I32: &str = "i32";
 
 // Opérateurs
-pub const ASSIGN_LEXEME: &str = "="; // mets ":=" si tu préfères Pascal-style
+pub const ASSIGN_LEXEME: &str = "="; // `:=` dans le profil pascal
+pub const PLUS:    &str = "+";
+pub const MINUS:   &str = "-";
+pub const STAR:    &str = "*";
+pub const SLASH:   &str = "/";
+pub const PERCENT: &str = "%";
+pub const EQ:      &str = "==";
+pub const NE:      &str = "!=";
+pub const LE:      &str = "<=";
+pub const GE:      &str = ">=";
+pub const LT:      &str = "<";
+pub const GT:      &str = ">";
+pub const AND:     &str = "&&";
+pub const OR:      &str = "||";
 
 // Ponctuation (gardées en &str pour uniformiser)
 pub const LPAREN:  &str = "(";
@@ -14,3 +64,282 @@ pub const RPAREN:  &str = ")";
 pub const LBRACE:  &str = "{";
 pub const RBRACE:  &str = "}";
 pub const COMMA:   &str = ",";
+pub const COLON:   &str = ":";
+pub const PATH_SEP: &str = "::";
+pub const ARROW:   &str = "->";
+pub const AT:      &str = "@"; // attribut : @inline, @export("nom")...
+pub const BANG:    &str = "!"; // appel de macro : name!(...), et `!x` (non logique)
+pub const DOLLAR:  &str = "$"; // variable de motif de macro : `$x:expr`
+pub const FAT_ARROW: &str = "=>"; // règle de macro : `(motif) => { corps }`
+pub const COMMENT: &str = "//"; // jusqu'à la fin de la ligne
+
+/// Ce qu'un profil peut redéfinir : (clé du fichier `.gram`, token, lexème `en`).
+const CONFIGURABLE: &[(&str, Token, &str)] = &[
+    ("fn", Token::Fn, KW_FN),
+    ("main", Token::Main, KW_MAIN),
+    ("log", Token::Log, KW_LOG),
+    ("for", Token::For, KW_FOR),
+    ("to", Token::To, KW_TO),
+    ("downto", Token::Downto, KW_DOWNTO),
+    ("until", Token::Until, KW_UNTIL),
+    ("step", Token::Step, KW_STEP),
+    ("const", Token::Const, KW_CONST),
+    ("global", Token::Global, KW_GLOBAL),
+    ("export", Token::Export, KW_EXPORT),
+    ("return", Token::Return, KW_RETURN),
+    ("import", Token::Import, KW_IMPORT),
+    ("use", Token::Use, KW_USE),
+    ("pub", Token::Pub, KW_PUB),
+    ("macro", Token::Macro, KW_MACRO),
+    ("if", Token::If, KW_IF),
+    ("else", Token::Else, KW_ELSE),
+    ("wat", Token::Wat, KW_WAT),
+    ("assign", Token::Assign, ASSIGN_LEXEME),
+    ("eq", Token::EqEq, EQ),
+    ("ne", Token::NotEq, NE),
+    ("le", Token::Le, LE),
+    ("ge", Token::Ge, GE),
+    ("lt", Token::Lt, LT),
+    ("gt", Token::Gt, GT),
+    ("and", Token::AndAnd, AND),
+    ("or", Token::OrOr, OR),
+];
+
+/// Ponctuation commune à tous les profils.
+const FIXED: &[(Token, &str)] = &[
+    (Token::PathSep, PATH_SEP),
+    (Token::Arrow, ARROW),
+    (Token::FatArrow, FAT_ARROW),
+    (Token::LParen, LPAREN),
+    (Token::RParen, RPAREN),
+    (Token::LBrace, LBRACE),
+    (Token::RBrace, RBRACE),
+    (Token::Comma, COMMA),
+    (Token::Colon, COLON),
+    (Token::Bang, BANG),
+    (Token::At, AT),
+    (Token::Dollar, DOLLAR),
+    (Token::Plus, PLUS),
+    (Token::Minus, MINUS),
+    (Token::Star, STAR),
+    (Token::Slash, SLASH),
+    (Token::Percent, PERCENT),
+];
+
+/// Profils intégrés : différences avec `en`.
+const BUILTIN: &[(&str, &[(&str, &str)])] = &[
+    ("en", &[]),
+    ("fr", &[
+        ("fn", "fonction"), ("main", "principal"), ("log", "journal"),
+        ("for", "pour"), ("to", "jusqua"), ("downto", "descend"), ("until", "avant"),
+        ("step", "pas"), ("const", "constante"), ("global", "globale"),
+        ("export", "exporte"), ("return", "retourne"), ("import", "importe"),
+        ("use", "utilise"), ("pub", "public"), ("if", "si"), ("else", "sinon"),
+        ("and", "et"), ("or", "ou"),
+    ]),
+    ("pascal", &[("assign", ":="), ("eq", "="), ("ne", "<>"), ("and", "and"), ("or", "or")]),
+];
+
+pub const BUILTIN_NAMES: &str = "en, fr, pascal";
+
+#[derive(Debug, Clone)]
+pub struct GrammarError {
+    pub code: &'static str,
+    pub message: String,
+    pub span: Option<Span>,
+}
+impl fmt::Display for GrammarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+impl std::error::Error for GrammarError {}
+impl From<GrammarError> for Diagnostic {
+    fn from(e: GrammarError) -> Self {
+        Diagnostic::new(e.code, tr!("grammar.error", e.message), e.span)
+    }
+}
+
+/// Un profil de lexèmes, validé : aucun lexème n'en masque un autre.
+#[derive(Debug, Clone)]
+pub struct Grammar {
+    words: HashMap<String, Token>, // mots-clés (et `and`, `or`... écrits en lettres)
+    ops: Vec<(String, Token)>,     // symboles, les plus longs d'abord
+}
+
+#[derive(PartialEq)]
+enum Shape { Word, Symbols }
+
+fn shape(lexeme: &str) -> Result<Shape, String> {
+    let mut cs = lexeme.chars();
+    match cs.next() {
+        None => Err(tr!("grammar.empty")),
+        Some(c) if (c.is_ascii_alphabetic() || c == '_') && cs.all(|c| c.is_ascii_alphanumeric() || c == '_') => {
+            Ok(Shape::Word)
+        }
+        // `"` ouvre une chaîne, `#` un pragma, `//` un commentaire
+        _ if lexeme.contains(COMMENT) => Err(tr!("grammar.comment")),
+        _ if lexeme.chars().all(|c| c.is_ascii_punctuation() && c != '"' && c != '#') => Ok(Shape::Symbols),
+        _ => Err(tr!("grammar.shape")),
+    }
+}
+
+impl Grammar {
+    /// Le profil `en` redéfini par `overrides` (clé, lexème, position dans
+    /// le fichier `.gram` s'il y en a un).
+    fn build(overrides: &[(&str, &str, Option<Span>)]) -> Result<Self, GrammarError> {
+        let mut entries: Vec<(&str, Token, &str, Option<Span>)> =
+            CONFIGURABLE.iter().map(|(k, t, l)| (*k, t.clone(), *l, None)).collect();
+        for &(key, lexeme, span) in overrides {
+            let Some(e) = entries.iter_mut().find(|e| e.0 == key) else {
+                let keys: Vec<&str> = CONFIGURABLE.iter().map(|e| e.0).collect();
+                return Err(GrammarError { code: code!("G0603"), message: tr!("grammar.unknown_key", key, keys.join(", ")), span });
+            };
+            (e.2, e.3) = (lexeme, span);
+        }
+
+        let mut g = Grammar { words: HashMap::new(), ops: Vec::new() };
+        let mut owner: HashMap<&str, (String, Option<Span>)> =
+            FIXED.iter().map(|(_, l)| (*l, (tr!("grammar.punct", l), None))).collect();
+        for (t, l) in FIXED {
+            g.ops.push((l.to_string(), t.clone()));
+        }
+        for (key, tok, lexeme, span) in entries {
+            let err = |message: String| GrammarError { code: code!("G0602"), message, span };
+            let sh = shape(lexeme).map_err(|m| err(format!("`{key}`: {m}")))?;
+            if lexeme == TY_I32 {
+                return Err(err(format!("`{key}`: {}", tr!("grammar.type_name", TY_I32))));
+            }
+            // le conflit est montré sur la ligne du fichier `.gram` en cause
+            if let Some((other, other_span)) = owner.insert(lexeme, (format!("`{key}`"), span)) {
+                let message = tr!("grammar.conflict", lexeme, other, format!("`{key}`"));
+                return Err(GrammarError { code: code!("G0604"), message, span: span.or(other_span) });
+            }
+            match sh {
+                Shape::Word => { g.words.insert(lexeme.to_string(), tok); }
+                Shape::Symbols => g.ops.push((lexeme.to_string(), tok)),
+            }
+        }
+        // plus long d'abord : `::` avant `:`, `<=` avant `<`, `:=` avant `:`
+        g.ops.sort_by_key(|(l, _)| std::cmp::Reverse(l.len()));
+        Ok(g)
+    }
+
+    pub fn builtin(name: &str) -> Option<Self> {
+        let (_, diff) = BUILTIN.iter().find(|(n, _)| *n == name)?;
+        let overrides: Vec<_> = diff.iter().map(|&(k, l)| (k, l, None)).collect();
+        Some(Self::build(&overrides).expect("profil intégré invalide"))
+    }
+
+    /// Lit un fichier `.gram` (`file` : son index dans le SourceMap).
+    pub fn parse_profile(text: &str, file: usize) -> Result<Self, GrammarError> {
+        let mut overrides: Vec<(&str, &str, Option<Span>)> = Vec::new();
+        let mut offset = 0;
+        for line in text.split_inclusive('\n') {
+            let lo = offset;
+            offset += line.len();
+            let content = line.split('#').next().unwrap().trim();
+            if content.is_empty() {
+                continue;
+            }
+            let start = lo + line.find(content).unwrap();
+            let span = Span::new(file, start, start + content.len());
+            let mut parts = content.split_whitespace();
+            let (Some(key), Some(lexeme), None) = (parts.next(), parts.next(), parts.next()) else {
+                return Err(GrammarError { code: code!("G0605"), message: tr!("grammar.bad_line"), span: Some(span) });
+            };
+            if overrides.iter().any(|o| o.0 == key) {
+                return Err(GrammarError { code: code!("G0606"), message: tr!("grammar.duplicate_key", key), span: Some(span) });
+            }
+            overrides.push((key, lexeme, Some(span)));
+        }
+        Self::build(&overrides)
+    }
+
+    /// Token du mot-clé `id`, s'il en est un dans ce profil.
+    pub fn word(&self, id: &str) -> Option<&Token> {
+        self.words.get(id)
+    }
+
+    /// Plus long symbole au début de `rest`, et sa longueur.
+    pub fn op_at(&self, rest: &str) -> Option<(&Token, usize)> {
+        self.ops.iter().find(|(l, _)| rest.starts_with(l.as_str())).map(|(l, t)| (t, l.len()))
+    }
+
+    /// Comment ce profil écrit `tok` (mots-clés, opérateurs, ponctuation).
+    pub fn spell(&self, tok: &Token) -> Option<&str> {
+        self.words.iter().chain(self.ops.iter().map(|(l, t)| (l, t)))
+            .find(|(_, t)| *t == tok)
+            .map(|(l, _)| l.as_str())
+    }
+}
+
+/// `#grammar xxx` en tête de fichier (après d'éventuelles lignes vides).
+pub struct Pragma<'s> {
+    pub arg: &'s str,
+    pub lo: usize, // position de `arg`
+    pub end: usize, // fin de la ligne : le lexer commence là
+}
+
+pub const PRAGMA: &str = "#grammar";
+
+pub fn pragma(src: &str) -> Option<Pragma<'_>> {
+    let start = src.len() - src.trim_start().len();
+    let rest = src[start..].strip_prefix(PRAGMA)?;
+    if !rest.starts_with([' ', '\t']) {
+        return None;
+    }
+    let line_end = rest.find('\n').unwrap_or(rest.len());
+    let arg = rest[..line_end].trim();
+    let lo = start + PRAGMA.len() + rest.find(arg).unwrap_or(0);
+    Some(Pragma { arg, lo, end: start + PRAGMA.len() + line_end })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{pragma, Grammar};
+    use crate::lexer::Token;
+
+    #[test]
+    fn builtin_profiles() {
+        let fr = Grammar::builtin("fr").unwrap();
+        assert_eq!(fr.word("pour"), Some(&Token::For));
+        assert_eq!(fr.word("for"), None);
+        assert_eq!(fr.spell(&Token::AndAnd), Some("et"));
+        // le plus long symbole d'abord
+        let pascal = Grammar::builtin("pascal").unwrap();
+        assert_eq!(pascal.op_at(":= 1"), Some((&Token::Assign, 2)));
+        assert_eq!(pascal.op_at(": i32"), Some((&Token::Colon, 1)));
+        assert_eq!(pascal.op_at("<> b"), Some((&Token::NotEq, 2)));
+        assert!(Grammar::builtin("klingon").is_none());
+    }
+
+    #[test]
+    fn gram_files() {
+        let g = Grammar::parse_profile("# pascal-ish\nassign :=\n\nfor   loop  # boucle\n", 0).unwrap();
+        assert_eq!((g.word("loop"), g.word("for")), (Some(&Token::For), None));
+        // (code, texte montré par l'erreur)
+        let err = |text: &'static str| {
+            let e = Grammar::parse_profile(text, 0).unwrap_err();
+            (e.code, e.span.map(|s| &text[s.lo..s.hi]))
+        };
+        assert_eq!(err("for 9x\n"), ("G0602", Some("for 9x")));
+        assert_eq!(err("eq //\n"), ("G0602", Some("eq //")));
+        assert_eq!(err("log i32\n"), ("G0602", Some("log i32")));
+        assert_eq!(err("loop for\n"), ("G0603", Some("loop for")));
+        // deux clés pour le même lexème, ou un lexème qui masque la ponctuation
+        assert_eq!(err("for to\n"), ("G0604", Some("for to")));
+        assert_eq!(err("assign ::\n"), ("G0604", Some("assign ::")));
+        assert_eq!(err("for\n"), ("G0605", Some("for")));
+        assert_eq!(err("for pour\nfor boucle\n"), ("G0606", Some("for boucle")));
+    }
+
+    #[test]
+    fn pragma_line() {
+        let src = "\n#grammar  fr \nfonction principal() {}\n";
+        let p = pragma(src).unwrap();
+        assert_eq!((p.arg, &src[p.lo..p.lo + 2], &src[p.end..p.end + 1]), ("fr", "fr", "\n"));
+        assert!(pragma("#grammarfr\n").is_none());
+        assert!(pragma("fn main() {}\n#grammar fr\n").is_none());
+    }
+}
@@ -0,0 +1,397 @@
+//! Messages du compilateur, en français et en anglais.
+//!
+//! Chaque message a une clé stable (`parse.unexpected`); le texte se récupère
+//! avec `tr!("clé", arg0, arg1...)`, où `{0}`, `{1}`... désignent les
+//! arguments. Une clé absente du catalogue ne compile pas.
+//! La langue est choisie une fois au démarrage (`--lang`, sinon `LANG`).
+
+use std::fmt;
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lang { Fr, En }
+
+impl Lang {
+    /// `fr`, `en`, ou une locale POSIX (`en_US.UTF-8`, `fr_CA`...).
+    pub fn parse(s: &str) -> Option<Lang> {
+        match s.get(..2)? {
+            "fr" => Some(Lang::Fr),
+            "en" => Some(Lang::En),
+            _ => None,
+        }
+    }
+
+    /// D'après l'environnement (`LC_ALL`, `LC_MESSAGES`, puis `LANG`);
+    /// français si rien n'est reconnu.
+    pub fn from_env() -> Lang {
+        ["LC_ALL", "LC_MESSAGES", "LANG"].iter()
+            .filter_map(|v| std::env::var(v).ok())
+            .find(|v| !v.is_empty())
+            .and_then(|v| Lang::parse(&v))
+            .unwrap_or(Lang::Fr)
+    }
+}
+
+static LANG: OnceLock<Lang> = OnceLock::new();
+
+/// À appeler avant le premier message; les appels suivants sont ignorés.
+pub fn set_lang(lang: Lang) {
+    let _ = LANG.set(lang);
+}
+
+pub fn lang() -> Lang {
+    *LANG.get().unwrap_or(&Lang::Fr)
+}
+
+/// (clé, français, anglais)
+const CATALOG: &[(&str, &str, &str)] = &[
+    // rendu des diagnostics
+    ("diag.error", "erreur", "error"),
+    ("diag.warning", "avertissement", "warning"),
+    ("diag.note", "note", "note"),
+    ("diag.help", "aide", "help"),
+    ("diag.in_expansion", "dans l'expansion de `{0}!` appelée ici", "in the expansion of `{0}!` called here"),
+    ("diag.macro_defined_here", "`{0}!` est définie ici", "`{0}!` is defined here"),
+    ("diag.more_expansions", "... et {0} expansion(s) de plus", "... and {0} more expansion(s)"),
+    ("diag.explain", "pour en savoir plus: `gaufre explain {0}`", "for more information: `gaufre explain {0}`"),
+    ("explain.wrong", "Exemple fautif :", "Wrong:"),
+    ("explain.fixed", "Correction :", "Corrected:"),
+    ("explain.unknown", "code inconnu: `{0}`", "unknown code: `{0}`"),
+    ("note.first_definition", "première définition ici", "first defined here"),
+    ("note.macro_defined", "macro définie ici", "macro defined here"),
+    ("note.macro_hygiene", "`{0}` est un local de l'appelant, invisible depuis le corps de la macro : le passer en argument", "`{0}` is a local of the caller, not visible from the macro body: pass it as an argument"),
+    ("note.evaluating", "pendant l'évaluation de `{0}`", "while evaluating `{0}`"),
+    ("note.private", "défini ici (ajouter `pub` pour l'exporter)", "defined here (add `pub` to export it)"),
+    ("note.deprecated", "marqué `@deprecated` ici", "marked `@deprecated` here"),
+    ("note.lint_denied", "`{0}` changé en erreur par cet attribut", "`{0}` turned into an error by this attribute"),
+    ("note.previous_log", "le précédent", "the previous one"),
+    ("note.returns_here", "la fonction se termine ici", "the function returns here"),
+    ("note.shadowed", "`{0}` déclaré ici", "`{0}` declared here"),
+    // construction du module WebAssembly (bugs du compilateur)
+    ("wasm.internal", "erreur interne du compilateur dans `{0}`: {1}", "internal compiler error in `{0}`: {1}"),
+    ("wasm.underflow", "`{0}` attend {1} valeur(s) sur la pile, {2} disponible(s)", "`{0}` expects {1} value(s) on the stack, {2} available"),
+    ("wasm.block_end", "`{0}` avec {2} valeur(s) sur la pile au lieu de {1}", "`{0}` with {2} value(s) on the stack instead of {1}"),
+    ("wasm.bad_index", "{0} {1} inexistant(e)", "no {0} {1}"),
+    ("wasm.entity.func", "fonction", "function"),
+    ("wasm.entity.memory", "mémoire", "memory"),
+    ("wasm.unmatched", "`{0}` sans bloc ouvert", "`{0}` without an open block"),
+    ("wasm.if_without_else", "un `if` qui produit une valeur demande un `else`", "an `if` producing a value needs an `else`"),
+    ("wasm.unclosed", "{0} bloc(s) non refermé(s) en fin de fonction", "{0} unclosed block(s) at the end of the function"),
+    // validation du module
+    ("validate.invalid", "module invalide : {0}", "invalid module: {0}"),
+    ("validate.internal", "erreur interne du compilateur : {0}", "internal compiler error: {0}"),
+    ("validate.in_func", "module invalide dans `{0}` : {1}", "invalid module in `{0}`: {1}"),
+    ("validate.wat", "bloc `wat` invalide dans `{0}` : {1}", "invalid `wat` block in `{0}`: {1}"),
+    ("validate.type", "`{0}` attend un {1}, trouvé un {2}", "`{0}` expects an {1}, found an {2}"),
+    ("validate.immutable", "`global.set` sur la globale immuable `{0}`", "`global.set` on immutable global `{0}`"),
+    ("validate.no_memory", "`{0}` sans mémoire déclarée", "`{0}` without a declared memory"),
+    ("validate.align", "alignement {0} supérieur à l'alignement naturel de `{1}`", "alignment {0} larger than the natural alignment of `{1}`"),
+    ("validate.params", "les premiers locals ne sont pas les paramètres du type de la fonction", "the first locals are not the parameters of the function type"),
+    ("validate.memory_pages", "mémoire de {0} pages, au-delà de 65536", "memory of {0} pages, beyond 65536"),
+    ("validate.duplicate_export", "export `{0}` en double", "duplicate export `{0}`"),
+    ("validate.no_table", "`{0}` sans table déclarée", "`{0}` without a declared table"),
+    ("validate.elem_bounds", "segment de table à {0} ({1} fonctions) hors de la table ({2} entrées)", "table segment at {0} ({1} functions) outside of the table ({2} entries)"),
+    ("validate.data_bounds", "segment de données à {0} ({1} octets) hors de la mémoire ({2} octets)", "data segment at {0} ({1} bytes) outside of memory ({2} bytes)"),
+    // assemblage des blocs wat en binaire
+    ("binary.asm", "bloc `wat` de `{0}` impossible à assembler : {1}", "cannot assemble `wat` block in `{0}`: {1}"),
+    ("asm.expected", "`{0}` attendu", "expected `{0}`"),
+    ("asm.unknown_instr", "instruction inconnue ou non prise en charge : `{0}`", "unknown or unsupported instruction: `{0}`"),
+    ("asm.unknown_name", "{0} inconnu(e) : `{1}`", "unknown {0}: `{1}`"),
+    ("asm.duplicate_name", "`{0}` est déclaré plusieurs fois", "`{0}` is declared more than once"),
+    ("asm.bad_number", "nombre invalide : `{0}`", "invalid number: `{0}`"),
+    ("asm.unsupported", "`{0}` non pris en charge", "`{0}` is not supported"),
+    ("asm.bad_escape", "échappement invalide dans la chaîne {0}", "invalid escape in string {0}"),
+    ("asm.bad_name", "nom qui n'est pas de l'UTF-8 : {0}", "name is not UTF-8: {0}"),
+    ("asm.import_order", "les imports doivent précéder les fonctions", "imports must come before functions"),
+    // ligne de commande
+    ("cli.usage",
+        "usage: gaufre [-D NOM[=VALEUR]]... [-A|-W|--deny LINT]... [-W error] [--emit wat|wasm|ir] [--source-map] [--grammar PROFIL] [--lang fr|en] [--error-format human|json] [--test] <fichier.gfr> [sortie.wat]\n       gaufre check [--validate] [-D NOM[=VALEUR]]... [--grammar PROFIL] [--test] <fichier.gfr>\n       gaufre assemble [--source-map] <fichier.wat> [sortie.wasm]\n       gaufre validate <fichier.wat>\n       gaufre translate [--from PROFIL] --to PROFIL <fichier.gfr> [sortie.gfr]\n       gaufre explain <CODE>",
+        "usage: gaufre [-D NAME[=VALUE]]... [-A|-W|--deny LINT]... [-W error] [--emit wat|wasm|ir] [--source-map] [--grammar PROFILE] [--lang fr|en] [--error-format human|json] [--test] <file.gfr> [output.wat]\n       gaufre check [--validate] [-D NAME[=VALUE]]... [--grammar PROFILE] [--test] <file.gfr>\n       gaufre assemble [--source-map] <file.wat> [output.wasm]\n       gaufre validate <file.wat>\n       gaufre translate [--from PROFILE] --to PROFILE <file.gfr> [output.gfr]\n       gaufre explain <CODE>"),
+    ("cli.written", "Écrit: {0}", "Written: {0}"),
+    ("cli.checked", "Vérifié: {0}", "Checked: {0}"),
+    ("cli.profile_arg", "`{0}` attend un profil ({1}) ou un fichier .gram", "`{0}` expects a profile ({1}) or a .gram file"),
+    ("cli.error_format_arg", "`--error-format` attend `human` ou `json`", "`--error-format` expects `human` or `json`"),
+    ("cli.emit_arg", "`--emit` attend `wat`, `wasm` ou `ir`", "`--emit` expects `wat`, `wasm` or `ir`"),
+    ("cli.source_map_emit", "`--source-map` accompagne un module binaire : ajouter `--emit wasm`", "`--source-map` goes with a binary module: add `--emit wasm`"),
+    ("cli.lang_arg", "`--lang` attend `fr` ou `en`", "`--lang` expects `fr` or `en`"),
+    ("cli.lint_arg", "`{0}` attend un nom de lint ({1})", "`{0}` expects a lint name ({1})"),
+    ("cli.define_arg", "`-D` attend NOM ou NOM=VALEUR", "`-D` expects NAME or NAME=VALUE"),
+    ("cli.define_value", "`-D {0}`: valeur i32 attendue après `=`", "`-D {0}`: expected an i32 value after `=`"),
+    ("cli.define_name", "`-D {0}`: nom invalide", "`-D {0}`: invalid name"),
+    ("cli.unknown_option", "option inconnue: `{0}`", "unknown option: `{0}`"),
+    ("cli.translate_to", "`translate` attend `--to PROFIL`", "`translate` expects `--to PROFILE`"),
+    ("cli.explain_code", "`explain` attend un code, par exemple `G0101`", "`explain` expects a code, for example `G0101`"),
+    ("cli.missing_input", "fichier source manquant", "missing source file"),
+    ("cli.extra_arg", "argument en trop: `{0}`", "unexpected argument: `{0}`"),
+    ("io.read", "impossible de lire `{0}`: {1}", "cannot read `{0}`: {1}"),
+    ("io.write", "impossible d'écrire `{0}`: {1}", "cannot write `{0}`: {1}"),
+    // lexer et blocs wat
+    ("lex.unterminated_string", "chaîne non terminée", "unterminated string"),
+    ("lex.unterminated_wat", "bloc `wat` non terminé", "unterminated `wat` block"),
+    ("lex.wat", "wat: {0}", "wat: {0}"),
+    ("lex.unexpected_char", "caractère inattendu: 0x{0}", "unexpected character: 0x{0}"),
+    ("wat.unclosed_comment", "commentaire `(;` non fermé", "unclosed `(;` comment"),
+    ("wat.end_before_paren", "`{0}` non fermé par `end` avant `)`", "`{0}` not closed by `end` before `)`"),
+    ("wat.stray_paren", "`)` sans `(` correspondante", "`)` without a matching `(`"),
+    ("wat.else_outside_if", "`else` hors d'un `if`", "`else` outside of an `if`"),
+    ("wat.stray_end", "`end` sans bloc ouvert", "`end` without an open block"),
+    ("wat.unclosed_paren", "`(` non fermée", "unclosed `(`"),
+    ("wat.unclosed_block", "`{0}` non fermé par `end`", "`{0}` not closed by `end`"),
+    // parser
+    ("parse.lex", "Lexer: {0}", "Lexer: {0}"),
+    ("parse.unexpected", "Attendu {0}, trouvé {1}", "expected {0}, found {1}"),
+    ("parse.int_overflow", "Entier hors plage i32: {0}", "integer out of i32 range: {0}"),
+    ("parse.unknown_macro", "macro inconnue: `{0}!`", "unknown macro: `{0}!`"),
+    ("parse.duplicate_param", "le paramètre `{0}` est défini plusieurs fois", "parameter `{0}` is defined more than once"),
+    ("parse.duplicate_macro", "la macro `{0}!` est définie plusieurs fois", "macro `{0}!` is defined more than once"),
+    ("parse.macro_arity", "`{0}!` attend {1} argument(s), {2} fourni(s)", "`{0}!` expects {1} argument(s), {2} given"),
+    ("parse.macro_no_match", "aucune règle de `{0}!` ne reconnaît cet appel", "no rule of `{0}!` matches this call"),
+    ("parse.macro_repeat", "dans `{0}!`, `${1}` et `${2}` ne se répètent pas le même nombre de fois", "in `{0}!`, `${1}` and `${2}` do not repeat the same number of times"),
+    ("parse.macro.no_rule", "la macro `{0}!` n'a aucune règle `(motif) => { corps }`", "macro `{0}!` has no `(pattern) => { body }` rule"),
+    ("parse.macro.fragment", "`${0}` attend un fragment : `${0}:expr` ({1})", "`${0}` expects a fragment: `${0}:expr` ({1})"),
+    ("parse.macro.dollar", "`$` attend `$nom`, `$nom:fragment` ou `$( ... )*`", "`$` expects `$name`, `$name:fragment` or `$( ... )*`"),
+    ("parse.macro.repetition_op", "`$( ... )` attend `*` ou `+`, éventuellement après un séparateur", "`$( ... )` expects `*` or `+`, optionally after a separator"),
+    ("parse.macro.duplicate_var", "`${0}` apparaît deux fois dans le motif", "`${0}` appears twice in the pattern"),
+    ("parse.macro.unbound", "`${0}` n'est pas une variable du motif", "`${0}` is not a pattern variable"),
+    ("parse.macro.not_repeated", "`${0}` se répète dans le motif : l'utiliser dans `$( ... )*`", "`${0}` repeats in the pattern: use it inside `$( ... )*`"),
+    ("parse.macro.no_repeated_var", "`$( ... )` ne contient aucune variable répétée par le motif", "`$( ... )` contains no variable repeated by the pattern"),
+    ("parse.macro_recursion", "récursion de macro trop profonde dans `{0}!` (limite {1})", "macro recursion too deep in `{0}!` (limit {1})"),
+    ("parse.attr_on_macro", "attribut sur un appel de macro non supporté", "attributes on macro calls are not supported"),
+    ("parse.inner_attr_position", "un attribut `@!` ne peut être qu'en tête du fichier, avant tout item",
+        "an `@!` attribute can only appear at the top of the file, before any item"),
+    ("parse.attr_position", "un attribut doit précéder une fonction, une constante ou une globale",
+        "an attribute must come before a function, a constant or a global"),
+    ("parse.or", "{0} ou {1}", "{0} or {1}"),
+    ("parse.expected.ident", "identifiant", "identifier"),
+    ("parse.expected.int", "un entier i32", "an i32 integer"),
+    ("parse.expected.expr", "une expression (ident | int | `(`)", "an expression (ident | int | `(`)"),
+    ("parse.expected.if_const", "{0} (seul {1} existe pour l'instant)", "{0} (only {1} exists for now)"),
+    ("parse.expected.wat_body", "`{` suivi d'instructions WAT", "`{` followed by WAT instructions"),
+    ("parse.expected.call_or_assign", "un appel ou une affectation", "a call or an assignment"),
+    ("parse.expected.import_path", "un chemin de fichier entre guillemets", "a quoted file path"),
+    ("parse.expected.macro_end", "la fin de la macro", "the end of the macro"),
+    ("tok.ident", "l'identifiant `{0}`", "identifier `{0}`"),
+    ("tok.number", "le nombre `{0}`", "number `{0}`"),
+    ("tok.string", "la chaîne \"{0}\"", "string \"{0}\""),
+    ("tok.wat", "un bloc WAT", "a WAT block"),
+    ("tok.eof", "la fin du fichier", "end of file"),
+    // attributs
+    ("attr.target.fn", "une fonction", "a function"),
+    ("attr.target.const", "une constante", "a constant"),
+    ("attr.target.global", "une globale", "a global"),
+    ("attr.target.stmt", "une instruction", "a statement"),
+    ("attr.target.file", "un fichier", "a file"),
+    ("attr.unknown", "attribut inconnu: `@{0}`", "unknown attribute: `@{0}`"),
+    ("attr.wrong_target", "`@{0}` ne s'applique pas à {1}", "`@{0}` does not apply to {1}"),
+    ("attr.repeated", "`@{0}` est répété", "`@{0}` is repeated"),
+    ("attr.no_args", "`@{0}` ne prend pas d'argument", "`@{0}` takes no argument"),
+    ("attr.opt_str", "`@{0}` prend au plus une chaîne: `@{0}(\"...\")`", "`@{0}` takes at most one string: `@{0}(\"...\")`"),
+    ("attr.cond", "`@{0}` prend une condition: `@{0}(DEBUG)`", "`@{0}` takes a condition: `@{0}(DEBUG)`"),
+    ("attr.lints", "`@!{0}` prend des noms de lint: `@!{0}(shadowed, unreachable)`", "`@!{0}` takes lint names: `@!{0}(shadowed, unreachable)`"),
+    ("attr.unknown_lint", "lint inconnu: `{0}` (connus: {1})", "unknown lint: `{0}` (known: {1})"),
+    ("attr.inline_cold", "`@inline` et `@cold` sont contradictoires", "`@inline` and `@cold` contradict each other"),
+    // évaluation à la compilation
+    ("eval.not_const", "initialiseur non constant: `{0}` n'est pas une constante", "non-constant initializer: `{0}` is not a constant"),
+    ("eval.unknown", "nom inconnu: `{0}`", "unknown name: `{0}`"),
+    ("eval.div_by_zero", "division par zéro", "division by zero"),
+    ("eval.overflow", "débordement (i32::MIN / -1)", "overflow (i32::MIN / -1)"),
+    ("eval.cycle", "définition circulaire de `{0}`", "circular definition of `{0}`"),
+    ("eval.not_const_fn", "`{0}` n'est pas une `const fn`", "`{0}` is not a `const fn`"),
+    ("eval.out_of_fuel", "évaluation trop longue (plus de {0} étapes)", "evaluation too long (more than {0} steps)"),
+    ("eval.too_deep", "récursion trop profonde dans `{0}` (limite {1})", "recursion too deep in `{0}` (limit {1})"),
+    ("eval.invalid_step", "Pas de boucle invalide: {0} (doit être > 0)", "invalid loop step: {0} (must be > 0)"),
+    ("eval.no_return", "`{0}` se termine sans `return`", "`{0}` ends without `return`"),
+    ("const_fn.read_global", "une `const fn` ne peut pas lire la globale `{0}`", "a `const fn` cannot read global `{0}`"),
+    ("const_fn.call", "une `const fn` ne peut appeler que des `const fn` (`{0}` n'en est pas une)",
+        "a `const fn` can only call `const fn`s (`{0}` is not one)"),
+    ("const_fn.log", "pas de `log` dans une `const fn`", "no `log` in a `const fn`"),
+    ("const_fn.wat", "pas de bloc `wat` dans une `const fn`", "no `wat` block in a `const fn`"),
+    ("const_fn.write_global", "une `const fn` ne peut pas modifier la globale `{0}`", "a `const fn` cannot modify global `{0}`"),
+    ("const_fn.ret", "une `const fn` doit renvoyer `i32`", "a `const fn` must return `i32`"),
+    // modules et résolution des noms
+    ("module.circular_import", "import circulaire: {0}", "circular import: {0}"),
+    ("module.bad_name", "nom de module invalide: `{0}`", "invalid module name: `{0}`"),
+    ("module.imported_twice", "module `{0}` importé deux fois", "module `{0}` imported twice"),
+    ("module.not_evaluable", "`{0}()` n'est pas évaluable à la compilation", "`{0}()` cannot be evaluated at compile time"),
+    ("module.condition", "condition de `{0}`: {1}", "`{0}` condition: {1}"),
+    ("module.duplicate", "`{0}` est défini plusieurs fois", "`{0}` is defined more than once"),
+    ("module.use_conflict", "`{0}` est déjà défini dans ce module", "`{0}` is already defined in this module"),
+    ("module.unknown_module", "module inconnu: `{0}`", "unknown module: `{0}`"),
+    ("module.no_submodule", "pas de module `{0}` dans `{1}`", "no module `{0}` in `{1}`"),
+    ("module.private", "`{0}` est privé dans le module `{1}`", "`{0}` is private in module `{1}`"),
+    ("module.missing_item", "`{0}` n'existe pas dans le module `{1}`", "`{0}` does not exist in module `{1}`"),
+    ("module.is_module", "`{0}` est un module", "`{0}` is a module"),
+    ("module.not_fn", "`{0}` n'est pas une fonction", "`{0}` is not a function"),
+    ("module.assign_fn", "impossible d'affecter la fonction `{0}`", "cannot assign to function `{0}`"),
+    ("module.deprecated", "`{0}` est obsolète", "`{0}` is deprecated"),
+    ("module.deprecated_why", "`{0}` est obsolète: {1}", "`{0}` is deprecated: {1}"),
+    ("module.assign_loop_var", "impossible d'affecter la variable de boucle `{0}`", "cannot assign to loop variable `{0}`"),
+    ("module.assign_const", "impossible d'affecter la constante `{0}`", "cannot assign to constant `{0}`"),
+    ("module.no_main", "aucune fonction `{0}` dans le fichier principal", "no `{0}` function in the main file"),
+    ("module.main_signature", "`{0}` ne prend pas de paramètres et ne renvoie rien", "`{0}` takes no parameters and returns nothing"),
+    ("module.main_elsewhere", "`{0}` n'est autorisé que dans le fichier principal", "`{0}` is only allowed in the main file"),
+    // génération
+    ("codegen.const", "const `{0}`: {1}", "const `{0}`: {1}"),
+    ("codegen.global", "global `{0}`: {1}", "global `{0}`: {1}"),
+    ("codegen.eval", "évaluation à la compilation: {0}", "compile-time evaluation: {0}"),
+    ("codegen.arity", "`{0}` attend {1} argument(s), {2} fourni(s)", "`{0}` expects {1} argument(s), {2} given"),
+    ("codegen.no_value", "la fonction `{0}` ne renvoie pas de valeur", "function `{0}` does not return a value"),
+    ("codegen.return_value", "`return` avec une valeur dans une fonction sans `-> i32`", "`return` with a value in a function without `-> i32`"),
+    ("codegen.missing_return_value", "`return` sans valeur dans une fonction qui renvoie i32", "`return` without a value in a function returning i32"),
+    ("codegen.type", "`{0}` attendu, `{1}` trouvé", "expected `{0}`, found `{1}`"),
+    ("codegen.missing_return", "la fonction `{0}` doit se terminer par `return`", "function `{0}` must end with `return`"),
+    ("codegen.test_call", "`{0}` est une fonction `@test`: elle n'est appelable que depuis un autre `@test`",
+        "`{0}` is a `@test` function: it can only be called from another `@test`"),
+    ("codegen.inline_body", "`@inline` demande un corps `return expr`", "`@inline` requires a `return expr` body"),
+    ("codegen.test_signature", "une fonction `@test` ne prend ni ne renvoie rien", "a `@test` function takes and returns nothing"),
+    ("codegen.export_main", "`main` est toujours exporté sous le nom `main` : `@export` ne s'y applique pas", "`main` is always exported as `main`: `@export` does not apply to it"),
+    ("codegen.duplicate_export", "export `{0}` en double", "duplicate export `{0}`"),
+    // lints
+    ("lint.unused_loop_var", "variable de boucle `{0}` jamais lue", "loop variable `{0}` is never read"),
+    ("lint.prefix_underscore", "préfixer le nom par `_` si c'est voulu", "prefix the name with `_` if this is intended"),
+    ("lint.empty_range", "intervalle vide ({0} puis {1}): la boucle ne s'exécute jamais",
+        "empty range ({0} then {1}): the loop never runs"),
+    ("lint.unreachable", "code inaccessible", "unreachable code"),
+    ("lint.shadowed", "la variable de boucle `{0}` masque {1} du même nom", "loop variable `{0}` shadows {1} of the same name"),
+    ("lint.shadowed.param", "un paramètre", "a parameter"),
+    ("lint.shadowed.loop_var", "une variable de boucle englobante", "an enclosing loop variable"),
+    ("lint.shadowed.const", "une constante", "a constant"),
+    ("lint.shadowed.global", "une globale", "a global"),
+    ("lint.long_string", "chaîne de {0} octets (plus de {1})", "string literal of {0} bytes (over {1})"),
+    ("lint.duplicate_log", "`{0}` identique au précédent", "`{0}` identical to the previous one"),
+    // suggestions
+    ("suggest.did_you_mean", "vouliez-vous dire `{0}` ?", "did you mean `{0}`?"),
+    // profils de grammaire et traduction
+    ("grammar.error", "grammaire: {0}", "grammar: {0}"),
+    ("grammar.unknown_profile", "profil de grammaire inconnu: `{0}` (profils: {1}, ou chemin d'un fichier .gram)",
+        "unknown grammar profile: `{0}` (profiles: {1}, or path to a .gram file)"),
+    ("grammar.empty", "lexème vide", "empty lexeme"),
+    ("grammar.comment", "`//` commence un commentaire", "`//` starts a comment"),
+    ("grammar.shape", "un lexème est soit un mot (lettres, chiffres, `_`), soit une suite de symboles sans `\"` ni `#`",
+        "a lexeme is either a word (letters, digits, `_`) or a run of symbols without `\"` or `#`"),
+    ("grammar.type_name", "`{0}` est un nom de type", "`{0}` is a type name"),
+    ("grammar.unknown_key", "clé inconnue `{0}` (clés: {1})", "unknown key `{0}` (keys: {1})"),
+    ("grammar.punct", "la ponctuation `{0}`", "punctuation `{0}`"),
+    ("grammar.conflict", "`{0}` désigne à la fois {1} et {2}", "`{0}` stands for both {1} and {2}"),
+    ("grammar.bad_line", "ligne attendue: `clé lexème`", "expected a line of the form `key lexeme`"),
+    ("grammar.duplicate_key", "`{0}` est défini deux fois", "`{0}` is defined twice"),
+    ("translate.keyword", "`{0}` est un mot-clé dans la grammaire cible: renomme-le avant de traduire",
+        "`{0}` is a keyword in the target grammar: rename it before translating"),
+    ("translate.relex", "la traduction serait relue autrement: ajoute des espaces autour de cet opérateur",
+        "the translation would read differently: add spaces around this operator"),
+    ("translate.add_space", "ajouter une espace ici", "add a space here"),
+];
+
+pub const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Utilisé par `tr!` pour refuser une clé inconnue à la compilation.
+pub const fn has(key: &str) -> bool {
+    let mut i = 0;
+    while i < CATALOG.len() {
+        if str_eq(CATALOG[i].0, key) {
+            return true;
+        }
+        i += 1;
+    }
+    false
+}
+
+const fn unique_keys() -> bool {
+    let mut i = 0;
+    while i < CATALOG.len() {
+        let mut j = i + 1;
+        while j < CATALOG.len() {
+            if str_eq(CATALOG[i].0, CATALOG[j].0) {
+                return false;
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+    true
+}
+const _: () = assert!(unique_keys(), "clé en double dans CATALOG");
+
+/// Texte de `key` dans la langue courante, `{n}` remplacé par `args[n]`.
+pub fn message(key: &str, args: &[&dyn fmt::Display]) -> String {
+    let Some(&(_, fr, en)) = CATALOG.iter().find(|e| e.0 == key) else {
+        return key.to_string();
+    };
+    fill(match lang() {
+        Lang::Fr => fr,
+        Lang::En => en,
+    }, args)
+}
+
+/// `text` avec `{n}` remplacé par `args[n]`; une autre accolade reste telle quelle.
+fn fill(mut rest: &str, args: &[&dyn fmt::Display]) -> String {
+    let mut out = String::new();
+    while let Some(i) = rest.find('{') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+        let arg = rest[1..].find('}').and_then(|j| Some((rest[1..1 + j].parse::<usize>().ok()?, j)));
+        match arg {
+            Some((n, j)) if n < args.len() => {
+                out.push_str(&args[n].to_string());
+                rest = &rest[j + 2..];
+            }
+            // `{` du texte, pas un argument
+            _ => {
+                out.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+macro_rules! tr {
+    ($key:literal $(, $arg:expr)* $(,)?) => {{
+        const _: () = assert!($crate::i18n::has($key), concat!("message inconnu: ", $key));
+        $crate::i18n::message($key, &[$(&$arg as &dyn std::fmt::Display),*])
+    }};
+}
+pub(crate) use tr;
+
+#[cfg(test)]
+mod tests {
+    use super::{fill, Lang, CATALOG};
+
+    #[test]
+    fn lang_names() {
+        assert_eq!(Lang::parse("fr_CA.UTF-8"), Some(Lang::Fr));
+        assert_eq!(Lang::parse("en"), Some(Lang::En));
+        assert_eq!(Lang::parse("de_DE"), None);
+        assert_eq!(Lang::parse("C"), None);
+    }
+
+    #[test]
+    fn arguments() {
+        assert_eq!(fill("`{1}` après `{0}`", &[&"a", &2]), "`2` après `a`");
+        // accolades du texte et argument manquant recopiés
+        assert_eq!(fill("{ {x} {3} }", &[&1]), "{ {x} {3} }");
+    }
+
+    /// Les deux traductions d'un message utilisent les mêmes arguments.
+    #[test]
+    fn same_arguments_in_both_languages() {
+        let args = |text: &str| (0..10).filter(|n| text.contains(&format!("{{{n}}}"))).collect::<Vec<_>>();
+        for (key, fr, en) in CATALOG {
+            assert_eq!(args(fr), args(en), "{key}");
+        }
+    }
+}
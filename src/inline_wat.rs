@@ -0,0 +1,179 @@
+//! Blocs `wat { ... }` : texte WAT recopié tel quel dans la sortie, après une
+//! validation minimale (parenthèses et blocs équilibrés) et le renommage des
+//! `$noms` qui désignent des variables ou items gaufre.
+
+use crate::i18n::tr;
+
+#[derive(Debug, Clone)]
+pub struct WatError {
+    pub message: String,
+    pub offset: usize, // dans le texte du bloc
+    pub len: usize,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Kind { Open, Close, Word }
+
+/// Découpe en `(`, `)` et mots, en sautant chaînes et commentaires.
+pub(crate) fn tokens(text: &str) -> Result<Vec<(Kind, usize, usize)>, WatError> {
+    let b = text.as_bytes();
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < b.len() {
+        match b[i] {
+            b' ' | b'\t' | b'\n' | b'\r' => i += 1,
+            b';' if b.get(i + 1) == Some(&b';') => {
+                while i < b.len() && b[i] != b'\n' { i += 1; }
+            }
+            b'(' if b.get(i + 1) == Some(&b';') => {
+                let start = i;
+                let end = text[i..].find(";)").ok_or(WatError {
+                    message: tr!("wat.unclosed_comment"), offset: start, len: 2,
+                })?;
+                i += end + 2;
+            }
+            b'(' => { out.push((Kind::Open, i, i + 1)); i += 1; }
+            b')' => { out.push((Kind::Close, i, i + 1)); i += 1; }
+            b'"' => {
+                let start = i;
+                i += 1;
+                loop {
+                    match b.get(i) {
+                        None => return Err(WatError { message: tr!("lex.unterminated_string"), offset: start, len: 1 }),
+                        Some(b'\\') => i += 2,
+                        Some(b'"') => { i += 1; break; }
+                        Some(_) => i += 1,
+                    }
+                }
+                out.push((Kind::Word, start, i));
+            }
+            _ => {
+                let start = i;
+                while i < b.len() && !matches!(b[i], b' ' | b'\t' | b'\n' | b'\r' | b'(' | b')' | b'"' | b';') {
+                    i += 1;
+                }
+                out.push((Kind::Word, start, i));
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Position de la première `}` hors chaînes et commentaires (fin du bloc).
+pub fn closing_brace(text: &str) -> Option<usize> {
+    let b = text.as_bytes();
+    let mut i = 0;
+    while i < b.len() {
+        match b[i] {
+            b'}' => return Some(i),
+            b';' if b.get(i + 1) == Some(&b';') => {
+                while i < b.len() && b[i] != b'\n' { i += 1; }
+            }
+            b'(' if b.get(i + 1) == Some(&b';') => i += text[i..].find(";)")? + 2,
+            b'"' => {
+                i += 1;
+                while *b.get(i)? != b'"' {
+                    i += if b[i] == b'\\' { 2 } else { 1 };
+                }
+                i += 1;
+            }
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+/// Parenthèses équilibrées et `block`/`loop`/`if` ... `end` bien imbriqués
+/// (forme plate; la forme parenthésée se ferme avec sa parenthèse).
+pub fn validate(text: &str) -> Result<(), WatError> {
+    let toks = tokens(text)?;
+    let mut stack: Vec<(&str, usize, usize)> = Vec::new(); // ("(" ou mot-clé, début, fin)
+    let mut prev = None;
+    for &(kind, lo, hi) in &toks {
+        let word = &text[lo..hi];
+        match kind {
+            Kind::Open => stack.push(("(", lo, hi)),
+            Kind::Close => match stack.pop() {
+                Some(("(", _, _)) => {}
+                Some((kw, _, _)) => {
+                    return Err(WatError { message: tr!("wat.end_before_paren", kw), offset: lo, len: 1 });
+                }
+                None => return Err(WatError { message: tr!("wat.stray_paren"), offset: lo, len: 1 }),
+            },
+            // `(block ...)`, `(if ...)`, `(then ...)` : fermés par la parenthèse
+            Kind::Word if prev == Some(Kind::Open) => {}
+            Kind::Word => match word {
+                "block" | "loop" | "if" => stack.push((word, lo, hi)),
+                "else" if matches!(stack.last(), Some(("if", _, _))) => {}
+                "else" => return Err(WatError { message: tr!("wat.else_outside_if"), offset: lo, len: hi - lo }),
+                "end" => match stack.last() {
+                    Some((kw, _, _)) if *kw != "(" => { stack.pop(); }
+                    _ => return Err(WatError { message: tr!("wat.stray_end"), offset: lo, len: hi - lo }),
+                },
+                _ => {}
+            },
+        }
+        prev = Some(kind);
+    }
+    match stack.pop() {
+        None => Ok(()),
+        Some(("(", lo, _)) => Err(WatError { message: tr!("wat.unclosed_paren"), offset: lo, len: 1 }),
+        Some((kw, lo, hi)) => Err(WatError { message: tr!("wat.unclosed_block", kw), offset: lo, len: hi - lo }),
+    }
+}
+
+/// Remplace chaque identifiant `$x` pour lequel `f("x")` renvoie un nom
+/// (sans `$`); le reste du texte est recopié à l'identique.
+pub fn map_ids(text: &str, f: &mut dyn FnMut(&str) -> Option<String>) -> String {
+    let Ok(toks) = tokens(text) else { return text.to_string() };
+    let mut out = String::new();
+    let mut last = 0;
+    for (kind, lo, hi) in toks {
+        let word = &text[lo..hi];
+        if kind != Kind::Word || !word.starts_with('$') {
+            continue;
+        }
+        if let Some(new) = f(&word[1..]) {
+            out.push_str(&text[last..lo]);
+            out.push('$');
+            out.push_str(&new);
+            last = hi;
+        }
+    }
+    out.push_str(&text[last..]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{closing_brace, map_ids, validate};
+
+    #[test]
+    fn block_end_skips_strings_and_comments() {
+        assert_eq!(closing_brace("i32.const 1 drop } rest"), Some(17));
+        assert_eq!(closing_brace("(; } ;) ;; }\n\"}\" }"), Some(17));
+        assert_eq!(closing_brace("\"} sans fin"), None);
+    }
+
+    #[test]
+    fn validation_points_at_the_culprit() {
+        validate("block $b (if (then nop)) loop br 0 end end").unwrap();
+        validate("if nop else nop end ;; end").unwrap();
+        let at = |text: &str| validate(text).map_err(|e| (e.offset, e.len)).unwrap_err();
+        assert_eq!(at("nop )"), (4, 1));
+        assert_eq!(at("(block end"), (7, 3));
+        assert_eq!(at("(block nop"), (0, 1));
+        assert_eq!(at("nop end"), (4, 3));
+        assert_eq!(at("block nop else"), (10, 4));
+        assert_eq!(at("(i32.add loop)"), (13, 1));
+        assert_eq!(at("nop loop"), (4, 4));
+        assert_eq!(at("(; ouvert"), (0, 2));
+    }
+
+    #[test]
+    fn ids_renamed_outside_strings_and_comments() {
+        let text = "local.get $x ;; $x\n(local.set $y \"$x\") (; $x ;) local.get $x";
+        let out = map_ids(text, &mut |id| (id == "x").then(|| "x#1".to_string()));
+        assert_eq!(out, "local.get $x#1 ;; $x\n(local.set $y \"$x\") (; $x ;) local.get $x#1");
+    }
+}
@@ -0,0 +1,440 @@
+//! Représentation intermédiaire : le programme résolu, abaissé en fonctions
+//! à locals explicites, expressions i32 et contrôle structuré (block, loop,
+//! if), sans plus rien de la syntaxe gaufre ni encore rien de celle de WAT.
+//! `codegen` la produit, `emit` la traduit en module `wasm`, `--emit ir` en
+//! affiche le dump.
+
+use crate::diag::{SourceMap, Span};
+use std::fmt;
+
+/// Type d'une valeur (gaufre n'a que des entiers 32 bits).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ty {
+    I32,
+}
+
+/// Index dans l'espace des fonctions : imports d'abord, puis `Module::funcs`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FuncId(pub u32);
+/// Index dans `Module::globals`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GlobalId(pub u32);
+/// Index dans `Func::locals`, paramètres en tête.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LocalId(pub u32);
+/// Index dans `Func::labels`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Label(pub u32);
+/// Index dans `Module::sigs`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SigId(pub u32);
+
+/// Index de la première fonction de `Module::table` dans la table : 0 reste
+/// vide, appeler une valeur fonction nulle piège.
+pub const TABLE_BASE: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnOp {
+    Eqz,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    DivS,
+    DivU,
+    RemS,
+    RemU,
+    Eq,
+    Ne,
+    LtS,
+    LtU,
+    LeS,
+    LeU,
+    GtS,
+    GeS,
+    GeU,
+}
+
+#[derive(Debug, Clone)]
+pub enum Expr {
+    Const(i32),
+    Local(LocalId),
+    Global(GlobalId),
+    Unary(UnOp, Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+    /// octet non signé à l'adresse
+    Load8U(Box<Expr>),
+    /// appel d'une fonction qui renvoie une valeur
+    Call(FuncId, Vec<Expr>),
+    /// appel par la table de l'index calculé par la deuxième expression
+    /// (évaluée après les arguments)
+    CallIndirect(SigId, Box<Expr>, Vec<Expr>),
+    /// `cond ? a : b`, une seule branche évaluée (`&&`, `||`)
+    If(Box<Expr>, Box<Expr>, Box<Expr>),
+    /// instructions puis valeur (arguments d'un appel `@inline`)
+    Seq(Vec<Stmt>, Box<Expr>),
+}
+
+impl Expr {
+    pub fn bin(op: BinOp, l: Expr, r: Expr) -> Expr {
+        Expr::Binary(op, Box::new(l), Box::new(r))
+    }
+    pub fn eqz(x: Expr) -> Expr {
+        Expr::Unary(UnOp::Eqz, Box::new(x))
+    }
+}
+
+/// Instruction, avec la position de l'instruction gaufre dont elle vient
+/// (vide pour le code que le compilateur ajoute de lui-même).
+#[derive(Debug, Clone)]
+pub struct Stmt {
+    pub kind: StmtKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum StmtKind {
+    Set(LocalId, Expr),
+    SetGlobal(GlobalId, Expr),
+    Store8 { addr: Expr, value: Expr },
+    /// `len` octets de `src` vers `dst`
+    MemoryCopy { dst: Expr, src: Expr, len: Expr },
+    /// appel d'une fonction sans valeur de retour
+    Call(FuncId, Vec<Expr>),
+    CallIndirect(SigId, Expr, Vec<Expr>),
+    /// valeur calculée puis ignorée
+    Drop(Expr),
+    Return(Option<Expr>),
+    /// `Br` vers le label d'un bloc en sort
+    Block(Label, Vec<Stmt>),
+    /// `Br` vers le label d'une boucle la recommence
+    Loop(Label, Vec<Stmt>),
+    Br(Label),
+    BrIf(Label, Expr),
+    If(Expr, Vec<Stmt>, Vec<Stmt>),
+    Unreachable,
+    /// bloc `wat { ... }`, `$var` déjà renvoyés aux noms des locals
+    Wat(String),
+}
+
+impl StmtKind {
+    pub fn at(self, span: Span) -> Stmt {
+        Stmt { kind: self, span }
+    }
+}
+impl From<StmtKind> for Stmt {
+    fn from(kind: StmtKind) -> Stmt {
+        kind.at(Span::default())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Local {
+    pub name: String,
+    pub ty: Ty,
+}
+
+#[derive(Debug, Clone)]
+pub struct Func {
+    pub name: String,
+    pub export: Option<String>,
+    /// les `params` premiers locals
+    pub params: u32,
+    pub result: Option<Ty>,
+    pub locals: Vec<Local>,
+    pub labels: Vec<String>,
+    pub body: Vec<Stmt>,
+    pub span: Span,
+}
+
+impl Func {
+    pub fn local_name(&self, l: LocalId) -> &str {
+        &self.locals[l.0 as usize].name
+    }
+    pub fn label_name(&self, l: Label) -> &str {
+        &self.labels[l.0 as usize]
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Import {
+    pub module: String,
+    pub field: String,
+    /// nom interne, comme ceux des fonctions
+    pub name: String,
+    pub params: Vec<Ty>,
+    pub result: Option<Ty>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Global {
+    pub name: String,
+    pub ty: Ty,
+    pub mutable: bool,
+    pub init: i32,
+    pub export: Option<String>,
+}
+
+/// Signature d'un appel par la table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sig {
+    pub params: Vec<Ty>,
+    pub result: Option<Ty>,
+}
+
+/// Segment de données actif, copié en mémoire à l'instanciation.
+#[derive(Debug, Clone)]
+pub struct Data {
+    pub offset: u32,
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Module {
+    pub imports: Vec<Import>,
+    /// taille de la mémoire en pages de 64 Kio
+    pub pages: u32,
+    pub memory_export: Option<String>,
+    pub globals: Vec<Global>,
+    pub data: Vec<Data>,
+    /// fonctions prises comme valeurs, rangées à partir de `TABLE_BASE`
+    pub table: Vec<FuncId>,
+    pub sigs: Vec<Sig>,
+    pub funcs: Vec<Func>,
+}
+
+impl Module {
+    pub fn func_name(&self, f: FuncId) -> &str {
+        let i = f.0 as usize;
+        match self.imports.get(i) {
+            Some(imp) => &imp.name,
+            None => &self.funcs[i - self.imports.len()].name,
+        }
+    }
+    pub fn global_name(&self, g: GlobalId) -> &str {
+        &self.globals[g.0 as usize].name
+    }
+}
+
+impl fmt::Display for Ty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Ty::I32 => write!(f, "i32"),
+        }
+    }
+}
+
+impl BinOp {
+    fn symbol(self) -> &'static str {
+        match self {
+            BinOp::Add => "+",
+            BinOp::Sub => "-",
+            BinOp::Mul => "*",
+            BinOp::DivS => "/s",
+            BinOp::DivU => "/u",
+            BinOp::RemS => "%s",
+            BinOp::RemU => "%u",
+            BinOp::Eq => "==",
+            BinOp::Ne => "!=",
+            BinOp::LtS => "<s",
+            BinOp::LtU => "<u",
+            BinOp::LeS => "<=s",
+            BinOp::LeU => "<=u",
+            BinOp::GtS => ">s",
+            BinOp::GeS => ">=s",
+            BinOp::GeU => ">=u",
+        }
+    }
+}
+
+/// Dump de `--emit ir` : locals préfixés de `%`, globales de `$`, une
+/// instruction par ligne, les blocs indentés et, en commentaire, la ligne
+/// gaufre d'où vient chaque instruction quand elle change.
+struct Dump<'m> {
+    module: &'m Module,
+    func: &'m Func,
+    sm: Option<&'m SourceMap>, // absent dans une expression `Seq`, dumpée sur une ligne
+    last: Option<(usize, usize)>,
+    out: String,
+}
+
+impl Dump<'_> {
+    fn line(&mut self, depth: usize, text: &str, span: Span) {
+        self.out.push_str(&"  ".repeat(depth));
+        self.out.push_str(text);
+        if let Some(sm) = self.sm
+            && span != Span::default()
+        {
+            let at = (span.file, sm.line_col(span).0);
+            if self.last != Some(at) {
+                self.last = Some(at);
+                let path = &sm.file(span.file).path;
+                let name = path.file_name().unwrap_or(path.as_os_str()).to_string_lossy();
+                self.out.push_str(&format!("  // {name}:{}", at.1));
+            }
+        }
+        self.out.push('\n');
+    }
+
+    fn call(&self, f: FuncId, args: &[Expr]) -> String {
+        let args: Vec<String> = args.iter().map(|a| self.expr(a)).collect();
+        format!("{}({})", self.module.func_name(f), args.join(", "))
+    }
+
+    fn call_indirect(&self, sig: SigId, callee: &Expr, args: &[Expr]) -> String {
+        let args: Vec<String> = args.iter().map(|a| self.expr(a)).collect();
+        format!("call_indirect[{}] {}({})", sig.0, self.expr(callee), args.join(", "))
+    }
+
+    fn expr(&self, e: &Expr) -> String {
+        match e {
+            Expr::Const(v) => v.to_string(),
+            Expr::Local(l) => format!("%{}", self.func.local_name(*l)),
+            Expr::Global(g) => format!("${}", self.module.global_name(*g)),
+            Expr::Unary(UnOp::Eqz, x) => format!("eqz {}", self.expr(x)),
+            Expr::Binary(op, l, r) => format!("({} {} {})", self.expr(l), op.symbol(), self.expr(r)),
+            Expr::Load8U(a) => format!("load8_u[{}]", self.expr(a)),
+            Expr::Call(f, args) => self.call(*f, args),
+            Expr::CallIndirect(sig, callee, args) => self.call_indirect(*sig, callee, args),
+            Expr::If(c, a, b) => format!("({} ? {} : {})", self.expr(c), self.expr(a), self.expr(b)),
+            Expr::Seq(ss, x) => {
+                let mut d = Dump { module: self.module, func: self.func, sm: None, last: None, out: String::new() };
+                d.stmts(0, ss);
+                let ss: Vec<&str> = d.out.lines().collect();
+                format!("{{ {}; {} }}", ss.join("; "), self.expr(x))
+            }
+        }
+    }
+
+    fn stmts(&mut self, depth: usize, ss: &[Stmt]) {
+        for s in ss {
+            self.stmt(depth, s);
+        }
+    }
+
+    fn nested(&mut self, depth: usize, head: &str, body: &[Stmt], span: Span) {
+        self.line(depth, &format!("{head} {{"), span);
+        self.stmts(depth + 1, body);
+        self.line(depth, "}", Span::default());
+    }
+
+    fn stmt(&mut self, depth: usize, s: &Stmt) {
+        let text = match &s.kind {
+            StmtKind::Set(l, e) => format!("%{} = {}", self.func.local_name(*l), self.expr(e)),
+            StmtKind::SetGlobal(g, e) => format!("${} = {}", self.module.global_name(*g), self.expr(e)),
+            StmtKind::Store8 { addr, value } => format!("store8[{}] = {}", self.expr(addr), self.expr(value)),
+            StmtKind::MemoryCopy { dst, src, len } => {
+                format!("memory.copy({}, {}, {})", self.expr(dst), self.expr(src), self.expr(len))
+            }
+            StmtKind::Call(f, args) => self.call(*f, args),
+            StmtKind::CallIndirect(sig, callee, args) => self.call_indirect(*sig, callee, args),
+            StmtKind::Drop(e) => format!("drop {}", self.expr(e)),
+            StmtKind::Return(None) => "return".to_string(),
+            StmtKind::Return(Some(e)) => format!("return {}", self.expr(e)),
+            StmtKind::Block(l, body) => {
+                let head = format!("block {}", self.func.label_name(*l));
+                return self.nested(depth, &head, body, s.span);
+            }
+            StmtKind::Loop(l, body) => {
+                let head = format!("loop {}", self.func.label_name(*l));
+                return self.nested(depth, &head, body, s.span);
+            }
+            StmtKind::Br(l) => format!("br {}", self.func.label_name(*l)),
+            StmtKind::BrIf(l, c) => format!("br_if {} {}", self.func.label_name(*l), self.expr(c)),
+            StmtKind::If(c, a, b) => {
+                let head = format!("if {}", self.expr(c));
+                self.nested(depth, &head, a, s.span);
+                if !b.is_empty() {
+                    self.nested(depth, "else", b, s.span);
+                }
+                return;
+            }
+            StmtKind::Unreachable => "unreachable".to_string(),
+            StmtKind::Wat(code) => {
+                self.line(depth, "wat {", s.span);
+                for l in code.lines().map(str::trim).filter(|l| !l.is_empty()) {
+                    self.line(depth + 1, l, Span::default());
+                }
+                self.line(depth, "}", Span::default());
+                return;
+            }
+        };
+        self.line(depth, &text, s.span);
+    }
+}
+
+fn types(ts: &[Ty]) -> String {
+    ts.iter().map(Ty::to_string).collect::<Vec<_>>().join(", ")
+}
+
+impl Module {
+    /// Texte de `--emit ir`.
+    pub fn dump(&self, sm: &SourceMap) -> String {
+        let export = |e: &Option<String>| e.as_ref().map(|n| format!(" export {n:?}")).unwrap_or_default();
+        let result = |r: Option<Ty>| r.map(|t| format!(" -> {t}")).unwrap_or_default();
+        let mut out = String::new();
+        for i in &self.imports {
+            let params = types(&i.params);
+            out.push_str(&format!("import {} = {}.{}({params}){}\n", i.name, i.module, i.field, result(i.result)));
+        }
+        out.push_str(&format!("memory {}{}\n", self.pages, export(&self.memory_export)));
+        for g in &self.globals {
+            let m = if g.mutable { "mut " } else { "" };
+            out.push_str(&format!("global ${}: {m}{} = {}{}\n", g.name, g.ty, g.init, export(&g.export)));
+        }
+        for d in &self.data {
+            out.push_str(&format!("data {} {:?}\n", d.offset, String::from_utf8_lossy(&d.bytes)));
+        }
+        if !self.table.is_empty() {
+            let funcs: Vec<&str> = self.table.iter().map(|&f| self.func_name(f)).collect();
+            out.push_str(&format!("table {TABLE_BASE} [{}]\n", funcs.join(", ")));
+        }
+        for (i, s) in self.sigs.iter().enumerate() {
+            out.push_str(&format!("sig {i} ({}){}\n", types(&s.params), result(s.result)));
+        }
+        for func in &self.funcs {
+            let (params, locals) = func.locals.split_at(func.params as usize);
+            let params: Vec<String> = params.iter().map(|l| format!("%{}: {}", l.name, l.ty)).collect();
+            let head = format!("fn {}({}){}{} {{", func.name, params.join(", "), result(func.result), export(&func.export));
+            let mut d = Dump { module: self, func, sm: Some(sm), last: None, out: String::from("\n") };
+            d.line(0, &head, func.span);
+            for l in locals {
+                d.line(1, &format!("local %{}: {}", l.name, l.ty), Span::default());
+            }
+            d.stmts(1, &func.body);
+            d.line(0, "}", Span::default());
+            out.push_str(&d.out);
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diag::SourceMap;
+    use crate::{codegen, lints, modules};
+    use std::collections::HashMap;
+
+    const SRC: &str = "global g: i32 = 2\nfn add(a: i32, b: i32) -> i32 {\n  return a + b * g\n}\nfn main() {\n  for i = 1 to 3 {\n    add(i, 1)\n  }\n}\n";
+
+    #[test]
+    fn dump() {
+        let path = std::env::temp_dir().join("gaufre-ir-dump.gfr");
+        std::fs::write(&path, SRC).unwrap();
+        let mut sm = SourceMap::default();
+        let prog = modules::load_program(&path, &mut sm, &HashMap::new(), "en", &lints::Config::default(), &mut Vec::new())
+            .unwrap_or_else(|d| panic!("{}", d.message));
+        let out = codegen::lower(&prog, false).unwrap().dump(&sm);
+        assert!(out.contains("\nglobal $g: mut i32 = 2\n"), "{out}");
+        // la ligne gaufre en commentaire, seulement quand elle change
+        let add = "fn add(%a: i32, %b: i32) -> i32 {  // gaufre-ir-dump.gfr:2\n  local %@pos: i32\n  return (%a + (%b * $g))  // gaufre-ir-dump.gfr:3\n}\n";
+        assert!(out.contains(add), "{out}");
+        let main = &out[out.find("fn main()").unwrap()..];
+        assert!(main.starts_with("fn main() export \"main\" {  // gaufre-ir-dump.gfr:5\n"), "{out}");
+        assert!(main.contains("\n  %i = 1  // gaufre-ir-dump.gfr:6\n  %@end0 = 3\n  block exit0 {\n    br_if exit0 (%i >s %@end0)\n    loop loop0 {\n"), "{out}");
+        assert!(main.contains("      drop add(%i, 1)  // gaufre-ir-dump.gfr:7\n      br_if exit0 ((%@end0 - %i) <u 1)  // gaufre-ir-dump.gfr:6\n"), "{out}");
+    }
+}
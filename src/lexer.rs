@@ -1,28 +1,37 @@
-use crate::grammar;
+use crate::codes::code;
+use crate::diag::Span;
+use crate::grammar::{self, Grammar};
+use crate::i18n::tr;
+use crate::inline_wat;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Token {
     // Mots-clés
-    Fn, Main, Log, For, To,
+    Fn, Main, Log, For, To, Downto, Until, Step, Const, Global, Export,
+    Return, Import, Use, Pub, Macro, Wat, If, Else,
     // Identifiants / littéraux
     Ident(String),
     Number(String),   // entier décimal
     Str(String),      // "…"
+    WatBody(String),  // texte entre les accolades qui suivent `wat`
     // Ponctuation / opérateurs
-    LParen, RParen, LBrace, RBrace, Comma,
-    Assign,
+    LParen, RParen, LBrace, RBrace, Comma, Colon, PathSep, Arrow, Bang, At,
+    Dollar, FatArrow, // motifs de macro : `$x:expr`, `(...) => { ... }`
+    Assign, Plus, Minus, Star, Slash, Percent,
+    EqEq, NotEq, Le, Ge, Lt, Gt, AndAnd, OrOr,
     // Fin
     Eof,
 }
 
 #[derive(Debug, Clone)]
 pub struct LexError {
+    pub code: &'static str,
     pub message: String,
-    pub at_byte: usize,
+    pub span: Span,
 }
 impl std::fmt::Display for LexError {
     fn fmt(&self, f:&mut std::fmt::Formatter<'_>)->std::fmt::Result {
-        write!(f, "{} (byte {})", self.message, self.at_byte)
+        write!(f, "{}", self.message)
     }
 }
 impl std::error::Error for LexError {}
@@ -31,21 +40,35 @@ pub struct Lexer<'a> {
     input: &'a str,
     bytes: &'a [u8],
     i: usize, // index byte courant
+    file: usize, // index dans le SourceMap, recopié dans chaque Span
+    raw_next: bool, // après `wat`, la prochaine `{` ouvre du WAT brut
+    grammar: &'a Grammar,
 }
 
 impl<'a> Lexer<'a> {
-    pub fn new(input: &'a str) -> Self {
-        Self { input, bytes: input.as_bytes(), i: 0 }
+    /// Lit `input` avec les lexèmes de `grammar`; une ligne `#grammar` en tête
+    /// est sautée (elle a servi à choisir `grammar`).
+    pub fn new(input: &'a str, file: usize, grammar: &'a Grammar) -> Self {
+        let i = grammar::pragma(input).map_or(0, |p| p.end);
+        Self { input, bytes: input.as_bytes(), i, file, raw_next: false, grammar }
+    }
+
+    pub fn grammar(&self) -> &'a Grammar {
+        self.grammar
     }
 
     fn eof(&self) -> bool { self.i >= self.bytes.len() }
     fn peek(&self) -> Option<u8> { self.bytes.get(self.i).copied() }
     fn bump(&mut self) -> Option<u8> { let b=self.peek()?; self.i += 1; Some(b) }
 
+    /// Blancs et commentaires `// ...` jusqu'à la fin de la ligne.
     fn skip_ws(&mut self) {
         while let Some(b) = self.peek() {
             match b {
                 b' ' | b'\t' | b'\n' | b'\r' => self.i += 1,
+                _ if self.starts_with(grammar::COMMENT) => {
+                    while self.peek().is_some_and(|b| b != b'\n') { self.i += 1; }
+                }
                 _ => break,
             }
         }
@@ -54,15 +77,12 @@ impl<'a> Lexer<'a> {
     fn starts_with(&self, s: &str) -> bool {
         self.input[self.i..].starts_with(s)
     }
-    fn try_take(&mut self, s: &str) -> bool {
-        if self.starts_with(s) { self.i += s.len(); true } else { false }
-    }
 
     fn is_ident_start(b: u8) -> bool {
-        (b'a'..=b'z').contains(&b) || (b'A'..=b'Z').contains(&b) || b == b'_'
+        b.is_ascii_alphabetic() || b == b'_'
     }
     fn is_ident_continue(b: u8) -> bool {
-        Self::is_ident_start(b) || (b'0'..=b'9').contains(&b)
+        Self::is_ident_start(b) || b.is_ascii_digit()
     }
 
     fn read_ident(&mut self) -> (&'a str, usize, usize) {
@@ -76,7 +96,7 @@ impl<'a> Lexer<'a> {
     fn read_number(&mut self) -> (&'a str, usize, usize) {
         let start = self.i;
         while let Some(b) = self.peek() {
-            if (b'0'..=b'9').contains(&b) { self.i += 1; } else { break; }
+            if b.is_ascii_digit() { self.i += 1; } else { break; }
         }
         (&self.input[start..self.i], start, self.i)
     }
@@ -93,70 +113,75 @@ impl<'a> Lexer<'a> {
             }
             self.i += 1;
         }
-        Err(LexError { message: "chaine non terminée".into(), at_byte: start })
+        Err(LexError { code: code!("G0001"), message: tr!("lex.unterminated_string"), span: Span::new(self.file, start, start + 1) })
     }
 
-    /// Essaie l'opérateur d'affectation paramétrable (ASSIGN_LEXEME).
-    fn try_assign(&mut self) -> Option<Token> {
-        if self.try_take(grammar::ASSIGN_LEXEME) {
-            Some(Token::Assign)
-        } else {
-            None
-        }
+    /// `{ ... }` après `wat` : le texte brut jusqu'à l'accolade fermante
+    /// (hors chaînes et commentaires WAT), vérifié par inline_wat::validate.
+    fn read_wat_body(&mut self) -> Result<Token, LexError> {
+        let start = self.i;
+        let inner = start + 1;
+        let Some(len) = inline_wat::closing_brace(&self.input[inner..]) else {
+            return Err(LexError { code: code!("G0002"), message: tr!("lex.unterminated_wat"), span: Span::new(self.file, start, start + 1) });
+        };
+        let text = &self.input[inner..inner + len];
+        inline_wat::validate(text).map_err(|e| LexError {
+            code: code!("G0004"),
+            message: tr!("lex.wat", e.message),
+            span: Span::new(self.file, inner + e.offset, inner + e.offset + e.len),
+        })?;
+        self.i = inner + len + 1;
+        self.raw_next = false;
+        Ok(Token::WatBody(text.to_string()))
     }
 
-    /// Essaie la ponctuation paramétrable (toutes en &str).
-    fn try_punct(&mut self) -> Option<Token> {
-        if self.try_take(grammar::LPAREN)  { return Some(Token::LParen) }
-        if self.try_take(grammar::RPAREN)  { return Some(Token::RParen) }
-        if self.try_take(grammar::LBRACE)  { return Some(Token::LBrace) }
-        if self.try_take(grammar::RBRACE)  { return Some(Token::RBrace) }
-        if self.try_take(grammar::COMMA)   { return Some(Token::Comma) }
-        None
+    /// Prochain token et sa position.
+    pub fn next_token(&mut self) -> Result<(Token, Span), LexError> {
+        self.skip_ws();
+        let start = self.i;
+        let tok = self.scan()?;
+        Ok((tok, Span::new(self.file, start, self.i)))
     }
 
-    pub fn next_token(&mut self) -> Result<Token, LexError> {
-        self.skip_ws();
+    fn scan(&mut self) -> Result<Token, LexError> {
         if self.eof() { return Ok(Token::Eof) }
 
-        // 1) opérateur d'affectation (supporte "=" ou ":=" selon grammar.rs)
-        if let Some(tok) = self.try_assign() {
-            return Ok(tok);
+        if self.raw_next && self.starts_with(grammar::LBRACE) {
+            return self.read_wat_body();
         }
 
-        // 2) ponctuation
-        if let Some(tok) = self.try_punct() {
-            return Ok(tok);
+        // 1) opérateurs et ponctuation, le plus long d'abord ("==" avant "=")
+        if let Some((tok, len)) = self.grammar.op_at(&self.input[self.i..]) {
+            self.i += len;
+            return Ok(tok.clone());
         }
 
-        // 3) littéral string
+        // 2) littéral string
         if self.peek() == Some(b'"') {
             return self.read_string();
         }
 
-        // 4) identifiant / mot-clé
+        // 3) identifiant / mot-clé
         if let Some(b) = self.peek() {
             if Self::is_ident_start(b) {
-                let (id, at, _) = self.read_ident();
-                return Ok(match id {
-                    x if x == grammar::KW_FN   => Token::Fn,
-                    x if x == grammar::KW_MAIN => Token::Main,
-                    x if x == grammar::KW_LOG  => Token::Log,
-                    x if x == grammar::KW_FOR  => Token::For,
-                    x if x == grammar::KW_TO   => Token::To,
-                    _ => Token::Ident(id.to_string()),
+                let (id, _, _) = self.read_ident();
+                return Ok(match self.grammar.word(id) {
+                    Some(Token::Wat) => { self.raw_next = true; Token::Wat }
+                    Some(tok) => tok.clone(),
+                    None => Token::Ident(id.to_string()),
                 });
             }
-            // 5) nombre décimal
-            if (b'0'..=b'9').contains(&b) {
+            // 4) nombre décimal
+            if b.is_ascii_digit() {
                 let (n, _, _) = self.read_number();
                 return Ok(Token::Number(n.to_string()));
             }
         }
 
         Err(LexError {
-            message: format!("caractère inattendu: 0x{:02X}", self.peek().unwrap()),
-            at_byte: self.i,
+            code: code!("G0003"),
+            message: tr!("lex.unexpected_char", format!("{:02X}", self.peek().unwrap())),
+            span: Span::new(self.file, self.i, self.i + 1),
         })
     }
 }
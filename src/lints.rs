@@ -0,0 +1,353 @@
+//! Avertissements sur du code valide mais suspect (lints).
+//!
+//! `check` parcourt un module tel qu'écrit (les deux branches d'un
+//! `if const` comprises), après `@cfg`; le code produit par une macro n'est
+//! pas signalé. `apply` fixe ensuite le niveau de chaque avertissement :
+//! `warn` par défaut, puis la ligne de commande (`-A`, `-W`, `--deny`), puis
+//! les `@!allow(...)`, `@!warn(...)`, `@!deny(...)` du fichier, le dernier
+//! l'emportant. `-W error` change en erreur tout ce qui reste en `warn`.
+
+use crate::codes::code;
+use crate::diag::{Diagnostic, Severity, Span};
+use crate::grammar;
+use crate::i18n::tr;
+use crate::inline_wat;
+use crate::modules::local_name;
+use crate::suggest;
+use crate::parser::{Attr, Expr, ExprKind, ForKind, Module, Stmt, StmtKind};
+
+/// Au-delà (en octets), une chaîne gonfle la section de données et le
+/// tampon des `log`.
+pub const LONG_STRING: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level { Allow, Warn, Deny }
+impl Level {
+    /// Nom de l'attribut (`allow`, `warn`, `deny`).
+    pub fn parse(s: &str) -> Option<Level> {
+        match s {
+            "allow" => Some(Level::Allow),
+            "warn" => Some(Level::Warn),
+            "deny" => Some(Level::Deny),
+            _ => None,
+        }
+    }
+}
+
+/// (nom, code du diagnostic)
+const LINTS: &[(&str, &str)] = &[
+    ("unused_loop_var", code!("G0901")),
+    ("empty_range", code!("G0902")),
+    ("unreachable", code!("G0903")),
+    ("shadowed", code!("G0904")),
+    ("long_string", code!("G0905")),
+    ("duplicate_log", code!("G0906")),
+    ("deprecated", code!("G0314")),
+];
+
+pub fn code(name: &str) -> Option<&'static str> {
+    LINTS.iter().find(|(n, _)| *n == name).map(|&(_, c)| c)
+}
+
+fn name_of(code: &str) -> Option<&'static str> {
+    LINTS.iter().find(|(_, c)| *c == code).map(|&(n, _)| n)
+}
+
+/// Le nom de lint le plus proche de `name` mal écrit.
+pub fn closest(name: &str) -> Option<&'static str> {
+    suggest::closest(name, LINTS.iter().map(|&(n, _)| n))
+}
+
+/// "`unused_loop_var`, `empty_range`, ..." pour les messages.
+pub fn names() -> String {
+    LINTS.iter().map(|(n, _)| format!("`{n}`")).collect::<Vec<_>>().join(", ")
+}
+
+/// Niveaux demandés sur la ligne de commande.
+#[derive(Debug, Default)]
+pub struct Config {
+    /// `-A nom`, `-W nom`, `--deny nom`, dans l'ordre
+    pub levels: Vec<(String, Level)>,
+    /// `-W error`
+    pub warnings_are_errors: bool,
+}
+
+/// Applique les niveaux aux diagnostics de lint de `diags` : retire ceux en
+/// `allow`, change en erreur ceux en `deny`. `files` donne les attributs
+/// `@!` de chaque fichier (par index dans le SourceMap).
+pub fn apply(diags: &mut Vec<Diagnostic>, config: &Config, files: &[(usize, &[Attr])]) {
+    diags.retain_mut(|d| {
+        let Some(name) = name_of(d.code) else { return true };
+        let mut level = Level::Warn;
+        let mut origin = None;
+        for (n, l) in &config.levels {
+            if n == name {
+                level = *l;
+                origin = None;
+            }
+        }
+        let attrs = d.span.and_then(|s| files.iter().find(|(f, _)| *f == s.file)).map_or(&[][..], |(_, a)| a);
+        for a in attrs {
+            if let Some(l) = Level::parse(&a.name).filter(|_| a.lint_args().any(|n| n == Some(name))) {
+                level = l;
+                origin = Some(a);
+            }
+        }
+        if level == Level::Warn && config.warnings_are_errors {
+            level = Level::Deny;
+        }
+        match level {
+            Level::Allow => false,
+            Level::Warn => true,
+            Level::Deny => {
+                d.severity = Severity::Error;
+                if let Some(a) = origin {
+                    d.add_note(a.span, tr!("note.lint_denied", name));
+                }
+                true
+            }
+        }
+    });
+}
+
+/// Avertissements d'un module, au niveau `warn`.
+pub fn check(m: &Module) -> Vec<Diagnostic> {
+    let mut cx = Checker { m, out: Vec::new() };
+    for f in &m.funcs {
+        // pas de position propre aux paramètres : celle du nom de la fonction
+        let mut scope: Vec<(String, Span, Shadowed)> = f.params.iter().map(|p| (p.name.clone(), f.span, Shadowed::Param)).collect();
+        cx.block(&f.body, &mut scope);
+    }
+    for c in &m.consts {
+        cx.expr(&c.value);
+    }
+    for g in &m.globals {
+        cx.expr(&g.init);
+    }
+    // dans une expansion, rien à corriger à l'endroit signalé
+    cx.out.retain(|d| d.span.is_none_or(|s| s.expn == 0));
+    cx.out
+}
+
+/// Ce qu'une variable de boucle peut masquer.
+#[derive(Clone, Copy)]
+enum Shadowed { Param, LoopVar, Const, Global }
+
+struct Checker<'a> {
+    m: &'a Module,
+    out: Vec<Diagnostic>,
+}
+
+impl<'a> Checker<'a> {
+    /// `scope` : locals visibles, nommés comme par `local_name`.
+    fn block(&mut self, ss: &'a [Stmt], scope: &mut Vec<(String, Span, Shadowed)>) {
+        let mut prev_log: Option<(Span, &[Expr])> = None;
+        for (i, st) in ss.iter().enumerate() {
+            match &st.kind {
+                StmtKind::Log(args) => {
+                    // même position : un argument de macro recopié deux fois
+                    if let Some((prev_span, prev)) = prev_log
+                        && prev_span != st.span
+                        && prev.len() == args.len()
+                        && prev.iter().zip(args).all(|(a, b)| same(a, b))
+                    {
+                        self.out.push(
+                            Diagnostic::new(code!("G0906"), tr!("lint.duplicate_log", grammar::KW_LOG), Some(st.span))
+                                .with_note(prev_span, tr!("note.previous_log")),
+                        );
+                    }
+                    args.iter().for_each(|a| self.expr(a));
+                }
+                StmtKind::Assign { value, .. } => self.expr(value),
+                StmtKind::For { name, name_span, start, end, step, kind, body } => {
+                    [start, end].into_iter().chain(step).for_each(|e| self.expr(e));
+                    self.range(start, end, *kind);
+                    self.shadowing(name, *name_span, scope);
+                    if !name.starts_with('_') && !uses(body, &local_name(name, *name_span)) {
+                        self.out.push(
+                            Diagnostic::new(code!("G0901"), tr!("lint.unused_loop_var", name), Some(*name_span))
+                                .with_fix(tr!("lint.prefix_underscore"), *name_span, format!("_{name}")),
+                        );
+                    }
+                    scope.push((local_name(name, *name_span), *name_span, Shadowed::LoopVar));
+                    self.block(body, scope);
+                    scope.pop();
+                }
+                StmtKind::Return(v) => {
+                    v.iter().for_each(|e| self.expr(e));
+                    if let Some(next) = ss.get(i + 1) {
+                        self.out.push(
+                            Diagnostic::new(code!("G0903"), tr!("lint.unreachable"), Some(next.span))
+                                .with_note(st.span, tr!("note.returns_here")),
+                        );
+                    }
+                }
+                StmtKind::Expr(e) => self.expr(e),
+                StmtKind::Wat(_) => {}
+                StmtKind::IfConst { cond, then, els } => {
+                    self.expr(cond);
+                    self.block(then, scope);
+                    self.block(els, scope);
+                }
+            }
+            prev_log = match &st.kind {
+                StmtKind::Log(args) => Some((st.span, args)),
+                _ => None,
+            };
+            if matches!(st.kind, StmtKind::Return(_)) {
+                break; // code inaccessible signalé une seule fois, en bloc
+            }
+        }
+    }
+
+    fn expr(&mut self, e: &Expr) {
+        match &e.kind {
+            ExprKind::Str(s) if s.len() > LONG_STRING => {
+                self.out.push(Diagnostic::new(code!("G0905"), tr!("lint.long_string", s.len(), LONG_STRING), Some(e.span)));
+            }
+            ExprKind::Str(_) | ExprKind::Var(_) | ExprKind::Int(_) => {}
+            ExprKind::Neg(x) | ExprKind::Not(x) => self.expr(x),
+            ExprKind::Bin(_, a, b) => {
+                self.expr(a);
+                self.expr(b);
+            }
+            ExprKind::Call { args, .. } => args.iter().for_each(|a| self.expr(a)),
+        }
+    }
+
+    /// `for i = 10 to 1` : bornes littérales qui ne laissent aucun tour.
+    fn range(&mut self, start: &Expr, end: &Expr, kind: ForKind) {
+        let (Some(a), Some(b)) = (literal(start), literal(end)) else { return };
+        let empty = match kind {
+            ForKind::To => a > b,
+            ForKind::Downto => a < b,
+            ForKind::Until => a >= b,
+        };
+        if empty {
+            self.out.push(Diagnostic::new(code!("G0902"), tr!("lint.empty_range", a, b), Some(start.span.to(end.span))));
+        }
+    }
+
+    fn shadowing(&mut self, name: &str, span: Span, scope: &[(String, Span, Shadowed)]) {
+        let local = local_name(name, span);
+        let outer = scope.iter().rev().find(|(n, _, _)| *n == local).map(|&(_, s, what)| (s, what))
+            .or_else(|| self.m.consts.iter().find(|c| c.name == name).map(|c| (c.span, Shadowed::Const)))
+            .or_else(|| self.m.globals.iter().find(|g| g.name == name).map(|g| (g.span, Shadowed::Global)));
+        let Some((at, what)) = outer else { return };
+        let what = match what {
+            Shadowed::Param => tr!("lint.shadowed.param"),
+            Shadowed::LoopVar => tr!("lint.shadowed.loop_var"),
+            Shadowed::Const => tr!("lint.shadowed.const"),
+            Shadowed::Global => tr!("lint.shadowed.global"),
+        };
+        self.out.push(
+            Diagnostic::new(code!("G0904"), tr!("lint.shadowed", name, what), Some(span))
+                .with_note(at, tr!("note.shadowed", name)),
+        );
+    }
+}
+
+/// `n` ou `-n`.
+fn literal(e: &Expr) -> Option<i64> {
+    match &e.kind {
+        ExprKind::Int(n) => Some(*n as i64),
+        ExprKind::Neg(x) => literal(x).map(|n| -n),
+        _ => None,
+    }
+}
+
+/// Le local `name` (d'après `local_name`) est-il lu dans `ss` (expressions
+/// ou `$name` d'un bloc `wat`) ? Une boucle interne du même nom le masque
+/// dans son corps.
+fn uses(ss: &[Stmt], name: &str) -> bool {
+    fn expr(e: &Expr, name: &str) -> bool {
+        match &e.kind {
+            ExprKind::Var(v) => local_name(v, e.span) == name,
+            ExprKind::Str(_) | ExprKind::Int(_) => false,
+            ExprKind::Neg(x) | ExprKind::Not(x) => expr(x, name),
+            ExprKind::Bin(_, a, b) => expr(a, name) || expr(b, name),
+            ExprKind::Call { args, .. } => args.iter().any(|a| expr(a, name)),
+        }
+    }
+    ss.iter().any(|st| match &st.kind {
+        StmtKind::Log(args) => args.iter().any(|a| expr(a, name)),
+        StmtKind::Assign { value, .. } => expr(value, name),
+        StmtKind::For { name: inner, name_span, start, end, step, body, .. } => {
+            [start, end].into_iter().chain(step).any(|e| expr(e, name))
+                || (local_name(inner, *name_span) != name && uses(body, name))
+        }
+        StmtKind::Return(v) => v.as_ref().is_some_and(|e| expr(e, name)),
+        StmtKind::Expr(e) => expr(e, name),
+        StmtKind::Wat(text) => {
+            let mut found = false;
+            inline_wat::map_ids(text, &mut |id| {
+                found |= id == name;
+                None
+            });
+            found
+        }
+        StmtKind::IfConst { cond, then, els } => expr(cond, name) || uses(then, name) || uses(els, name),
+    })
+}
+
+/// Même expression au texte près (positions ignorées).
+fn same(a: &Expr, b: &Expr) -> bool {
+    match (&a.kind, &b.kind) {
+        (ExprKind::Str(x), ExprKind::Str(y)) | (ExprKind::Var(x), ExprKind::Var(y)) => x == y,
+        (ExprKind::Int(x), ExprKind::Int(y)) => x == y,
+        (ExprKind::Neg(x), ExprKind::Neg(y)) | (ExprKind::Not(x), ExprKind::Not(y)) => same(x, y),
+        (ExprKind::Bin(o1, a1, b1), ExprKind::Bin(o2, a2, b2)) => o1 == o2 && same(a1, a2) && same(b1, b2),
+        (ExprKind::Call { name: n1, args: a1 }, ExprKind::Call { name: n2, args: a2 }) => {
+            n1 == n2 && a1.len() == a2.len() && a1.iter().zip(a2).all(|(x, y)| same(x, y))
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Config, Level};
+    use crate::diag::{Severity, SourceMap};
+    use crate::modules;
+    use std::collections::HashMap;
+
+    /// Un `for` vide (G0902) dont la variable n'est pas lue (G0901).
+    const SRC: &str = "fn main() {\n  for i = 3 to 1 {}\n}\n";
+
+    /// (code, erreur ?) des avertissements restants, triés.
+    fn lints(name: &str, src: &str, levels: &[(&str, Level)], werror: bool) -> Vec<(&'static str, bool)> {
+        let path = std::env::temp_dir().join(format!("gaufre-lints-{name}.gfr"));
+        std::fs::write(&path, src).unwrap();
+        let config = Config { levels: levels.iter().map(|&(n, l)| (n.to_string(), l)).collect(), warnings_are_errors: werror };
+        let mut sm = SourceMap::default();
+        let mut found = Vec::new();
+        modules::load_program(&path, &mut sm, &HashMap::new(), "en", &config, &mut found).unwrap_or_else(|d| panic!("{}", d.message));
+        let mut out: Vec<_> = found.iter().map(|d| (d.code, d.severity == Severity::Error)).collect();
+        out.sort();
+        out
+    }
+
+    #[test]
+    fn command_line_levels() {
+        assert_eq!(lints("default", SRC, &[], false), [("G0901", false), ("G0902", false)]);
+        assert_eq!(lints("allow", SRC, &[("unused_loop_var", Level::Allow)], false), [("G0902", false)]);
+        assert_eq!(lints("deny", SRC, &[("empty_range", Level::Deny)], false), [("G0901", false), ("G0902", true)]);
+        // le dernier niveau donné pour un lint l'emporte
+        let levels = [("empty_range", Level::Deny), ("empty_range", Level::Warn)];
+        assert_eq!(lints("last", SRC, &levels, false), [("G0901", false), ("G0902", false)]);
+        // `-W error` ne touche que ce qui reste en `warn`
+        assert_eq!(lints("werror", SRC, &[("unused_loop_var", Level::Allow)], true), [("G0902", true)]);
+    }
+
+    #[test]
+    fn file_attributes() {
+        // le fichier l'emporte sur la ligne de commande
+        let src = format!("@!allow(empty_range)\n@!deny(unused_loop_var)\n{SRC}");
+        assert_eq!(lints("file", &src, &[("empty_range", Level::Deny)], false), [("G0901", true)]);
+        let src = format!("@!warn(empty_range)\n{SRC}");
+        assert_eq!(lints("file-warn", &src, &[("empty_range", Level::Allow)], false), [("G0901", false), ("G0902", false)]);
+        // rien n'est relevé dans le code produit par une macro
+        let src = "macro empty() { for i = 3 to 1 {} }\nfn main() {\n  empty!()\n}\n";
+        assert_eq!(lints("macro", src, &[], false), []);
+    }
+}
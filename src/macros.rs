@@ -0,0 +1,559 @@
+//! Règles de macro : motifs sur des arbres de tokens et corps à recopier.
+//!
+//! `macro nom { (motif) => { corps } ... }` : la première règle dont le motif
+//! reconnaît tous les tokens de l'appel est recopiée. Un motif contient des
+//! tokens à retrouver tels quels, des variables `$x:fragment` et des
+//! répétitions `$( ... ) sep *` (ou `+`, au moins une fois). La forme simple
+//! `macro nom(a, b) { corps }` est une seule règle dont chaque paramètre
+//! prend les tokens jusqu'à la virgule suivante.
+
+use crate::diag::Span;
+use crate::i18n::tr;
+use crate::inline_wat;
+use crate::lexer::Token;
+use crate::parser::ParseError;
+use std::collections::HashMap;
+
+/// Ce que reconnaît `$x:fragment`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frag {
+    /// un identifiant
+    Ident,
+    /// un nombre, `-` et un nombre, ou une chaîne
+    Literal,
+    /// une expression entière; recopiée entre parenthèses
+    Expr,
+    /// `{ ... }`
+    Block,
+    /// un token, ou un groupe `( ... )` / `{ ... }` entier
+    Tt,
+    /// paramètre de la forme simple : tout jusqu'à la virgule suivante
+    Arg,
+}
+impl Frag {
+    pub const NAMES: &'static str = "`ident`, `literal`, `expr`, `block`, `tt`";
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "ident" => Some(Self::Ident),
+            "literal" => Some(Self::Literal),
+            "expr" => Some(Self::Expr),
+            "block" => Some(Self::Block),
+            "tt" => Some(Self::Tt),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Matcher {
+    Tok(Token),
+    /// `( ... )` ou `{ ... }` dont le contenu doit être reconnu en entier
+    Group(Token, Vec<Matcher>),
+    Var(String, Frag),
+    /// contenu, séparateur, au moins une fois (`+`)
+    Rep(Vec<Matcher>, Option<Token>, bool),
+}
+
+#[derive(Debug)]
+enum Piece {
+    Tok(Token, Span),
+    Var(String),
+    Rep(Vec<Piece>, Option<(Token, Span)>),
+}
+
+#[derive(Debug)]
+pub struct Rule {
+    pattern: Vec<Matcher>,
+    body: Vec<Piece>,
+    /// variables des `for` écrits dans le corps, renommées dans le WAT brut
+    binders: Vec<String>,
+}
+
+/// Tokens reconnus par une variable, ou par une répétition : un élément par tour.
+#[derive(Debug)]
+pub enum Bound {
+    One(Vec<(Token, Span)>, Frag),
+    Seq(Vec<Bound>),
+}
+
+pub type Bindings = HashMap<String, Bound>;
+
+/// Fin (exclue) de l'arbre de tokens qui commence en `i`.
+fn tree_end(toks: &[(Token, Span)], i: usize) -> usize {
+    if !matches!(toks[i].0, Token::LParen | Token::LBrace) {
+        return i + 1;
+    }
+    let mut depth = 0usize;
+    for (j, (t, _)) in toks.iter().enumerate().skip(i) {
+        match t {
+            Token::LParen | Token::LBrace => depth += 1,
+            Token::RParen | Token::RBrace => {
+                depth -= 1;
+                if depth == 0 {
+                    return j + 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    toks.len()
+}
+
+fn closing(open: &Token) -> Token {
+    if *open == Token::LParen { Token::RParen } else { Token::RBrace }
+}
+
+fn error(message: String, span: Span) -> ParseError {
+    ParseError::MacroPattern { message, span }
+}
+
+/// `$( ... )` vient d'être lu jusqu'à `*i` : séparateur éventuel puis `*` ou `+`.
+fn repetition(toks: &[(Token, Span)], i: &mut usize, at: Span) -> Result<(Option<(Token, Span)>, bool), ParseError> {
+    let op = |t: &Token| match t {
+        Token::Star => Some(false),
+        Token::Plus => Some(true),
+        _ => None,
+    };
+    if let Some(plus) = toks.get(*i).and_then(|(t, _)| op(t)) {
+        *i += 1;
+        return Ok((None, plus));
+    }
+    if let (Some(sep), Some(plus)) = (toks.get(*i), toks.get(*i + 1).and_then(|(t, _)| op(t))) {
+        *i += 2;
+        return Ok((Some(sep.clone()), plus));
+    }
+    Err(error(tr!("parse.macro.repetition_op"), at))
+}
+
+fn pattern(toks: &[(Token, Span)]) -> Result<Vec<Matcher>, ParseError> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < toks.len() {
+        let (tok, span) = &toks[i];
+        match tok {
+            Token::Dollar => match &toks.get(i + 1).map(|t| &t.0) {
+                Some(Token::Ident(name)) => {
+                    let frag = match (toks.get(i + 2), toks.get(i + 3)) {
+                        (Some((Token::Colon, _)), Some((Token::Ident(f), _))) => Frag::parse(f),
+                        _ => None,
+                    };
+                    let Some(frag) = frag else {
+                        return Err(error(tr!("parse.macro.fragment", name, Frag::NAMES), span.to(toks[i + 1].1)));
+                    };
+                    out.push(Matcher::Var(name.clone(), frag));
+                    i += 4;
+                }
+                Some(Token::LParen) => {
+                    let end = tree_end(toks, i + 1);
+                    let inner = pattern(&toks[i + 2..end - 1])?;
+                    i = end;
+                    let (sep, plus) = repetition(toks, &mut i, *span)?;
+                    out.push(Matcher::Rep(inner, sep.map(|s| s.0), plus));
+                }
+                _ => return Err(error(tr!("parse.macro.dollar"), *span)),
+            },
+            Token::LParen | Token::LBrace => {
+                let end = tree_end(toks, i);
+                out.push(Matcher::Group(tok.clone(), pattern(&toks[i + 1..end - 1])?));
+                i = end;
+            }
+            _ => {
+                out.push(Matcher::Tok(tok.clone()));
+                i += 1;
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn body(toks: &[(Token, Span)]) -> Result<Vec<Piece>, ParseError> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < toks.len() {
+        let (tok, span) = &toks[i];
+        match (tok, toks.get(i + 1).map(|t| &t.0)) {
+            (Token::Dollar, Some(Token::Ident(name))) => {
+                out.push(Piece::Var(name.clone()));
+                i += 2;
+            }
+            (Token::Dollar, Some(Token::LParen)) => {
+                let end = tree_end(toks, i + 1);
+                let inner = body(&toks[i + 2..end - 1])?;
+                i = end;
+                let (sep, _) = repetition(toks, &mut i, *span)?;
+                out.push(Piece::Rep(inner, sep));
+            }
+            (Token::Dollar, _) => return Err(error(tr!("parse.macro.dollar"), *span)),
+            _ => {
+                out.push(Piece::Tok(tok.clone(), *span));
+                i += 1;
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Variables du motif et leur profondeur de répétition.
+fn pattern_vars(pats: &[Matcher], depth: usize, vars: &mut HashMap<String, usize>, at: Span) -> Result<(), ParseError> {
+    for p in pats {
+        match p {
+            Matcher::Var(name, _) => {
+                if vars.insert(name.clone(), depth).is_some() {
+                    return Err(error(tr!("parse.macro.duplicate_var", name), at));
+                }
+            }
+            Matcher::Group(_, inner) => pattern_vars(inner, depth, vars, at)?,
+            Matcher::Rep(inner, ..) => pattern_vars(inner, depth + 1, vars, at)?,
+            Matcher::Tok(_) => {}
+        }
+    }
+    Ok(())
+}
+
+/// Chaque `$x` du corps est lié par le motif et n'est pas plus répété que
+/// lui; chaque `$( ... )` contient une variable répétée à ce niveau.
+fn check_body(pieces: &[Piece], depth: usize, vars: &HashMap<String, usize>, at: Span) -> Result<bool, ParseError> {
+    let mut repeats = false;
+    for p in pieces {
+        match p {
+            Piece::Var(name) => match vars.get(name) {
+                None => return Err(error(tr!("parse.macro.unbound", name), at)),
+                Some(&d) if d > depth => return Err(error(tr!("parse.macro.not_repeated", name), at)),
+                Some(&d) => repeats |= d == depth,
+            },
+            Piece::Rep(inner, _) => {
+                if !check_body(inner, depth + 1, vars, at)? {
+                    return Err(error(tr!("parse.macro.no_repeated_var"), at));
+                }
+                repeats = true;
+            }
+            Piece::Tok(..) => {}
+        }
+    }
+    Ok(repeats)
+}
+
+fn binders(pieces: &[Piece], out: &mut Vec<String>) {
+    let mut prev_for = false;
+    for p in pieces {
+        match p {
+            Piece::Tok(Token::Ident(v), _) if prev_for => out.push(v.clone()),
+            Piece::Rep(inner, _) => binders(inner, out),
+            _ => {}
+        }
+        prev_for = matches!(p, Piece::Tok(Token::For, _));
+    }
+}
+
+impl Rule {
+    /// `(motif) => { corps }`, `at` situant les erreurs de la règle.
+    pub fn new(pat: &[(Token, Span)], toks: &[(Token, Span)], at: Span) -> Result<Self, ParseError> {
+        let pattern = pattern(pat)?;
+        let body = body(toks)?;
+        let mut vars = HashMap::new();
+        pattern_vars(&pattern, 0, &mut vars, at)?;
+        check_body(&body, 0, &vars, at)?;
+        let mut names = Vec::new();
+        binders(&body, &mut names);
+        Ok(Rule { pattern, body, binders: names })
+    }
+
+    /// Forme simple : paramètres séparés par des virgules, désignés dans le
+    /// corps par leur seul nom.
+    pub fn simple(params: &[String], toks: &[(Token, Span)]) -> Self {
+        let mut pattern = Vec::new();
+        for (i, p) in params.iter().enumerate() {
+            if i > 0 {
+                pattern.push(Matcher::Tok(Token::Comma));
+            }
+            pattern.push(Matcher::Var(p.clone(), Frag::Arg));
+        }
+        let body: Vec<Piece> = toks
+            .iter()
+            .map(|(t, span)| match t {
+                Token::Ident(v) if params.contains(v) => Piece::Var(v.clone()),
+                _ => Piece::Tok(t.clone(), *span),
+            })
+            .collect();
+        let mut names = Vec::new();
+        binders(&body, &mut names);
+        Rule { pattern, body, binders: names }
+    }
+
+    /// Variables liées si le motif reconnaît exactement `args`.
+    pub fn matches(&self, args: &[(Token, Span)]) -> Option<Bindings> {
+        let mut binds = HashMap::new();
+        (match_seq(&self.pattern, args, &mut binds)? == args.len()).then_some(binds)
+    }
+
+    /// Corps de la règle, variables remplacées, pour l'expansion `expn`.
+    /// Une répétition dont les variables n'ont pas le même nombre de tours
+    /// échoue avec leurs noms.
+    pub fn expand(&self, binds: &Bindings, expn: usize) -> Result<Vec<(Token, Span)>, (String, String)> {
+        let env = binds.iter().map(|(k, v)| (k.as_str(), v)).collect();
+        let mut out = Vec::new();
+        self.transcribe(&self.body, &env, expn, &mut out)?;
+        Ok(out)
+    }
+
+    fn transcribe(
+        &self,
+        pieces: &[Piece],
+        env: &HashMap<&str, &Bound>,
+        expn: usize,
+        out: &mut Vec<(Token, Span)>,
+    ) -> Result<(), (String, String)> {
+        for p in pieces {
+            match p {
+                // les `$noms` du WAT brut : variables de boucle du corps
+                // renommées, variable du motif remplacée si elle vaut un
+                // simple identifiant
+                Piece::Tok(Token::WatBody(code), span) => {
+                    let code = inline_wat::map_ids(code, &mut |id| {
+                        if self.binders.iter().any(|b| b == id) {
+                            return Some(format!("{id}#{expn}"));
+                        }
+                        match env.get(id) {
+                            Some(Bound::One(toks, _)) => match toks.as_slice() {
+                                [(Token::Ident(a), _)] => Some(a.clone()),
+                                _ => None,
+                            },
+                            _ => None,
+                        }
+                    });
+                    out.push((Token::WatBody(code), Span { expn, ..*span }));
+                }
+                Piece::Tok(t, span) => out.push((t.clone(), Span { expn, ..*span })),
+                Piece::Var(name) => match env.get(name.as_str()) {
+                    // une expression de plusieurs tokens garde sa priorité
+                    Some(Bound::One(toks, Frag::Expr)) if toks.len() > 1 => {
+                        out.push((Token::LParen, toks[0].1));
+                        out.extend(toks.iter().cloned());
+                        out.push((Token::RParen, toks[toks.len() - 1].1));
+                    }
+                    Some(Bound::One(toks, _)) => out.extend(toks.iter().cloned()),
+                    // exclu par `check_body`
+                    Some(Bound::Seq(_)) | None => {}
+                },
+                Piece::Rep(inner, sep) => {
+                    let mut vars = Vec::new();
+                    piece_vars(inner, &mut vars);
+                    let seqs: Vec<(&str, &Vec<Bound>)> = vars
+                        .iter()
+                        .filter_map(|v| match env.get(v.as_str()) {
+                            Some(Bound::Seq(items)) => Some((v.as_str(), items)),
+                            _ => None,
+                        })
+                        .collect();
+                    let Some(&(first, items)) = seqs.first() else { continue };
+                    if let Some((other, _)) = seqs.iter().find(|(_, s)| s.len() != items.len()) {
+                        return Err((first.to_string(), other.to_string()));
+                    }
+                    for i in 0..items.len() {
+                        if let (true, Some((t, span))) = (i > 0, sep) {
+                            out.push((t.clone(), Span { expn, ..*span }));
+                        }
+                        let mut env = env.clone();
+                        for (name, items) in &seqs {
+                            env.insert(name, &items[i]);
+                        }
+                        self.transcribe(inner, &env, expn, out)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn piece_vars(pieces: &[Piece], out: &mut Vec<String>) {
+    for p in pieces {
+        match p {
+            Piece::Var(name) => out.push(name.clone()),
+            Piece::Rep(inner, _) => piece_vars(inner, out),
+            Piece::Tok(..) => {}
+        }
+    }
+}
+
+fn matcher_vars(pats: &[Matcher], out: &mut Vec<String>) {
+    for p in pats {
+        match p {
+            Matcher::Var(name, _) => out.push(name.clone()),
+            Matcher::Group(_, inner) | Matcher::Rep(inner, ..) => matcher_vars(inner, out),
+            Matcher::Tok(_) => {}
+        }
+    }
+}
+
+/// Opérateurs binaires d'une expression gaufre.
+fn binary_op(t: &Token) -> bool {
+    matches!(
+        t,
+        Token::Plus | Token::Minus | Token::Star | Token::Slash | Token::Percent
+            | Token::EqEq | Token::NotEq | Token::Le | Token::Ge | Token::Lt | Token::Gt
+            | Token::AndAnd | Token::OrOr
+    )
+}
+
+/// Longueur de l'opérande au début de `rest` : préfixes `!` et `-`, puis
+/// littéral, groupe parenthésé, chemin, appel ou appel de macro.
+fn operand_len(rest: &[(Token, Span)]) -> Option<usize> {
+    let mut n = 0;
+    while matches!(rest.get(n)?.0, Token::Bang | Token::Minus) {
+        n += 1;
+    }
+    match rest[n].0 {
+        Token::Number(_) | Token::Str(_) => Some(n + 1),
+        Token::LParen => Some(tree_end(rest, n)),
+        Token::Ident(_) => {
+            n += 1;
+            while let (Some((Token::PathSep, _)), Some((Token::Ident(_), _))) = (rest.get(n), rest.get(n + 1)) {
+                n += 2;
+            }
+            if matches!(rest.get(n), Some((Token::Bang, _))) && matches!(rest.get(n + 1), Some((Token::LParen, _))) {
+                n += 1;
+            }
+            Some(match rest.get(n) {
+                Some((Token::LParen, _)) => tree_end(rest, n),
+                _ => n,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Nombre de tokens de `rest` reconnus par `frag`.
+fn frag_len(frag: Frag, rest: &[(Token, Span)]) -> Option<usize> {
+    let first = &rest.first()?.0;
+    match frag {
+        Frag::Ident => matches!(first, Token::Ident(_)).then_some(1),
+        Frag::Literal => match (first, rest.get(1).map(|t| &t.0)) {
+            (Token::Number(_) | Token::Str(_), _) => Some(1),
+            (Token::Minus, Some(Token::Number(_))) => Some(2),
+            _ => None,
+        },
+        Frag::Block => (*first == Token::LBrace).then(|| tree_end(rest, 0)),
+        Frag::Tt => Some(tree_end(rest, 0)),
+        // opérandes séparés par des opérateurs binaires, le plus loin
+        // possible, sans retour en arrière
+        Frag::Expr => {
+            let mut n = operand_len(rest)?;
+            while rest.get(n).is_some_and(|t| binary_op(&t.0)) {
+                n += 1 + operand_len(&rest[n + 1..])?;
+            }
+            Some(n)
+        }
+        Frag::Arg => {
+            let mut n = 0;
+            while n < rest.len() && rest[n].0 != Token::Comma {
+                n = tree_end(rest, n);
+            }
+            Some(n)
+        }
+    }
+}
+
+/// Reconnaît `pats` au début de `toks`; nombre de tokens lus.
+fn match_seq(pats: &[Matcher], toks: &[(Token, Span)], binds: &mut Bindings) -> Option<usize> {
+    let mut i = 0;
+    for p in pats {
+        let rest = &toks[i..];
+        i += match p {
+            Matcher::Tok(t) => (rest.first()?.0 == *t).then_some(1)?,
+            Matcher::Group(open, inner) => {
+                if rest.first()?.0 != *open {
+                    return None;
+                }
+                let end = tree_end(rest, 0);
+                if rest[end - 1].0 != closing(open) || match_seq(inner, &rest[1..end - 1], binds)? != end - 2 {
+                    return None;
+                }
+                end
+            }
+            Matcher::Var(name, frag) => {
+                let n = frag_len(*frag, rest)?;
+                binds.insert(name.clone(), Bound::One(rest[..n].to_vec(), *frag));
+                n
+            }
+            Matcher::Rep(inner, sep, plus) => {
+                let mut rounds: Vec<Bindings> = Vec::new();
+                let mut n = 0;
+                loop {
+                    let mut at = n;
+                    if let (false, Some(sep)) = (rounds.is_empty(), sep) {
+                        if rest.get(at).map(|t| &t.0) != Some(sep) {
+                            break;
+                        }
+                        at += 1;
+                    }
+                    let mut round = HashMap::new();
+                    match match_seq(inner, &rest[at..], &mut round) {
+                        Some(k) if k > 0 => {
+                            n = at + k;
+                            rounds.push(round);
+                        }
+                        _ => break,
+                    }
+                }
+                if *plus && rounds.is_empty() {
+                    return None;
+                }
+                let mut names = Vec::new();
+                matcher_vars(inner, &mut names);
+                for name in names {
+                    let items = rounds.iter_mut().filter_map(|r| r.remove(&name)).collect();
+                    binds.insert(name, Bound::Seq(items));
+                }
+                n
+            }
+        };
+    }
+    Some(i)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::consteval::Ctx;
+    use crate::diag::{Diagnostic, SourceMap};
+    use crate::parser::Program;
+    use crate::{lints, modules};
+    use std::collections::HashMap;
+
+    /// Programme `src` chargé, macros expansées et noms résolus.
+    fn load(name: &str, src: &str) -> Result<Program, Diagnostic> {
+        let path = std::env::temp_dir().join(format!("gaufre-macros-{name}.gfr"));
+        std::fs::write(&path, src).unwrap();
+        let mut sm = SourceMap::default();
+        modules::load_program(&path, &mut sm, &HashMap::new(), "en", &lints::Config::default(), &mut Vec::new())
+    }
+
+    const RULES: &str = "macro sq {\n  ($x:expr) => { $x * $x }\n}\nmacro sum {\n  ($x:expr) => { $x }\n  ($x:expr, $($rest:expr),+) => { $x + sum!($($rest),+) }\n}\n";
+
+    #[test]
+    fn expansion_and_recursive_rules() {
+        let src = format!("{RULES}const A = sq!(1 + 2) * 2\nconst B = sum!(1, 2, 3, 4)\nfn main() {{}}\n");
+        let prog = load("values", &src).unwrap_or_else(|d| panic!("{}", d.message));
+        let values = Ctx::new(&prog.consts, &prog.funcs, &|_| false).eval_consts().unwrap_or_else(|(_, e)| panic!("{e}"));
+        assert_eq!((values["A"], values["B"]), (18, 10));
+    }
+
+    #[test]
+    fn hygiene_and_limits() {
+        // un ident passé en argument désigne la variable de l'appelant
+        let by_arg = "macro bump(v) { v = v + 1 }\nfn f(n: i32) {\n  bump!(n)\n  log(n)\n}\nfn main() {}\n";
+        load("by-arg", by_arg).unwrap_or_else(|d| panic!("{}", d.message));
+        // le corps ne voit pas les locaux de l'appelant
+        let captured = "macro bump() { n = n + 1 }\nfn f(n: i32) {\n  bump!()\n}\nfn main() {}\n";
+        let d = load("captured", captured).unwrap_err();
+        assert_eq!(d.code, "G0402");
+        // la note montre l'appel
+        let spans: Vec<&str> = d.notes.iter().map(|(s, _)| &captured[s.lo..s.hi]).collect();
+        assert_eq!(spans, ["bump!()"]);
+
+        let forever = "macro again {\n  ($x:expr) => { again!($x) }\n}\nfn main() {\n  log(again!(1))\n}\n";
+        assert_eq!(load("forever", forever).err().map(|d| d.code), Some("G0106"));
+        let src = format!("{RULES}fn main() {{\n  log(sum!())\n}}\n");
+        assert_eq!(load("no-match", &src).err().map(|d| d.code), Some("G0110"));
+    }
+}
@@ -1,26 +1,203 @@
 mod lexer;
 mod parser;
+mod macros;
 mod codegen;
 mod grammar;
+mod consteval;
+mod diag;
+mod modules;
+mod inline_wat;
+mod cli;
+mod attrs;
+mod translate;
+mod i18n;
+mod codes;
+mod lints;
+mod suggest;
+mod ir;
+mod wat;
+mod wasm;
+mod emit;
+mod asm;
+mod binary;
+mod validate;
+mod sha256;
+mod sourcemap;
 
-use lexer::Lexer;
-use parser::Parser;
-use std::{env, fs, path::Path};
+use cli::{Emit, ErrorFormat};
+use codes::code;
+use diag::{Diagnostic, Severity, SourceMap};
+use i18n::tr;
+use std::{env, fs, path::Path, process::ExitCode};
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let path = env::args().nth(1).expect("usage: gaufre <fichier.gfr> [out.wat]");
-    let out_path = env::args().nth(2);
+fn compile(opts: &cli::Options, sm: &mut SourceMap, warnings: &mut Vec<Diagnostic>) -> Result<ir::Module, Diagnostic> {
+    let prog = modules::load_program(Path::new(&opts.input), sm, &opts.defines, &opts.grammar, &opts.lints, warnings)?;
+    // un lint en `deny` arrête la compilation avant la génération
+    if let Some(i) = warnings.iter().rposition(|d| d.severity == Severity::Error) {
+        return Err(warnings.remove(i));
+    }
+    Ok(codegen::lower(&prog, opts.test)?)
+}
 
-    let src = fs::read_to_string(&path)?;
-    let lx = Lexer::new(&src);
-    let mut p = Parser::new(lx)?;
-    let ast = p.parse_program()?;
+/// Module WebAssembly du programme, validé si demandé et toujours dans les
+/// builds de débogage du compilateur.
+fn wasm_module(module: &ir::Module, validate: bool) -> Result<wasm::Module, Diagnostic> {
+    let w = emit::module(module)?;
+    if validate || cfg!(debug_assertions) {
+        validate::module(&w, validate)?;
+    }
+    Ok(w)
+}
 
-    let wat = codegen::generate_wat(&ast);
+/// Codes de sortie du processus (0 : succès, avertissements compris).
+#[derive(Clone, Copy)]
+enum Exit {
+    /// erreur dans le programme compilé ou traduit
+    Compile = 1,
+    /// ligne de commande invalide, code inconnu pour `explain`
+    Usage = 2,
+    /// fichier illisible ou impossible à écrire
+    Io = 3,
+}
+impl Exit {
+    fn of(d: &Diagnostic) -> Exit {
+        if d.code == code!("G0301") || d.code == code!("G0801") { Exit::Io } else { Exit::Compile }
+    }
+}
 
-    let default_out = Path::new(&path).with_extension("wat").to_string_lossy().into_owned();
-    let out = out_path.unwrap_or(default_out);
-    fs::write(&out, wat)?;
-    eprintln!("Écrit: {}", out);
+/// Affiche les diagnostics dans le format demandé; en texte, rappelle
+/// ensuite où trouver l'explication de leurs codes.
+fn report(diags: &[Diagnostic], sm: &SourceMap, format: ErrorFormat) {
+    if format == ErrorFormat::Json {
+        for d in diags {
+            eprintln!("{}", d.to_json(sm));
+        }
+        return;
+    }
+    let mut codes: Vec<&str> = Vec::new();
+    for d in diags {
+        eprint!("{}", d.render(sm));
+        if !codes.contains(&d.code) {
+            codes.push(d.code);
+        }
+    }
+    for c in codes {
+        eprintln!("{}", tr!("diag.explain", c));
+    }
+}
+
+/// Erreur d'usage : pas de code ni de position, suivie de l'aide.
+fn usage_error(msg: &str, format: ErrorFormat) -> Exit {
+    let rendered = format!("{}: {msg}\n{}", tr!("diag.error"), cli::usage());
+    match format {
+        ErrorFormat::Human => eprintln!("{rendered}"),
+        ErrorFormat::Json => eprintln!("{}", serde_json::json!({
+            "code": null,
+            "severity": "error",
+            "message": msg,
+            "spans": [],
+            "fixes": [],
+            "rendered": rendered,
+        })),
+    }
+    Exit::Usage
+}
+
+fn write_output(path: &str, contents: &[u8], format: ErrorFormat) -> Result<(), Diagnostic> {
+    fs::write(path, contents).map_err(|e| Diagnostic::new(code!("G0801"), tr!("io.write", path, e), None))?;
+    if format == ErrorFormat::Human {
+        eprintln!("{}", tr!("cli.written", path));
+    }
     Ok(())
 }
+
+/// Binaire de `w` dans `out`, et avec `source_map` sa source map dans
+/// `out.map`, que le module désigne par son nom de fichier.
+fn write_wasm(w: &wasm::Module, out: &str, source_map: bool, sm: &SourceMap, format: ErrorFormat) -> Result<(), Diagnostic> {
+    let map = format!("{out}.map");
+    let url = source_map.then(|| Path::new(&map).file_name().unwrap_or_default().to_string_lossy().into_owned());
+    let binary = binary::encode(w, sm.files(), url.as_deref())?;
+    write_output(out, &binary.bytes, format)?;
+    if source_map {
+        write_output(&map, sourcemap::build(&binary.offsets, sm).as_bytes(), format)?;
+    }
+    Ok(())
+}
+
+fn run(args: Vec<String>) -> Result<(), Exit> {
+    let format = cli::error_format(&args);
+    let opts = cli::parse(args).map_err(|msg| usage_error(&msg, format))?;
+
+    let mut sm = SourceMap::default();
+    let fail = |mut diags: Vec<Diagnostic>, d: Diagnostic, sm: &SourceMap| {
+        let exit = Exit::of(&d);
+        diags.push(d);
+        report(&diags, sm, format);
+        exit
+    };
+    match &opts.command {
+        cli::Command::Explain(code) => {
+            let e = codes::find(code).ok_or_else(|| usage_error(&tr!("explain.unknown", code), format))?;
+            print!("{}", e.render());
+        }
+        cli::Command::Translate { from, to } => {
+            let out = translate::translate_file(Path::new(&opts.input), from.as_deref(), to, &mut sm)
+                .map_err(|d| fail(Vec::new(), d, &sm))?;
+            match &opts.output {
+                Some(path) => write_output(path, out.as_bytes(), format).map_err(|d| fail(Vec::new(), d, &sm))?,
+                None => print!("{out}"),
+            }
+        }
+        cli::Command::Assemble | cli::Command::Validate => {
+            let w = asm::assemble_file(Path::new(&opts.input), &mut sm).map_err(|d| fail(Vec::new(), d, &sm))?;
+            validate::module(&w, true).map_err(|e| fail(Vec::new(), e.into_wat_file(), &sm))?;
+            if opts.command == cli::Command::Validate {
+                if format == ErrorFormat::Human {
+                    eprintln!("{}", tr!("cli.checked", opts.input));
+                }
+                return Ok(());
+            }
+            let default_out = Path::new(&opts.input).with_extension("wasm").to_string_lossy().into_owned();
+            let out = opts.output.clone().unwrap_or(default_out);
+            write_wasm(&w, &out, opts.source_map, &sm, format).map_err(|d| fail(Vec::new(), d, &sm))?;
+        }
+        cli::Command::Build | cli::Command::Check { .. } => {
+            let mut warnings = Vec::new();
+            let module = match compile(&opts, &mut sm, &mut warnings) {
+                Ok(module) => module,
+                Err(d) => return Err(fail(warnings, d, &sm)),
+            };
+            report(&warnings, &sm, format);
+            if let cli::Command::Check { validate } = opts.command {
+                wasm_module(&module, validate).map_err(|d| fail(Vec::new(), d, &sm))?;
+                if format == ErrorFormat::Human {
+                    eprintln!("{}", tr!("cli.checked", opts.input));
+                }
+                return Ok(());
+            }
+            let default_out = Path::new(&opts.input).with_extension(opts.emit.extension()).to_string_lossy().into_owned();
+            let out = opts.output.clone().unwrap_or(default_out);
+            let contents = match opts.emit {
+                Emit::Wat | Emit::Wasm => {
+                    let w = wasm_module(&module, false).map_err(|d| fail(Vec::new(), d, &sm))?;
+                    if opts.emit == Emit::Wasm {
+                        return write_wasm(&w, &out, opts.source_map, &sm, format).map_err(|d| fail(Vec::new(), d, &sm));
+                    }
+                    wat::print(&w).into_bytes()
+                }
+                Emit::Ir => module.dump(&sm).into_bytes(),
+            };
+            write_output(&out, &contents, format).map_err(|d| fail(Vec::new(), d, &sm))?;
+        }
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    i18n::set_lang(cli::lang(&args));
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(exit) => ExitCode::from(exit as u8),
+    }
+}
@@ -0,0 +1,649 @@
+//! Chargement des fichiers importés et résolution des noms entre modules.
+//!
+//! Chaque `import "x.gfr"` est lu relativement au fichier qui l'importe et
+//! devient le module `x`. Le résultat est un `Program` à plat où chaque item
+//! porte son nom complet (`util::helper`, sans préfixe pour le fichier
+//! principal); seules les variables locales gardent leur nom d'origine.
+
+use crate::attrs;
+use crate::consteval::{self, EvalError};
+use crate::codes::code;
+use crate::diag::{Diagnostic, SourceMap, Span};
+use crate::grammar::{self, Grammar};
+use crate::i18n::tr;
+use crate::inline_wat;
+use crate::lexer::{Lexer, Token};
+use crate::lints;
+use crate::suggest;
+use crate::parser::{Attr, ConstDecl, Expr, ExprKind, FnDecl, GlobalDecl, Module, Parser, Program, Stmt, StmtKind};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+struct Loaded {
+    prefix: String, // "" pour le fichier principal, sinon "util::", "util::math::"...
+    file: usize,
+    grammar: Grammar, // pour proposer un mot-clé à la place d'un nom inconnu
+    ast: Module,
+    children: HashMap<String, usize>, // nom d'import -> module
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ItemKind { Const, Global, Fn }
+
+#[derive(Clone, Copy)]
+struct Item<'a> { kind: ItemKind, public: bool, span: Span, deprecated: Option<&'a Attr> }
+
+/// Ce qu'un nom (ou un `use`) désigne.
+#[derive(Clone)]
+enum Target {
+    Item(usize, String), // module, nom local
+    Module(usize),
+}
+
+struct Loader<'s> {
+    sm: &'s mut SourceMap,
+    defines: &'s HashMap<String, i32>,
+    grammar: Grammar, // pour les fichiers sans `#grammar`
+    profiles: HashMap<PathBuf, Grammar>, // fichiers `.gram` déjà lus
+    modules: Vec<Loaded>,
+    by_path: HashMap<PathBuf, usize>,
+    stack: Vec<(PathBuf, String)>, // imports en cours (chemin canonique, chemin affiché)
+}
+
+impl Loader<'_> {
+    fn load(&mut self, path: &Path, prefix: String, from: Option<Span>) -> Result<usize, Diagnostic> {
+        let shown = path.display().to_string();
+        let canon = fs::canonicalize(path)
+            .map_err(|e| Diagnostic::new(code!("G0301"), tr!("io.read", shown, e), from))?;
+        if let Some(pos) = self.stack.iter().position(|(p, _)| *p == canon) {
+            let mut chain: Vec<&str> = self.stack[pos..].iter().map(|(_, s)| s.as_str()).collect();
+            chain.push(&shown);
+            return Err(Diagnostic::new(code!("G0302"), tr!("module.circular_import", chain.join(" -> ")), from));
+        }
+        if let Some(&id) = self.by_path.get(&canon) {
+            return Ok(id);
+        }
+
+        let src = fs::read_to_string(path)
+            .map_err(|e| Diagnostic::new(code!("G0301"), tr!("io.read", shown, e), from))?;
+        let pragma = grammar::pragma(&src).map(|p| (p.arg.to_string(), p.lo));
+        let file = self.sm.add(path, src);
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let g = match pragma {
+            Some((arg, lo)) => self.grammar(&arg, dir, Some(Span::new(file, lo, lo + arg.len())))?,
+            None => self.grammar.clone(),
+        };
+        let (ast, expansions) = {
+            let mut p = Parser::new(Lexer::new(&self.sm.file(file).src, file, &g))?;
+            (p.parse_module(), std::mem::take(&mut p.expansions))
+        };
+        self.sm.set_expansions(file, expansions);
+        let mut ast = ast?;
+        strip_cfg(&mut ast, self.defines)?;
+        let imports = ast.imports.clone();
+
+        let id = self.modules.len();
+        self.modules.push(Loaded { prefix: prefix.clone(), file, grammar: g, ast, children: HashMap::new() });
+        self.by_path.insert(canon.clone(), id);
+        self.stack.push((canon, shown));
+
+        for imp in &imports {
+            let child_path = dir.join(&imp.path);
+            let name = child_path.file_stem().and_then(|s| s.to_str()).unwrap_or("").to_string();
+            if !is_ident(&name) {
+                return Err(Diagnostic::new(code!("G0303"), tr!("module.bad_name", name), Some(imp.span)));
+            }
+            if self.modules[id].children.contains_key(&name) {
+                return Err(Diagnostic::new(code!("G0304"), tr!("module.imported_twice", name), Some(imp.span)));
+            }
+            let child = self.load(&child_path, format!("{prefix}{name}{}", grammar::PATH_SEP), Some(imp.span))?;
+            self.modules[id].children.insert(name, child);
+        }
+
+        self.stack.pop();
+        Ok(id)
+    }
+
+    /// Profil `spec` : un nom intégré, sinon un fichier `.gram` relatif à `dir`.
+    fn grammar(&mut self, spec: &str, dir: &Path, from: Option<Span>) -> Result<Grammar, Diagnostic> {
+        load_grammar(self.sm, &mut self.profiles, spec, dir, from)
+    }
+}
+
+/// Profil `spec` : un nom intégré, sinon un fichier `.gram` relatif à `dir`
+/// (lu une seule fois grâce à `profiles`).
+pub fn load_grammar(
+    sm: &mut SourceMap,
+    profiles: &mut HashMap<PathBuf, Grammar>,
+    spec: &str,
+    dir: &Path,
+    from: Option<Span>,
+) -> Result<Grammar, Diagnostic> {
+    if let Some(g) = Grammar::builtin(spec) {
+        return Ok(g);
+    }
+    let path = dir.join(spec);
+    let Ok(canon) = fs::canonicalize(&path) else {
+        return Err(Diagnostic::new(code!("G0601"), tr!("grammar.unknown_profile", spec, grammar::BUILTIN_NAMES), from));
+    };
+    if let Some(g) = profiles.get(&canon) {
+        return Ok(g.clone());
+    }
+    let text = fs::read_to_string(&path)
+        .map_err(|e| Diagnostic::new(code!("G0301"), tr!("io.read", path.display(), e), from))?;
+    let file = sm.add(&path, text);
+    let g = Grammar::parse_profile(&sm.file(file).src, file)?;
+    profiles.insert(canon, g.clone());
+    Ok(g)
+}
+
+fn is_ident(s: &str) -> bool {
+    let mut cs = s.chars();
+    cs.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && cs.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Lit `path` et tous ses imports, puis résout les noms et passe les lints
+/// sur chaque fichier. Les avertissements sont ajoutés à `warnings` (même si
+/// la résolution échoue ensuite), au niveau fixé par `lints` et les `@!`
+/// des fichiers : certains peuvent être devenus des erreurs.
+pub fn load_program(
+    path: &Path,
+    sm: &mut SourceMap,
+    defines: &HashMap<String, i32>,
+    grammar: &str,
+    lints: &lints::Config,
+    warnings: &mut Vec<Diagnostic>,
+) -> Result<Program, Diagnostic> {
+    let mut profiles = HashMap::new();
+    let grammar = load_grammar(sm, &mut profiles, grammar, Path::new(""), None)?;
+    let mut loader = Loader {
+        sm,
+        defines,
+        grammar,
+        profiles,
+        modules: Vec::new(),
+        by_path: HashMap::new(),
+        stack: Vec::new(),
+    };
+    loader.load(path, String::new(), None)?;
+    let r = Resolver::new(&loader.modules, loader.sm, defines)?;
+    let prog = r.run();
+    let mut found = r.warnings.take();
+    if prog.is_ok() {
+        found.extend(loader.modules.iter().flat_map(|m| lints::check(&m.ast)));
+    }
+    let files: Vec<(usize, &[Attr])> = loader.modules.iter().map(|m| (m.file, &m.ast.attrs[..])).collect();
+    lints::apply(&mut found, lints, &files);
+    warnings.extend(found);
+    prog
+}
+
+/// Valeur d'une condition de compilation (`if const`, `@cfg`) : seuls les
+/// `-D` y sont visibles, un nom absent vaut 0.
+fn cond_true(defines: &HashMap<String, i32>, cond: &Expr, what: &str) -> Result<bool, Diagnostic> {
+    let v = consteval::eval(cond, &mut |n: &str| Ok(defines.get(n).copied().unwrap_or(0))).map_err(|e| {
+        let msg = match e {
+            EvalError::NotConst(n) => tr!("module.not_evaluable", n),
+            e => e.to_string(),
+        };
+        Diagnostic::new(code!("G0320"), tr!("module.condition", what, msg), Some(cond.span))
+    })?;
+    Ok(v != 0)
+}
+
+fn cfg_enabled(defines: &HashMap<String, i32>, attrs: &[Attr]) -> Result<bool, Diagnostic> {
+    match attrs::find(attrs, "cfg") {
+        Some(a) => cond_true(defines, &a.args[0], "@cfg"),
+        None => Ok(true),
+    }
+}
+
+/// Retire les items et instructions dont le `@cfg(...)` est faux : ils
+/// n'existent pas pour la suite (noms, génération).
+fn strip_cfg(m: &mut Module, defines: &HashMap<String, i32>) -> Result<(), Diagnostic> {
+    fn retain<T>(items: &mut Vec<T>, defines: &HashMap<String, i32>, attrs: fn(&T) -> &[Attr]) -> Result<(), Diagnostic> {
+        let mut keep = Vec::new();
+        for it in std::mem::take(items) {
+            if cfg_enabled(defines, attrs(&it))? {
+                keep.push(it);
+            }
+        }
+        *items = keep;
+        Ok(())
+    }
+    fn stmts(ss: &mut Vec<Stmt>, defines: &HashMap<String, i32>) -> Result<(), Diagnostic> {
+        retain(ss, defines, |s| &s.attrs)?;
+        for st in ss {
+            match &mut st.kind {
+                StmtKind::For { body, .. } => stmts(body, defines)?,
+                StmtKind::IfConst { then, els, .. } => {
+                    stmts(then, defines)?;
+                    stmts(els, defines)?;
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+    retain(&mut m.consts, defines, |c| &c.attrs)?;
+    retain(&mut m.globals, defines, |g| &g.attrs)?;
+    retain(&mut m.funcs, defines, |f| &f.attrs)?;
+    for f in &mut m.funcs {
+        stmts(&mut f.body, defines)?;
+    }
+    Ok(())
+}
+
+/// Nom d'un local déclaré ou lu à `span` : suffixé `#N` quand le nom vient
+/// du corps de l'expansion de macro N. Deux contextes ne partagent ainsi
+/// jamais un local (hygiène); les items restent visibles de partout.
+pub fn local_name(name: &str, span: Span) -> String {
+    match span.expn {
+        0 => name.to_string(),
+        n => format!("{name}#{n}"),
+    }
+}
+
+struct Resolver<'a> {
+    modules: &'a [Loaded],
+    sm: &'a SourceMap, // pour situer un appel de macro ou un nom à corriger
+    defines: &'a HashMap<String, i32>, // `-D NOM=valeur`, lus par `if const`
+    items: Vec<HashMap<&'a str, Item<'a>>>,
+    warnings: RefCell<Vec<Diagnostic>>,
+    uses: Vec<HashMap<&'a str, Target>>,
+}
+
+impl<'a> Resolver<'a> {
+    fn new(modules: &'a [Loaded], sm: &'a SourceMap, defines: &'a HashMap<String, i32>) -> Result<Self, Diagnostic> {
+        let mut items = Vec::new();
+        for (id, m) in modules.iter().enumerate() {
+            let mut table: HashMap<&str, Item> = HashMap::new();
+            let decls = m.ast.consts.iter().map(|c| (&c.name, ItemKind::Const, c.public, &c.attrs, c.span))
+                .chain(m.ast.globals.iter().map(|g| (&g.name, ItemKind::Global, g.public, &g.attrs, g.span)))
+                .chain(m.ast.funcs.iter().map(|f| (&f.name, ItemKind::Fn, f.public, &f.attrs, f.span)));
+            for (name, kind, public, attrs, span) in decls {
+                if let Some(first) = table.get(name.as_str()) {
+                    return Err(Diagnostic::new(code!("G0305"), tr!("module.duplicate", name), Some(span))
+                        .with_note(first.span, tr!("note.first_definition")));
+                }
+                table.insert(name, Item { kind, public, span, deprecated: attrs::find(attrs, "deprecated") });
+            }
+            check_main(id, m)?;
+            items.push(table);
+        }
+
+        let mut r = Resolver {
+            modules,
+            sm,
+            defines,
+            items,
+            warnings: RefCell::new(Vec::new()),
+            uses: vec![HashMap::new(); modules.len()],
+        };
+        for (id, m) in modules.iter().enumerate() {
+            for u in &m.ast.uses {
+                let target = r.resolve_path(id, &u.path, u.span).map_err(|d| r.suggest(d, id, &u.path, u.span, &[], None))?;
+                let alias = u.path.last().unwrap().as_str();
+                if r.items[id].contains_key(alias) || r.uses[id].contains_key(alias) {
+                    return Err(Diagnostic::new(code!("G0306"), tr!("module.use_conflict", alias), Some(u.span)));
+                }
+                r.uses[id].insert(alias, target);
+            }
+        }
+        Ok(r)
+    }
+
+    fn module_name(&self, id: usize) -> String {
+        match self.modules[id].prefix.strip_suffix(grammar::PATH_SEP) {
+            Some(p) => p.to_string(),
+            None => grammar::KW_MAIN.to_string(),
+        }
+    }
+
+    /// `a::b::c` vu depuis le module `from`.
+    fn resolve_path(&self, from: usize, path: &[String], span: Span) -> Result<Target, Diagnostic> {
+        let (first, rest) = path.split_first().unwrap();
+        if rest.is_empty() {
+            if self.items[from].contains_key(first.as_str()) {
+                return Ok(Target::Item(from, first.clone()));
+            }
+            if let Some(t) = self.uses[from].get(first.as_str()) {
+                return Ok(t.clone());
+            }
+            if let Some(&m) = self.modules[from].children.get(first) {
+                return Ok(Target::Module(m));
+            }
+            return Err(Diagnostic::new(code!("G0402"), tr!("eval.unknown", first), Some(span)));
+        }
+
+        let mut m = match (self.modules[from].children.get(first), self.uses[from].get(first.as_str())) {
+            (Some(&m), _) | (None, Some(&Target::Module(m))) => m,
+            _ => return Err(Diagnostic::new(code!("G0307"), tr!("module.unknown_module", first), Some(span))),
+        };
+        let (last, middle) = rest.split_last().unwrap();
+        for seg in middle {
+            m = *self.modules[m].children.get(seg).ok_or_else(|| {
+                Diagnostic::new(code!("G0308"), tr!("module.no_submodule", seg, self.module_name(m)), Some(span))
+            })?;
+        }
+        if let Some(item) = self.items[m].get(last.as_str()) {
+            if !item.public {
+                return Err(Diagnostic::new(code!("G0309"), tr!("module.private", last, self.module_name(m)), Some(span))
+                    .with_note(item.span, tr!("note.private")));
+            }
+            return Ok(Target::Item(m, last.clone()));
+        }
+        if let Some(&child) = self.modules[m].children.get(last) {
+            return Ok(Target::Module(child));
+        }
+        Err(Diagnostic::new(code!("G0310"), tr!("module.missing_item", last, self.module_name(m)), Some(span)))
+    }
+
+    /// Nom complet d'un item désigné depuis `from`, avec vérification de sa nature.
+    /// Un nom mal écrit : ajoute à `d` la correction vers le nom connu le
+    /// plus proche, parmi ceux de la bonne nature visibles à cet endroit
+    /// (`scope` : locals, comme pour `stmts`; `want` : None pour un `use`).
+    fn suggest(&self, d: Diagnostic, from: usize, path: &[String], span: Span, scope: &[(String, bool)], want: Option<ItemKind>) -> Diagnostic {
+        let fits = |it: &Item| match want {
+            None => true,
+            Some(ItemKind::Fn) => it.kind == ItemKind::Fn,
+            Some(ItemKind::Global) => it.kind == ItemKind::Global,
+            Some(ItemKind::Const) => it.kind != ItemKind::Fn,
+        };
+        let fits_target = |t: &Target| match t {
+            Target::Item(m, local) => fits(&self.items[*m][local.as_str()]),
+            Target::Module(_) => want.is_none(),
+        };
+        // (segment à remplacer, candidats)
+        let (seg, candidates): (usize, Vec<&str>) = if d.code == code!("G0402") {
+            let locals = scope.iter()
+                .filter(|(_, is_loop)| match want {
+                    Some(ItemKind::Fn) => false,
+                    Some(ItemKind::Global) => !is_loop, // une variable de boucle ne s'affecte pas
+                    _ => true,
+                })
+                .map(|(n, _)| n.as_str())
+                // ceux d'une expansion de macro ne s'écrivent pas
+                .filter(|n| !n.contains('#'));
+            let items = self.items[from].iter().filter(|(_, it)| fits(it)).map(|(n, _)| *n);
+            let uses = self.uses[from].iter().filter(|(_, t)| fits_target(t)).map(|(n, _)| *n);
+            let children = self.modules[from].children.keys().filter(|_| want.is_none()).map(String::as_str);
+            (0, locals.chain(items).chain(uses).chain(children).collect())
+        } else if d.code == code!("G0307") {
+            let children = self.modules[from].children.keys().map(String::as_str);
+            let uses = self.uses[from].iter().filter(|(_, t)| matches!(t, Target::Module(_))).map(|(n, _)| *n);
+            (0, children.chain(uses).collect())
+        } else if d.code == code!("G0310") {
+            let Ok(Target::Module(m)) = self.resolve_path(from, &path[..path.len() - 1], span) else { return d };
+            let items = self.items[m].iter().filter(|(_, it)| it.public && fits(it)).map(|(n, _)| *n);
+            let children = self.modules[m].children.keys().filter(|_| want.is_none()).map(String::as_str);
+            (path.len() - 1, items.chain(children).collect())
+        } else {
+            return d;
+        };
+        let Some(best) = suggest::closest(&path[seg], candidates) else { return d };
+        let mut fixed = path.to_vec();
+        fixed[seg] = best.to_string();
+        self.with_fix(d, span, &path.join(grammar::PATH_SEP), &fixed.join(grammar::PATH_SEP))
+    }
+
+    /// Propose `replacement` à la place de `name`, écrit au début de `span`
+    /// (sinon, par exemple `a :: b` espacé, pas de correction).
+    fn with_fix(&self, d: Diagnostic, span: Span, name: &str, replacement: &str) -> Diagnostic {
+        let at = Span { hi: span.lo + name.len(), ..span };
+        match self.sm.file(span.file).src.get(at.lo..at.hi) {
+            Some(text) if text == name => d.with_fix(tr!("suggest.did_you_mean", replacement), at, replacement),
+            _ => d,
+        }
+    }
+
+    /// `lgo("x")` : appel d'une fonction inconnue au nom proche de `log`.
+    fn suggest_log(&self, d: Diagnostic, from: usize, e: &Expr) -> Diagnostic {
+        let ExprKind::Call { name, .. } = &e.kind else { return d };
+        if d.code != code!("G0402") || d.span != Some(e.span) || !d.fixes.is_empty() {
+            return d;
+        }
+        let log = self.modules[from].grammar.spell(&Token::Log);
+        match suggest::closest(name, log) {
+            Some(kw) => self.with_fix(d, e.span, name, kw),
+            None => d,
+        }
+    }
+
+    fn resolve_item(&self, from: usize, name: &str, want: ItemKind, span: Span, scope: &[(String, bool)]) -> Result<(String, ItemKind), Diagnostic> {
+        let path: Vec<String> = name.split(grammar::PATH_SEP).map(str::to_string).collect();
+        let target = self.resolve_path(from, &path, span).map_err(|d| self.suggest(d, from, &path, span, scope, Some(want)))?;
+        let Target::Item(m, local) = target else {
+            return Err(Diagnostic::new(code!("G0311"), tr!("module.is_module", name), Some(span)));
+        };
+        let item = self.items[m][local.as_str()];
+        match (want, item.kind) {
+            // une globale s'appelle si elle est de type `fn(...)`, une fonction
+            // se lit comme valeur : codegen vérifie les types
+            (ItemKind::Fn, ItemKind::Fn | ItemKind::Global) | (ItemKind::Const, _) | (ItemKind::Global, ItemKind::Const | ItemKind::Global) => {}
+            (ItemKind::Fn, ItemKind::Const) => return Err(Diagnostic::new(code!("G0312"), tr!("module.not_fn", name), Some(span))),
+            (ItemKind::Global, ItemKind::Fn) => return Err(Diagnostic::new(code!("G0313"), tr!("module.assign_fn", name), Some(span))),
+        }
+        if let Some(attr) = item.deprecated {
+            let msg = match attr.str_arg() {
+                Some(why) => tr!("module.deprecated_why", name, why),
+                None => tr!("module.deprecated", name),
+            };
+            self.warnings.borrow_mut().push(Diagnostic::new(code!("G0314"), msg, Some(span)).with_note(attr.span, tr!("note.deprecated")));
+        }
+        Ok((format!("{}{local}", self.modules[m].prefix), item.kind))
+    }
+
+    /// Un nom écrit dans une macro dont l'appelant a un local de ce nom :
+    /// note sur l'appel, le local n'étant pas visible depuis le corps.
+    fn hygiene_note(&self, d: Diagnostic, name: &str, span: Span, scope: &[(String, bool)]) -> Diagnostic {
+        let caller = scope.iter().any(|(v, _)| v.split('#').next() == Some(name));
+        match self.sm.expansion(span) {
+            Some(e) if caller && d.code == code!("G0402") => d.with_note(e.call, tr!("note.macro_hygiene", name)),
+            _ => d,
+        }
+    }
+
+    fn expr(&self, from: usize, e: &Expr, scope: &[(String, bool)]) -> Result<Expr, Diagnostic> {
+        let kind = match &e.kind {
+            ExprKind::Var(n) if scope.iter().any(|(v, _)| *v == local_name(n, e.span)) => ExprKind::Var(local_name(n, e.span)),
+            ExprKind::Var(n) => ExprKind::Var(
+                self.resolve_item(from, n, ItemKind::Const, e.span, scope).map_err(|d| self.hygiene_note(d, n, e.span, scope))?.0,
+            ),
+            ExprKind::Call { name, args } => ExprKind::Call {
+                name: match scope.iter().any(|(v, _)| *v == local_name(name, e.span)) {
+                    true => local_name(name, e.span),
+                    false => self.resolve_item(from, name, ItemKind::Fn, e.span, scope)?.0,
+                },
+                args: args.iter().map(|a| self.expr(from, a, scope)).collect::<Result<_, _>>()?,
+            },
+            ExprKind::Neg(x) => ExprKind::Neg(Box::new(self.expr(from, x, scope)?)),
+            ExprKind::Not(x) => ExprKind::Not(Box::new(self.expr(from, x, scope)?)),
+            ExprKind::Bin(op, l, r) => {
+                ExprKind::Bin(*op, Box::new(self.expr(from, l, scope)?), Box::new(self.expr(from, r, scope)?))
+            }
+            ExprKind::Str(_) | ExprKind::Int(_) => e.kind.clone(),
+        };
+        Ok(Expr { kind, span: e.span })
+    }
+
+    /// `scope`: locals visibles (nom d'après `local_name`, est une variable
+    /// de boucle).
+    fn stmts(&self, from: usize, ss: &[Stmt], scope: &mut Vec<(String, bool)>) -> Result<Vec<Stmt>, Diagnostic> {
+        let mut out = Vec::new();
+        for st in ss {
+            let kind = match &st.kind {
+                StmtKind::Log(args) => {
+                    StmtKind::Log(args.iter().map(|a| self.expr(from, a, scope)).collect::<Result<_, _>>()?)
+                }
+                StmtKind::Assign { name, value } => {
+                    let local = local_name(name, st.span);
+                    let target = match scope.iter().rev().find(|(v, _)| *v == local) {
+                        Some((_, true)) => {
+                            return Err(Diagnostic::new(code!("G0315"), tr!("module.assign_loop_var", name), Some(st.span)));
+                        }
+                        Some((_, false)) => local,
+                        None => {
+                            let (full, kind) = self
+                                .resolve_item(from, name, ItemKind::Global, st.span, scope)
+                                .map_err(|d| self.hygiene_note(d, name, st.span, scope))?;
+                            if kind == ItemKind::Const {
+                                return Err(Diagnostic::new(code!("G0316"), tr!("module.assign_const", name), Some(st.span)));
+                            }
+                            full
+                        }
+                    };
+                    StmtKind::Assign { name: target, value: self.expr(from, value, scope)? }
+                }
+                StmtKind::For { name, name_span, start, end, step, kind, body } => {
+                    // bornes et pas sont évalués avant que la variable existe
+                    let start = self.expr(from, start, scope)?;
+                    let end = self.expr(from, end, scope)?;
+                    let step = step.as_ref().map(|s| self.expr(from, s, scope)).transpose()?;
+                    let name = local_name(name, *name_span);
+                    scope.push((name.clone(), true));
+                    let body = self.stmts(from, body, scope)?;
+                    scope.pop();
+                    StmtKind::For { name, name_span: *name_span, start, end, step, kind: *kind, body }
+                }
+                // seule la branche choisie est résolue et gardée : l'autre peut
+                // nommer des items absents de cette compilation
+                StmtKind::IfConst { cond, then, els } => {
+                    let branch = if cond_true(self.defines, cond, "if const")? { then } else { els };
+                    out.extend(self.stmts(from, branch, scope)?);
+                    continue;
+                }
+                StmtKind::Return(v) => StmtKind::Return(v.as_ref().map(|e| self.expr(from, e, scope)).transpose()?),
+                StmtKind::Expr(e) => StmtKind::Expr(self.expr(from, e, scope).map_err(|d| self.suggest_log(d, from, e))?),
+                // `$nom` d'un item -> nom complet; les locals sont laissés à codegen
+                StmtKind::Wat(code) => StmtKind::Wat(inline_wat::map_ids(code, &mut |id| {
+                    if scope.iter().any(|(v, _)| *v == id) {
+                        return None;
+                    }
+                    let path: Vec<String> = id.split(grammar::PATH_SEP).map(str::to_string).collect();
+                    match self.resolve_path(from, &path, st.span) {
+                        Ok(Target::Item(m, local)) => Some(format!("{}{local}", self.modules[m].prefix)),
+                        _ => None,
+                    }
+                })),
+            };
+            out.push(Stmt { kind, attrs: st.attrs.clone(), span: st.span });
+        }
+        Ok(out)
+    }
+
+    fn run(&self) -> Result<Program, Diagnostic> {
+        let mut prog = Program::default();
+        for (id, m) in self.modules.iter().enumerate() {
+            let full = |n: &str| format!("{}{n}", m.prefix);
+            for c in &m.ast.consts {
+                prog.consts.push(ConstDecl { name: full(&c.name), value: self.expr(id, &c.value, &[])?, ..c.clone() });
+            }
+            for g in &m.ast.globals {
+                prog.globals.push(GlobalDecl { name: full(&g.name), init: self.expr(id, &g.init, &[])?, ..g.clone() });
+            }
+            for f in &m.ast.funcs {
+                let mut scope: Vec<(String, bool)> = f.params.iter().map(|p| (p.name.clone(), false)).collect();
+                let body = self.stmts(id, &f.body, &mut scope)?;
+                prog.funcs.push(FnDecl { name: full(&f.name), body, ..f.clone() });
+            }
+        }
+        Ok(prog)
+    }
+}
+
+/// `main` : obligatoire et sans signature dans le fichier principal, interdit ailleurs.
+fn check_main(id: usize, m: &Loaded) -> Result<(), Diagnostic> {
+    let main = m.ast.funcs.iter().find(|f| f.name == grammar::KW_MAIN);
+    match (id, main) {
+        (0, None) => Err(Diagnostic::new(code!("G0317"), tr!("module.no_main", grammar::KW_MAIN), Some(Span::new(m.file, 0, 0)))),
+        (0, Some(f)) if !f.params.is_empty() || f.ret => {
+            Err(Diagnostic::new(code!("G0318"), tr!("module.main_signature", grammar::KW_MAIN), Some(f.span)))
+        }
+        (0, Some(_)) => Ok(()),
+        (_, Some(f)) => Err(Diagnostic::new(code!("G0319"), tr!("module.main_elsewhere", grammar::KW_MAIN), Some(f.span))),
+        (_, None) => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::load_program;
+    use crate::diag::{Diagnostic, SourceMap};
+    use crate::lints;
+    use crate::parser::Program;
+    use std::collections::HashMap;
+
+    /// Programme de `files` (nom, texte), le premier étant le fichier
+    /// principal, écrits dans un dossier temporaire propre à `name`.
+    fn load(name: &str, files: &[(&str, &str)], defines: &[(&str, i32)]) -> Result<Program, Diagnostic> {
+        let dir = std::env::temp_dir().join(format!("gaufre-modules-{name}"));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (file, src) in files {
+            std::fs::write(dir.join(file), src).unwrap();
+        }
+        let defines = defines.iter().map(|&(n, v)| (n.to_string(), v)).collect::<HashMap<_, _>>();
+        let mut sm = SourceMap::default();
+        load_program(&dir.join(files[0].0), &mut sm, &defines, "en", &lints::Config::default(), &mut Vec::new())
+    }
+
+    fn code(name: &str, files: &[(&str, &str)]) -> Option<&'static str> {
+        load(name, files, &[]).err().map(|d| d.code)
+    }
+
+    const MAIN: &str = "import \"util.gfr\"\nuse util::twice\n\nfn main() {\n  log(twice(2), util::LIMIT)\n}\n";
+
+    #[test]
+    fn public_items_across_modules() {
+        let util = "pub fn twice(x: i32) -> i32 {\n  return helper(x)\n}\nfn helper(x: i32) -> i32 {\n  return x * 2\n}\npub const LIMIT = 10\n";
+        let prog = load("pub", &[("main.gfr", MAIN), ("util.gfr", util)], &[]).unwrap_or_else(|d| panic!("{}", d.message));
+        let mut names: Vec<&str> = prog.funcs.iter().map(|f| f.name.as_str()).collect();
+        names.sort();
+        assert_eq!(names, ["main", "util::helper", "util::twice"]);
+        assert_eq!(prog.consts[0].name, "util::LIMIT");
+    }
+
+    #[test]
+    fn private_items_and_cycles() {
+        let private = "fn twice(x: i32) -> i32 {\n  return x * 2\n}\npub const LIMIT = 10\n";
+        assert_eq!(code("private", &[("main.gfr", MAIN), ("util.gfr", private)]), Some("G0309"));
+        let cycle = "import \"other.gfr\"\npub fn twice(x: i32) -> i32 {\n  return x\n}\npub const LIMIT = 1\n";
+        let other = "import \"util.gfr\"\n";
+        assert_eq!(code("cycle", &[("main.gfr", MAIN), ("util.gfr", cycle), ("other.gfr", other)]), Some("G0302"));
+        let main_elsewhere = "pub fn twice(x: i32) -> i32 {\n  return x\n}\npub const LIMIT = 1\nfn main() {}\n";
+        assert_eq!(code("main_elsewhere", &[("main.gfr", MAIN), ("util.gfr", main_elsewhere)]), Some("G0319"));
+    }
+
+    const CFG: &str = "fn main() {\n  if const DEBUG {\n    trace()\n  } else if const LEVEL >= 2 && !QUIET {\n    details()\n  } else {\n    missing()\n  }\n  @cfg(DEBUG) trace()\n}\n@cfg(DEBUG)\nfn trace() {}\nfn details() {}\n";
+
+    /// Fonctions appelées par `main` et fonctions gardées, selon les `-D`.
+    fn cfg(name: &str, defines: &[(&str, i32)]) -> Result<(Vec<String>, usize), Diagnostic> {
+        use crate::parser::{ExprKind, StmtKind};
+        let prog = load(name, &[("main.gfr", CFG)], defines)?;
+        let main = prog.funcs.iter().find(|f| f.name == "main").unwrap();
+        let calls = main.body.iter().filter_map(|s| match &s.kind {
+            StmtKind::Expr(e) => match &e.kind {
+                ExprKind::Call { name, .. } => Some(name.clone()),
+                _ => None,
+            },
+            _ => None,
+        });
+        Ok((calls.collect(), prog.funcs.len()))
+    }
+
+    #[test]
+    fn conditional_compilation() {
+        assert_eq!(cfg("debug", &[("DEBUG", 1)]).unwrap(), (vec!["trace".to_string(), "trace".to_string()], 3));
+        assert_eq!(cfg("level", &[("LEVEL", 3)]).unwrap(), (vec!["details".to_string()], 2));
+        // `missing` n'existe pas : la branche gardée est résolue
+        assert_eq!(cfg("quiet", &[("LEVEL", 3), ("QUIET", 1)]).map_err(|d| d.code).unwrap_err(), "G0402");
+        // les `const` du programme ne sont pas visibles dans la condition
+        let src = "const ON = 1\nfn main() {\n  if const ON {\n    missing()\n  }\n}\n";
+        assert!(load("const-cond", &[("main.gfr", src)], &[]).is_ok());
+        let src = "fn main() {\n  if const 10 / LEVEL > 1 {\n    log(1)\n  }\n}\n";
+        assert_eq!(load("div-cond", &[("main.gfr", src)], &[]).err().map(|d| d.code), Some("G0320"));
+    }
+}
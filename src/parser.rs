@@ -1,142 +1,1067 @@
+use crate::attrs;
+use crate::codes::code;
+use crate::diag::{Diagnostic, Expansion, Span};
 use crate::grammar;
+use crate::i18n::tr;
 use crate::lexer::{Lexer, Token, LexError};
+use crate::macros::Rule;
+use crate::suggest;
+use std::collections::{HashMap, VecDeque};
 use std::fmt;
 
+/// Profondeur maximale d'expansions imbriquées (macro qui appelle une macro).
+const MACRO_DEPTH_LIMIT: usize = 64;
+
+/// Un fichier .gfr tel qu'écrit, avant résolution des imports.
+#[derive(Debug, Clone, Default)]
+pub struct Module {
+    pub attrs: Vec<Attr>, // `@!allow(...)` en tête du fichier
+    pub imports: Vec<Import>,
+    pub uses: Vec<Use>,
+    pub consts: Vec<ConstDecl>,
+    pub globals: Vec<GlobalDecl>,
+    pub funcs: Vec<FnDecl>,
+}
+
+/// Programme complet après résolution des modules : tous les noms d'items
+/// sont uniques (préfixés par leur module, ex. `util::helper`).
+#[derive(Debug, Clone, Default)]
+pub struct Program {
+    pub consts: Vec<ConstDecl>,
+    pub globals: Vec<GlobalDecl>,
+    pub funcs: Vec<FnDecl>,
+}
+
+/// `import "util.gfr"` : le module prend le nom du fichier (`util`).
+#[derive(Debug, Clone)]
+pub struct Import { pub path: String, pub span: Span }
+
+/// `use util::helper` : rend `helper` visible sans préfixe.
+#[derive(Debug, Clone)]
+pub struct Use { pub path: Vec<String>, pub span: Span }
+
+/// `@nom` ou `@nom(args)` devant un item ou une instruction (voir attrs.rs).
+#[derive(Debug, Clone)]
+pub struct Attr { pub name: String, pub args: Vec<Expr>, pub span: Span, pub name_span: Span }
+
+/// `const NAME = expr` : évalué à la compilation et recopié à chaque usage.
 #[derive(Debug, Clone)]
-pub struct Program { pub stmts: Vec<Stmt> }
+pub struct ConstDecl { pub name: String, pub value: Expr, pub public: bool, pub attrs: Vec<Attr>, pub span: Span }
 
+/// `[export] global name: type = expr` : un `global` WASM mutable.
 #[derive(Debug, Clone)]
-pub enum Stmt {
+pub struct GlobalDecl { pub name: String, pub ty: Type, pub init: Expr, pub export: bool, pub public: bool, pub attrs: Vec<Attr>, pub span: Span }
+
+/// Type d'un paramètre ou d'une globale; tous deux sont des i32 en WASM.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Type {
+    I32,
+    Fn(FnType), // index de la fonction dans la table
+}
+
+/// `fn(i32, ...) [-> i32]` : signature d'une valeur fonction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FnType { pub params: Vec<Type>, pub ret: bool }
+
+impl Type {
+    pub fn as_fn(&self) -> Option<&FnType> {
+        match self {
+            Self::Fn(t) => Some(t),
+            Self::I32 => None,
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::I32 => write!(f, "{}", grammar::TY_I32),
+            Self::Fn(t) => write!(f, "{t}"),
+        }
+    }
+}
+impl fmt::Display for FnType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let params: Vec<String> = self.params.iter().map(Type::to_string).collect();
+        write!(f, "{}({})", grammar::KW_FN, params.join(", "))?;
+        if self.ret {
+            write!(f, " {} {}", grammar::ARROW, grammar::TY_I32)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Param { pub name: String, pub ty: Type }
+
+/// `fn name(a: type, ...) [-> i32] { ... }`; pour `wat fn`, le corps est un
+/// unique `StmtKind::Wat`.
+#[derive(Debug, Clone)]
+pub struct FnDecl {
+    pub name: String,
+    pub params: Vec<Param>,
+    pub ret: bool,           // `-> i32`
+    pub konst: bool,         // `const fn` : exécutable à la compilation
+    pub body: Vec<Stmt>,
+    pub public: bool,
+    pub attrs: Vec<Attr>,
+    pub span: Span,          // le nom
+}
+
+impl FnDecl {
+    /// Type de la fonction prise comme valeur.
+    pub fn ty(&self) -> FnType {
+        FnType { params: self.params.iter().map(|p| p.ty.clone()).collect(), ret: self.ret }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Stmt { pub kind: StmtKind, pub attrs: Vec<Attr>, pub span: Span }
+
+#[derive(Debug, Clone)]
+pub enum StmtKind {
     Log(Vec<Expr>),
-    For { name: String, start: i32, end: i32, body: Vec<Stmt> },
+    Assign { name: String, value: Expr },
+    For {
+        name: String,
+        name_span: Span,
+        start: Expr,
+        end: Expr,
+        step: Option<Expr>, // None = pas de 1
+        kind: ForKind,
+        body: Vec<Stmt>,
+    },
+    Return(Option<Expr>),
+    Expr(Expr), // appel dont le résultat éventuel est ignoré
+    Wat(String), // `wat { ... }`, déjà validé par le lexer
+    /// `if const cond { ... } else { ... }` : une seule branche survit à la
+    /// résolution des noms, choisie d'après les `-D` de la ligne de commande.
+    IfConst { cond: Expr, then: Vec<Stmt>, els: Vec<Stmt> },
+}
+
+/// Sens et inclusion de la borne d'un `for`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForKind {
+    To,     // croissant, borne incluse
+    Downto, // décroissant, borne incluse
+    Until,  // croissant, borne exclue
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    Add, Sub, Mul, Div, Rem,
+    Eq, Ne, Lt, Le, Gt, Ge, // 1 ou 0, comparaison signée
+    And, Or,                // court-circuit, 1 ou 0
 }
 
 #[derive(Debug, Clone)]
-pub enum Expr { Str(String), Var(String), Int(i32) }
+pub struct Expr { pub kind: ExprKind, pub span: Span }
+
+#[derive(Debug, Clone)]
+pub enum ExprKind {
+    Str(String),
+    Var(String), // éventuellement un chemin `a::b`
+    Int(i32),
+    Neg(Box<Expr>),
+    Not(Box<Expr>), // `!x` : 1 si x == 0, sinon 0
+    Bin(BinOp, Box<Expr>, Box<Expr>),
+    Call { name: String, args: Vec<Expr> },
+}
 
 #[derive(Debug, Clone)]
 pub enum ParseError {
     Lex(LexError),
-    Unexpected { found: Token, expected: &'static str },
-    IntOverflow(String),
+    /// `found` et `expected` déjà rendus dans la grammaire du fichier;
+    /// `suggestion` : mot-clé proche du mot trouvé, à mettre à sa place
+    Unexpected { found: String, expected: String, span: Span, suggestion: Option<String> },
+    IntOverflow(String, Span),
+    UnknownMacro { name: String, span: Span, suggestion: Option<String> },
+    DuplicateMacro { name: String, span: Span, first: Span },
+    DuplicateParam { name: String, span: Span, first: Span },
+    MacroArity { name: String, expected: usize, found: usize, span: Span, def: Span },
+    /// aucune règle ne reconnaît l'appel
+    MacroNoMatch { name: String, span: Span, def: Span },
+    /// les variables `vars` d'une même répétition n'ont pas le même nombre
+    /// de tours
+    MacroRepeat { name: String, vars: Box<(String, String)>, span: Span, def: Span },
+    /// règle mal écrite : motif, `$x` inconnu du motif...
+    MacroPattern { message: String, span: Span },
+    MacroRecursion { name: String, span: Span },
+    /// code, message, position, et le nom proche à mettre à la place du
+    /// texte de cette position
+    Attr(&'static str, String, Span, Option<String>),
 }
 impl From<LexError> for ParseError { fn from(e:LexError)->Self { Self::Lex(e) } }
 impl fmt::Display for ParseError {
     fn fmt(&self,f:&mut fmt::Formatter<'_>)->fmt::Result{
         match self{
-            Self::Lex(e)=>write!(f,"Lexer: {e}"),
-            Self::Unexpected{found,expected}=>write!(f,"Attendu {expected}, trouvé {:?}",found),
-            Self::IntOverflow(s)=>write!(f,"Entier hors plage i32: {s}"),
+            Self::Lex(e)=>write!(f,"{}",tr!("parse.lex",e)),
+            Self::Unexpected{found,expected,..}=>write!(f,"{}",tr!("parse.unexpected",expected,found)),
+            Self::IntOverflow(s,_)=>write!(f,"{}",tr!("parse.int_overflow",s)),
+            Self::UnknownMacro{name,..}=>write!(f,"{}",tr!("parse.unknown_macro",name)),
+            Self::DuplicateMacro{name,..}=>write!(f,"{}",tr!("parse.duplicate_macro",name)),
+            Self::DuplicateParam{name,..}=>write!(f,"{}",tr!("parse.duplicate_param",name)),
+            Self::MacroArity{name,expected,found,..}=>write!(f,"{}",tr!("parse.macro_arity",name,expected,found)),
+            Self::MacroNoMatch{name,..}=>write!(f,"{}",tr!("parse.macro_no_match",name)),
+            Self::MacroRepeat{name,vars,..}=>write!(f,"{}",tr!("parse.macro_repeat",name,vars.0,vars.1)),
+            Self::MacroPattern{message,..}=>write!(f,"{message}"),
+            Self::MacroRecursion{name,..}=>write!(f,"{}",tr!("parse.macro_recursion",name,MACRO_DEPTH_LIMIT)),
+            Self::Attr(_,m,..)=>write!(f,"{m}"),
         }
     }
 }
 impl std::error::Error for ParseError {}
+impl From<ParseError> for Diagnostic {
+    fn from(e: ParseError) -> Self {
+        let d = Diagnostic::new(e.code(), e.to_string(), Some(e.span()));
+        match e {
+            ParseError::DuplicateMacro { first, .. } | ParseError::DuplicateParam { first, .. } => {
+                d.with_note(first, tr!("note.first_definition"))
+            }
+            ParseError::MacroArity { def, .. } | ParseError::MacroNoMatch { def, .. } | ParseError::MacroRepeat { def, .. } => {
+                d.with_note(def, tr!("note.macro_defined"))
+            }
+            ParseError::Unexpected { span, suggestion: Some(s), .. } | ParseError::Attr(_, _, span, Some(s)) => {
+                d.with_fix(tr!("suggest.did_you_mean", s), span, s)
+            }
+            // le nom seul, sans `!(...)`
+            ParseError::UnknownMacro { name, span, suggestion: Some(s) } => {
+                d.with_fix(tr!("suggest.did_you_mean", format!("{s}!")), Span { hi: span.lo + name.len(), ..span }, s)
+            }
+            _ => d,
+        }
+    }
+}
+impl ParseError {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Lex(e) => e.span,
+            Self::Unexpected { span, .. } | Self::IntOverflow(_, span) | Self::UnknownMacro { span, .. } => *span,
+            Self::Attr(_, _, span, _) => *span,
+            Self::DuplicateMacro { span, .. } | Self::DuplicateParam { span, .. } => *span,
+            Self::MacroArity { span, .. } | Self::MacroRecursion { span, .. } => *span,
+            Self::MacroNoMatch { span, .. } | Self::MacroRepeat { span, .. } | Self::MacroPattern { span, .. } => *span,
+        }
+    }
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Lex(e) => e.code,
+            Self::Unexpected { .. } => code!("G0101"),
+            Self::IntOverflow(..) => code!("G0102"),
+            Self::UnknownMacro { .. } => code!("G0103"),
+            Self::DuplicateMacro { .. } => code!("G0104"),
+            Self::DuplicateParam { .. } => code!("G0321"),
+            Self::MacroArity { .. } => code!("G0105"),
+            Self::MacroRecursion { .. } => code!("G0106"),
+            Self::MacroNoMatch { .. } | Self::MacroRepeat { .. } => code!("G0110"),
+            Self::MacroPattern { .. } => code!("G0111"),
+            Self::Attr(code, ..) => code,
+        }
+    }
+}
+
+/// Mots-clés qui commencent une instruction.
+const STMT_KEYWORDS: [Token; 5] = [Token::Log, Token::For, Token::If, Token::Return, Token::Wat];
+
+/// "`if const`" à partir de "`if`" et "`const`".
+fn if_const(kw_if: &str, kw_const: &str) -> String {
+    format!("{} {}", kw_if.trim_end_matches('`'), kw_const.trim_start_matches('`'))
+}
+
+/// `macro name(a, b) { ... }` ou `macro name { (motif) => { ... } ... }`.
+struct MacroDef {
+    /// forme simple : nombre de paramètres, vérifié avant les motifs
+    arity: Option<usize>,
+    rules: Vec<Rule>,
+    span: Span, // le nom
+}
 
 pub struct Parser<'a> {
     lx: Lexer<'a>,
     cur: Token,
+    span: Span, // position de `cur`
+    prev: Span, // position du dernier token consommé
+    queue: VecDeque<(Token, Span)>, // tokens à lire avant le lexer (expansions, lookahead)
+    macros: HashMap<String, MacroDef>,
+    /// expansions faites dans ce fichier; `Span::expn` y renvoie (index + 1)
+    pub expansions: Vec<Expansion>,
 }
 impl<'a> Parser<'a> {
     pub fn new(mut lx:Lexer<'a>) -> Result<Self, ParseError> {
-        let cur = lx.next_token()?;
-        Ok(Self { lx, cur })
+        let (cur, span) = lx.next_token()?;
+        Ok(Self { lx, cur, span, prev: span, queue: VecDeque::new(), macros: HashMap::new(), expansions: Vec::new() })
     }
 
     fn bump(&mut self) -> Result<(), ParseError> {
-        self.cur = self.lx.next_token()?;
+        self.prev = self.span;
+        (self.cur, self.span) = match self.queue.pop_front() {
+            Some(t) => t,
+            None => self.lx.next_token()?,
+        };
         Ok(())
     }
 
-    fn expect(&mut self, want: Token, expected: &'static str) -> Result<(), ParseError> {
+    fn peek(&mut self) -> Result<&Token, ParseError> {
+        if self.queue.is_empty() {
+            let t = self.lx.next_token()?;
+            self.queue.push_back(t);
+        }
+        Ok(&self.queue[0].0)
+    }
+
+    fn unexpected(&self, expected: impl Into<String>) -> ParseError {
+        ParseError::Unexpected { found: self.describe(&self.cur), expected: expected.into(), span: self.span, suggestion: None }
+    }
+
+    /// Comme `unexpected`, quand `toks` étaient attendus : si le token
+    /// courant est un identifiant proche de l'un de ces mots-clés, il est
+    /// proposé à sa place.
+    fn unexpected_kw(&self, expected: impl Into<String>, toks: &[Token]) -> ParseError {
+        let mut e = self.unexpected(expected);
+        if let (Token::Ident(word), ParseError::Unexpected { suggestion, .. }) = (&self.cur, &mut e) {
+            *suggestion = self.closest_kw(word, toks);
+        }
+        e
+    }
+
+    fn closest_kw(&self, word: &str, toks: &[Token]) -> Option<String> {
+        let g = self.lx.grammar();
+        suggest::closest(word, toks.iter().filter_map(|t| g.spell(t)).filter(|l| g.word(l).is_some())).map(str::to_string)
+    }
+
+    /// `tok` pour un message : "l'identifiant `x`", "`pour`"...
+    fn describe(&self, tok: &Token) -> String {
+        match tok {
+            Token::Ident(x) => tr!("tok.ident", x),
+            Token::Number(n) => tr!("tok.number", n),
+            Token::Str(s) => tr!("tok.string", s),
+            Token::WatBody(_) => tr!("tok.wat"),
+            Token::Eof => tr!("tok.eof"),
+            t => self.spell(t),
+        }
+    }
+
+    /// `tok` tel qu'il s'écrit dans la grammaire du fichier, entre backticks.
+    fn spell(&self, tok: &Token) -> String {
+        format!("`{}`", self.lx.grammar().spell(tok).unwrap_or("?"))
+    }
+
+    /// "`a`, `b` ou `c`"
+    fn one_of(&self, items: &[String]) -> String {
+        match items {
+            [init @ .., last] if !init.is_empty() => tr!("parse.or", init.join(", "), last),
+            _ => items.join(""),
+        }
+    }
+
+    fn expect(&mut self, want: Token) -> Result<(), ParseError> {
         if std::mem::discriminant(&self.cur) == std::mem::discriminant(&want) {
             self.bump()?;
             Ok(())
         } else {
-            Err(ParseError::Unexpected { found: self.cur.clone(), expected })
+            Err(self.unexpected(self.spell(&want)))
         }
     }
 
+    fn parse_ident(&mut self) -> Result<String, ParseError> {
+        if let Token::Ident(s) = &self.cur {
+            let v = s.clone(); self.bump()?; Ok(v)
+        } else {
+            Err(self.unexpected(tr!("parse.expected.ident")))
+        }
+    }
+
+    /// chemin := ident ('::' ident)*
+    fn parse_path(&mut self) -> Result<Vec<String>, ParseError> {
+        let mut path = vec![self.parse_ident()?];
+        while matches!(self.cur, Token::PathSep) {
+            self.bump()?;
+            path.push(self.parse_ident()?);
+        }
+        Ok(path)
+    }
+
     fn parse_number_i32(&mut self) -> Result<i32, ParseError> {
         if let Token::Number(s) = &self.cur {
-            let v = s.parse::<i64>().map_err(|_| ParseError::IntOverflow(s.clone()))?;
+            let v = s.parse::<i64>().map_err(|_| ParseError::IntOverflow(s.clone(), self.span))?;
             if !(i32::MIN as i64 ..= i32::MAX as i64).contains(&v) {
-                return Err(ParseError::IntOverflow(s.clone()));
+                return Err(ParseError::IntOverflow(s.clone(), self.span));
             }
             let out = v as i32;
             self.bump()?;
             Ok(out)
         } else {
-            Err(ParseError::Unexpected { found: self.cur.clone(), expected: "un entier i32" })
+            Err(self.unexpected(tr!("parse.expected.int")))
         }
     }
 
+    /// expr := et ('||' et)*
     fn parse_expr(&mut self) -> Result<Expr, ParseError> {
+        let mut lhs = self.parse_and()?;
+        while matches!(self.cur, Token::OrOr) {
+            self.bump()?;
+            let rhs = self.parse_and()?;
+            let span = lhs.span.to(rhs.span);
+            lhs = Expr { kind: ExprKind::Bin(BinOp::Or, Box::new(lhs), Box::new(rhs)), span };
+        }
+        Ok(lhs)
+    }
+
+    /// et := comparaison ('&&' comparaison)*
+    fn parse_and(&mut self) -> Result<Expr, ParseError> {
+        let mut lhs = self.parse_cmp()?;
+        while matches!(self.cur, Token::AndAnd) {
+            self.bump()?;
+            let rhs = self.parse_cmp()?;
+            let span = lhs.span.to(rhs.span);
+            lhs = Expr { kind: ExprKind::Bin(BinOp::And, Box::new(lhs), Box::new(rhs)), span };
+        }
+        Ok(lhs)
+    }
+
+    /// comparaison := somme [('==' | '!=' | '<' | '<=' | '>' | '>=') somme]
+    fn parse_cmp(&mut self) -> Result<Expr, ParseError> {
+        let lhs = self.parse_sum()?;
+        let op = match self.cur {
+            Token::EqEq  => BinOp::Eq,
+            Token::NotEq => BinOp::Ne,
+            Token::Lt    => BinOp::Lt,
+            Token::Le    => BinOp::Le,
+            Token::Gt    => BinOp::Gt,
+            Token::Ge    => BinOp::Ge,
+            _ => return Ok(lhs),
+        };
+        self.bump()?;
+        let rhs = self.parse_sum()?;
+        let span = lhs.span.to(rhs.span);
+        Ok(Expr { kind: ExprKind::Bin(op, Box::new(lhs), Box::new(rhs)), span })
+    }
+
+    /// somme := terme (('+' | '-') terme)*
+    fn parse_sum(&mut self) -> Result<Expr, ParseError> {
+        let mut lhs = self.parse_term()?;
+        loop {
+            let op = match self.cur {
+                Token::Plus  => BinOp::Add,
+                Token::Minus => BinOp::Sub,
+                _ => return Ok(lhs),
+            };
+            self.bump()?;
+            let rhs = self.parse_term()?;
+            let span = lhs.span.to(rhs.span);
+            lhs = Expr { kind: ExprKind::Bin(op, Box::new(lhs), Box::new(rhs)), span };
+        }
+    }
+
+    /// terme := unaire (('*' | '/' | '%') unaire)*
+    fn parse_term(&mut self) -> Result<Expr, ParseError> {
+        let mut lhs = self.parse_unary()?;
+        loop {
+            let op = match self.cur {
+                Token::Star    => BinOp::Mul,
+                Token::Slash   => BinOp::Div,
+                Token::Percent => BinOp::Rem,
+                _ => return Ok(lhs),
+            };
+            self.bump()?;
+            let rhs = self.parse_unary()?;
+            let span = lhs.span.to(rhs.span);
+            lhs = Expr { kind: ExprKind::Bin(op, Box::new(lhs), Box::new(rhs)), span };
+        }
+    }
+
+    /// unaire := '-' unaire | '!' unaire | primaire
+    fn parse_unary(&mut self) -> Result<Expr, ParseError> {
+        let start = self.span;
+        if matches!(self.cur, Token::Bang) {
+            self.bump()?;
+            let inner = self.parse_unary()?;
+            let span = start.to(inner.span);
+            return Ok(Expr { kind: ExprKind::Not(Box::new(inner)), span });
+        }
+        if !matches!(self.cur, Token::Minus) {
+            return self.parse_primary();
+        }
+        self.bump()?;
+        // -<littéral> est plié ici pour accepter i32::MIN (2147483648 seul déborde)
+        if let Token::Number(n) = &self.cur {
+            let span = start.to(self.span);
+            let v = n.parse::<i64>().map_err(|_| ParseError::IntOverflow(format!("-{n}"), span))?;
+            let v = i32::try_from(-v).map_err(|_| ParseError::IntOverflow(format!("-{n}"), span))?;
+            self.bump()?;
+            return Ok(Expr { kind: ExprKind::Int(v), span });
+        }
+        let inner = self.parse_unary()?;
+        let span = start.to(inner.span);
+        Ok(Expr { kind: ExprKind::Neg(Box::new(inner)), span })
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, ParseError> {
+        let start = self.span;
+        if matches!(self.cur, Token::Ident(_)) && matches!(self.peek()?, Token::Bang) {
+            self.expand_macro(true)?;
+            return self.parse_primary();
+        }
         match &self.cur {
-            Token::Str(s)    => { let v = s.clone(); self.bump()?; Ok(Expr::Str(v)) }
-            Token::Ident(s)  => { let v = s.clone(); self.bump()?; Ok(Expr::Var(v)) }
-            Token::Number(n) => {
-                let v = n.parse::<i32>().map_err(|_| ParseError::IntOverflow(n.clone()))?;
-                self.bump()?; Ok(Expr::Int(v))
+            Token::Ident(_) => {
+                let name = self.parse_path()?.join(grammar::PATH_SEP);
+                if matches!(self.cur, Token::LParen) {
+                    let args = self.parse_call_args()?;
+                    return Ok(Expr { kind: ExprKind::Call { name, args }, span: start.to(self.prev) });
+                }
+                Ok(Expr { kind: ExprKind::Var(name), span: start.to(self.prev) })
+            }
+            Token::Number(_) => Ok(Expr { kind: ExprKind::Int(self.parse_number_i32()?), span: start }),
+            Token::LParen => {
+                self.bump()?;
+                let e = self.parse_expr()?;
+                self.expect(Token::RParen)?;
+                Ok(e)
+            }
+            _ => Err(self.unexpected(tr!("parse.expected.expr")))
+        }
+    }
+
+    /// '(' [expr (',' expr)*] ')'
+    fn parse_call_args(&mut self) -> Result<Vec<Expr>, ParseError> {
+        self.expect(Token::LParen)?;
+        let mut args = Vec::new();
+        if !matches!(self.cur, Token::RParen) {
+            args.push(self.parse_expr()?);
+            while matches!(self.cur, Token::Comma) {
+                self.bump()?;
+                args.push(self.parse_expr()?);
             }
-            _ => Err(ParseError::Unexpected { found: self.cur.clone(), expected: "une expression (string | ident | int)" })
         }
+        self.expect(Token::RParen)?;
+        Ok(args)
+    }
+
+    /// Argument de `log` : une chaîne littérale ou une expression entière.
+    fn parse_log_arg(&mut self) -> Result<Expr, ParseError> {
+        if let Token::Str(s) = &self.cur {
+            let e = Expr { kind: ExprKind::Str(s.clone()), span: self.span };
+            self.bump()?;
+            return Ok(e);
+        }
+        self.parse_expr()
     }
 
-    fn parse_log(&mut self) -> Result<Stmt, ParseError> {
-        self.expect(Token::Log, grammar::KW_LOG)?;
-        self.expect(Token::LParen, grammar::LPAREN)?;
+    fn parse_log(&mut self) -> Result<StmtKind, ParseError> {
+        self.expect(Token::Log)?;
+        self.expect(Token::LParen)?;
         // au moins 1 argument
-        let mut args = vec![ self.parse_expr()? ];
+        let mut args = vec![ self.parse_log_arg()? ];
         while matches!(self.cur, Token::Comma) {
             self.bump()?;
-            args.push(self.parse_expr()?);
+            args.push(self.parse_log_arg()?);
         }
-        self.expect(Token::RParen, grammar::RPAREN)?;
-        Ok(Stmt::Log(args))
+        self.expect(Token::RParen)?;
+        Ok(StmtKind::Log(args))
     }
 
-    fn parse_for(&mut self) -> Result<Stmt, ParseError> {
-        self.expect(Token::For, grammar::KW_FOR)?;
-        let name = if let Token::Ident(s) = &self.cur {
-            let v = s.clone(); self.bump()?; v
-        } else {
-            return Err(ParseError::Unexpected { found: self.cur.clone(), expected: "identifiant" });
-        };
-        self.expect(Token::Assign, grammar::ASSIGN_LEXEME)?;
-        let start = self.parse_number_i32()?;
-        self.expect(Token::To, grammar::KW_TO)?;
-        let end = self.parse_number_i32()?;
-        self.expect(Token::LBrace, grammar::LBRACE)?;
+    fn parse_block(&mut self) -> Result<Vec<Stmt>, ParseError> {
+        self.expect(Token::LBrace)?;
         let mut body = Vec::new();
         while !matches!(self.cur, Token::RBrace) {
+            // une expansion peut produire zéro ou plusieurs instructions
+            if matches!(self.cur, Token::Ident(_)) && matches!(self.peek()?, Token::Bang) {
+                self.expand_macro(false)?;
+                continue;
+            }
             body.push(self.parse_stmt()?);
         }
-        self.expect(Token::RBrace, grammar::RBRACE)?;
-        Ok(Stmt::For { name, start, end, body })
+        self.expect(Token::RBrace)?;
+        Ok(body)
+    }
+
+    fn parse_for(&mut self) -> Result<StmtKind, ParseError> {
+        self.expect(Token::For)?;
+        let name_span = self.span;
+        let name = self.parse_ident()?;
+        self.expect(Token::Assign)?;
+        let start = self.parse_expr()?;
+        let kind = match self.cur {
+            Token::To     => ForKind::To,
+            Token::Downto => ForKind::Downto,
+            Token::Until  => ForKind::Until,
+            _ => {
+                let toks = [Token::To, Token::Downto, Token::Until];
+                let kws = toks.clone().map(|t| self.spell(&t));
+                return Err(self.unexpected_kw(self.one_of(&kws), &toks));
+            }
+        };
+        self.bump()?;
+        let end = self.parse_expr()?;
+        let step = if matches!(self.cur, Token::Step) {
+            self.bump()?;
+            Some(self.parse_expr()?)
+        } else {
+            None
+        };
+        let body = self.parse_block()?;
+        Ok(StmtKind::For { name, name_span, start, end, step, kind, body })
+    }
+
+    fn parse_return(&mut self) -> Result<StmtKind, ParseError> {
+        self.expect(Token::Return)?;
+        let starts_expr = matches!(self.cur, Token::Ident(_) | Token::Number(_) | Token::Minus | Token::Bang | Token::LParen);
+        Ok(StmtKind::Return(if starts_expr { Some(self.parse_expr()?) } else { None }))
+    }
+
+    /// `if const cond { ... } [else if const ... | else { ... }]`
+    fn parse_if_const(&mut self) -> Result<StmtKind, ParseError> {
+        self.expect(Token::If)?;
+        if !matches!(self.cur, Token::Const) {
+            let (kw_if, kw_const) = (self.spell(&Token::If), self.spell(&Token::Const));
+            return Err(self.unexpected_kw(tr!("parse.expected.if_const", kw_const, if_const(&kw_if, &kw_const)), &[Token::Const]));
+        }
+        self.bump()?;
+        let cond = self.parse_expr()?;
+        let then = self.parse_block()?;
+        let els = if matches!(self.cur, Token::Else) {
+            self.bump()?;
+            if matches!(self.cur, Token::If) {
+                vec![self.parse_stmt()?]
+            } else {
+                self.parse_block()?
+            }
+        } else {
+            Vec::new()
+        };
+        Ok(StmtKind::IfConst { cond, then, els })
+    }
+
+    /// `wat { ... }`
+    fn parse_wat_body(&mut self) -> Result<String, ParseError> {
+        self.expect(Token::Wat)?;
+        self.wat_text()
+    }
+
+    /// Le `{ ... }` qui suit `wat`, déjà découpé (et validé) par le lexer.
+    fn wat_text(&mut self) -> Result<String, ParseError> {
+        let Token::WatBody(code) = &self.cur else {
+            return Err(self.unexpected(tr!("parse.expected.wat_body")));
+        };
+        let code = code.clone();
+        self.bump()?;
+        Ok(code)
+    }
+
+    /// `chemin(args)` ou `chemin = expr`
+    fn parse_call_or_assign(&mut self) -> Result<StmtKind, ParseError> {
+        let start = self.span;
+        let name = self.parse_path()?.join(grammar::PATH_SEP);
+        if matches!(self.cur, Token::LParen) {
+            let args = self.parse_call_args()?;
+            return Ok(StmtKind::Expr(Expr { kind: ExprKind::Call { name, args }, span: start.to(self.prev) }));
+        }
+        self.expect(Token::Assign)?;
+        let value = self.parse_expr()?;
+        Ok(StmtKind::Assign { name, value })
+    }
+
+    /// attributs := ('@' nom ['(' arg (',' arg)* ')'])*
+    fn parse_attrs(&mut self) -> Result<Vec<Attr>, ParseError> {
+        let mut attrs = Vec::new();
+        while matches!(self.cur, Token::At) {
+            let start = self.span;
+            self.bump()?;
+            if matches!(self.cur, Token::Bang) {
+                return Err(ParseError::Attr(code!("G0109"), tr!("parse.inner_attr_position"), start.to(self.span), None));
+            }
+            attrs.push(self.parse_attr_body(start)?);
+        }
+        Ok(attrs)
+    }
+
+    /// Attributs du fichier : ('@' '!' nom ['(' arg (',' arg)* ')'])*, avant
+    /// tout item.
+    fn parse_inner_attrs(&mut self) -> Result<Vec<Attr>, ParseError> {
+        let mut attrs = Vec::new();
+        while matches!(self.cur, Token::At) && matches!(self.peek()?, Token::Bang) {
+            let start = self.span;
+            self.bump()?;
+            self.bump()?;
+            attrs.push(self.parse_attr_body(start)?);
+        }
+        Ok(attrs)
+    }
+
+    /// Nom et arguments d'un attribut, `@` (et `!`) déjà lus.
+    fn parse_attr_body(&mut self, start: Span) -> Result<Attr, ParseError> {
+        let name_span = self.span;
+        let name = match &self.cur {
+            // `export` est aussi un mot-clé
+            Token::Export => { self.bump()?; grammar::KW_EXPORT.to_string() }
+            _ => self.parse_ident()?,
+        };
+        let mut args = Vec::new();
+        if matches!(self.cur, Token::LParen) {
+            self.bump()?;
+            while !matches!(self.cur, Token::RParen) {
+                if !args.is_empty() {
+                    self.expect(Token::Comma)?;
+                }
+                args.push(self.parse_log_arg()?);
+            }
+            self.bump()?;
+        }
+        Ok(Attr { name, args, span: start.to(self.prev), name_span })
+    }
+
+    fn check_attrs(&self, attrs: &[Attr], target: attrs::Target) -> Result<(), ParseError> {
+        attrs::check(attrs, target).map_err(|e| ParseError::Attr(e.code, e.message, e.span, e.suggestion))
     }
 
     fn parse_stmt(&mut self) -> Result<Stmt, ParseError> {
-        match self.cur {
-            Token::Log => self.parse_log(),
-            Token::For => self.parse_for(),
-            _ => Err(ParseError::Unexpected { found: self.cur.clone(), expected: "`log` ou `for`" }),
+        let attrs = self.parse_attrs()?;
+        self.check_attrs(&attrs, attrs::Target::Stmt)?;
+        if !attrs.is_empty() && matches!(self.cur, Token::Ident(_)) && matches!(self.peek()?, Token::Bang) {
+            return Err(ParseError::Attr(code!("G0107"), tr!("parse.attr_on_macro"), attrs[0].span, None));
         }
+        let start = self.span;
+        let kind = match &self.cur {
+            Token::Log => self.parse_log()?,
+            Token::For => self.parse_for()?,
+            Token::Return => self.parse_return()?,
+            Token::Wat => StmtKind::Wat(self.parse_wat_body()?),
+            Token::If => self.parse_if_const()?,
+            Token::Ident(word) => {
+                // `lgo("x")`, `fro i = ...` : un mot-clé mal écrit se lit d'abord
+                // comme un appel ou une affectation; si celle-ci échoue juste
+                // après le mot (ou dans ses parenthèses), on propose le mot-clé
+                let word = word.clone();
+                let call = matches!(self.peek()?, Token::LParen);
+                match self.parse_call_or_assign() {
+                    Ok(kind) => kind,
+                    Err(e) if call || self.prev == start => {
+                        let Some(kw) = self.closest_kw(&word, &STMT_KEYWORDS) else { return Err(e) };
+                        let found = self.describe(&Token::Ident(word));
+                        return Err(ParseError::Unexpected { found, expected: self.stmt_expected(), span: start, suggestion: Some(kw) });
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+            _ => return Err(self.unexpected(self.stmt_expected())),
+        };
+        Ok(Stmt { kind, attrs, span: start.to(self.prev) })
     }
 
-    pub fn parse_program(&mut self) -> Result<Program, ParseError> {
-        self.expect(Token::Fn,   grammar::KW_FN)?;
-        self.expect(Token::Main, grammar::KW_MAIN)?;
-        self.expect(Token::LParen, grammar::LPAREN)?;
-        self.expect(Token::RParen, grammar::RPAREN)?;
-        self.expect(Token::LBrace, grammar::LBRACE)?;
+    /// "`log`, `for`, `if const`, `return`, `wat` ou un appel ou une affectation"
+    fn stmt_expected(&self) -> String {
+        let mut kws = STMT_KEYWORDS.map(|t| self.spell(&t)).to_vec();
+        kws[2] = if_const(&kws[2], &self.spell(&Token::Const));
+        kws.push(tr!("parse.expected.call_or_assign"));
+        self.one_of(&kws)
+    }
 
-        let mut stmts = Vec::new();
-        while !matches!(self.cur, Token::RBrace) {
-            stmts.push(self.parse_stmt()?);
+    fn parse_const(&mut self, public: bool, attrs: Vec<Attr>) -> Result<ConstDecl, ParseError> {
+        self.check_attrs(&attrs, attrs::Target::Const)?;
+        self.expect(Token::Const)?;
+        let span = self.span;
+        let name = self.parse_ident()?;
+        self.expect(Token::Assign)?;
+        let value = self.parse_expr()?;
+        Ok(ConstDecl { name, value, public, attrs, span })
+    }
+
+    /// `i32` ou `fn(type, ...) [-> i32]`
+    fn parse_type(&mut self) -> Result<Type, ParseError> {
+        if matches!(self.cur, Token::Fn) {
+            self.bump()?;
+            self.expect(Token::LParen)?;
+            let mut params = Vec::new();
+            while !matches!(self.cur, Token::RParen) {
+                if !params.is_empty() {
+                    self.expect(Token::Comma)?;
+                }
+                params.push(self.parse_type()?);
+            }
+            self.expect(Token::RParen)?;
+            let ret = matches!(self.cur, Token::Arrow);
+            if ret {
+                self.bump()?;
+                self.parse_i32()?;
+            }
+            return Ok(Type::Fn(FnType { params, ret }));
         }
+        self.parse_i32()?;
+        Ok(Type::I32)
+    }
+
+    /// seul type de retour pour l'instant
+    fn parse_i32(&mut self) -> Result<(), ParseError> {
+        if !matches!(&self.cur, Token::Ident(t) if t == grammar::TY_I32) {
+            return Err(self.unexpected(grammar::TY_I32));
+        }
+        self.bump()
+    }
+
+    fn parse_global(&mut self, public: bool, attrs: Vec<Attr>) -> Result<GlobalDecl, ParseError> {
+        self.check_attrs(&attrs, attrs::Target::Global)?;
+        let export = matches!(self.cur, Token::Export);
+        if export {
+            self.bump()?;
+        }
+        self.expect(Token::Global)?;
+        let span = self.span;
+        let name = self.parse_ident()?;
+        self.expect(Token::Colon)?;
+        let ty = self.parse_type()?;
+        self.expect(Token::Assign)?;
+        let init = self.parse_expr()?;
+        Ok(GlobalDecl { name, ty, init, export, public, attrs, span })
+    }
+
+    /// `fn ...`, `const fn ...` ou `wat fn ...` (corps en WAT brut)
+    fn parse_fn(&mut self, public: bool, attrs: Vec<Attr>) -> Result<FnDecl, ParseError> {
+        self.check_attrs(&attrs, attrs::Target::Fn)?;
+        let raw = matches!(self.cur, Token::Wat);
+        let konst = matches!(self.cur, Token::Const);
+        if raw || konst {
+            self.bump()?;
+        }
+        self.expect(Token::Fn)?;
+        let span = self.span;
+        let name = match &self.cur {
+            Token::Main => { self.bump()?; grammar::KW_MAIN.to_string() }
+            _ => self.parse_ident()?,
+        };
+        self.expect(Token::LParen)?;
+        let mut params: Vec<Param> = Vec::new();
+        let mut spans = Vec::new();
+        while !matches!(self.cur, Token::RParen) {
+            if !params.is_empty() {
+                self.expect(Token::Comma)?;
+            }
+            let at = self.span;
+            let name = self.parse_ident()?;
+            if let Some(i) = params.iter().position(|p| p.name == name) {
+                return Err(ParseError::DuplicateParam { name, span: at, first: spans[i] });
+            }
+            spans.push(at);
+            self.expect(Token::Colon)?;
+            let ty = self.parse_type()?;
+            params.push(Param { name, ty });
+        }
+        self.expect(Token::RParen)?;
+        let ret = matches!(self.cur, Token::Arrow);
+        if ret {
+            self.bump()?;
+            self.parse_i32()?;
+        }
+        let body = if raw {
+            let start = self.span;
+            vec![Stmt { kind: StmtKind::Wat(self.wat_text()?), attrs: Vec::new(), span: start }]
+        } else {
+            self.parse_block()?
+        };
+        Ok(FnDecl { name, params, ret, konst, body, public, attrs, span })
+    }
+
+    fn parse_import(&mut self) -> Result<Import, ParseError> {
+        let start = self.span;
+        self.expect(Token::Import)?;
+        let Token::Str(path) = &self.cur else {
+            return Err(self.unexpected(tr!("parse.expected.import_path")));
+        };
+        let path = path.clone();
+        self.bump()?;
+        Ok(Import { path, span: start.to(self.prev) })
+    }
+
+    fn parse_use(&mut self) -> Result<Use, ParseError> {
+        self.expect(Token::Use)?;
+        let start = self.span;
+        let path = self.parse_path()?;
+        Ok(Use { path, span: start.to(self.prev) })
+    }
+
+    /// Tokens jusqu'au délimiteur fermant correspondant (exclu), `cur` étant
+    /// juste après l'ouvrant; le délimiteur fermant reste dans `cur`.
+    fn token_tree(&mut self) -> Result<Vec<(Token, Span)>, ParseError> {
+        let mut out = Vec::new();
+        let mut depth = 0usize;
+        loop {
+            match self.cur {
+                Token::LParen | Token::LBrace => depth += 1,
+                Token::RParen | Token::RBrace if depth == 0 => return Ok(out),
+                Token::RParen | Token::RBrace => depth -= 1,
+                Token::Eof => return Err(self.unexpected(tr!("parse.expected.macro_end"))),
+                _ => {}
+            }
+            out.push((self.cur.clone(), self.span));
+            self.bump()?;
+        }
+    }
 
-        self.expect(Token::RBrace, grammar::RBRACE)?;
-        self.expect(Token::Eof, "fin de fichier")?;
-        Ok(Program { stmts })
+    fn parse_macro_def(&mut self) -> Result<(), ParseError> {
+        self.expect(Token::Macro)?;
+        let span = self.span;
+        let name = self.parse_ident()?;
+        let (arity, rules) = if matches!(self.cur, Token::LParen) {
+            let params = self.parse_macro_params()?;
+            self.expect(Token::LBrace)?;
+            let body = self.token_tree()?;
+            self.expect(Token::RBrace)?;
+            (Some(params.len()), vec![Rule::simple(&params, &body)])
+        } else {
+            // `{ (motif) => { corps } ... }`, règles séparées ou non par des virgules
+            self.expect(Token::LBrace)?;
+            let mut rules = Vec::new();
+            while !matches!(self.cur, Token::RBrace) {
+                if !rules.is_empty() && matches!(self.cur, Token::Comma) {
+                    self.bump()?;
+                    continue;
+                }
+                let at = self.span;
+                self.expect(Token::LParen)?;
+                let pattern = self.token_tree()?;
+                self.expect(Token::RParen)?;
+                self.expect(Token::FatArrow)?;
+                self.expect(Token::LBrace)?;
+                let body = self.token_tree()?;
+                self.expect(Token::RBrace)?;
+                rules.push(Rule::new(&pattern, &body, at.to(self.prev))?);
+            }
+            if rules.is_empty() {
+                return Err(ParseError::MacroPattern { message: tr!("parse.macro.no_rule", name), span });
+            }
+            self.bump()?;
+            (None, rules)
+        };
+        if let Some(first) = self.macros.get(&name) {
+            return Err(ParseError::DuplicateMacro { name, span, first: first.span });
+        }
+        self.macros.insert(name, MacroDef { arity, rules, span });
+        Ok(())
+    }
+
+    /// `(a, b)` de la forme simple; deux fois le même nom est refusé.
+    fn parse_macro_params(&mut self) -> Result<Vec<String>, ParseError> {
+        self.expect(Token::LParen)?;
+        let mut params: Vec<String> = Vec::new();
+        let mut spans = Vec::new();
+        while !matches!(self.cur, Token::RParen) {
+            if !params.is_empty() {
+                self.expect(Token::Comma)?;
+            }
+            let at = self.span;
+            let param = self.parse_ident()?;
+            if let Some(i) = params.iter().position(|p| *p == param) {
+                return Err(ParseError::DuplicateParam { name: param, span: at, first: spans[i] });
+            }
+            params.push(param);
+            spans.push(at);
+        }
+        self.expect(Token::RParen)?;
+        Ok(params)
+    }
+
+    /// `name!(...)` : remplace l'appel par le corps de la première règle dont
+    /// le motif reconnaît les tokens entre parenthèses, devant le flot de
+    /// tokens; entre parenthèses si l'appel est une expression (`expr`).
+    /// Les tokens du corps portent le numéro de l'expansion dans leur Span :
+    /// la résolution des noms les sépare ainsi de ceux de l'appelant
+    /// (hygiène), une variable de boucle du corps ne capturant ni ne masquant
+    /// jamais un nom passé en argument.
+    fn expand_macro(&mut self, expr: bool) -> Result<(), ParseError> {
+        let call_start = self.span;
+        let name = self.parse_ident()?;
+        self.expect(Token::Bang)?;
+        self.expect(Token::LParen)?;
+        let args = self.token_tree()?;
+        self.expect(Token::RParen)?;
+        let call = call_start.to(self.prev);
+
+        let Some(def) = self.macros.get(&name) else {
+            let suggestion = suggest::closest(&name, self.macros.keys().map(String::as_str)).map(str::to_string);
+            return Err(ParseError::UnknownMacro { name, span: call, suggestion });
+        };
+        if let Some(expected) = def.arity {
+            // un argument par virgule de premier niveau, aucun pour `name!()`
+            let mut depth = 0usize;
+            let commas = args.iter().filter(|(t, _)| {
+                match t {
+                    Token::LParen | Token::LBrace => depth += 1,
+                    Token::RParen | Token::RBrace => depth -= 1,
+                    _ => {}
+                }
+                depth == 0 && *t == Token::Comma
+            });
+            let found = if args.is_empty() { 0 } else { commas.count() + 1 };
+            if found != expected {
+                return Err(ParseError::MacroArity { name, expected, found, span: call, def: def.span });
+            }
+        }
+        let mut depth = 1;
+        let mut up = call;
+        while let Some(e) = up.expn.checked_sub(1).map(|i| &self.expansions[i]) {
+            depth += 1;
+            up = e.call;
+        }
+        if depth > MACRO_DEPTH_LIMIT {
+            return Err(ParseError::MacroRecursion { name, span: call });
+        }
+        let Some((rule, binds)) = def.rules.iter().find_map(|r| Some((r, r.matches(&args)?))) else {
+            return Err(ParseError::MacroNoMatch { name, span: call, def: def.span });
+        };
+
+        let expn = self.expansions.len() + 1;
+        let mut out = match rule.expand(&binds, expn) {
+            Ok(out) => out,
+            Err(vars) => return Err(ParseError::MacroRepeat { name, vars: Box::new(vars), span: call, def: def.span }),
+        };
+        self.expansions.push(Expansion { name, call, def: def.span });
+        if expr {
+            out.insert(0, (Token::LParen, call));
+            out.push((Token::RParen, call));
+        }
+        // le token courant (après l'appel) passe derrière l'expansion
+        out.push((self.cur.clone(), self.span));
+        for t in out.into_iter().rev() {
+            self.queue.push_front(t);
+        }
+        self.bump()
+    }
+
+    pub fn parse_module(&mut self) -> Result<Module, ParseError> {
+        let mut m = Module { attrs: self.parse_inner_attrs()?, ..Module::default() };
+        self.check_attrs(&m.attrs, attrs::Target::File)?;
+        loop {
+            let attrs = self.parse_attrs()?;
+            let public = matches!(self.cur, Token::Pub);
+            if public {
+                self.bump()?;
+            }
+            let const_fn = matches!(self.cur, Token::Const) && matches!(self.peek()?, Token::Fn);
+            match self.cur {
+                Token::Const if const_fn => m.funcs.push(self.parse_fn(public, attrs)?),
+                Token::Const => m.consts.push(self.parse_const(public, attrs)?),
+                Token::Global | Token::Export => m.globals.push(self.parse_global(public, attrs)?),
+                Token::Fn | Token::Wat => m.funcs.push(self.parse_fn(public, attrs)?),
+                _ if !attrs.is_empty() => {
+                    return Err(ParseError::Attr(
+                        code!("G0108"),
+                        tr!("parse.attr_position"),
+                        attrs[0].span,
+                        None,
+                    ));
+                }
+                Token::Macro if !public => self.parse_macro_def()?,
+                Token::Import if !public => m.imports.push(self.parse_import()?),
+                Token::Use if !public => m.uses.push(self.parse_use()?),
+                Token::Eof if !public => break,
+                _ => {
+                    let [kw_fn, kw_const, kw_wat] = [Token::Fn, Token::Const, Token::Wat].map(|t| self.lx.grammar().spell(&t).unwrap_or("?"));
+                    let mut kws = vec![format!("`{kw_fn}`"), format!("`{kw_const} {kw_fn}`"), format!("`{kw_wat} {kw_fn}`")];
+                    kws.extend([Token::Const, Token::Global, Token::Macro, Token::Import, Token::Use].map(|t| self.spell(&t)));
+                    let toks = [Token::Fn, Token::Const, Token::Wat, Token::Global, Token::Export, Token::Pub, Token::Macro, Token::Import, Token::Use];
+                    return Err(self.unexpected_kw(self.one_of(&kws), &toks));
+                }
+            }
+        }
+        Ok(m)
     }
 }
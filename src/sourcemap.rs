@@ -0,0 +1,100 @@
+//! Source map (version 3) d'un module binaire, pour que les outils de
+//! développement des navigateurs affichent le `.gfr` au lieu du code
+//! WebAssembly. Comme pour tout module wasm, il n'y a qu'une ligne générée
+//! et sa « colonne » est la position de l'instruction dans le fichier
+//! `.wasm`.
+
+use crate::diag::{SourceMap, Span};
+use serde_json::json;
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Entier en VLQ base64 : signe dans le bit de poids faible, puis groupes
+/// de 5 bits, le bit 6 annonçant une suite.
+fn vlq(out: &mut String, n: i64) {
+    let mut v = if n < 0 { (-n << 1) | 1 } else { n << 1 } as u64;
+    loop {
+        let mut digit = (v & 0x1F) as usize;
+        v >>= 5;
+        if v != 0 {
+            digit |= 0x20;
+        }
+        out.push(BASE64[digit] as char);
+        if v == 0 {
+            break;
+        }
+    }
+}
+
+/// Texte écrit par l'utilisateur : une expansion de macro renvoie à son appel.
+fn written(mut span: Span, sm: &SourceMap) -> Span {
+    while let Some(e) = sm.expansion(span) {
+        span = e.call;
+    }
+    span
+}
+
+/// `offsets` : position dans le `.wasm` de chaque instruction et son Span,
+/// dans l'ordre du fichier. Les instructions sans Span (ajoutées par le
+/// compilateur) et celles qui répètent la position précédente sont omises.
+pub fn build(offsets: &[(usize, Span)], sm: &SourceMap) -> String {
+    let mut mappings = String::new();
+    // valeurs du segment précédent, les champs étant relatifs
+    let (mut at, mut file, mut line, mut col) = (0i64, 0i64, 0i64, 0i64);
+    let mut last = None;
+    for &(offset, span) in offsets {
+        if span == Span::default() {
+            continue;
+        }
+        let span = written(span, sm);
+        let (l, c) = sm.line_col(span);
+        let here = (span.file as i64, l as i64 - 1, c as i64 - 1);
+        if last == Some(here) {
+            continue;
+        }
+        last = Some(here);
+        if !mappings.is_empty() {
+            mappings.push(',');
+        }
+        for (prev, v) in [(&mut at, offset as i64), (&mut file, here.0), (&mut line, here.1), (&mut col, here.2)] {
+            vlq(&mut mappings, v - *prev);
+            *prev = v;
+        }
+    }
+    let files = sm.files();
+    json!({
+        "version": 3,
+        "sources": files.iter().map(|f| f.path.to_string_lossy().replace('\\', "/")).collect::<Vec<_>>(),
+        "sourcesContent": files.iter().map(|f| &f.src).collect::<Vec<_>>(),
+        "names": [],
+        "mappings": mappings,
+    })
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::vlq;
+
+    fn encoded(n: i64) -> String {
+        let mut out = String::new();
+        vlq(&mut out, n);
+        out
+    }
+
+    #[test]
+    fn vlq_boundaries() {
+        assert_eq!(encoded(0), "A");
+        assert_eq!(encoded(1), "C");
+        assert_eq!(encoded(-1), "D");
+        // 15 tient dans un chiffre, 16 en demande deux
+        assert_eq!(encoded(15), "e");
+        assert_eq!(encoded(16), "gB");
+        assert_eq!(encoded(63), "+D");
+        assert_eq!(encoded(64), "gE");
+        assert_eq!(encoded(-64), "hE");
+        assert_eq!(encoded(-65), "jE");
+        assert_eq!(encoded(i32::MAX as i64), "+/////D");
+        assert_eq!(encoded(i32::MIN as i64), "hgggggE");
+    }
+}
@@ -0,0 +1,90 @@
+//! « Vouliez-vous dire ... ? » : le candidat le plus proche d'un mot mal
+//! écrit, par distance d'édition.
+
+/// Nombre d'insertions, suppressions, substitutions ou inversions de deux
+/// caractères voisins pour passer de `a` à `b` (`lgo` -> `log` : 1).
+fn distance(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    // trois lignes de la matrice suffisent : i - 2, i - 1 et i
+    let mut prev2: Vec<usize> = Vec::new();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        let mut cur = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            cur[j] = (prev[j] + 1).min(cur[j - 1] + 1).min(prev[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                cur[j] = cur[j].min(prev2[j - 2] + 1);
+            }
+        }
+        prev2 = std::mem::replace(&mut prev, cur);
+    }
+    prev[b.len()]
+}
+
+/// Le candidat le plus proche de `word`, s'il l'est assez : au plus une
+/// erreur par tranche de trois caractères (une au minimum), ou seulement
+/// une différence de casse. À distance égale, le premier dans l'ordre
+/// alphabétique, pour une réponse stable.
+pub fn closest<'a>(word: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let max = (word.chars().count() / 3).max(1);
+    candidates
+        .into_iter()
+        .filter(|c| *c != word)
+        .map(|c| {
+            let d = if c.eq_ignore_ascii_case(word) { 0 } else { distance(word, c) };
+            (d, c)
+        })
+        .filter(|&(d, _)| d <= max)
+        .min()
+        .map(|(_, c)| c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{closest, distance};
+    use crate::diag::SourceMap;
+    use crate::{lints, modules};
+    use std::collections::HashMap;
+
+    #[test]
+    fn edit_distance() {
+        assert_eq!(distance("lgo", "log"), 1);
+        assert_eq!(distance("retrun", "return"), 1);
+        assert_eq!(distance("fn", "function"), 6);
+        assert_eq!(distance("", "abc"), 3);
+        assert_eq!(distance("été", "ete"), 2);
+    }
+
+    #[test]
+    fn closest_candidate() {
+        let kws = ["for", "fn", "from", "log", "return"];
+        assert_eq!(closest("lgo", kws), Some("log"));
+        assert_eq!(closest("LOG", kws), Some("log"));
+        // une erreur par tranche de trois caractères
+        assert_eq!(closest("retrn", kws), Some("return"));
+        assert_eq!(closest("rtrn", kws), None);
+        // à égalité, l'ordre alphabétique
+        assert_eq!(closest("fo", kws), Some("fn"));
+        assert_eq!(closest("log", kws), None);
+    }
+
+    /// Corrections proposées pour le programme `src`.
+    fn fixes(name: &str, src: &str) -> Vec<String> {
+        let path = std::env::temp_dir().join(format!("gaufre-suggest-{name}.gfr"));
+        std::fs::write(&path, src).unwrap();
+        let mut sm = SourceMap::default();
+        let d = modules::load_program(&path, &mut sm, &HashMap::new(), "en", &lints::Config::default(), &mut Vec::new()).unwrap_err();
+        d.fixes.iter().map(|f| format!("{}{}{}", &src[..f.span.lo], f.replacement, &src[f.span.hi..])).collect()
+    }
+
+    #[test]
+    fn applicable_fixes() {
+        assert_eq!(fixes("keyword", "fnn main() {}\n"), ["fn main() {}\n"]);
+        assert_eq!(fixes("log", "fn main() {\n  lgo(1)\n}\n"), ["fn main() {\n  log(1)\n}\n"]);
+        let src = "fn twice(count: i32) -> i32 {\n  return cuont * 2\n}\nfn main() {}\n";
+        assert_eq!(fixes("local", src), [src.replace("cuont", "count")]);
+        let src = "macro sq(x) { x * x }\nfn main() {\n  log(qs!(2))\n}\n";
+        assert_eq!(fixes("macro", src), [src.replace("qs!", "sq!")]);
+    }
+}
@@ -0,0 +1,153 @@
+//! `gaufre translate` : réécrit un fichier d'un profil de grammaire dans un
+//! autre. Seuls les mots-clés et opérateurs changent; blancs, commentaires,
+//! chaînes et blocs `wat` sont recopiés à l'octet près.
+
+use crate::codes::code;
+use crate::diag::{Diagnostic, SourceMap, Span};
+use crate::grammar::{self, Grammar};
+use crate::i18n::tr;
+use crate::lexer::{LexError, Lexer, Token};
+use crate::modules;
+use crate::parser::ParseError;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+fn lex_error(e: LexError) -> Diagnostic {
+    ParseError::from(e).into()
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Tokens de `src` avec leur position. Le nom d'un attribut (`@export`) est
+/// ramené à un identifiant : `export` peut être un mot-clé ou non selon le
+/// profil, le parser accepte les deux.
+fn tokens(src: &str, file: usize, g: &Grammar) -> Result<Vec<(Token, Span)>, LexError> {
+    let mut lx = Lexer::new(src, file, g);
+    let mut out: Vec<(Token, Span)> = Vec::new();
+    loop {
+        let (mut tok, span) = lx.next_token()?;
+        if tok == Token::Eof {
+            return Ok(out);
+        }
+        if matches!(out.last(), Some((Token::At, _))) && tok == Token::Export {
+            tok = Token::Ident(grammar::KW_EXPORT.to_string());
+        }
+        out.push((tok, span));
+    }
+}
+
+/// `src` (fichier `file` du SourceMap) réécrit de `from` vers `to`; un
+/// `#grammar` en tête prend la valeur `to_spec`, et s'il manque il est
+/// ajouté pour tout profil autre que `en`.
+pub fn translate(src: &str, file: usize, from: &Grammar, to: &Grammar, to_spec: &str) -> Result<String, Diagnostic> {
+    let mut out = String::new();
+    let mut last = 0;
+    match grammar::pragma(src) {
+        Some(p) => {
+            out.push_str(&src[..p.lo]);
+            out.push_str(to_spec);
+            last = p.lo + p.arg.len();
+        }
+        None if to_spec != "en" => out.push_str(&format!("{} {to_spec}\n", grammar::PRAGMA)),
+        None => {}
+    }
+    let toks = tokens(src, file, from).map_err(lex_error)?;
+    for (i, (tok, span)) in toks.iter().enumerate() {
+        out.push_str(&src[last..span.lo]);
+        let text = &src[span.lo..span.hi];
+        let attr_name = i > 0 && toks[i - 1].0 == Token::At;
+        match tok {
+            Token::Ident(id) if !attr_name && to.word(id).is_some() => {
+                return Err(Diagnostic::new(
+                    code!("G0701"),
+                    tr!("translate.keyword", id),
+                    Some(*span),
+                ));
+            }
+            Token::Ident(id) if attr_name && id == grammar::KW_EXPORT => {
+                out.push_str(to.spell(&Token::Export).expect("token sans lexème"));
+            }
+            Token::Ident(_) | Token::Number(_) | Token::Str(_) | Token::WatBody(_) => out.push_str(text),
+            _ => {
+                let new = to.spell(tok).expect("token sans lexème");
+                // un mot qui remplace un symbole collé (`a&&b` -> `a and b`)
+                let word = new.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_');
+                if word && out.ends_with(is_ident_char) {
+                    out.push(' ');
+                }
+                out.push_str(new);
+                if word && src[span.hi..].starts_with(is_ident_char) {
+                    out.push(' ');
+                }
+            }
+        }
+        last = span.hi;
+    }
+    out.push_str(&src[last..]);
+
+    // garde-fou : relu avec `to`, le résultat doit donner les mêmes tokens
+    // (ex. deux symboles qui, collés, en forment un troisième)
+    let again = tokens(&out, file, to).map_err(lex_error)?;
+    let differs = toks.iter().map(|t| &t.0).ne(again.iter().map(|t| &t.0));
+    if differs {
+        let at = toks.iter().zip(&again).find(|(a, b)| a.0 != b.0).map_or(toks.last().map(|t| t.1), |(a, _)| Some(a.1));
+        let d = Diagnostic::new(code!("G0702"), tr!("translate.relex"), at);
+        // le token fusionne avec le suivant une fois traduit : les séparer
+        return Err(match at {
+            Some(span) => d.with_fix(tr!("translate.add_space"), Span { lo: span.hi, ..span }, " "),
+            None => d,
+        });
+    }
+    Ok(out)
+}
+
+/// Lit `path` et le traduit. `from` absent : le `#grammar` du fichier, sinon `en`.
+pub fn translate_file(path: &Path, from: Option<&str>, to: &str, sm: &mut SourceMap) -> Result<String, Diagnostic> {
+    let shown = path.display();
+    let src = fs::read_to_string(path).map_err(|e| Diagnostic::new(code!("G0301"), tr!("io.read", shown, e), None))?;
+    let pragma = grammar::pragma(&src).map(|p| (p.arg.to_string(), p.lo));
+    let file = sm.add(path, src);
+    let mut profiles = HashMap::new();
+    let from = match (from, pragma) {
+        (Some(spec), _) => modules::load_grammar(sm, &mut profiles, spec, Path::new(""), None)?,
+        (None, Some((arg, lo))) => {
+            let dir = path.parent().unwrap_or(Path::new(""));
+            modules::load_grammar(sm, &mut profiles, &arg, dir, Some(Span::new(file, lo, lo + arg.len())))?
+        }
+        (None, None) => Grammar::builtin("en").unwrap(),
+    };
+    let to_grammar = modules::load_grammar(sm, &mut profiles, to, Path::new(""), None)?;
+    translate(&sm.file(file).src, file, &from, &to_grammar, to)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::translate;
+    use crate::grammar::Grammar;
+
+    fn tr(src: &str, from: &str, to: &str) -> String {
+        let (from, to_g) = (Grammar::builtin(from).unwrap(), Grammar::builtin(to).unwrap());
+        translate(src, 0, &from, &to_g, to).unwrap_or_else(|d| panic!("{}", d.message))
+    }
+
+    #[test]
+    fn round_trip() {
+        let src = "// commentaire\nfn main() {\n    if 1 < 2&&3 > 2 { log(\"si\") }\n}\n";
+        let fr = tr(src, "en", "fr");
+        assert!(fr.starts_with("#grammar fr\n// commentaire\n"), "{fr}");
+        assert!(fr.contains("fonction principal()"), "{fr}");
+        assert!(fr.contains("2 et 3"), "{fr}");
+        // `et` a dû être entouré d'espaces, qui restent au retour
+        assert_eq!(tr(&fr, "fr", "en"), format!("#grammar en\n{}", src.replace("2&&3", "2 && 3")));
+    }
+
+    #[test]
+    fn pragma_before_file_attributes() {
+        let src = "@!allow(shadowed)\nfn main() {}\n";
+        assert_eq!(tr(src, "en", "fr"), "#grammar fr\n@!allow(shadowed)\nfonction principal() {}\n");
+        assert_eq!(tr(src, "en", "en"), src);
+    }
+}
@@ -0,0 +1,430 @@
+//! Validation d'un module `wasm` selon les règles de typage de WebAssembly,
+//! pour ce que gaufre produit : pile d'opérandes typée, blocs et labels,
+//! locals, globales, accès mémoire, `memory.copy`/`memory.fill`, appels et
+//! imports, exports et données. Indépendante du constructeur de fonctions,
+//! elle relit le module terminé; le texte des blocs `wat { ... }` est
+//! assemblé et vérifié avec le reste quand l'assembleur le comprend (et
+//! doit l'être avec `check --validate`).
+
+use crate::asm::{self, Scope};
+use crate::codes::code;
+use crate::diag::{Diagnostic, Span};
+use crate::i18n::tr;
+use crate::wasm::{ExportKind, Func, Instr, MemArg, MemOp, Module, ValType};
+use std::fmt;
+
+/// Taille d'une page mémoire et nombre maximal de pages.
+const PAGE: u64 = 65536;
+const MAX_PAGES: u32 = 65536;
+
+/// Module invalide : un bug du compilateur, sauf dans un bloc `wat`.
+#[derive(Debug, Clone)]
+pub struct ValidationError {
+    /// fonction en cause; `None` pour les sections du module
+    pub func: Option<String>,
+    pub message: String,
+    /// instruction gaufre d'où vient l'instruction refusée
+    pub span: Span,
+    /// l'instruction refusée vient d'un bloc `wat`
+    pub in_wat: bool,
+    /// le bloc `wat` lui-même n'a pas pu être assemblé (`check --validate`)
+    pub asm: bool,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = tr!("validate.invalid", self.message);
+        match &self.func {
+            Some(func) if self.asm => write!(f, "{}", tr!("binary.asm", func, self.message)),
+            Some(func) if self.in_wat => write!(f, "{}", tr!("validate.wat", func, self.message)),
+            Some(func) => write!(f, "{}", tr!("wasm.internal", func, message)),
+            None => write!(f, "{}", tr!("validate.internal", message)),
+        }
+    }
+}
+impl std::error::Error for ValidationError {}
+impl From<ValidationError> for Diagnostic {
+    fn from(e: ValidationError) -> Self {
+        let span = (e.span != Span::default()).then_some(e.span);
+        let code = match (e.asm, e.in_wat) {
+            (true, _) => code!("G0507"),
+            (false, true) => code!("G0508"),
+            (false, false) => code!("G0506"),
+        };
+        Diagnostic::new(code, e.to_string(), span)
+    }
+}
+
+impl ValidationError {
+    /// Pour un module lu dans un fichier `.wat` : l'erreur est dans le
+    /// fichier, pas dans le compilateur.
+    pub fn into_wat_file(self) -> Diagnostic {
+        let span = (self.span != Span::default()).then_some(self.span);
+        let message = match &self.func {
+            Some(func) => tr!("validate.in_func", func, self.message),
+            None => tr!("validate.invalid", self.message),
+        };
+        Diagnostic::new(code!("G1002"), message, span)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Func,
+    Block,
+    Loop,
+    If,
+    Else,
+}
+
+/// Bloc de contrôle ouvert.
+struct Ctrl {
+    kind: Kind,
+    label: Option<String>,
+    results: Vec<ValType>,
+    /// hauteur de la pile d'opérandes à l'entrée
+    height: usize,
+    /// après `br`, `return` ou `unreachable` : la pile est polymorphe
+    unreachable: bool,
+    /// ouvert par le bloc `wat` à cette position
+    wat: Option<Span>,
+}
+
+impl Ctrl {
+    /// valeurs attendues par un branchement vers ce bloc
+    fn label_types(&self) -> Vec<ValType> {
+        if self.kind == Kind::Loop { Vec::new() } else { self.results.clone() }
+    }
+}
+
+/// Valeur de la pile d'opérandes.
+#[derive(Clone, Copy)]
+struct Val {
+    /// `None` : type inconnu, sur une pile polymorphe
+    ty: Option<ValType>,
+    /// empilée par le bloc `wat` à cette position
+    wat: Option<Span>,
+}
+
+struct Checker<'m> {
+    m: &'m Module,
+    f: &'m Func,
+    results: &'m [ValType],
+    vals: Vec<Val>,
+    ctrls: Vec<Ctrl>,
+    /// bloc `wat` de l'instruction en cours
+    wat: Option<Span>,
+}
+
+impl Checker<'_> {
+    fn frame(&self) -> &Ctrl {
+        self.ctrls.last().expect("bloc de la fonction")
+    }
+
+    /// Dépile `types` (le dernier au sommet), en vérifiant nombre et types.
+    fn pop(&mut self, what: &str, types: &[ValType]) -> Result<(), String> {
+        let frame = self.frame();
+        let available = self.vals.len() - frame.height;
+        if available < types.len() && !frame.unreachable {
+            return Err(tr!("wasm.underflow", what, types.len(), available));
+        }
+        for &expected in types.iter().rev() {
+            let actual = if self.vals.len() > self.frame().height { self.vals.pop().and_then(|v| v.ty) } else { None };
+            if let Some(actual) = actual.filter(|&a| a != expected) {
+                return Err(tr!("validate.type", what, expected, actual));
+            }
+        }
+        Ok(())
+    }
+
+    fn push(&mut self, types: &[ValType]) {
+        let wat = self.wat;
+        self.vals.extend(types.iter().map(|&t| Val { ty: Some(t), wat }));
+    }
+
+    fn open(&mut self, kind: Kind, label: Option<String>, result: Option<ValType>) {
+        let height = self.vals.len();
+        let results = result.into_iter().collect();
+        self.ctrls.push(Ctrl { kind, label, results, height, unreachable: false, wat: self.wat });
+    }
+
+    /// Referme le bloc courant : il laisse exactement ses résultats.
+    fn close(&mut self, what: &str) -> Result<Ctrl, String> {
+        let results = self.frame().results.clone();
+        self.pop(what, &results)?;
+        let frame = self.ctrls.pop().expect("bloc ouvert");
+        if self.vals.len() != frame.height {
+            return Err(tr!("wasm.block_end", what, results.len(), self.vals.len() - frame.height + results.len()));
+        }
+        Ok(frame)
+    }
+
+    fn set_unreachable(&mut self) {
+        let frame = self.ctrls.last_mut().expect("bloc de la fonction");
+        frame.unreachable = true;
+        self.vals.truncate(frame.height);
+    }
+
+    fn index(what: &str, i: u32, len: usize) -> Result<usize, String> {
+        if (i as usize) < len { Ok(i as usize) } else { Err(tr!("wasm.bad_index", what, i)) }
+    }
+
+    fn label(&self, d: u32) -> Result<&Ctrl, String> {
+        let i = Self::index("label", d, self.ctrls.len())?;
+        Ok(&self.ctrls[self.ctrls.len() - 1 - i])
+    }
+
+    fn memory(&self, what: &str) -> Result<(), String> {
+        match self.m.memory {
+            Some(_) => Ok(()),
+            None => Err(tr!("validate.no_memory", what)),
+        }
+    }
+
+    fn memarg(&self, op: MemOp, a: MemArg) -> Result<(), String> {
+        self.memory(op.name())?;
+        if a.align > op.natural_align() {
+            return Err(tr!("validate.align", 1u32 << a.align, op.name()));
+        }
+        Ok(())
+    }
+
+    fn instr(&mut self, ins: &Instr) -> Result<(), String> {
+        use ValType::I32;
+        let what = ins.keyword();
+        match ins {
+            Instr::Unreachable => self.set_unreachable(),
+            Instr::Nop => {}
+            Instr::Block { label, result } => self.open(Kind::Block, label.clone(), *result),
+            Instr::Loop { label, result } => self.open(Kind::Loop, label.clone(), *result),
+            Instr::If { result } => {
+                self.pop(what, &[I32])?;
+                self.open(Kind::If, None, *result);
+            }
+            Instr::Else => {
+                if self.frame().kind != Kind::If {
+                    return Err(tr!("wasm.unmatched", what));
+                }
+                let frame = self.close("if")?;
+                self.ctrls.push(Ctrl { kind: Kind::Else, unreachable: false, ..frame });
+            }
+            Instr::End => {
+                if self.ctrls.len() == 1 {
+                    return Err(tr!("wasm.unmatched", what));
+                }
+                let frame = self.close(what)?;
+                // sans `else`, la branche vide ne produit rien
+                if frame.kind == Kind::If && !frame.results.is_empty() {
+                    return Err(tr!("wasm.if_without_else"));
+                }
+                self.push(&frame.results);
+            }
+            Instr::Br(d) => {
+                let types = self.label(*d)?.label_types();
+                self.pop(what, &types)?;
+                self.set_unreachable();
+            }
+            Instr::BrIf(d) => {
+                let types = self.label(*d)?.label_types();
+                self.pop(what, &[I32])?;
+                self.pop(what, &types)?;
+                self.push(&types);
+            }
+            Instr::Return => {
+                self.pop(what, self.results)?;
+                self.set_unreachable();
+            }
+            Instr::Call(f) => {
+                Self::index(&tr!("wasm.entity.func"), *f, self.m.imports.len() + self.m.funcs.len())?;
+                let ty = self.m.type_of(*f);
+                self.pop(what, &ty.params)?;
+                self.push(&ty.results);
+            }
+            Instr::CallIndirect(t) => {
+                if self.m.table.is_none() {
+                    return Err(tr!("validate.no_table", what));
+                }
+                let ty = &self.m.types[Self::index("type", *t, self.m.types.len())?];
+                self.pop(what, &[I32])?;
+                self.pop(what, &ty.params)?;
+                self.push(&ty.results);
+            }
+            Instr::Drop => {
+                self.pop_any(what)?;
+            }
+            Instr::Select => {
+                self.pop(what, &[I32])?;
+                let b = self.pop_any(what)?;
+                let a = self.pop_any(what)?;
+                if let (Some(a), Some(b)) = (a, b)
+                    && a != b
+                {
+                    return Err(tr!("validate.type", what, a, b));
+                }
+                self.vals.push(Val { ty: a.or(b), wat: self.wat });
+            }
+            Instr::LocalGet(l) | Instr::LocalSet(l) | Instr::LocalTee(l) => {
+                let ty = self.f.locals[Self::index("local", *l, self.f.locals.len())?].ty;
+                if !matches!(ins, Instr::LocalGet(_)) {
+                    self.pop(what, &[ty])?;
+                }
+                if !matches!(ins, Instr::LocalSet(_)) {
+                    self.push(&[ty]);
+                }
+            }
+            Instr::GlobalGet(g) => {
+                let ty = self.m.globals[Self::index("global", *g, self.m.globals.len())?].ty;
+                self.push(&[ty]);
+            }
+            Instr::GlobalSet(g) => {
+                let global = &self.m.globals[Self::index("global", *g, self.m.globals.len())?];
+                if !global.mutable {
+                    return Err(tr!("validate.immutable", global.name));
+                }
+                self.pop(what, &[global.ty])?;
+            }
+            Instr::Mem(op, a) => {
+                self.memarg(*op, *a)?;
+                if op.is_store() {
+                    self.pop(what, &[I32, I32])?;
+                } else {
+                    self.pop(what, &[I32])?;
+                    self.push(&[I32]);
+                }
+            }
+            Instr::MemorySize => {
+                self.memory(what)?;
+                self.push(&[I32]);
+            }
+            Instr::MemoryGrow => {
+                self.memory(what)?;
+                self.pop(what, &[I32])?;
+                self.push(&[I32]);
+            }
+            Instr::MemoryCopy | Instr::MemoryFill => {
+                self.memory(what)?;
+                self.pop(what, &[I32, I32, I32])?;
+            }
+            Instr::I32Const(_) => self.push(&[I32]),
+            Instr::I32(op) => {
+                self.pop(what, &vec![I32; op.arity()])?;
+                self.push(&[I32]);
+            }
+            Instr::Raw(_) => unreachable!("bloc `wat` assemblé par `func`"),
+        }
+        Ok(())
+    }
+
+    fn pop_any(&mut self, what: &str) -> Result<Option<ValType>, String> {
+        let frame = self.frame();
+        if self.vals.len() > frame.height {
+            return Ok(self.vals.pop().and_then(|v| v.ty));
+        }
+        if frame.unreachable { Ok(None) } else { Err(tr!("wasm.underflow", what, 1, 0)) }
+    }
+}
+
+/// Corps d'une fonction déclarée; `strict` : voir `module`.
+fn func(m: &Module, f: &Func, strict: bool) -> Result<(), ValidationError> {
+    let error = |message: String, span: Span, in_wat: bool| ValidationError {
+        func: Some(f.name.clone()),
+        message,
+        span,
+        in_wat,
+        asm: false,
+    };
+    let ty = &m.types[f.ty as usize];
+    let params: Vec<ValType> = f.locals.iter().take(ty.params.len()).map(|l| l.ty).collect();
+    if params != ty.params {
+        return Err(error(tr!("validate.params"), Span::default(), false));
+    }
+    let func = Ctrl { kind: Kind::Func, label: None, results: ty.results.clone(), height: 0, unreachable: false, wat: None };
+    let mut c = Checker { m, f, results: &ty.results, vals: Vec::new(), ctrls: vec![func], wat: None };
+
+    for (k, ins) in f.body.iter().enumerate() {
+        let span = f.spans.get(k).copied().unwrap_or_default();
+        let Instr::Raw(text) = ins else {
+            c.instr(ins).map_err(|msg| error(msg, span, false))?;
+            continue;
+        };
+        let scope = Scope {
+            locals: f.locals.iter().map(|l| l.name.as_str()).collect(),
+            globals: m.globals.iter().map(|g| g.name.as_str()).collect(),
+            funcs: (0..(m.imports.len() + m.funcs.len()) as u32).map(|g| m.func_name(g)).collect(),
+            types: Vec::new(),
+            labels: c.ctrls[1..].iter().map(|ctrl| ctrl.label.as_deref()).collect(),
+        };
+        match asm::instrs(text, &scope) {
+            Ok(code) => {
+                c.wat = Some(span);
+                for ins in &code {
+                    c.instr(ins).map_err(|msg| error(msg, span, true))?;
+                }
+                c.wat = None;
+            }
+            Err(e) if strict => return Err(ValidationError { asm: true, ..error(e.message, span, true) }),
+            // hors de portée de l'assembleur : la suite du bloc n'est plus suivie
+            Err(_) => c.set_unreachable(),
+        }
+    }
+    // un bloc resté ouvert ou une valeur en trop à la fin : la faute au bloc
+    // `wat` qui l'a ouvert ou empilée, s'il y en a un
+    let end = f.spans.last().copied().unwrap_or_default();
+    if c.ctrls.len() > 1 {
+        let wat = c.ctrls.last().and_then(|ctrl| ctrl.wat);
+        return Err(error(tr!("wasm.unclosed", c.ctrls.len() - 1), wat.unwrap_or(end), wat.is_some()));
+    }
+    let wat = c.vals.iter().find_map(|v| v.wat);
+    c.close("end").map_err(|msg| error(msg, wat.unwrap_or(end), wat.is_some()))?;
+    Ok(())
+}
+
+/// Vérifie tout le module; renvoie la première erreur. `strict`
+/// (`check --validate`) : un bloc `wat` que l'assembleur ne comprend pas est
+/// une erreur, au lieu de ne plus suivre la pile après lui.
+pub fn module(m: &Module, strict: bool) -> Result<(), ValidationError> {
+    let error = |message: String| ValidationError { func: None, message, span: Span::default(), in_wat: false, asm: false };
+    let types = m.types.len();
+    for i in &m.imports {
+        Checker::index("type", i.ty, types).map_err(error)?;
+    }
+    for f in &m.funcs {
+        Checker::index("type", f.ty, types).map_err(error)?;
+    }
+    if let Some(pages) = m.memory.filter(|&p| p > MAX_PAGES) {
+        return Err(error(tr!("validate.memory_pages", pages)));
+    }
+    let funcs = m.imports.len() + m.funcs.len();
+    for (i, e) in m.exports.iter().enumerate() {
+        if m.exports[..i].iter().any(|other| other.name == e.name) {
+            return Err(error(tr!("validate.duplicate_export", e.name)));
+        }
+        match e.kind {
+            ExportKind::Func(f) => Checker::index(&tr!("wasm.entity.func"), f, funcs),
+            ExportKind::Memory(mem) => Checker::index(&tr!("wasm.entity.memory"), mem, m.memory.iter().len()),
+            ExportKind::Global(g) => Checker::index("global", g, m.globals.len()),
+        }
+        .map_err(error)?;
+    }
+    for e in &m.elems {
+        let Some(size) = m.table else { return Err(error(tr!("validate.no_table", "elem"))) };
+        let end = e.offset as u64 + e.funcs.len() as u64;
+        if end > size as u64 {
+            return Err(error(tr!("validate.elem_bounds", e.offset, e.funcs.len(), size)));
+        }
+        for &f in &e.funcs {
+            Checker::index(&tr!("wasm.entity.func"), f, funcs).map_err(error)?;
+        }
+    }
+    for d in &m.data {
+        let Some(pages) = m.memory else { return Err(error(tr!("validate.no_memory", "data"))) };
+        let end = d.offset as u64 + d.bytes.len() as u64;
+        if end > pages as u64 * PAGE {
+            return Err(error(tr!("validate.data_bounds", d.offset, d.bytes.len(), pages as u64 * PAGE)));
+        }
+    }
+    for f in &m.funcs {
+        func(m, f, strict)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,770 @@
+//! Modèle d'un module WebAssembly (types, imports, fonctions, mémoire,
+//! globales, exports, données) et constructeur de fonctions qui tient la
+//! hauteur de pile à chaque instruction : un bloc mal refermé ou une valeur
+//! manquante est une erreur du compilateur, pas de wat2wasm.
+
+use crate::codes::code;
+use crate::diag::{Diagnostic, Span};
+use crate::i18n::tr;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValType {
+    I32,
+}
+
+impl fmt::Display for ValType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValType::I32 => write!(f, "i32"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FuncType {
+    pub params: Vec<ValType>,
+    pub results: Vec<ValType>,
+}
+
+/// Opérations numériques sur i32.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumOp {
+    Eqz,
+    Eq,
+    Ne,
+    LtS,
+    LtU,
+    GtS,
+    GtU,
+    LeS,
+    LeU,
+    GeS,
+    GeU,
+    Clz,
+    Ctz,
+    Popcnt,
+    Add,
+    Sub,
+    Mul,
+    DivS,
+    DivU,
+    RemS,
+    RemU,
+    And,
+    Or,
+    Xor,
+    Shl,
+    ShrS,
+    ShrU,
+    Rotl,
+    Rotr,
+}
+
+impl NumOp {
+    /// dans l'ordre des codes d'opération
+    pub const ALL: [NumOp; 29] = [
+        NumOp::Eqz,
+        NumOp::Eq,
+        NumOp::Ne,
+        NumOp::LtS,
+        NumOp::LtU,
+        NumOp::GtS,
+        NumOp::GtU,
+        NumOp::LeS,
+        NumOp::LeU,
+        NumOp::GeS,
+        NumOp::GeU,
+        NumOp::Clz,
+        NumOp::Ctz,
+        NumOp::Popcnt,
+        NumOp::Add,
+        NumOp::Sub,
+        NumOp::Mul,
+        NumOp::DivS,
+        NumOp::DivU,
+        NumOp::RemS,
+        NumOp::RemU,
+        NumOp::And,
+        NumOp::Or,
+        NumOp::Xor,
+        NumOp::Shl,
+        NumOp::ShrS,
+        NumOp::ShrU,
+        NumOp::Rotl,
+        NumOp::Rotr,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            NumOp::Eqz => "i32.eqz",
+            NumOp::Eq => "i32.eq",
+            NumOp::Ne => "i32.ne",
+            NumOp::LtS => "i32.lt_s",
+            NumOp::LtU => "i32.lt_u",
+            NumOp::GtS => "i32.gt_s",
+            NumOp::GtU => "i32.gt_u",
+            NumOp::LeS => "i32.le_s",
+            NumOp::LeU => "i32.le_u",
+            NumOp::GeS => "i32.ge_s",
+            NumOp::GeU => "i32.ge_u",
+            NumOp::Clz => "i32.clz",
+            NumOp::Ctz => "i32.ctz",
+            NumOp::Popcnt => "i32.popcnt",
+            NumOp::Add => "i32.add",
+            NumOp::Sub => "i32.sub",
+            NumOp::Mul => "i32.mul",
+            NumOp::DivS => "i32.div_s",
+            NumOp::DivU => "i32.div_u",
+            NumOp::RemS => "i32.rem_s",
+            NumOp::RemU => "i32.rem_u",
+            NumOp::And => "i32.and",
+            NumOp::Or => "i32.or",
+            NumOp::Xor => "i32.xor",
+            NumOp::Shl => "i32.shl",
+            NumOp::ShrS => "i32.shr_s",
+            NumOp::ShrU => "i32.shr_u",
+            NumOp::Rotl => "i32.rotl",
+            NumOp::Rotr => "i32.rotr",
+        }
+    }
+
+    /// code d'opération binaire : comparaisons à partir de 0x45, le reste
+    /// à partir de 0x67
+    pub fn opcode(self) -> u8 {
+        let i = NumOp::ALL.iter().position(|&op| op == self).expect("opération listée") as u8;
+        if i <= 10 { 0x45 + i } else { 0x67 + (i - 11) }
+    }
+
+    /// nombre d'opérandes dépilés (le résultat est toujours un i32)
+    pub fn arity(self) -> usize {
+        if matches!(self, NumOp::Eqz | NumOp::Clz | NumOp::Ctz | NumOp::Popcnt) { 1 } else { 2 }
+    }
+}
+
+/// Lectures et écritures mémoire d'i32, éventuellement sur 8 ou 16 bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemOp {
+    Load,
+    Load8S,
+    Load8U,
+    Load16S,
+    Load16U,
+    Store,
+    Store8,
+    Store16,
+}
+
+impl MemOp {
+    pub const ALL: [MemOp; 8] = [
+        MemOp::Load,
+        MemOp::Load8S,
+        MemOp::Load8U,
+        MemOp::Load16S,
+        MemOp::Load16U,
+        MemOp::Store,
+        MemOp::Store8,
+        MemOp::Store16,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MemOp::Load => "i32.load",
+            MemOp::Load8S => "i32.load8_s",
+            MemOp::Load8U => "i32.load8_u",
+            MemOp::Load16S => "i32.load16_s",
+            MemOp::Load16U => "i32.load16_u",
+            MemOp::Store => "i32.store",
+            MemOp::Store8 => "i32.store8",
+            MemOp::Store16 => "i32.store16",
+        }
+    }
+
+    pub fn opcode(self) -> u8 {
+        match self {
+            MemOp::Load => 0x28,
+            MemOp::Load8S => 0x2C,
+            MemOp::Load8U => 0x2D,
+            MemOp::Load16S => 0x2E,
+            MemOp::Load16U => 0x2F,
+            MemOp::Store => 0x36,
+            MemOp::Store8 => 0x3A,
+            MemOp::Store16 => 0x3B,
+        }
+    }
+
+    /// alignement naturel, en log2 d'octets
+    pub fn natural_align(self) -> u32 {
+        match self {
+            MemOp::Load | MemOp::Store => 2,
+            MemOp::Load16S | MemOp::Load16U | MemOp::Store16 => 1,
+            MemOp::Load8S | MemOp::Load8U | MemOp::Store8 => 0,
+        }
+    }
+
+    pub fn is_store(self) -> bool {
+        matches!(self, MemOp::Store | MemOp::Store8 | MemOp::Store16)
+    }
+}
+
+/// Accès mémoire : `offset` s'ajoute à l'adresse, `align` en log2 d'octets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemArg {
+    pub align: u32,
+    pub offset: u32,
+}
+
+impl MemArg {
+    /// sans décalage, à l'alignement naturel de l'accès
+    pub fn natural(op: MemOp) -> MemArg {
+        MemArg { align: op.natural_align(), offset: 0 }
+    }
+}
+
+/// Instruction d'un corps de fonction, à plat comme dans le format binaire :
+/// les blocs s'ouvrent par `Block`/`Loop`/`If` et se ferment par `End`, et
+/// les branchements visent un bloc englobant par sa profondeur (0 : le plus
+/// proche).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Instr {
+    Unreachable,
+    Nop,
+    /// `label` ne sert qu'aux noms du texte WAT
+    Block { label: Option<String>, result: Option<ValType> },
+    Loop { label: Option<String>, result: Option<ValType> },
+    If { result: Option<ValType> },
+    Else,
+    End,
+    Br(u32),
+    BrIf(u32),
+    Return,
+    Call(u32),
+    /// appel par la table 0 (index de type de la signature)
+    CallIndirect(u32),
+    Drop,
+    Select,
+    LocalGet(u32),
+    LocalSet(u32),
+    LocalTee(u32),
+    GlobalGet(u32),
+    GlobalSet(u32),
+    Mem(MemOp, MemArg),
+    MemorySize,
+    MemoryGrow,
+    I32Const(i32),
+    I32(NumOp),
+    MemoryCopy,
+    MemoryFill,
+    /// bloc `wat { ... }` recopié tel quel : la pile n'y est pas suivie, et
+    /// il n'est assemblé que pour le format binaire
+    Raw(String),
+}
+
+impl Instr {
+    /// Mot-clé WAT de l'instruction, sans ses immédiats.
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            Instr::Unreachable => "unreachable",
+            Instr::Nop => "nop",
+            Instr::Block { .. } => "block",
+            Instr::Loop { .. } => "loop",
+            Instr::If { .. } => "if",
+            Instr::Else => "else",
+            Instr::End => "end",
+            Instr::Br(_) => "br",
+            Instr::BrIf(_) => "br_if",
+            Instr::Return => "return",
+            Instr::Call(_) => "call",
+            Instr::CallIndirect(_) => "call_indirect",
+            Instr::Drop => "drop",
+            Instr::Select => "select",
+            Instr::LocalGet(_) => "local.get",
+            Instr::LocalSet(_) => "local.set",
+            Instr::LocalTee(_) => "local.tee",
+            Instr::GlobalGet(_) => "global.get",
+            Instr::GlobalSet(_) => "global.set",
+            Instr::Mem(op, _) => op.name(),
+            Instr::MemorySize => "memory.size",
+            Instr::MemoryGrow => "memory.grow",
+            Instr::I32Const(_) => "i32.const",
+            Instr::I32(op) => op.name(),
+            Instr::MemoryCopy => "memory.copy",
+            Instr::MemoryFill => "memory.fill",
+            Instr::Raw(_) => "wat",
+        }
+    }
+
+    /// (valeurs dépilées, valeurs empilées) hors contrôle et appels
+    fn effect(&self) -> (usize, usize) {
+        match self {
+            Instr::I32Const(_) | Instr::LocalGet(_) | Instr::GlobalGet(_) | Instr::MemorySize => (0, 1),
+            Instr::LocalSet(_) | Instr::GlobalSet(_) | Instr::Drop => (1, 0),
+            Instr::LocalTee(_) | Instr::MemoryGrow => (1, 1),
+            Instr::Mem(op, _) if op.is_store() => (2, 0),
+            Instr::Mem(..) => (1, 1),
+            Instr::Select => (3, 1),
+            Instr::MemoryCopy | Instr::MemoryFill => (3, 0),
+            Instr::I32(op) => (op.arity(), 1),
+            _ => (0, 0),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Local {
+    pub name: String,
+    pub ty: ValType,
+}
+
+#[derive(Debug, Clone)]
+pub struct Func {
+    pub name: String,
+    /// index dans `Module::types`
+    pub ty: u32,
+    /// paramètres puis locals déclarés
+    pub locals: Vec<Local>,
+    pub body: Vec<Instr>,
+    /// pour chaque instruction, l'instruction gaufre qui l'a produite
+    pub spans: Vec<Span>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Import {
+    pub module: String,
+    pub field: String,
+    pub name: String,
+    pub ty: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct Global {
+    pub name: String,
+    pub ty: ValType,
+    pub mutable: bool,
+    pub init: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportKind {
+    Func(u32),
+    Memory(u32),
+    Global(u32),
+}
+
+#[derive(Debug, Clone)]
+pub struct Export {
+    pub name: String,
+    pub kind: ExportKind,
+}
+
+/// Segment actif de la table 0 : `funcs` rangées à partir de `offset`.
+#[derive(Debug, Clone)]
+pub struct Elem {
+    pub offset: u32,
+    pub funcs: Vec<u32>,
+}
+
+/// Segment actif de la mémoire 0.
+#[derive(Debug, Clone)]
+pub struct Data {
+    pub offset: u32,
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Module {
+    pub types: Vec<FuncType>,
+    /// fonctions importées; leurs index précèdent ceux de `funcs`
+    pub imports: Vec<Import>,
+    pub funcs: Vec<Func>,
+    /// taille minimale de l'unique table de `funcref`, si elle existe
+    pub table: Option<u32>,
+    /// pages minimales de l'unique mémoire, si elle existe
+    pub memory: Option<u32>,
+    pub globals: Vec<Global>,
+    pub exports: Vec<Export>,
+    pub elems: Vec<Elem>,
+    pub data: Vec<Data>,
+}
+
+impl Module {
+    /// Index du type, ajouté s'il n'existe pas encore.
+    pub fn func_type(&mut self, params: Vec<ValType>, results: Vec<ValType>) -> u32 {
+        let ty = FuncType { params, results };
+        let i = self.types.iter().position(|t| *t == ty).unwrap_or_else(|| {
+            self.types.push(ty);
+            self.types.len() - 1
+        });
+        i as u32
+    }
+
+    /// Type d'une fonction, importée ou non.
+    pub fn type_of(&self, f: u32) -> &FuncType {
+        let f = f as usize;
+        let ty = match self.imports.get(f) {
+            Some(i) => i.ty,
+            None => self.funcs[f - self.imports.len()].ty,
+        };
+        &self.types[ty as usize]
+    }
+
+    pub fn func_name(&self, f: u32) -> &str {
+        let f = f as usize;
+        match self.imports.get(f) {
+            Some(i) => &i.name,
+            None => &self.funcs[f - self.imports.len()].name,
+        }
+    }
+
+    /// Constructeur du corps de la fonction déclarée `f` (index dans
+    /// l'espace des fonctions), qui connaît les signatures et globales.
+    pub fn builder(&self, f: u32, locals: Vec<Local>) -> FuncBuilder {
+        let decl = &self.funcs[f as usize - self.imports.len()];
+        let ty = &self.types[decl.ty as usize];
+        FuncBuilder {
+            name: decl.name.clone(),
+            ty: decl.ty,
+            calls: (0..(self.imports.len() + self.funcs.len()) as u32)
+                .map(|g| {
+                    let t = self.type_of(g);
+                    (t.params.len(), t.results.len())
+                })
+                .collect(),
+            types: self.types.iter().map(|t| (t.params.len(), t.results.len())).collect(),
+            globals: self.globals.len(),
+            locals,
+            body: Vec::new(),
+            spans: Vec::new(),
+            frames: vec![Frame { kind: FrameKind::Func, height: 0, results: ty.results.len(), unknown: false }],
+            height: 0,
+            span: Span::default(),
+            error: None,
+        }
+    }
+}
+
+/// Erreur du compilateur lui-même : le code généré ne tient pas debout.
+#[derive(Debug, Clone)]
+pub struct BuildError {
+    pub func: String,
+    pub message: String,
+    /// instruction gaufre en cours de génération, si elle est connue
+    pub span: Span,
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", tr!("wasm.internal", self.func, self.message))
+    }
+}
+impl std::error::Error for BuildError {}
+impl From<BuildError> for Diagnostic {
+    fn from(e: BuildError) -> Self {
+        let span = (e.span != Span::default()).then_some(e.span);
+        Diagnostic::new(code!("G0506"), e.to_string(), span)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FrameKind {
+    Func,
+    Block,
+    Loop,
+    If,
+    Else,
+}
+
+/// Bloc ouvert : hauteur de pile à l'entrée et valeurs qu'il laisse.
+#[derive(Debug, Clone)]
+struct Frame {
+    kind: FrameKind,
+    height: usize,
+    results: usize,
+    /// après `br`, `return`, `unreachable` ou du WAT recopié, la pile n'est
+    /// plus connue jusqu'à la fin du bloc
+    unknown: bool,
+}
+
+/// Corps de fonction en construction. La première erreur est gardée et
+/// rendue par `finish`; les instructions suivantes sont ignorées.
+pub struct FuncBuilder {
+    name: String,
+    ty: u32,
+    /// (paramètres, résultats) de chaque fonction
+    calls: Vec<(usize, usize)>,
+    /// (paramètres, résultats) de chaque type
+    types: Vec<(usize, usize)>,
+    globals: usize,
+    locals: Vec<Local>,
+    body: Vec<Instr>,
+    spans: Vec<Span>,
+    frames: Vec<Frame>,
+    height: usize,
+    span: Span,
+    error: Option<BuildError>,
+}
+
+impl FuncBuilder {
+    /// Instruction gaufre dont viennent les instructions suivantes; renvoie
+    /// la précédente, à rétablir après.
+    pub fn at(&mut self, span: Span) -> Span {
+        std::mem::replace(&mut self.span, span)
+    }
+
+    fn fail(&mut self, message: String) {
+        if self.error.is_none() {
+            self.error = Some(BuildError { func: self.name.clone(), message, span: self.span });
+        }
+    }
+
+    fn frame(&mut self) -> &mut Frame {
+        self.frames.last_mut().expect("bloc de la fonction")
+    }
+
+    fn pop(&mut self, n: usize, what: &str) {
+        let frame = self.frames.last().expect("bloc de la fonction");
+        let available = self.height - frame.height;
+        if available < n && !frame.unknown {
+            self.fail(tr!("wasm.underflow", what, n, available));
+        }
+        self.height -= n.min(available);
+    }
+
+    fn check_index(&mut self, what: &str, i: u32, len: usize) -> bool {
+        if (i as usize) < len {
+            return true;
+        }
+        self.fail(tr!("wasm.bad_index", what, i));
+        false
+    }
+
+    /// Le reste du bloc courant n'est pas atteint (ou pas suivi).
+    fn set_unknown(&mut self) {
+        let frame = self.frame();
+        frame.unknown = true;
+        let h = frame.height;
+        self.height = h;
+    }
+
+    /// Referme le bloc courant : il doit laisser exactement ses résultats.
+    fn close(&mut self, what: &str) -> Frame {
+        let frame = self.frames.pop().expect("bloc ouvert");
+        let found = self.height - frame.height;
+        if found != frame.results && !frame.unknown {
+            self.fail(tr!("wasm.block_end", what, frame.results, found));
+        }
+        self.height = frame.height;
+        frame
+    }
+
+    /// Ajoute une instruction en suivant la pile.
+    pub fn push(&mut self, ins: Instr) {
+        if self.error.is_some() {
+            return;
+        }
+        match &ins {
+            Instr::Block { result, .. } | Instr::Loop { result, .. } => {
+                let kind = if matches!(ins, Instr::Block { .. }) { FrameKind::Block } else { FrameKind::Loop };
+                let results = usize::from(result.is_some());
+                self.frames.push(Frame { kind, height: self.height, results, unknown: false });
+            }
+            Instr::If { result } => {
+                self.pop(1, "if");
+                let results = usize::from(result.is_some());
+                self.frames.push(Frame { kind: FrameKind::If, height: self.height, results, unknown: false });
+            }
+            Instr::Else => {
+                if self.frames.last().map(|f| f.kind) != Some(FrameKind::If) {
+                    self.fail(tr!("wasm.unmatched", "else"));
+                    return;
+                }
+                let frame = self.close("if");
+                self.frames.push(Frame { kind: FrameKind::Else, unknown: false, ..frame });
+            }
+            Instr::End => {
+                if self.frames.len() == 1 {
+                    self.fail(tr!("wasm.unmatched", "end"));
+                    return;
+                }
+                let frame = self.close("end");
+                // un `if` sans `else` ne peut pas produire de valeur
+                if frame.kind == FrameKind::If && frame.results > 0 {
+                    self.fail(tr!("wasm.if_without_else"));
+                }
+                self.height += frame.results;
+            }
+            Instr::Br(d) | Instr::BrIf(d) => {
+                let conditional = matches!(ins, Instr::BrIf(_));
+                if !self.check_index("label", *d, self.frames.len()) {
+                    return;
+                }
+                if conditional {
+                    self.pop(1, "br_if");
+                }
+                let target = &self.frames[self.frames.len() - 1 - *d as usize];
+                // une boucle se recommence sans valeur
+                let arity = if target.kind == FrameKind::Loop { 0 } else { target.results };
+                self.pop(arity, if conditional { "br_if" } else { "br" });
+                if conditional {
+                    self.height += arity;
+                } else {
+                    self.set_unknown();
+                }
+            }
+            Instr::Return => {
+                let results = self.frames[0].results;
+                self.pop(results, "return");
+                self.set_unknown();
+            }
+            Instr::Unreachable | Instr::Raw(_) => self.set_unknown(),
+            Instr::Call(f) => {
+                if !self.check_index("fonction", *f, self.calls.len()) {
+                    return;
+                }
+                let (params, results) = self.calls[*f as usize];
+                self.pop(params, "call");
+                self.height += results;
+            }
+            Instr::CallIndirect(t) => {
+                if !self.check_index("type", *t, self.types.len()) {
+                    return;
+                }
+                // l'index dans la table au sommet, sous lui les arguments
+                let (params, results) = self.types[*t as usize];
+                self.pop(params + 1, "call_indirect");
+                self.height += results;
+            }
+            Instr::LocalGet(l) | Instr::LocalSet(l) | Instr::LocalTee(l) if !self.check_index("local", *l, self.locals.len()) => return,
+            Instr::GlobalGet(g) | Instr::GlobalSet(g) if !self.check_index("global", *g, self.globals) => return,
+            _ => {}
+        }
+        let (pops, pushes) = ins.effect();
+        if pops + pushes > 0 {
+            let what = ins.keyword();
+            self.pop(pops, what);
+            self.height += pushes;
+        }
+        self.body.push(ins);
+        self.spans.push(self.span);
+    }
+
+    pub fn i32_const(&mut self, v: i32) {
+        self.push(Instr::I32Const(v));
+    }
+
+    pub fn local_get(&mut self, l: u32) {
+        self.push(Instr::LocalGet(l));
+    }
+
+    pub fn local_set(&mut self, l: u32) {
+        self.push(Instr::LocalSet(l));
+    }
+
+    pub fn num(&mut self, op: NumOp) {
+        self.push(Instr::I32(op));
+    }
+
+    pub fn call(&mut self, f: u32) {
+        self.push(Instr::Call(f));
+    }
+
+    /// Termine la fonction : tous les blocs refermés, ses résultats sur la pile.
+    pub fn finish(mut self) -> Result<Func, BuildError> {
+        if self.error.is_none() {
+            if self.frames.len() > 1 {
+                self.fail(tr!("wasm.unclosed", self.frames.len() - 1));
+            } else {
+                self.close("end");
+            }
+        }
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(Func { name: self.name, ty: self.ty, locals: self.locals, body: self.body, spans: self.spans }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FuncBuilder, Instr, Local, Module, NumOp, ValType};
+    use crate::diag::Span;
+    use crate::i18n::tr;
+
+    /// Module à deux fonctions : `f(x: i32) -> i32`, dont on construit le
+    /// corps, et `g(i32, i32)` sans résultat.
+    fn builder() -> FuncBuilder {
+        let mut m = Module::default();
+        let f = m.func_type(vec![ValType::I32], vec![ValType::I32]);
+        let g = m.func_type(vec![ValType::I32, ValType::I32], vec![]);
+        for (name, ty) in [("f", f), ("g", g)] {
+            m.funcs.push(super::Func { name: name.into(), ty, locals: Vec::new(), body: Vec::new(), spans: Vec::new() });
+        }
+        m.builder(0, vec![Local { name: "x".into(), ty: ValType::I32 }])
+    }
+
+    /// Corps `body` construit, ou le message de sa première erreur.
+    fn build(body: Vec<Instr>) -> Result<(), String> {
+        let mut b = builder();
+        for ins in body {
+            b.push(ins);
+        }
+        b.finish().map(|_| ()).map_err(|e| e.message)
+    }
+
+    fn error(body: Vec<Instr>) -> String {
+        build(body).unwrap_err()
+    }
+
+    #[test]
+    fn stack_is_tracked() {
+        let mut b = builder();
+        b.at(Span::new(0, 4, 9));
+        b.local_get(0);
+        b.push(Instr::Block { label: Some("b".into()), result: Some(ValType::I32) });
+        b.local_get(0);
+        b.i32_const(0);
+        b.push(Instr::BrIf(0)); // la valeur du bloc reste si on ne sort pas
+        b.local_get(0);
+        b.push(Instr::If { result: Some(ValType::I32) });
+        b.i32_const(2);
+        b.push(Instr::Else);
+        b.i32_const(3);
+        b.push(Instr::End);
+        b.num(NumOp::Add);
+        b.push(Instr::End);
+        b.num(NumOp::Add);
+        b.i32_const(7);
+        b.local_get(0);
+        b.call(1);
+        let f = b.finish().unwrap();
+        assert_eq!(f.body.len(), 17);
+        assert_eq!(f.spans[0], Span::new(0, 4, 9));
+        // après `return`, la pile n'est plus suivie jusqu'à la fin du bloc
+        let mut b = builder();
+        b.i32_const(1);
+        b.push(Instr::Return);
+        b.num(NumOp::Add);
+        b.finish().unwrap();
+    }
+
+    #[test]
+    fn stack_errors() {
+        assert_eq!(error(vec![Instr::I32Const(1), Instr::I32(NumOp::Add)]), tr!("wasm.underflow", "i32.add", 2, 1));
+        assert_eq!(error(vec![Instr::LocalGet(0), Instr::LocalGet(0)]), tr!("wasm.block_end", "end", 1, 2));
+        let block = vec![Instr::Block { label: None, result: None }, Instr::I32Const(1), Instr::End, Instr::LocalGet(0)];
+        assert_eq!(error(block), tr!("wasm.block_end", "end", 0, 1));
+        assert_eq!(error(vec![Instr::LocalGet(0), Instr::End]), tr!("wasm.unmatched", "end"));
+        assert_eq!(error(vec![Instr::Else]), tr!("wasm.unmatched", "else"));
+        let open = vec![Instr::LocalGet(0), Instr::Loop { label: None, result: None }];
+        assert_eq!(error(open), tr!("wasm.unclosed", 1));
+        let no_else = vec![Instr::LocalGet(0), Instr::If { result: Some(ValType::I32) }, Instr::I32Const(1), Instr::End];
+        assert_eq!(error(no_else), tr!("wasm.if_without_else"));
+        // une boucle se recommence sans valeur, un bloc se quitte avec la sienne
+        let to_loop = vec![Instr::Loop { label: None, result: Some(ValType::I32) }, Instr::I32Const(1), Instr::Br(0), Instr::End];
+        build(to_loop).unwrap();
+        let to_block = vec![Instr::Block { label: None, result: Some(ValType::I32) }, Instr::Br(0), Instr::End];
+        assert_eq!(error(to_block), tr!("wasm.underflow", "br", 1, 0));
+        assert_eq!(error(vec![Instr::LocalGet(1)]), tr!("wasm.bad_index", "local", 1));
+        assert_eq!(error(vec![Instr::Call(2)]), tr!("wasm.bad_index", "fonction", 2));
+    }
+}
@@ -0,0 +1,160 @@
+//! Impression d'un module WebAssembly en texte : une instruction par ligne,
+//! les blocs indentés, les index remplacés par les noms (`$main`, `$i`,
+//! `$exit0`).
+
+use crate::wasm::{ExportKind, Func, Instr, MemArg, MemOp, Module, ValType};
+
+/// échappement pour littéral WAT
+fn escape(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for &b in bytes {
+        match b {
+            b'"' => out.push_str("\\\""),
+            b'\\' => out.push_str("\\\\"),
+            b'\n' => out.push_str("\\n"),
+            b'\r' => out.push_str("\\r"),
+            b'\t' => out.push_str("\\t"),
+            0x20..=0x7E => out.push(b as char),
+            _ => out.push_str(&format!("\\{:02x}", b)),
+        }
+    }
+    out
+}
+
+/// `(export "...")` en ligne, pour chaque export de l'item.
+fn exports(m: &Module, kind: ExportKind) -> String {
+    m.exports
+        .iter()
+        .filter(|e| e.kind == kind)
+        .map(|e| format!(" (export \"{}\")", escape(e.name.as_bytes())))
+        .collect()
+}
+
+/// `offset=` et `align=` seulement s'ils diffèrent des valeurs par défaut
+/// (alignement naturel de l'accès).
+fn memarg(op: MemOp, a: MemArg) -> String {
+    let mut s = String::new();
+    if a.offset != 0 {
+        s.push_str(&format!(" offset={}", a.offset));
+    }
+    if a.align != op.natural_align() {
+        s.push_str(&format!(" align={}", 1u32 << a.align));
+    }
+    s
+}
+
+fn block_type(result: &Option<ValType>) -> String {
+    result.map(|t| format!(" (result {t})")).unwrap_or_default()
+}
+
+fn func(m: &Module, idx: u32, f: &Func) -> String {
+    let ty = &m.types[f.ty as usize];
+    let mut wat = format!("  (func ${}{}", f.name, exports(m, ExportKind::Func(idx)));
+    let (params, locals) = f.locals.split_at(ty.params.len());
+    for p in params {
+        wat.push_str(&format!(" (param ${} {})", p.name, p.ty));
+    }
+    for t in &ty.results {
+        wat.push_str(&format!(" (result {t})"));
+    }
+    wat.push('\n');
+    for l in locals {
+        wat.push_str(&format!("    (local ${} {})\n", l.name, l.ty));
+    }
+
+    // noms des blocs ouverts, pour écrire `br $exit0` plutôt que `br 1`
+    let mut labels: Vec<Option<&str>> = Vec::new();
+    let label = |labels: &[Option<&str>], d: u32| match labels.len().checked_sub(d as usize + 1).and_then(|i| labels[i]) {
+        Some(name) => format!("${name}"),
+        None => d.to_string(),
+    };
+    for ins in &f.body {
+        if matches!(ins, Instr::Else | Instr::End) {
+            labels.pop();
+        }
+        let text = match ins {
+            Instr::Block { label, result } | Instr::Loop { label, result } => {
+                let name = label.as_ref().map(|l| format!(" ${l}")).unwrap_or_default();
+                format!("{}{name}{}", ins.keyword(), block_type(result))
+            }
+            Instr::If { result } => format!("if{}", block_type(result)),
+            Instr::Br(d) | Instr::BrIf(d) => format!("{} {}", ins.keyword(), label(&labels, *d)),
+            Instr::Call(g) => format!("call ${}", m.func_name(*g)),
+            Instr::CallIndirect(t) => format!("call_indirect (type {t})"),
+            Instr::LocalGet(l) | Instr::LocalSet(l) | Instr::LocalTee(l) => {
+                format!("{} ${}", ins.keyword(), f.locals[*l as usize].name)
+            }
+            Instr::GlobalGet(g) | Instr::GlobalSet(g) => format!("{} ${}", ins.keyword(), m.globals[*g as usize].name),
+            Instr::Mem(op, a) => format!("{}{}", op.name(), memarg(*op, *a)),
+            Instr::I32Const(v) => format!("i32.const {v}"),
+            Instr::Raw(code) => {
+                let indent = "  ".repeat(labels.len() + 2);
+                wat.push_str(&format!("{indent};; wat\n"));
+                for line in code.lines().map(str::trim).filter(|l| !l.is_empty()) {
+                    wat.push_str(&format!("{indent}{line}\n"));
+                }
+                continue;
+            }
+            _ => ins.keyword().to_string(),
+        };
+        wat.push_str(&"  ".repeat(labels.len() + 2));
+        wat.push_str(&text);
+        wat.push('\n');
+        match ins {
+            Instr::Block { label, .. } | Instr::Loop { label, .. } => labels.push(label.as_deref()),
+            Instr::If { .. } | Instr::Else => labels.push(None),
+            _ => {}
+        }
+    }
+    wat.push_str("  )\n");
+    wat
+}
+
+pub fn print(m: &Module) -> String {
+    let mut wat = String::from("(module\n");
+    // avec une table, les types sont écrits pour que `call_indirect (type N)`
+    // désigne le même index que dans le module
+    if m.table.is_some() {
+        for t in &m.types {
+            let params: String = t.params.iter().map(|t| format!(" {t}")).collect();
+            let results: String = t.results.iter().map(|t| format!(" (result {t})")).collect();
+            wat.push_str(&format!("  (type (func (param{params}){results}))\n"));
+        }
+    }
+    for imp in &m.imports {
+        let ty = &m.types[imp.ty as usize];
+        let params: String = ty.params.iter().map(|t| format!(" {t}")).collect();
+        let results: String = ty.results.iter().map(|t| format!(" (result {t})")).collect();
+        wat.push_str(&format!(
+            "  (import \"{}\" \"{}\" (func ${} (param{params}){results}))\n",
+            escape(imp.module.as_bytes()),
+            escape(imp.field.as_bytes()),
+            imp.name,
+        ));
+    }
+    if let Some(size) = m.table {
+        wat.push_str(&format!("  (table {size} funcref)\n"));
+    }
+    if let Some(pages) = m.memory {
+        wat.push_str(&format!("  (memory{} {pages})\n", exports(m, ExportKind::Memory(0))));
+    }
+    for (i, g) in m.globals.iter().enumerate() {
+        let ty = if g.mutable { format!("(mut {})", g.ty) } else { g.ty.to_string() };
+        let export = exports(m, ExportKind::Global(i as u32));
+        wat.push_str(&format!("  (global ${}{export} {ty} (i32.const {}))\n", g.name, g.init));
+    }
+    for e in &m.elems {
+        let funcs: String = e.funcs.iter().map(|&f| format!(" ${}", m.func_name(f))).collect();
+        wat.push_str(&format!("  (elem (i32.const {}){funcs})\n", e.offset));
+    }
+    for d in &m.data {
+        wat.push_str(&format!("  (data (i32.const {}) \"{}\")\n", d.offset, escape(&d.bytes)));
+    }
+    let first = m.imports.len() as u32;
+    for (i, f) in m.funcs.iter().enumerate() {
+        wat.push('\n');
+        wat.push_str(&func(m, first + i as u32, f));
+    }
+    wat.push_str(")\n");
+    wat
+}